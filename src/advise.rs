@@ -0,0 +1,266 @@
+//! `advise`: a one-line verdict for agent wrappers deciding whether to start
+//! the next task.
+//!
+//! stdout is exactly `<proceed|slow|stop> <wait_seconds>` — the stable part
+//! scripts parse. The reason goes to stderr for humans.
+
+use crate::api::{RateWindow, Snapshot};
+use crate::config::AdviseConfig;
+use crate::history::Mark;
+use chrono::{DateTime, Duration, Utc};
+
+/// Default usage at which to start pacing
+pub const DEFAULT_SLOW_AT: f64 = 80.0;
+
+/// Default usage at which to stop starting new work
+pub const DEFAULT_STOP_AT: f64 = 95.0;
+
+/// How much recent history the burn rate is measured over
+pub const PROJECTION_WINDOW: Duration = Duration::hours(1);
+
+/// Shortest span of history worth projecting from
+const MIN_PROJECTION_SECS: f64 = 120.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    Proceed,
+    Slow,
+    Stop,
+}
+
+impl Verdict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Verdict::Proceed => "proceed",
+            Verdict::Slow => "slow",
+            Verdict::Stop => "stop",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advice {
+    pub verdict: Verdict,
+    pub wait_seconds: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub slow_at: f64,
+    pub stop_at: f64,
+}
+
+impl Thresholds {
+    pub fn from_config(cfg: &AdviseConfig) -> Self {
+        Thresholds {
+            slow_at: cfg.slow_at.unwrap_or(DEFAULT_SLOW_AT),
+            stop_at: cfg.stop_at.unwrap_or(DEFAULT_STOP_AT),
+        }
+    }
+}
+
+/// Decide from the latest snapshot plus recent history (oldest first, the
+/// latest snapshot may or may not be included)
+pub fn advise(
+    snap: &Snapshot,
+    recent: &[Snapshot],
+    limits: Thresholds,
+    now: DateTime<Utc>,
+) -> Advice {
+    let rl = snap.usage.rate_limit.as_ref();
+    let label = |name: &str, w: Option<&RateWindow>| {
+        let span = w.and_then(|w| w.span(name));
+        let length = span.map_or_else(|| name.to_string(), |s| s.adjective());
+        format!("{length} window")
+    };
+    let primary = rl.and_then(|r| r.primary_window.as_ref());
+    let secondary = rl.and_then(|r| r.secondary_window.as_ref());
+    let windows = [
+        (label("primary", primary), false, primary),
+        (label("secondary", secondary), true, secondary),
+    ];
+
+    if rl.and_then(|r| r.limit_reached).unwrap_or(false) {
+        // Whichever window is full is the one to wait out
+        let wait = windows
+            .iter()
+            .filter_map(|(_, _, w)| w.filter(|w| w.used_percent.unwrap_or(0.0) >= 100.0))
+            .chain(windows[0].2)
+            .find_map(|w| w.resets_in(snap.fetched_at, now))
+            .unwrap_or(0);
+        return Advice {
+            verdict: Verdict::Stop,
+            wait_seconds: wait,
+            reason: "limit reached".to_string(),
+        };
+    }
+
+    let mut best = Advice {
+        verdict: Verdict::Proceed,
+        wait_seconds: 0,
+        reason: "plenty of capacity".to_string(),
+    };
+    for (label, secondary, window) in windows {
+        let Some(w) = window else { continue };
+        let advice = advise_window(
+            &label,
+            w,
+            snap,
+            burn_rate(recent, snap, secondary),
+            limits,
+            now,
+        );
+        if (advice.verdict, advice.wait_seconds) > (best.verdict, best.wait_seconds) {
+            best = advice;
+        }
+    }
+    best
+}
+
+fn advise_window(
+    label: &str,
+    w: &RateWindow,
+    snap: &Snapshot,
+    rate: Option<f64>,
+    limits: Thresholds,
+    now: DateTime<Utc>,
+) -> Advice {
+    let used = w.used_percent.unwrap_or(0.0).clamp(0.0, 100.0);
+    let resets_in = w.resets_in(snap.fetched_at, now);
+
+    if used >= limits.stop_at {
+        return Advice {
+            verdict: Verdict::Stop,
+            wait_seconds: resets_in.unwrap_or(0),
+            reason: format!("{label} at {used:.0}%"),
+        };
+    }
+
+    // At the current burn rate, will the limit arrive before the reset?
+    if let (Some(rate), Some(reset)) = (rate, resets_in) {
+        let to_limit = (100.0 - used) / rate;
+        if to_limit < reset as f64 {
+            return Advice {
+                verdict: Verdict::Slow,
+                // Pausing this long lets the remaining budget last until the reset
+                wait_seconds: (reset as f64 - to_limit).round() as u64,
+                reason: format!(
+                    "{label} at {used:.0}%, projected to hit the limit in {}m at the current pace",
+                    (to_limit / 60.0).round()
+                ),
+            };
+        }
+    }
+
+    if used >= limits.slow_at {
+        // Without a projection, wait a share of the remaining window that
+        // grows as usage approaches the stop threshold
+        let depth = (used - limits.slow_at) / (limits.stop_at - limits.slow_at).max(1.0);
+        return Advice {
+            verdict: Verdict::Slow,
+            wait_seconds: resets_in.map_or(0, |r| (r as f64 * depth * 0.5).round() as u64),
+            reason: format!("{label} at {used:.0}%"),
+        };
+    }
+
+    Advice {
+        verdict: Verdict::Proceed,
+        wait_seconds: 0,
+        reason: format!("{label} at {used:.0}%"),
+    }
+}
+
+/// Percentage points per second over the recent samples, ignoring anything
+/// from before the window last reset. `None` if there's too little to go on
+/// or usage isn't rising.
+fn burn_rate(recent: &[Snapshot], snap: &Snapshot, secondary: bool) -> Option<f64> {
+    let reset = if secondary {
+        Mark::SecondaryReset
+    } else {
+        Mark::PrimaryReset
+    };
+    let since_reset = match recent.iter().rposition(|s| s.marks.contains(&reset)) {
+        Some(i) => &recent[i..],
+        None => recent,
+    };
+    let first = since_reset.first()?;
+    let used = |s: &Snapshot| {
+        let rl = s.usage.rate_limit.as_ref()?;
+        let w = if secondary {
+            rl.secondary_window.as_ref()
+        } else {
+            rl.primary_window.as_ref()
+        };
+        w?.used_percent
+    };
+    let secs = (snap.fetched_at - first.fetched_at).num_seconds() as f64;
+    let delta = used(snap)? - used(first)?;
+    (secs >= MIN_PROJECTION_SECS && delta > 0.0).then(|| delta / secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    const LIMITS: Thresholds = Thresholds {
+        slow_at: DEFAULT_SLOW_AT,
+        stop_at: DEFAULT_STOP_AT,
+    };
+
+    fn sample(ts: &str, primary: f64, reset_after: u64, limit: bool) -> Snapshot {
+        let usage = serde_json::from_value(serde_json::json!({
+            "rate_limit": {
+                "primary_window": { "used_percent": primary, "reset_after_seconds": reset_after },
+                "secondary_window": { "used_percent": 10.0, "reset_after_seconds": 400000 },
+                "limit_reached": limit
+            }
+        }))
+        .unwrap();
+        Snapshot::new(usage, parse_timestamp(ts).unwrap())
+    }
+
+    #[test]
+    fn thresholds_and_limit() {
+        let now = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let calm = sample("2024-05-01T10:00:00Z", 20.0, 3600, false);
+        assert_eq!(advise(&calm, &[], LIMITS, now).verdict, Verdict::Proceed);
+
+        let full = sample("2024-05-01T10:00:00Z", 100.0, 3600, true);
+        let a = advise(&full, &[], LIMITS, now);
+        assert_eq!((a.verdict, a.wait_seconds), (Verdict::Stop, 3600));
+
+        let hot = sample("2024-05-01T10:00:00Z", 87.5, 3600, false);
+        let a = advise(&hot, &[], LIMITS, now);
+        assert_eq!((a.verdict, a.wait_seconds), (Verdict::Slow, 900));
+        assert_eq!(a.reason, "5-hour window at 88%");
+
+        // Labels follow the lengths the API reports
+        let mut short = hot.clone();
+        let rl = short.usage.rate_limit.as_mut().unwrap();
+        rl.primary_window.as_mut().unwrap().limit_window_seconds = Some(3 * 3600);
+        assert_eq!(
+            advise(&short, &[], LIMITS, now).reason,
+            "3-hour window at 88%"
+        );
+    }
+
+    #[test]
+    fn projection_slows_before_the_threshold() {
+        let now = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        // 30 points in 30 minutes, 40 left, 2 hours to the reset: the limit
+        // arrives in 40 minutes, so pause for the other 80
+        let earlier = sample("2024-05-01T09:30:00Z", 30.0, 9000, false);
+        let snap = sample("2024-05-01T10:00:00Z", 60.0, 7200, false);
+        let a = advise(&snap, std::slice::from_ref(&earlier), LIMITS, now);
+        assert_eq!((a.verdict, a.wait_seconds), (Verdict::Slow, 4800));
+
+        // A reset since then makes the old sample irrelevant
+        let mut reset = sample("2024-05-01T09:50:00Z", 5.0, 18000, false);
+        reset.marks = vec![Mark::PrimaryReset];
+        let snap = sample("2024-05-01T10:00:00Z", 6.0, 17400, false);
+        let a = advise(&snap, &[earlier, reset], LIMITS, now);
+        assert_eq!(a.verdict, Verdict::Proceed);
+    }
+}
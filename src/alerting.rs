@@ -0,0 +1,1622 @@
+//! Incident alerts from `serve`, configured per profile under `[alerting]`:
+//! a PagerDuty Events API v2 event, an Opsgenie alert or a webhook POST when
+//! the profile's limit is reached, resolved once it is lifted, and
+//! optionally one as each window crosses a `thresholds` percentage.
+//!
+//! `escalation` steps route a percentage, or the limit, to particular
+//! channels instead, e.g. a desktop notification at 70%, the webhook at 90%
+//! and the pager only at the limit. The limit goes to every configured
+//! service unless a step routes it.
+//!
+//! The webhook's body can be a Tera `template` instead, given the alert and
+//! the window it is about, so it can post straight to Slack, ntfy or any
+//! other endpoint that wants its own shape.
+//!
+//! A threshold fires once per window per reset cycle and re-arms when the
+//! window resets, closing its alert. What has been sent is kept in
+//! `alerts.json` beside the history, so a daemon that restarts mid-cycle
+//! doesn't page again.
+//!
+//! `notify test` sends a made-up alert through every channel, resolving it
+//! at once, to check URLs and keys before a real limit depends on them.
+//!
+//! During a profile's `quiet` hours or days new alerts are held back until
+//! the quiet time ends, and go out then unless the window has reset in the
+//! meantime, its resolve replacing them. The limit's own alert still pages
+//! unless `include_limit` is set; resolves always go out.
+
+use crate::api::{Snapshot, WhamUsage};
+use crate::config::{AlertingConfig, QuietConfig, StepAt};
+use crate::plans;
+use crate::render::{label, window_rows, WindowRow};
+use crate::server::Update;
+use crate::status::age;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use colored::Colorize;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const PAGERDUTY_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const OPSGENIE_URL: &str = "https://api.opsgenie.com/v2/alerts";
+const OPSGENIE_EU_URL: &str = "https://api.eu.opsgenie.com/v2/alerts";
+
+/// Longest we wait for either service before giving up on an event
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a target that failed to take an event waits before another try
+const RETRY_EVERY: Duration = Duration::from_secs(60);
+
+/// How long after a window's reset its old figures may still be served; a
+/// threshold re-arms only once this has passed too
+const CYCLE_SLACK_SECS: i64 = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    PagerDuty,
+    /// `eu` for accounts hosted in Opsgenie's EU region
+    Opsgenie {
+        eu: bool,
+    },
+    /// A JSON POST to the configured URL
+    Webhook,
+    /// notify-send, or Notification Center on macOS
+    Desktop,
+    /// A line on stderr
+    Log,
+}
+
+impl Service {
+    fn name(self) -> &'static str {
+        match self {
+            Service::PagerDuty => "PagerDuty",
+            Service::Opsgenie { .. } => "Opsgenie",
+            Service::Webhook => "Webhook",
+            Service::Desktop => "Desktop",
+            Service::Log => "Log",
+        }
+    }
+}
+
+/// One profile's alerting, keys resolved
+#[derive(Debug, Clone)]
+pub struct Alerts {
+    pub profile: String,
+    /// Percentages of any window that alert on the way to the limit
+    pub thresholds: Vec<Step>,
+    /// Indexes into `targets` the limit's alert goes to
+    pub limit_to: Vec<usize>,
+    pub targets: Vec<Target>,
+    pub quiet: Option<Quiet>,
+}
+
+/// One threshold and where its alerts go
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub percent: f64,
+    /// Indexes into the profile's `targets`
+    pub to: Vec<usize>,
+}
+
+/// When a profile's alerts are held back
+#[derive(Debug, Clone)]
+pub struct Quiet {
+    tz: Tz,
+    /// Start and end of the nightly stretch; the end is first when it wraps
+    hours: Option<(NaiveTime, NaiveTime)>,
+    /// Days quiet from midnight to midnight
+    days: Vec<Weekday>,
+    include_limit: bool,
+}
+
+impl Quiet {
+    fn from_config(cfg: &QuietConfig, profile: &str) -> Result<Self> {
+        let section = format!("[alerting.{profile}.quiet]");
+        let tz = match &cfg.timezone {
+            Some(name) => name
+                .parse::<Tz>()
+                .map_err(|_| anyhow::anyhow!("Unknown time zone '{name}' in {section}"))?,
+            None => Tz::UTC,
+        };
+        let hours = match &cfg.hours {
+            Some(s) => {
+                let time = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok();
+                let parsed = s
+                    .split_once('-')
+                    .and_then(|(a, b)| Some((time(a)?, time(b)?)));
+                let Some(hours) = parsed else {
+                    bail!("Invalid hours '{s}' in {section} (expected e.g. 22:00-07:00)");
+                };
+                Some(hours)
+            }
+            None => None,
+        };
+        let days = match &cfg.days {
+            Some(s) => crate::week::parse_days(s, &format!("{section} days"))?,
+            None => Vec::new(),
+        };
+        if hours.is_none() && days.is_empty() {
+            bail!("{section} needs hours or days");
+        }
+        Ok(Quiet {
+            tz,
+            hours,
+            days,
+            include_limit: cfg.include_limit,
+        })
+    }
+
+    /// Whether `at` falls in quiet time
+    fn covers(&self, at: DateTime<Utc>) -> bool {
+        let local = at.with_timezone(&self.tz);
+        if self.days.contains(&local.weekday()) {
+            return true;
+        }
+        let t = local.time();
+        match self.hours {
+            Some((from, to)) if from <= to => from <= t && t < to,
+            Some((from, to)) => t >= from || t < to,
+            None => false,
+        }
+    }
+
+    /// Whether `alert` is held back at `at`
+    fn holds(&self, alert: &Alert, at: DateTime<Utc>) -> bool {
+        (!alert.limit || self.include_limit) && self.covers(at)
+    }
+
+    /// When the quiet time around `at` is over: the first end of the
+    /// nightly hours or midnight after it that isn't quiet as well
+    fn ends(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let today = at.with_timezone(&self.tz).date_naive();
+        let ends = self
+            .hours
+            .map(|(_, to)| to)
+            .into_iter()
+            .chain([NaiveTime::MIN]);
+        let mut candidates: Vec<DateTime<Utc>> = (0..=8)
+            .flat_map(|days| {
+                let date = today + chrono::Duration::days(days);
+                ends.clone().filter_map(move |t| {
+                    let local = self.tz.from_local_datetime(&date.and_time(t));
+                    Some(local.earliest()?.with_timezone(&Utc))
+                })
+            })
+            .filter(|t| *t > at)
+            .collect();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .find(|t| !self.covers(*t))
+            .unwrap_or(at + chrono::Duration::days(1))
+    }
+}
+
+/// One service to alert
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub service: Service,
+    /// PagerDuty routing (integration) key, Opsgenie API key or webhook
+    /// URL; empty for local channels
+    pub key: String,
+    /// The webhook's body template, checked to parse
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Trigger,
+    Resolve,
+}
+
+/// One alert, as raised or resolved
+#[derive(Debug, Clone, PartialEq)]
+struct Alert {
+    /// The same for every event about this alert, so the service pairs a
+    /// resolve with its trigger and folds repeats into one incident
+    dedup_key: String,
+    /// Empty for a resolve, which needs none
+    summary: String,
+    /// The label of the window it is about, e.g. `5-hour session`
+    window: Option<String>,
+    /// The limit itself, rather than a threshold on the way to it
+    limit: bool,
+    /// Indexes into the profile's targets
+    to: Vec<usize>,
+}
+
+/// How one channel took `notify test`'s alert
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    pub profile: String,
+    /// As named in `notify`, e.g. `pagerduty`
+    pub channel: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What has been sent for one profile, as kept in `alerts.json`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct Sent {
+    /// Whether the limit's incident is open; absent until first known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit_open: Option<bool>,
+    /// Thresholds fired this cycle, e.g. `5h@80`, by when their window resets
+    #[serde(default)]
+    fired: BTreeMap<String, DateTime<Utc>>,
+}
+
+/// One request to a service
+#[derive(Debug)]
+struct Event {
+    url: String,
+    /// `Authorization` header, for services that don't take the key in the body
+    auth: Option<String>,
+    body: Value,
+}
+
+/// Each configured profile's alerting, with its keys resolved
+pub fn configure(
+    config: &BTreeMap<String, AlertingConfig>,
+    profiles: &[&String],
+) -> Result<Vec<Alerts>> {
+    let mut all = Vec::new();
+    for (profile, alerting) in config {
+        if !profiles.contains(&profile) {
+            bail!("[alerting.{profile}] names a profile that isn't configured");
+        }
+        let percents = alerting.escalation.iter().filter_map(|s| match s.at {
+            StepAt::Percent(p) => Some(p),
+            StepAt::Named(_) => None,
+        });
+        if let Some(t) = alerting
+            .thresholds
+            .iter()
+            .copied()
+            .chain(percents)
+            .find(|t| !(0.0..=100.0).contains(t))
+        {
+            bail!("Invalid threshold {t} in [alerting.{profile}] (expected 0–100)");
+        }
+        let services = [
+            (Service::PagerDuty, &alerting.pagerduty),
+            (Service::Opsgenie { eu: false }, &alerting.opsgenie),
+        ];
+        let mut targets = Vec::new();
+        for (service, key) in services {
+            let Some(key_config) = key else { continue };
+            let Some(key) = key_config.resolve() else {
+                bail!(
+                    "[alerting.{profile}.{}] has no key configured",
+                    service.name().to_ascii_lowercase()
+                );
+            };
+            let service = match (service, key_config.region.as_deref()) {
+                (Service::Opsgenie { .. }, Some(region)) => Service::Opsgenie {
+                    eu: region.eq_ignore_ascii_case("eu"),
+                },
+                (service, _) => service,
+            };
+            targets.push(Target {
+                service,
+                key,
+                template: None,
+            });
+        }
+        if let Some(webhook) = &alerting.webhook {
+            let Some(url) = webhook.resolve() else {
+                bail!("[alerting.{profile}.webhook] has no url configured");
+            };
+            if let Some(template) = &webhook.template {
+                tera::Tera::default()
+                    .add_raw_template("webhook", template)
+                    .with_context(|| format!("Invalid [alerting.{profile}.webhook] template"))?;
+            }
+            targets.push(Target {
+                service: Service::Webhook,
+                key: url,
+                template: webhook.template.clone(),
+            });
+        }
+        if targets.is_empty() && alerting.escalation.is_empty() {
+            bail!(
+                "[alerting.{profile}] needs a [alerting.{profile}.pagerduty], .opsgenie or \
+                 .webhook, or escalation steps"
+            );
+        }
+
+        // Unrouted, thresholds and the limit go to every configured service
+        let services: Vec<usize> = (0..targets.len()).collect();
+        let mut thresholds = Vec::new();
+        for &percent in &alerting.thresholds {
+            add_step(&mut thresholds, percent, &services);
+        }
+        let mut limit_to: Option<Vec<usize>> = None;
+        for step in &alerting.escalation {
+            if step.notify.is_empty() {
+                bail!("An escalation step in [alerting.{profile}] notifies no one");
+            }
+            let mut to = Vec::new();
+            for channel in &step.notify {
+                to.push(channel_target(&mut targets, channel, profile)?);
+            }
+            match &step.at {
+                StepAt::Percent(percent) => add_step(&mut thresholds, *percent, &to),
+                StepAt::Named(at) if at == "limit" => {
+                    let limit_to = limit_to.get_or_insert_with(Vec::new);
+                    for i in to {
+                        if !limit_to.contains(&i) {
+                            limit_to.push(i);
+                        }
+                    }
+                }
+                StepAt::Named(at) => bail!(
+                    "Invalid escalation step at = \"{at}\" in [alerting.{profile}] \
+                     (expected 0–100 or \"limit\")"
+                ),
+            }
+        }
+
+        let quiet = match &alerting.quiet {
+            Some(cfg) => Some(Quiet::from_config(cfg, profile)?),
+            None => None,
+        };
+        all.push(Alerts {
+            profile: profile.clone(),
+            thresholds,
+            limit_to: limit_to.unwrap_or(services),
+            targets,
+            quiet,
+        });
+    }
+    Ok(all)
+}
+
+/// `notify test`: trigger and resolve a made-up alert on every target of
+/// `alerts`, or only `channel`'s, ignoring quiet time
+pub fn test(alerts: &[Alerts], channel: Option<&str>) -> Result<Vec<Delivery>> {
+    const CHANNELS: [&str; 5] = ["log", "desktop", "webhook", "pagerduty", "opsgenie"];
+    if let Some(channel) = channel.filter(|c| !CHANNELS.contains(&c.to_ascii_lowercase().as_str()))
+    {
+        bail!(
+            "Unknown alert channel '{channel}' (expected log, desktop, webhook, pagerduty or \
+             opsgenie)"
+        );
+    }
+    if alerts.is_empty() {
+        bail!("No alerts are configured; set up [alerting.<profile>] first");
+    }
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let usage = WhamUsage {
+        plan_type: None,
+        rate_limit: None,
+        additional_rate_limits: Vec::new(),
+        extra: Default::default(),
+    };
+    let snap = Snapshot::new(usage, Utc::now());
+    let mut deliveries = Vec::new();
+    for alerts in alerts {
+        let profile = &alerts.profile;
+        let alert = Alert {
+            dedup_key: format!("codex-usage-{profile}-test"),
+            summary: format!("Test alert from codex-usage for {profile}; nothing is wrong"),
+            window: None,
+            limit: false,
+            to: Vec::new(),
+        };
+        for target in &alerts.targets {
+            let name = target.service.name().to_ascii_lowercase();
+            if channel.is_some_and(|c| !c.eq_ignore_ascii_case(&name)) {
+                continue;
+            }
+            let result = [Action::Trigger, Action::Resolve]
+                .into_iter()
+                .try_for_each(|action| deliver(&client, target, action, &alert, profile, &snap));
+            deliveries.push(Delivery {
+                profile: profile.clone(),
+                channel: name,
+                ok: result.is_ok(),
+                error: result.err().map(|e| format!("{e:#}")),
+            });
+        }
+    }
+    if deliveries.is_empty() {
+        bail!(
+            "No configured profile alerts through {}",
+            channel.unwrap_or_default()
+        );
+    }
+    Ok(deliveries)
+}
+
+/// `notify test`'s results, a line per channel
+pub fn render_test(deliveries: &[Delivery]) -> String {
+    let width = deliveries
+        .iter()
+        .map(|d| d.profile.len())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for d in deliveries {
+        let result = match &d.error {
+            None => format!("{} delivered", "✓".green()),
+            Some(e) => format!("{} {}", "✗".red(), e.lines().next().unwrap_or_default()),
+        };
+        let _ = writeln!(out, "  {:<width$}  {:<9}  {result}", d.profile, d.channel);
+    }
+    out
+}
+
+/// An error naming how many channels failed, if any did
+pub fn ensure_delivered(deliveries: &[Delivery]) -> Result<()> {
+    let failed = deliveries.iter().filter(|d| !d.ok).count();
+    if failed > 0 {
+        bail!("{failed} of {} channels failed", deliveries.len());
+    }
+    Ok(())
+}
+
+/// Send `percent`'s alerts to `to` as well
+fn add_step(steps: &mut Vec<Step>, percent: f64, to: &[usize]) {
+    let i = match steps.iter().position(|s| s.percent == percent) {
+        Some(i) => i,
+        None => {
+            steps.push(Step {
+                percent,
+                to: Vec::new(),
+            });
+            steps.len() - 1
+        }
+    };
+    for &t in to {
+        if !steps[i].to.contains(&t) {
+            steps[i].to.push(t);
+        }
+    }
+}
+
+/// The target an escalation step's `channel` names, adding the local ones
+/// on first use
+fn channel_target(targets: &mut Vec<Target>, channel: &str, profile: &str) -> Result<usize> {
+    if let Some(i) = targets
+        .iter()
+        .position(|t| t.service.name().eq_ignore_ascii_case(channel))
+    {
+        return Ok(i);
+    }
+    let service = match channel.to_ascii_lowercase().as_str() {
+        "log" => Service::Log,
+        "desktop" => Service::Desktop,
+        name @ ("pagerduty" | "opsgenie" | "webhook") => bail!(
+            "[alerting.{profile}] escalates to {name}, but there is no [alerting.{profile}.{name}]"
+        ),
+        _ => bail!(
+            "Unknown alert channel '{channel}' in [alerting.{profile}] escalation \
+             (expected log, desktop, webhook, pagerduty or opsgenie)"
+        ),
+    };
+    targets.push(Target {
+        service,
+        key: String::new(),
+        template: None,
+    });
+    Ok(targets.len() - 1)
+}
+
+/// Raise and resolve alerts as profiles' usage changes. A profile first
+/// seen under its limit with nothing on record sends a resolve, closing an
+/// incident a lost state file forgot. Each target is sent to on its own: one
+/// that fails is retried every `RETRY_EVERY` until it takes the event or a
+/// newer one for the same alert replaces it, and the others aren't sent to
+/// again. Triggers in quiet time wait in the outbox until it ends.
+pub fn run(alerts: &[Alerts], updates: Receiver<Update>) {
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let mut sent = load();
+    let mut outbox = Outbox::default();
+    loop {
+        let wait = outbox.next_due().map_or(RETRY_EVERY, |due| {
+            due.saturating_duration_since(Instant::now())
+        });
+        let update = match updates.recv_timeout(wait) {
+            Ok(update) => Some(update),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if let Some(update) = update {
+            let Some(alerts) = alerts.iter().find(|a| a.profile == update.profile) else {
+                continue;
+            };
+            let prev = sent.get(&update.profile).cloned().unwrap_or_default();
+            let (events, next) = decide(&prev, alerts, &update.snapshot);
+            if next == prev {
+                continue;
+            }
+            queue(
+                &mut outbox,
+                alerts,
+                events,
+                &update.snapshot,
+                (Utc::now(), Instant::now()),
+            );
+            // What's decided is on record now; the outbox owns the sending
+            sent.insert(update.profile, next);
+            if let Err(e) = save(&sent) {
+                eprintln!("alerting: {e:#}");
+            }
+        }
+        outbox.flush(Instant::now(), |p| {
+            deliver(
+                &client,
+                &p.target.1,
+                p.action,
+                &p.alert,
+                &p.profile,
+                &p.snapshot,
+            )
+        });
+    }
+}
+
+/// Put `events` in `outbox` for their targets, due at `now` (by both
+/// clocks), or for a trigger in quiet time when the quiet time ends
+fn queue(
+    outbox: &mut Outbox,
+    alerts: &Alerts,
+    events: Vec<(Action, Alert)>,
+    snap: &Snapshot,
+    now: (DateTime<Utc>, Instant),
+) {
+    let (now, instant) = now;
+    for (action, alert) in events {
+        let mut due = instant;
+        if let Some(quiet) = &alerts.quiet {
+            if action == Action::Trigger && quiet.holds(&alert, now) {
+                let ends = quiet.ends(now);
+                let at = ends.with_timezone(&quiet.tz).format("%a %H:%M");
+                eprintln!(
+                    "alerting: quiet time, holding until {at}: {}",
+                    alert.summary
+                );
+                due += (ends - now).to_std().unwrap_or_default();
+            }
+        }
+        for &i in &alert.to {
+            let Some(target) = alerts.targets.get(i) else {
+                continue;
+            };
+            outbox.push(Pending {
+                profile: alerts.profile.clone(),
+                target: (i, target.clone()),
+                action,
+                alert: alert.clone(),
+                snapshot: snap.clone(),
+                due,
+            });
+        }
+    }
+}
+
+/// One event still to be taken by one target
+#[derive(Debug)]
+struct Pending {
+    profile: String,
+    /// The target's index in the profile's `targets`, and the target
+    target: (usize, Target),
+    action: Action,
+    alert: Alert,
+    snapshot: Snapshot,
+    /// When to try it next
+    due: Instant,
+}
+
+/// Events waiting on their targets, sent as they come due
+#[derive(Debug, Default)]
+struct Outbox {
+    pending: Vec<Pending>,
+}
+
+impl Outbox {
+    /// Queue `event`, replacing one for the same alert and target that
+    /// hasn't gone yet: a resolve makes an unsent trigger moot, and the other
+    /// way round
+    fn push(&mut self, event: Pending) {
+        self.pending.retain(|p| {
+            p.profile != event.profile
+                || p.target.0 != event.target.0
+                || p.alert.dedup_key != event.alert.dedup_key
+        });
+        self.pending.push(event);
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.pending.iter().map(|p| p.due).min()
+    }
+
+    /// Try every event due by `now`, keeping those that fail for another go
+    /// in `RETRY_EVERY`
+    fn flush(&mut self, now: Instant, mut send: impl FnMut(&Pending) -> Result<()>) {
+        self.pending.retain_mut(|p| {
+            if p.due > now {
+                return true;
+            }
+            match send(p) {
+                Ok(()) => false,
+                Err(e) => {
+                    eprintln!("alerting: {e:#}; retrying in {}s", RETRY_EVERY.as_secs());
+                    p.due = now + RETRY_EVERY;
+                    true
+                }
+            }
+        });
+    }
+}
+
+/// The events `snap` calls for after `prev`, and what will then have been
+/// sent
+fn decide(prev: &Sent, alerts: &Alerts, snap: &Snapshot) -> (Vec<(Action, Alert)>, Sent) {
+    let now = snap.fetched_at;
+    let profile = &alerts.profile;
+    let mut next = prev.clone();
+    let mut events = Vec::new();
+
+    let reached = limit_reached(snap);
+    if let Some(action) = limit_action(prev.limit_open, reached) {
+        let alert = Alert {
+            dedup_key: dedup_key(profile, None),
+            summary: limit_summary(profile, snap),
+            window: full_window(snap).map(|row| label(&row)),
+            limit: true,
+            to: alerts.limit_to.clone(),
+        };
+        events.push((action, alert));
+        next.limit_open = Some(reached);
+    }
+
+    // Cycles that are over re-arm, closing what they raised
+    for (key, resets_at) in &prev.fired {
+        if now - *resets_at > chrono::Duration::seconds(CYCLE_SLACK_SECS) {
+            let alert = Alert {
+                dedup_key: dedup_key(profile, Some(key)),
+                summary: String::new(),
+                window: None,
+                limit: false,
+                to: alerts
+                    .step_for(key)
+                    .map(|s| s.to.clone())
+                    .unwrap_or_default(),
+            };
+            events.push((Action::Resolve, alert));
+            next.fired.remove(key);
+        }
+    }
+    for row in window_rows(snap, now) {
+        let pct = row.window.as_ref().and_then(|w| w.used_percent);
+        let (Some(pct), Some(secs)) = (pct, row.resets_in) else {
+            continue;
+        };
+        for step in &alerts.thresholds {
+            let key = threshold_key(&row, step.percent);
+            if pct < step.percent || next.fired.contains_key(&key) {
+                continue;
+            }
+            let alert = Alert {
+                dedup_key: dedup_key(profile, Some(&key)),
+                summary: threshold_summary(profile, &row, pct, snap),
+                window: Some(label(&row)),
+                limit: false,
+                to: step.to.clone(),
+            };
+            events.push((Action::Trigger, alert));
+            next.fired
+                .insert(key, now + chrono::Duration::seconds(secs as i64));
+        }
+    }
+    (events, next)
+}
+
+impl Alerts {
+    /// The step a fired threshold's key, e.g. `5h@80`, came from
+    fn step_for(&self, key: &str) -> Option<&Step> {
+        let (_, percent) = key.rsplit_once('@')?;
+        self.thresholds
+            .iter()
+            .find(|s| s.percent.to_string() == percent)
+    }
+}
+
+/// What to send when a profile's limit is `reached`, given whether its
+/// incident is open (`None` when not yet known)
+fn limit_action(open: Option<bool>, reached: bool) -> Option<Action> {
+    match (open, reached) {
+        (Some(open), reached) if open == reached => None,
+        (_, true) => Some(Action::Trigger),
+        (_, false) => Some(Action::Resolve),
+    }
+}
+
+fn limit_reached(snap: &Snapshot) -> bool {
+    snap.usage
+        .rate_limit
+        .as_ref()
+        .is_some_and(|r| r.limit_reached == Some(true))
+}
+
+/// `5h@80`, or `code_review.5h@80` for a feature limit's window
+fn threshold_key(row: &WindowRow, threshold: f64) -> String {
+    match &row.feature {
+        Some(feature) => format!("{feature}.{}@{threshold}", row.short_span()),
+        None => format!("{}@{threshold}", row.short_span()),
+    }
+}
+
+/// `codex-usage-<profile>` for the limit, with the threshold's key after
+fn dedup_key(profile: &str, threshold: Option<&str>) -> String {
+    match threshold {
+        Some(key) => format!("codex-usage-{profile}-{key}"),
+        None => format!("codex-usage-{profile}"),
+    }
+}
+
+/// Send one event to one target
+fn deliver(
+    client: &Client,
+    target: &Target,
+    action: Action,
+    alert: &Alert,
+    profile: &str,
+    snap: &Snapshot,
+) -> Result<()> {
+    match (target.service, action) {
+        (Service::Log, Action::Trigger) => eprintln!("alerting: {}", alert.summary),
+        (Service::Log, Action::Resolve) => eprintln!("alerting: {} resolved", alert.dedup_key),
+        (Service::Desktop, Action::Trigger) => notify_desktop(&alert.summary, alert.limit)?,
+        // A notification can't be taken back
+        (Service::Desktop, Action::Resolve) => {}
+        _ => {
+            if let Some(event) = event(target, action, alert, profile, snap)? {
+                send(client, &event)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The request for an HTTP service; `None` for local channels
+fn event(
+    target: &Target,
+    action: Action,
+    alert: &Alert,
+    profile: &str,
+    snap: &Snapshot,
+) -> Result<Option<Event>> {
+    let now = snap.fetched_at;
+    if let (Service::Webhook, Some(template)) = (target.service, &target.template) {
+        let body = render_template(template, action, alert, profile, snap)?;
+        return Ok(Some(Event {
+            url: target.key.clone(),
+            auth: None,
+            body,
+        }));
+    }
+    let event = match (target.service, action) {
+        (Service::PagerDuty, Action::Trigger) => Event {
+            url: PAGERDUTY_URL.into(),
+            auth: None,
+            body: json!({
+                "routing_key": target.key,
+                "event_action": "trigger",
+                "dedup_key": alert.dedup_key,
+                "client": "codex-usage",
+                "payload": {
+                    "summary": alert.summary,
+                    "source": "codex-usage",
+                    "severity": if alert.limit { "error" } else { "warning" },
+                    "custom_details": details(profile, snap, now),
+                },
+            }),
+        },
+        (Service::PagerDuty, Action::Resolve) => Event {
+            url: PAGERDUTY_URL.into(),
+            auth: None,
+            body: json!({
+                "routing_key": target.key,
+                "event_action": "resolve",
+                "dedup_key": alert.dedup_key,
+            }),
+        },
+        (Service::Opsgenie { eu }, action) => {
+            let base = if eu { OPSGENIE_EU_URL } else { OPSGENIE_URL };
+            let auth = Some(format!("GenieKey {}", target.key));
+            match action {
+                Action::Trigger => Event {
+                    url: base.into(),
+                    auth,
+                    body: json!({
+                        "message": alert.summary,
+                        "alias": alert.dedup_key,
+                        "source": "codex-usage",
+                        "priority": if alert.limit { "P3" } else { "P4" },
+                        "tags": ["codex-usage"],
+                        "details": details(profile, snap, now),
+                    }),
+                },
+                Action::Resolve => Event {
+                    url: close_url(base, &alert.dedup_key),
+                    auth,
+                    body: json!({
+                        "source": "codex-usage",
+                        "note": "Codex usage limit lifted",
+                    }),
+                },
+            }
+        }
+        (Service::Webhook, Action::Trigger) => Event {
+            url: target.key.clone(),
+            auth: None,
+            body: json!({
+                "action": "trigger",
+                "key": alert.dedup_key,
+                "profile": profile,
+                "summary": alert.summary,
+                "severity": if alert.limit { "critical" } else { "warning" },
+                "details": details(profile, snap, now),
+            }),
+        },
+        (Service::Webhook, Action::Resolve) => Event {
+            url: target.key.clone(),
+            auth: None,
+            body: json!({
+                "action": "resolve",
+                "key": alert.dedup_key,
+                "profile": profile,
+            }),
+        },
+        (Service::Desktop | Service::Log, _) => return Ok(None),
+    };
+    Ok(Some(event))
+}
+
+/// A webhook's templated body. The template gets `action` (`trigger` or
+/// `resolve`), `key`, `profile`, `plan`, `summary`, `severity` and
+/// `details`, and for an alert about one window its `window` label,
+/// `percent`, and `reset` time and `resets_in` when known.
+fn render_template(
+    template: &str,
+    action: Action,
+    alert: &Alert,
+    profile: &str,
+    snap: &Snapshot,
+) -> Result<Value> {
+    let now = snap.fetched_at;
+    let mut context = json!({
+        "action": match action {
+            Action::Trigger => "trigger",
+            Action::Resolve => "resolve",
+        },
+        "key": alert.dedup_key,
+        "profile": profile,
+        "plan": plans::display(snap.usage.plan_type.as_deref().unwrap_or("unknown")),
+        "summary": alert.summary,
+        "severity": if alert.limit { "critical" } else { "warning" },
+        "details": details(profile, snap, now),
+    });
+    let row = window_rows(snap, now)
+        .into_iter()
+        .find(|row| alert.window.as_deref() == Some(label(row).as_str()));
+    if let Some(row) = row {
+        context["window"] = label(&row).into();
+        context["percent"] = json!(row.window.as_ref().and_then(|w| w.used_percent));
+        if let Some(secs) = row.resets_in {
+            let at = now + chrono::Duration::seconds(secs as i64);
+            context["reset"] = at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true).into();
+            context["resets_in"] = age(chrono::Duration::seconds(secs as i64)).into();
+        }
+    }
+    let context = tera::Context::from_value(context)?;
+    let body = tera::Tera::one_off(template, &context, false)
+        .context("Could not render the webhook template")?;
+    serde_json::from_str(&body).context("The webhook template didn't render JSON")
+}
+
+/// Pop up `summary` on this machine's desktop
+fn notify_desktop(summary: &str, urgent: bool) -> Result<()> {
+    let mut command = match cfg!(target_os = "macos") {
+        true => {
+            // A JSON string is a valid AppleScript one for text like this
+            let text = serde_json::to_string(summary)?;
+            let mut c = Command::new("osascript");
+            c.arg("-e").arg(format!(
+                "display notification {text} with title \"codex-usage\""
+            ));
+            c
+        }
+        false => {
+            let mut c = Command::new("notify-send");
+            c.arg("--app-name=codex-usage")
+                .arg(match urgent {
+                    true => "--urgency=critical",
+                    false => "--urgency=normal",
+                })
+                .arg("Codex usage")
+                .arg(summary);
+            c
+        }
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .with_context(|| format!("Could not run {program} for a desktop notification"))?;
+    if !status.success() {
+        bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
+
+/// Opsgenie's close endpoint for the alert with `alias`, which is free text
+/// and so escaped
+fn close_url(base: &str, alias: &str) -> String {
+    let Ok(mut url) = reqwest::Url::parse(base) else {
+        return base.to_string();
+    };
+    if let Ok(mut path) = url.path_segments_mut() {
+        path.push(alias).push("close");
+    }
+    url.set_query(Some("identifierType=alias"));
+    url.to_string()
+}
+
+/// e.g. `Codex usage limit reached for work (Plus), resets in 3h 12m`
+fn limit_summary(profile: &str, snap: &Snapshot) -> String {
+    let plan = plans::display(snap.usage.plan_type.as_deref().unwrap_or("unknown"));
+    let resets_in = full_window(snap).and_then(|row| row.resets_in);
+    format!(
+        "Codex usage limit reached for {profile} ({plan}){}",
+        resets(resets_in)
+    )
+}
+
+/// The full main window that resets last, which is when the limit lifts
+fn full_window(snap: &Snapshot) -> Option<WindowRow> {
+    window_rows(snap, snap.fetched_at)
+        .into_iter()
+        .filter(|row| row.feature.is_none())
+        .filter(|row| {
+            row.window
+                .as_ref()
+                .and_then(|w| w.used_percent)
+                .is_some_and(|p| p >= 100.0)
+        })
+        .max_by_key(|row| row.resets_in)
+}
+
+/// e.g. `Codex 5-hour session at 82% for work (Plus), resets in 3h 12m`
+fn threshold_summary(profile: &str, row: &WindowRow, pct: f64, snap: &Snapshot) -> String {
+    let plan = plans::display(snap.usage.plan_type.as_deref().unwrap_or("unknown"));
+    format!(
+        "Codex {} at {}% for {profile} ({plan}){}",
+        label(row),
+        pct.round(),
+        resets(row.resets_in)
+    )
+}
+
+/// `, resets in 3h 12m`, or nothing when the reset isn't known
+fn resets(secs: Option<u64>) -> String {
+    match secs {
+        Some(secs) => format!(
+            ", resets in {}",
+            age(chrono::Duration::seconds(secs as i64))
+        ),
+        None => String::new(),
+    }
+}
+
+/// Every window's figure and reset, as strings (Opsgenie takes no others)
+fn details(profile: &str, snap: &Snapshot, now: DateTime<Utc>) -> Value {
+    let mut details = Map::new();
+    details.insert("profile".into(), profile.into());
+    let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+    details.insert("plan".into(), plans::display(plan).into());
+    for row in window_rows(snap, now) {
+        let Some(pct) = row.window.as_ref().and_then(|w| w.used_percent) else {
+            continue;
+        };
+        let reset = resets(row.resets_in);
+        details.insert(label(&row), format!("{pct:.1}%{reset}").into());
+    }
+    Value::Object(details)
+}
+
+/// alerts.json under the data directory
+fn path() -> PathBuf {
+    crate::paths::data_dir().join("alerts.json")
+}
+
+/// What earlier runs sent, by profile; nothing when the file is missing or
+/// unreadable, which at worst sends an alert twice
+fn load() -> BTreeMap<String, Sent> {
+    std::fs::read(path())
+        .ok()
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save(sent: &BTreeMap<String, Sent>) -> Result<()> {
+    let path = path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Replace by rename so a crash never leaves half a file
+    let tmp = path.with_extension(format!("json.{}", std::process::id()));
+    std::fs::write(&tmp, serde_json::to_vec_pretty(sent)?)?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("Could not write {}", path.display()))
+}
+
+fn send(client: &Client, event: &Event) -> Result<()> {
+    let mut request = client
+        .post(&event.url)
+        .header("User-Agent", "codex-usage")
+        .json(&event.body);
+    if let Some(auth) = &event.auth {
+        request = request.header("Authorization", auth);
+    }
+    let host = reqwest::Url::parse(&event.url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let resp = request
+        .send()
+        .with_context(|| format!("Failed to reach {host}"))?;
+    let status = resp.status();
+    if !status.is_success() {
+        let detail = crate::api::read_snippet(resp);
+        bail!("{host} returned HTTP {status}: {detail}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(five_hour: f64, reset: u64, at: DateTime<Utc>) -> Snapshot {
+        Snapshot::plus((five_hour, reset), (40.0, 86400), at)
+    }
+
+    #[test]
+    fn events_for_each_service() {
+        let now = Utc::now();
+        let snap = snapshot(100.0, 3 * 3600 + 720, now);
+        let limit = Alert {
+            dedup_key: dedup_key("work", None),
+            summary: limit_summary("work", &snap),
+            window: None,
+            limit: true,
+            to: Vec::new(),
+        };
+
+        let pagerduty = Target {
+            service: Service::PagerDuty,
+            key: "R0UT1NG".into(),
+            template: None,
+        };
+        let trigger = event(&pagerduty, Action::Trigger, &limit, "work", &snap)
+            .unwrap()
+            .unwrap();
+        assert_eq!(trigger.url, PAGERDUTY_URL);
+        assert_eq!(trigger.body["routing_key"], "R0UT1NG");
+        assert_eq!(trigger.body["dedup_key"], "codex-usage-work");
+        assert_eq!(
+            trigger.body["payload"]["summary"],
+            "Codex usage limit reached for work (Plus), resets in 3h 12m"
+        );
+        assert_eq!(
+            trigger.body["payload"]["custom_details"]["5-hour session"],
+            "100.0%, resets in 3h 12m"
+        );
+
+        let opsgenie = Target {
+            service: Service::Opsgenie { eu: true },
+            key: "G3N1E".into(),
+            template: None,
+        };
+        let alert = Alert {
+            dedup_key: dedup_key("my team", Some("5h@80")),
+            ..limit.clone()
+        };
+        let resolve = event(&opsgenie, Action::Resolve, &alert, "my team", &snap)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            resolve.url,
+            "https://api.eu.opsgenie.com/v2/alerts/codex-usage-my%20team-5h@80/close?identifierType=alias"
+        );
+        assert_eq!(resolve.auth.as_deref(), Some("GenieKey G3N1E"));
+
+        let webhook = Target {
+            service: Service::Webhook,
+            key: "https://hooks.example.com/codex".into(),
+            template: None,
+        };
+        let trigger = event(&webhook, Action::Trigger, &limit, "work", &snap)
+            .unwrap()
+            .unwrap();
+        assert_eq!(trigger.url, "https://hooks.example.com/codex");
+        assert_eq!(trigger.body["action"], "trigger");
+        assert_eq!(trigger.body["severity"], "critical");
+        assert_eq!(trigger.body["key"], "codex-usage-work");
+
+        let log = Target {
+            service: Service::Log,
+            key: String::new(),
+            template: None,
+        };
+        assert!(event(&log, Action::Trigger, &limit, "work", &snap)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn webhook_templates_render_the_alert() {
+        let now = crate::clock::parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let snap = snapshot(82.4, 3 * 3600 + 720, now);
+        let row = window_rows(&snap, now).into_iter().next().unwrap();
+        let alert = Alert {
+            dedup_key: dedup_key("work", Some("5h@80")),
+            summary: threshold_summary("work", &row, 82.4, &snap),
+            window: Some(label(&row)),
+            limit: false,
+            to: Vec::new(),
+        };
+        let slack = Target {
+            service: Service::Webhook,
+            key: "https://hooks.slack.com/services/T0/B0/x".into(),
+            template: Some(
+                r#"{"text": {{ profile ~ ": " ~ window ~ " at " ~ percent ~ "%, resets "
+                    ~ reset ~ " (in " ~ resets_in ~ ")" | json_encode() }},
+                    "severity": "{{ severity }}", "plan": "{{ plan }}"}"#
+                    .into(),
+            ),
+        };
+        let trigger = event(&slack, Action::Trigger, &alert, "work", &snap)
+            .unwrap()
+            .unwrap();
+        assert_eq!(trigger.url, "https://hooks.slack.com/services/T0/B0/x");
+        assert_eq!(
+            trigger.body,
+            json!({
+                "text": "work: 5-hour session at 82.4%, resets 2024-05-01T13:12:00Z (in 3h 12m)",
+                "severity": "warning",
+                "plan": "Plus",
+            })
+        );
+
+        // A resolve has no window to speak of
+        let resolve = Alert {
+            summary: String::new(),
+            window: None,
+            ..alert.clone()
+        };
+        let ntfy = Target {
+            template: Some(
+                r#"{"topic": "codex", "message": "{{ action }} {{ key }}{% if window %} {{ window }}{% endif %}"}"#
+                    .into(),
+            ),
+            ..slack.clone()
+        };
+        let body = event(&ntfy, Action::Resolve, &resolve, "work", &snap)
+            .unwrap()
+            .unwrap()
+            .body;
+        assert_eq!(body["message"], "resolve codex-usage-work-5h@80");
+
+        let broken = Target {
+            template: Some("not {{ profile }} JSON".into()),
+            ..slack
+        };
+        let err = event(&broken, Action::Trigger, &alert, "work", &snap).unwrap_err();
+        assert!(format!("{err:#}").contains("didn't render JSON"), "{err:#}");
+
+        // A template that won't parse is caught at start
+        let config: BTreeMap<String, AlertingConfig> = toml::from_str(
+            r#"
+            [team.webhook]
+            url = "https://hooks.example.com/codex"
+            template = "{{ profile"
+            "#,
+        )
+        .unwrap();
+        let err = configure(&config, &[&"team".to_string()]).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid [alerting.team.webhook] template");
+    }
+
+    #[test]
+    fn thresholds_fire_once_per_cycle() {
+        let step = |percent| Step {
+            percent,
+            to: Vec::new(),
+        };
+        let alerts = Alerts {
+            profile: "work".into(),
+            thresholds: vec![step(80.0), step(95.0)],
+            limit_to: Vec::new(),
+            targets: Vec::new(),
+            quiet: None,
+        };
+        let start = Utc::now();
+        let at = |mins| start + chrono::Duration::minutes(mins);
+        let sent = |events: &[(Action, Alert)]| -> Vec<(Action, String)> {
+            events
+                .iter()
+                .map(|(a, alert)| (*a, alert.dedup_key.clone()))
+                .collect()
+        };
+
+        // First sight, under every threshold: the limit is resolved in case
+        let (events, state) = decide(&Sent::default(), &alerts, &snapshot(50.0, 3600, at(0)));
+        assert_eq!(
+            sent(&events),
+            [(Action::Resolve, "codex-usage-work".into())]
+        );
+
+        let (events, state) = decide(&state, &alerts, &snapshot(82.0, 3000, at(10)));
+        assert_eq!(
+            sent(&events),
+            [(Action::Trigger, "codex-usage-work-5h@80".into())]
+        );
+
+        // Still over 80 in the same cycle, as after a restart: nothing new
+        let saved: Sent = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        let (events, state) = decide(&saved, &alerts, &snapshot(88.0, 2400, at(20)));
+        assert!(events.is_empty());
+
+        // The window resets: 80 re-arms and its alert closes
+        let (events, state) = decide(&state, &alerts, &snapshot(5.0, 18000, at(75)));
+        assert_eq!(
+            sent(&events),
+            [(Action::Resolve, "codex-usage-work-5h@80".into())]
+        );
+        assert!(state.fired.is_empty());
+
+        let (events, _) = decide(&state, &alerts, &snapshot(85.0, 9000, at(200)));
+        assert_eq!(
+            sent(&events),
+            [(Action::Trigger, "codex-usage-work-5h@80".into())]
+        );
+    }
+
+    #[test]
+    fn quiet_hours_and_days() {
+        let cfg = QuietConfig {
+            hours: Some("22:00-07:00".into()),
+            days: Some("sun".into()),
+            timezone: Some("Australia/Brisbane".into()),
+            include_limit: false,
+        };
+        let quiet = Quiet::from_config(&cfg, "work").unwrap();
+        // Brisbane is UTC+10 all year; 2024-05-03 is a Friday
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        assert!(quiet.covers(at("2024-05-03T12:30:00Z"))); // 22:30 Fri
+        assert!(quiet.covers(at("2024-05-02T20:59:00Z"))); // 06:59 Fri
+        assert!(!quiet.covers(at("2024-05-02T21:00:00Z"))); // 07:00 Fri
+        assert!(!quiet.covers(at("2024-05-03T04:00:00Z"))); // 14:00 Fri
+        assert!(quiet.covers(at("2024-05-05T04:00:00Z"))); // 14:00 Sun
+
+        let night = at("2024-05-03T12:30:00Z");
+        let alert = |limit| Alert {
+            dedup_key: String::new(),
+            summary: String::new(),
+            window: None,
+            limit,
+            to: Vec::new(),
+        };
+        assert!(quiet.holds(&alert(false), night));
+        assert!(!quiet.holds(&alert(true), night));
+        let all = Quiet {
+            include_limit: true,
+            ..quiet
+        };
+        assert!(all.holds(&alert(true), night));
+
+        let bad = QuietConfig {
+            hours: Some("10pm-7am".into()),
+            ..Default::default()
+        };
+        assert!(Quiet::from_config(&bad, "work")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid hours '10pm-7am' in [alerting.work.quiet]"));
+    }
+
+    #[test]
+    fn quiet_time_holds_triggers_until_it_ends() {
+        let cfg = QuietConfig {
+            hours: Some("22:00-07:00".into()),
+            days: Some("sun".into()),
+            timezone: Some("Australia/Brisbane".into()),
+            include_limit: false,
+        };
+        let quiet = Quiet::from_config(&cfg, "work").unwrap();
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        // 22:30 Fri to 07:00 Sat, and 23:00 Sat through Sunday to 07:00 Mon
+        let friday = at("2024-05-03T12:30:00Z");
+        assert_eq!(quiet.ends(friday), at("2024-05-03T21:00:00Z"));
+        assert_eq!(
+            quiet.ends(at("2024-05-04T13:00:00Z")),
+            at("2024-05-05T21:00:00Z")
+        );
+
+        let alerts = Alerts {
+            profile: "work".into(),
+            thresholds: vec![Step {
+                percent: 80.0,
+                to: vec![0],
+            }],
+            limit_to: vec![0],
+            targets: vec![Target {
+                service: Service::Log,
+                key: String::new(),
+                template: None,
+            }],
+            quiet: Some(quiet),
+        };
+        let sent = |outbox: &mut Outbox, now| {
+            let mut sent = Vec::new();
+            outbox.flush(now, |p| {
+                sent.push((p.action, p.alert.dedup_key.clone()));
+                Ok(())
+            });
+            sent
+        };
+        let (events, state) = decide(
+            &Sent {
+                limit_open: Some(false),
+                ..Default::default()
+            },
+            &alerts,
+            &snapshot(85.0, 5 * 3600, friday),
+        );
+        let start = Instant::now();
+        let mut outbox = Outbox::default();
+        queue(
+            &mut outbox,
+            &alerts,
+            events,
+            &snapshot(85.0, 5 * 3600, friday),
+            (friday, start),
+        );
+        // Nothing goes out in the night, but it isn't forgotten either
+        assert!(sent(&mut outbox, start).is_empty());
+        let morning = start + Duration::from_secs(8 * 3600 + 30 * 60);
+        assert_eq!(outbox.next_due(), Some(morning));
+        assert_eq!(
+            sent(&mut outbox, morning),
+            [(Action::Trigger, "codex-usage-work-5h@80".into())]
+        );
+
+        // Held again, but the window resets before morning: only its resolve
+        // goes out, at once
+        let (events, state) = decide(
+            &Sent {
+                fired: BTreeMap::new(),
+                ..state
+            },
+            &alerts,
+            &snapshot(85.0, 3600, friday),
+        );
+        queue(
+            &mut outbox,
+            &alerts,
+            events,
+            &snapshot(85.0, 3600, friday),
+            (friday, start),
+        );
+        let later = friday + chrono::Duration::hours(2);
+        let (events, _) = decide(&state, &alerts, &snapshot(3.0, 5 * 3600, later));
+        let hour = start + Duration::from_secs(2 * 3600);
+        queue(
+            &mut outbox,
+            &alerts,
+            events,
+            &snapshot(3.0, 5 * 3600, later),
+            (later, hour),
+        );
+        assert_eq!(
+            sent(&mut outbox, hour),
+            [(Action::Resolve, "codex-usage-work-5h@80".into())]
+        );
+        assert_eq!(outbox.next_due(), None);
+    }
+
+    #[test]
+    fn escalation_routes_steps_to_channels() {
+        let config = |extra: &str| -> BTreeMap<String, AlertingConfig> {
+            let raw = format!(
+                r#"
+                [team.pagerduty]
+                key = "R0UT1NG"
+                [team.webhook]
+                url = "https://hooks.example.com/codex"
+                [team]
+                escalation = [
+                    {{ at = 70, notify = ["desktop", "log"] }},
+                    {{ at = 90, notify = ["webhook"] }},
+                    {{ at = "limit", notify = ["pagerduty"] }},
+                    {extra}
+                ]
+                "#
+            );
+            toml::from_str(&raw).unwrap()
+        };
+        let team = "team".to_string();
+        let alerts = configure(&config(""), &[&team]).unwrap().remove(0);
+        let to = |alert: &Alert| -> Vec<&str> {
+            alert
+                .to
+                .iter()
+                .map(|&i| alerts.targets[i].service.name())
+                .collect()
+        };
+
+        let now = Utc::now();
+        let (events, _) = decide(&Sent::default(), &alerts, &snapshot(100.0, 3600, now));
+        let routes: Vec<(&str, Vec<&str>)> = events
+            .iter()
+            .map(|(_, alert)| (alert.dedup_key.as_str(), to(alert)))
+            .collect();
+        assert_eq!(
+            routes,
+            [
+                ("codex-usage-team", vec!["PagerDuty"]),
+                ("codex-usage-team-5h@70", vec!["Desktop", "Log"]),
+                ("codex-usage-team-5h@90", vec!["Webhook"]),
+            ]
+        );
+
+        let err = configure(&config(r#"{ at = 50, notify = ["slack"] }"#), &[&team])
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Unknown alert channel 'slack'"), "{err}");
+        let err = configure(&config(r#"{ at = 50, notify = ["opsgenie"] }"#), &[&team])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("there is no [alerting.team.opsgenie]"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_fires_each_channel() {
+        colored::control::set_override(false);
+        let target = |service, key: &str| Target {
+            service,
+            key: key.into(),
+            template: None,
+        };
+        let alerts = Alerts {
+            profile: "team".into(),
+            thresholds: Vec::new(),
+            limit_to: Vec::new(),
+            targets: vec![
+                target(Service::Log, ""),
+                // Nothing listens on the discard port
+                target(Service::Webhook, "http://127.0.0.1:9/hook"),
+            ],
+            quiet: None,
+        };
+        let deliveries = test(std::slice::from_ref(&alerts), None).unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert!(deliveries[0].ok && !deliveries[1].ok);
+        let out = render_test(&deliveries);
+        assert!(out.starts_with("  team  log        ✓ delivered\n"), "{out}");
+        assert!(out.contains("  team  webhook    ✗ Failed to reach 127.0.0.1"));
+        assert!(ensure_delivered(&deliveries).is_err());
+
+        let only_log = test(std::slice::from_ref(&alerts), Some("log")).unwrap();
+        assert_eq!(only_log.len(), 1);
+        assert!(test(&[alerts], Some("slack")).is_err());
+    }
+
+    #[test]
+    fn failed_targets_are_retried_alone() {
+        let snap = snapshot(100.0, 3600, Utc::now());
+        let pending = |target: usize, action| Pending {
+            profile: "work".into(),
+            target: (
+                target,
+                Target {
+                    service: Service::Webhook,
+                    key: format!("http://hook/{target}"),
+                    template: None,
+                },
+            ),
+            action,
+            alert: Alert {
+                dedup_key: dedup_key("work", None),
+                summary: limit_summary("work", &snap),
+                window: None,
+                limit: true,
+                to: vec![0, 1],
+            },
+            snapshot: snap.clone(),
+            due: Instant::now(),
+        };
+        let mut outbox = Outbox::default();
+        outbox.push(pending(0, Action::Trigger));
+        outbox.push(pending(1, Action::Trigger));
+
+        // Target 1 is down: only it stays queued, due a minute on
+        let now = Instant::now();
+        let mut tried = Vec::new();
+        outbox.flush(now, |p| {
+            tried.push(p.target.0);
+            match p.target.0 {
+                1 => bail!("down"),
+                _ => Ok(()),
+            }
+        });
+        assert_eq!(tried, [0, 1]);
+        assert_eq!(outbox.pending.len(), 1);
+        assert_eq!(outbox.next_due(), Some(now + RETRY_EVERY));
+
+        // Not yet due, so nothing is sent
+        outbox.flush(now, |_| bail!("sent early"));
+        assert_eq!(outbox.pending.len(), 1);
+
+        // The limit lifts before target 1 is back: the resolve replaces the
+        // trigger it never took
+        outbox.push(pending(1, Action::Resolve));
+        let mut sent = Vec::new();
+        outbox.flush(Instant::now(), |p| {
+            sent.push((p.target.0, p.action));
+            Ok(())
+        });
+        assert_eq!(sent, [(1, Action::Resolve)]);
+        assert_eq!(outbox.next_due(), None);
+    }
+
+    #[test]
+    fn error_bodies_are_cut_short() {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 4096]);
+            // A proxy's error page, far bigger than anyone needs to read
+            let body = "<p>upstream unavailable</p>\n".repeat(40_000);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 502 Bad Gateway\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+        });
+        let event = Event {
+            url,
+            auth: None,
+            body: json!({}),
+        };
+        let err = send(&Client::new(), &event).unwrap_err().to_string();
+        assert!(
+            err.starts_with("127.0.0.1 returned HTTP 502 Bad Gateway: <p>"),
+            "{err}"
+        );
+        assert!(err.ends_with("… (truncated)") && err.len() < 400, "{err}");
+    }
+}
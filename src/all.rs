@@ -0,0 +1,250 @@
+//! `all`: every configured profile on one screen. Profiles are fetched at
+//! once, each with its own deadline. The dashboard is drawn as soon as every
+//! profile has either answered or timed out; a slow one shows as "timed out
+//! (retrying)" and its row is filled in when a retry gets through, so one
+//! stuck account never holds up the rest.
+
+use crate::api::{self, Snapshot, TimedOut};
+use crate::clock::Clock;
+use crate::config::{Config, Profile};
+use crate::render::layout::fit_width;
+use crate::render::{self, Options};
+use anyhow::Result;
+use colored::Colorize;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Per-attempt deadline when neither `--timeout` nor the profile sets one
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Attempts per profile before a timeout is final
+const ATTEMPTS: u32 = 3;
+
+const PROFILE_WIDTH: usize = 14;
+const BAR_WIDTH: usize = 12;
+
+/// Where one profile's row stands
+#[derive(Debug)]
+pub enum Row {
+    Waiting,
+    /// Timed out; attempt `n` of `ATTEMPTS` is under way
+    Retrying(u32),
+    Ready(Box<Snapshot>),
+    Failed(String),
+}
+
+impl Row {
+    fn is_final(&self) -> bool {
+        matches!(self, Row::Ready(_) | Row::Failed(_))
+    }
+}
+
+/// Fetch every profile and draw the dashboard, updating rows as late
+/// answers arrive. `timeout` (from `--timeout`) overrides each profile's own.
+pub fn run(
+    config: &Config,
+    clock: &Clock,
+    timeout: Option<Duration>,
+    opts: &Options,
+) -> Result<()> {
+    let names = config.profile_names();
+    let profiles: Vec<Profile> = names
+        .iter()
+        .map(|n| config.profile(n))
+        .collect::<Result<_>>()?;
+    let mut rows: Vec<(String, Row)> = names.into_iter().map(|n| (n, Row::Waiting)).collect();
+    let tty = crate::term::capability().tty;
+
+    let (tx, rx) = mpsc::channel::<(usize, Row)>();
+    std::thread::scope(|scope| {
+        for (i, profile) in profiles.iter().enumerate() {
+            let tx = tx.clone();
+            let deadline = timeout
+                .or(profile.timeout_seconds.map(Duration::from_secs))
+                .unwrap_or(DEFAULT_TIMEOUT);
+            scope.spawn(move || watch(profile, deadline, clock, |row| tx.send((i, row)).is_ok()));
+        }
+        drop(tx);
+
+        // Lines on screen from the last draw, once the first one has happened
+        let mut drawn: Option<usize> = None;
+        for (i, row) in rx {
+            let became_final = row.is_final();
+            rows[i].1 = row;
+            match drawn {
+                None if rows.iter().all(|(_, r)| !matches!(r, Row::Waiting)) => {
+                    let out = render(&rows, opts);
+                    print!("{out}");
+                    drawn = Some(out.lines().count());
+                }
+                None => {}
+                Some(lines) if tty => {
+                    // Move back over the last draw and replace it
+                    let out = render(&rows, opts);
+                    print!("\x1b[{lines}A\x1b[J{out}");
+                    drawn = Some(out.lines().count());
+                }
+                Some(_) if became_final => {
+                    // Not a terminal: append the late row on its own
+                    let mut line = String::new();
+                    write_row(&mut line, &rows[i].0, &rows[i].1, opts);
+                    print!("{line}");
+                }
+                Some(_) => {}
+            }
+            let _ = std::io::stdout().flush();
+        }
+    });
+    Ok(())
+}
+
+/// Fetch one profile, retrying timeouts, reporting each change of state
+fn watch(profile: &Profile, timeout: Duration, clock: &Clock, report: impl Fn(Row) -> bool) {
+    let creds = match profile.credentials() {
+        Ok(c) => c,
+        Err(e) => {
+            report(Row::Failed(format!("{e:#}")));
+            return;
+        }
+    };
+    for attempt in 1..=ATTEMPTS {
+        match api::fetch_usage_within(&creds, timeout) {
+            Ok(usage) => {
+                report(Row::Ready(Box::new(Snapshot::new(usage, clock.now()))));
+                return;
+            }
+            Err(e) if e.is::<TimedOut>() && attempt < ATTEMPTS => {
+                if !report(Row::Retrying(attempt + 1)) {
+                    return;
+                }
+            }
+            Err(e) => {
+                report(Row::Failed(format!("{e:#}")));
+                return;
+            }
+        }
+    }
+}
+
+/// The whole dashboard
+pub fn render(rows: &[(String, Row)], opts: &Options) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "\n  {} Codex usage — all profiles", "◆".cyan().bold());
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+    let mut rows: Vec<_> = rows.iter().collect();
+    opts.sort.apply(&mut rows, |(name, row)| {
+        let usage = match row {
+            Row::Ready(snap) => snap
+                .usage
+                .rate_limit
+                .as_ref()
+                .map(|rl| (rl.limit_reached.unwrap_or(false), rl.highest())),
+            _ => None,
+        };
+        (name, usage)
+    });
+    for (name, row) in rows {
+        write_row(&mut out, name, row, opts);
+    }
+    let _ = writeln!(out, "  {}\n", "─".repeat(67).dimmed());
+    out
+}
+
+fn write_row(out: &mut String, name: &str, row: &Row, opts: &Options) {
+    let name = fit_width(name, PROFILE_WIDTH);
+    match row {
+        Row::Waiting => {
+            let _ = writeln!(out, "  {} {}", name.bold(), "waiting…".dimmed());
+        }
+        Row::Retrying(attempt) => {
+            let _ = writeln!(
+                out,
+                "  {} {} {}",
+                name.bold(),
+                "⧗ timed out (retrying)".yellow(),
+                format!("attempt {attempt} of {ATTEMPTS}").dimmed()
+            );
+        }
+        Row::Failed(err) => {
+            let first_line = err.lines().next().unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "  {} {} {}",
+                name.bold(),
+                "✗".red(),
+                first_line.dimmed()
+            );
+        }
+        Row::Ready(snap) => {
+            let rl = snap.usage.rate_limit.as_ref();
+            let cell = |name: &str, w: Option<&api::RateWindow>| {
+                let label = w
+                    .and_then(|w| w.span(name))
+                    .or_else(|| api::assumed_length(name).and_then(api::Span::from_seconds))
+                    .map(api::Span::short)
+                    .unwrap_or_default();
+                match w {
+                    Some(w) => {
+                        let pct = w.used_percent.unwrap_or(0.0).min(100.0);
+                        format!(
+                            "{label} {} {:>5}%",
+                            render::usage_bar(pct, BAR_WIDTH, opts),
+                            opts.locale.num(pct, 1)
+                        )
+                    }
+                    None => format!("{label} {}", "—".dimmed()),
+                }
+            };
+            let limited = match rl.and_then(|r| r.limit_reached).unwrap_or(false) {
+                true => format!("  {}", "limit reached".red().bold()),
+                false => String::new(),
+            };
+            let _ = writeln!(
+                out,
+                "  {} {}  {}{limited}",
+                name.bold(),
+                cell("primary", rl.and_then(|r| r.primary_window.as_ref())),
+                cell("secondary", rl.and_then(|r| r.secondary_window.as_ref())),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn slow_profiles_dont_hide_the_rest() {
+        colored::control::set_override(false);
+        let now = Utc::now();
+        let snap = Snapshot::plus((42.0, 3600), (18.0, 3600), now);
+        let rows = vec![
+            ("home".to_string(), Row::Ready(Box::new(snap))),
+            ("work".to_string(), Row::Retrying(2)),
+            ("client".to_string(), Row::Failed("HTTP 500\nbody".into())),
+        ];
+        let out = render(&rows, &Options::default());
+        let line = |name: &str| out.lines().find(|l| l.contains(name)).unwrap().to_string();
+        assert!(line("home").contains("42.0%") && line("home").contains("18.0%"));
+        assert!(line("work").contains("timed out (retrying)"));
+        assert!(line("client").ends_with("✗ HTTP 500"));
+        // Profiles with usage first, then the rest by name
+        let order = |out: &str| -> Vec<String> {
+            out.lines()
+                .filter_map(|l| l.split_whitespace().next())
+                .filter(|w| ["home", "work", "client"].contains(w))
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(order(&out), ["home", "client", "work"]);
+        let by_name = Options {
+            sort: render::Sort::Name,
+            ..Options::default()
+        };
+        assert_eq!(order(&render(&rows, &by_name)), ["client", "home", "work"]);
+    }
+}
@@ -0,0 +1,128 @@
+//! `--anonymize`: output that is safe to screenshot for a public issue or a
+//! blog post. The plan, the workspace and the hook command are masked, and
+//! so are email addresses, account ids and the home directory in `status`
+//! and `--dry-run`; bars, percentages and countdowns stay as they are.
+//! Only what is shown changes: history, the cache and pushes to InfluxDB or
+//! StatsD keep the real values.
+
+use crate::api::Snapshot;
+use crate::auth::Identity;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Stands in for anything masked
+pub const MASK: &str = "•••";
+
+static ANONYMIZE: AtomicBool = AtomicBool::new(false);
+
+/// Mask output for the rest of the run
+pub fn enable() {
+    ANONYMIZE.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    #[cfg(test)]
+    if THIS_THREAD.get() {
+        return true;
+    }
+    ANONYMIZE.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+thread_local! {
+    static THIS_THREAD: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Mask output on this thread only, so a test doesn't unmask or mask the
+/// others running beside it
+#[cfg(test)]
+pub fn enable_on_this_thread() {
+    THIS_THREAD.set(true);
+}
+
+/// `snap` as it may be shown
+pub fn snapshot(snap: &Snapshot) -> Cow<'_, Snapshot> {
+    match enabled() {
+        true => Cow::Owned(masked(snap)),
+        false => Cow::Borrowed(snap),
+    }
+}
+
+fn masked(snap: &Snapshot) -> Snapshot {
+    let mut shown = snap.clone();
+    mask(&mut shown.usage.plan_type);
+    mask(&mut shown.command);
+    if let Some(workspace) = &mut shown.workspace {
+        mask(&mut workspace.name);
+    }
+    shown
+}
+
+/// Mask the email and plan of `identity`
+pub fn identity(identity: &mut Identity) {
+    if enabled() {
+        mask(&mut identity.email);
+        mask(&mut identity.plan);
+    }
+}
+
+/// `text` with the home directory, which names the user, shown as `~`
+pub fn home(text: &str) -> String {
+    match std::env::var("HOME") {
+        Ok(home) if enabled() && !home.is_empty() => text.replace(&home, "~"),
+        _ => text.to_string(),
+    }
+}
+
+fn mask(value: &mut Option<String>) {
+    if value.is_some() {
+        *value = Some(MASK.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+    use crate::render::{Format, Options};
+    use crate::workspace::Workspace;
+
+    #[test]
+    fn masks_names_but_not_usage() {
+        colored::control::set_override(false);
+        let now = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let usage = include_str!("../tests/fixtures/extra_windows.json");
+        let mut snap = Snapshot::new(serde_json::from_str(usage).unwrap(), now);
+        snap.workspace = Some(Workspace {
+            name: Some("Acme Corp".into()),
+            role: Some("admin".into()),
+        });
+        let fancy = Format::Fancy.renderer(Options::default());
+        let shown = fancy.render(&masked(&snap), now);
+        assert!(shown.contains("OpenAI ••• Plan — Codex Usage Limits · ••• (admin)"));
+        assert!(!shown.contains("Team") && !shown.contains("Acme"));
+        // Everything below the header is untouched
+        let real = fancy.render(&snap, now);
+        assert!(shown.lines().skip(1).eq(real.lines().skip(1)));
+
+        // Nor do status bars, prompts or machine formats give them away
+        for name in Format::NAMES
+            .iter()
+            .filter(|n| !["fancy", "plain"].contains(n))
+        {
+            let format: Format = name.parse().unwrap();
+            let out = format
+                .renderer(Options::default())
+                .render(&masked(&snap), now);
+            assert!(
+                !out.to_lowercase().contains("team") && !out.contains("Acme"),
+                "{name}: {out}"
+            );
+        }
+
+        // Only with --anonymize given
+        assert!(matches!(snapshot(&snap), Cow::Borrowed(_)));
+        enable_on_this_thread();
+        assert_eq!(snapshot(&snap).usage.plan_type.as_deref(), Some(MASK));
+    }
+}
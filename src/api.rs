@@ -0,0 +1,892 @@
+//! The `/wham/usage` endpoint: response schema and the HTTP call itself.
+
+use crate::auth::Credentials;
+use crate::har;
+use crate::history::{Mark, SessionMark};
+use crate::timing;
+use crate::workspace::Workspace;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// ─── API response types ───────────────────────────────────────────────────────
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateWindow {
+    /// 0–100 percent used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_percent: Option<f64>,
+    /// seconds until window resets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_after_seconds: Option<u64>,
+    /// unix timestamp of the reset, when the API includes it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_at: Option<i64>,
+    /// length of the window in seconds, when the API includes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_window_seconds: Option<u64>,
+}
+
+impl RateWindow {
+    /// Seconds until this window resets as seen at `now`, for a sample taken
+    /// at `fetched_at`. The relative countdown is preferred because it does
+    /// not depend on the local clock agreeing with the server's.
+    pub fn resets_in(&self, fetched_at: DateTime<Utc>, now: DateTime<Utc>) -> Option<u64> {
+        if let Some(after) = self.reset_after_seconds {
+            let elapsed = (now - fetched_at).num_seconds();
+            return Some((after as i64 - elapsed).max(0) as u64);
+        }
+        self.reset_at.map(|at| (at - now.timestamp()).max(0) as u64)
+    }
+
+    /// Length in seconds: `limit_window_seconds` when the API sends it, else
+    /// the usual length of the window it calls `name`
+    pub fn length(&self, name: &str) -> Option<u64> {
+        self.limit_window_seconds
+            .filter(|&s| s > 0)
+            .or_else(|| assumed_length(name))
+    }
+
+    /// The length as a [`Span`], for labels
+    pub fn span(&self, name: &str) -> Option<Span> {
+        self.length(name).and_then(Span::from_seconds)
+    }
+}
+
+/// What the main limit's windows have always been, for responses that don't
+/// say. Feature limits are taken to follow the same layout.
+pub fn assumed_length(name: &str) -> Option<u64> {
+    match name {
+        "primary" => Some(5 * 3_600),
+        "secondary" => Some(7 * 86_400),
+        _ => None,
+    }
+}
+
+/// A window's length in its largest whole unit. Labels are built from this
+/// rather than from which window it is, so a plan whose windows differ in
+/// length is labelled by what they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub count: u64,
+    pub unit: Unit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Span {
+    pub fn from_seconds(secs: u64) -> Option<Span> {
+        let (count, unit) = match secs {
+            0 => return None,
+            s if s % 86_400 == 0 => (s / 86_400, Unit::Day),
+            s if s % 3_600 == 0 => (s / 3_600, Unit::Hour),
+            s => (s.div_ceil(60), Unit::Minute),
+        };
+        Some(Span { count, unit })
+    }
+
+    /// `30m`, `5h`, `7d`
+    pub fn short(self) -> String {
+        let unit = match self.unit {
+            Unit::Minute => 'm',
+            Unit::Hour => 'h',
+            Unit::Day => 'd',
+        };
+        format!("{}{unit}", self.count)
+    }
+
+    /// `hourly`, `daily` or `weekly` for those exact lengths
+    pub fn period(self) -> Option<&'static str> {
+        match (self.count, self.unit) {
+            (1, Unit::Hour) => Some("hourly"),
+            (1, Unit::Day) => Some("daily"),
+            (7, Unit::Day) => Some("weekly"),
+            _ => None,
+        }
+    }
+
+    /// `weekly` and the like, else `30-minute`, `5-hour`, `14-day`
+    pub fn adjective(self) -> String {
+        match self.period() {
+            Some(period) => period.to_string(),
+            None => self.hyphenated(),
+        }
+    }
+
+    /// `30-minute`, `5-hour`, `7-day`
+    pub fn hyphenated(self) -> String {
+        let unit = match self.unit {
+            Unit::Minute => "minute",
+            Unit::Hour => "hour",
+            Unit::Day => "day",
+        };
+        format!("{}-{unit}", self.count)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateLimit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_window: Option<RateWindow>, // 5-hour window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secondary_window: Option<RateWindow>, // 7-day window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_reached: Option<bool>,
+    /// Anything else the API sends here (`allowed`, further `<name>_window`
+    /// objects, …), kept as sent so the meaning of new flags can be worked
+    /// out from history and `--json`
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl RateLimit {
+    /// The API says the limit is reached, yet every window it reported is
+    /// under 100%. What the backend means by this isn't documented; it's
+    /// shown as its own state rather than as either of the ordinary ones.
+    pub fn soft_limited(&self) -> bool {
+        let windows: Vec<f64> = self
+            .windows()
+            .iter()
+            .filter_map(|(_, w)| w.used_percent)
+            .collect();
+        self.limit_reached == Some(true)
+            && !windows.is_empty()
+            && windows.iter().all(|&p| p < 100.0)
+    }
+
+    /// The windows it has, named as the API names them: `primary`,
+    /// `secondary`, then any other `<name>_window` it sends
+    pub fn windows(&self) -> Vec<(&str, RateWindow)> {
+        let known = [
+            ("primary", &self.primary_window),
+            ("secondary", &self.secondary_window),
+        ]
+        .into_iter()
+        .filter_map(|(name, w)| Some((name, w.clone()?)));
+        known.chain(self.other_windows()).collect()
+    }
+
+    /// `<name>_window` objects beyond the two the schema names
+    fn other_windows(&self) -> impl Iterator<Item = (&str, RateWindow)> {
+        self.extra.iter().filter_map(|(key, value)| {
+            let name = key.strip_suffix("_window")?;
+            Some((name, serde_json::from_value(value.clone()).ok()?))
+        })
+    }
+
+    /// Whether `key` in `extra` is a window rather than a flag
+    pub fn is_window_key(&self, key: &str) -> bool {
+        self.other_windows()
+            .any(|(name, _)| key.strip_suffix("_window") == Some(name))
+    }
+
+    /// Limit reached, or any window full
+    pub fn exhausted(&self) -> bool {
+        self.limit_reached == Some(true)
+            || self
+                .windows()
+                .iter()
+                .any(|(_, w)| w.used_percent.unwrap_or(0.0) >= 100.0)
+    }
+
+    /// The fullest window's usage
+    pub fn highest(&self) -> f64 {
+        self.windows()
+            .iter()
+            .filter_map(|(_, w)| w.used_percent)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// A feature's own quota (code review, cloud tasks, …), separate from the
+/// main Codex limit
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NamedRateLimit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metered_feature: Option<String>,
+    pub rate_limit: RateLimit,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WhamUsage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_rate_limits: Vec<NamedRateLimit>,
+    /// Everything else, kept as sent; `<feature>_rate_limit` objects here
+    /// are read as feature limits too
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl WhamUsage {
+    /// Whether two responses describe the same usage state. Countdowns tick
+    /// down on every poll, so only plan, percentages and the limit flags count.
+    pub fn same_state(&self, other: &WhamUsage) -> bool {
+        let key = |u: &WhamUsage| {
+            let state = |rl: Option<&RateLimit>| {
+                let pcts: Vec<(String, Option<f64>)> = rl
+                    .map(|r| r.windows())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, w)| (name.to_string(), w.used_percent))
+                    .collect();
+                (rl.and_then(|r| r.limit_reached), pcts)
+            };
+            let features: Vec<_> = u
+                .feature_limits()
+                .into_iter()
+                .map(|(name, rl)| (name, state(Some(&rl))))
+                .collect();
+            (u.plan_type.clone(), state(u.rate_limit.as_ref()), features)
+        };
+        key(self) == key(other)
+    }
+
+    /// Per-feature limits by name (`code_review`, `cloud_tasks`, …): the
+    /// `additional_rate_limits` list, then any `<feature>_rate_limit` object
+    pub fn feature_limits(&self) -> Vec<(String, RateLimit)> {
+        let listed = self.additional_rate_limits.iter().map(|l| {
+            let name = l.limit_name.as_ref().or(l.metered_feature.as_ref());
+            (
+                name.cloned().unwrap_or_else(|| "unnamed".into()),
+                l.rate_limit.clone(),
+            )
+        });
+        let keyed = self.extra.iter().filter_map(|(key, value)| {
+            let name = key.strip_suffix("_rate_limit")?;
+            let rl = serde_json::from_value(value.clone()).ok()?;
+            Some((name.to_string(), rl))
+        });
+        listed.chain(keyed).collect()
+    }
+}
+
+/// A usage response together with the moment it was fetched. This is also
+/// the record format of the local history file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub usage: WhamUsage,
+    pub fetched_at: DateTime<Utc>,
+    /// Shell command this sample was taken after (`hook` mode)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Boundaries crossed since the previous history sample
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub marks: Vec<Mark>,
+    /// The workspace a team or business token acts in, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<Workspace>,
+    /// `session start` or `session stop` took this sample
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionMark>,
+}
+
+impl Snapshot {
+    pub fn new(usage: WhamUsage, fetched_at: DateTime<Utc>) -> Self {
+        Snapshot {
+            usage,
+            fetched_at,
+            command: None,
+            marks: Vec::new(),
+            workspace: None,
+            session: None,
+        }
+    }
+
+    /// A Plus sample fetched `at`, its 5-hour and 7-day windows given as
+    /// `(percent used, seconds to reset)`; the limit is reached at 100%
+    #[cfg(test)]
+    pub fn plus(five_hour: (f64, u64), seven_day: (f64, u64), at: DateTime<Utc>) -> Self {
+        let usage = serde_json::from_value(serde_json::json!({
+            "plan_type": "plus",
+            "rate_limit": {
+                "primary_window": {
+                    "used_percent": five_hour.0,
+                    "reset_after_seconds": five_hour.1,
+                    "limit_window_seconds": 18000
+                },
+                "secondary_window": {
+                    "used_percent": seven_day.0,
+                    "reset_after_seconds": seven_day.1,
+                    "limit_window_seconds": 604800
+                },
+                "limit_reached": five_hour.0 >= 100.0 || seven_day.0 >= 100.0
+            }
+        }))
+        .unwrap();
+        Snapshot::new(usage, at)
+    }
+}
+
+// ─── API call ─────────────────────────────────────────────────────────────────
+
+pub fn fetch_usage(creds: &Credentials) -> Result<WhamUsage> {
+    fetch(creds, shared_client())
+}
+
+/// One client for the run, so repeated fetches (`--watch`, `serve`, sweeps)
+/// reuse its connections. Loading TLS roots makes setting it up a
+/// measurable part of a short run.
+fn shared_client() -> Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| timing::measure("client setup", Client::new))
+        .clone()
+}
+
+/// `fetch_usage`, giving up with [`TimedOut`] after `timeout`
+pub fn fetch_usage_within(creds: &Credentials, timeout: Duration) -> Result<WhamUsage> {
+    let client = timing::measure("client setup", || {
+        Client::builder().timeout(timeout).build()
+    })
+    .context("Failed to set up the HTTP client")?;
+    fetch(creds, client).map_err(|e| match is_timeout(&e) {
+        true => TimedOut(timeout).into(),
+        false => e,
+    })
+}
+
+fn fetch(creds: &Credentials, client: Client) -> Result<WhamUsage> {
+    if !creds.is_oauth {
+        bail!(
+            "Only an API key was found — Codex usage limits are only visible \
+             via an OAuth session token.\n\
+             Log in with:  codex login"
+        );
+    }
+    crate::ci::check()?;
+
+    let url = endpoint();
+    let headers = headers(creds);
+    let mut req = client.get(&url);
+    for (name, value) in &headers {
+        req = req.header(*name, value);
+    }
+
+    let entry = har::capturing().then(|| har::Entry::new(Utc::now(), &url, &headers));
+    let clock = std::time::Instant::now();
+    let resp = match req.send() {
+        Ok(resp) => resp,
+        Err(e) => {
+            timing::record("request", clock.elapsed());
+            if let Some(entry) = entry {
+                har::record(entry.failed(&e, clock.elapsed()));
+            }
+            return Err(e).context("Failed to reach ChatGPT API");
+        }
+    };
+    let status = resp.status();
+    let version = resp.version();
+    let response_headers = resp.headers().clone();
+    let content_length = resp.content_length();
+    let wait = clock.elapsed();
+    timing::record("request", wait);
+
+    let mut body = har::Tee::new(resp, entry.is_some());
+    let expires_at = crate::fleet::jwt_expiry(&creds.access_token);
+    let result = read_response(
+        status,
+        &response_headers,
+        content_length,
+        expires_at,
+        &mut body,
+    );
+    timing::record("read + parse", clock.elapsed() - wait);
+    if let Some(entry) = entry {
+        let body = body.into_captured();
+        har::record(entry.response(
+            status,
+            version,
+            &response_headers,
+            body,
+            wait,
+            clock.elapsed(),
+        ));
+    }
+    result
+}
+
+/// Turn a response into usage data, or an error that says what came back
+fn read_response(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    content_length: Option<u64>,
+    token_expiry: Option<DateTime<Utc>>,
+    resp: impl std::io::Read,
+) -> Result<WhamUsage> {
+    let max_body = max_body_bytes();
+
+    // Challenge pages and SSO portals come back as HTML, often with a 403
+    let is_html = headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.to_ascii_lowercase().contains("text/html"));
+    if is_html {
+        let head = read_prefix(resp, HTML_SNIFF_BYTES);
+        bail!("{}", describe_html_response(status, &head));
+    }
+
+    if status.as_u16() == 401 || status.as_u16() == 403 {
+        let server_date = headers
+            .get(reqwest::header::DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+            .map(|d| d.with_timezone(&Utc));
+        return Err(Unauthorised {
+            status,
+            expires_at: token_expiry,
+            server_date,
+        }
+        .into());
+    }
+    if !status.is_success() {
+        let body = read_snippet(resp);
+        bail!("API returned HTTP {status}: {body}");
+    }
+
+    if let Some(len) = content_length {
+        if len > max_body {
+            bail!(
+                "Usage response is {len} bytes, over the {max_body} byte limit \
+                 (set CODEX_USAGE_MAX_BODY to raise it)"
+            );
+        }
+    }
+
+    // Some proxies mislabel HTML as JSON; sniff the first bytes before parsing
+    let mut reader = std::io::BufReader::new(CappedReader::new(resp, max_body));
+    let looks_like_html = {
+        use std::io::BufRead;
+        let head = reader.fill_buf().unwrap_or_default();
+        head.iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|&b| b == b'<')
+    };
+    if looks_like_html {
+        let head = read_prefix(reader, HTML_SNIFF_BYTES);
+        bail!("{}", describe_html_response(status, &head));
+    }
+
+    // Parse straight off the wire — be lenient; the schema may evolve
+    serde_json::from_reader::<_, WhamUsage>(reader).map_err(|e| body_error(e, max_body))
+}
+
+/// What a failed read of the usage body means: the cap, the connection or
+/// the JSON
+fn body_error(e: serde_json::Error, max_body: u64) -> anyhow::Error {
+    if !e.is_io() {
+        return anyhow::anyhow!("Failed to parse usage response: {e}");
+    }
+    let io = std::io::Error::from(e);
+    if io.get_ref().is_some_and(|inner| inner.is::<BodyTooLarge>()) {
+        anyhow::anyhow!(
+            "Usage response exceeded the {max_body} byte limit \
+             (set CODEX_USAGE_MAX_BODY to raise it)"
+        )
+    } else if io.kind() == std::io::ErrorKind::TimedOut {
+        std::io::Error::from(std::io::ErrorKind::TimedOut).into()
+    } else {
+        anyhow::Error::new(io).context("Failed to reach ChatGPT API")
+    }
+}
+
+/// The usage endpoint, or CODEX_USAGE_ENDPOINT for a proxy or a test server
+pub fn endpoint() -> String {
+    std::env::var("CODEX_USAGE_ENDPOINT")
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+}
+
+const DEFAULT_ENDPOINT: &str = "https://chatgpt.com/backend-api/wham/usage";
+
+/// Every header sent with the usage request
+pub fn headers(creds: &Credentials) -> Vec<(&'static str, String)> {
+    let mut headers = vec![
+        ("Authorization", format!("Bearer {}", creds.access_token)),
+        ("Content-Type", "application/json".to_string()),
+        ("Accept", "application/json".to_string()),
+        (
+            "User-Agent",
+            "Mozilla/5.0 (compatible; codex-usage/0.1)".to_string(),
+        ),
+    ];
+    if let Some(account_id) = &creds.account_id {
+        headers.push(("chatgpt-account-id", account_id.clone()));
+    }
+    headers
+}
+
+/// The API rejected the token (HTTP 401/403). A distinct type so callers
+/// checking many tokens can tell a bad token from API trouble; the token's
+/// expiry and the server's clock let [`crate::hints`] say why.
+#[derive(Debug)]
+pub struct Unauthorised {
+    pub status: reqwest::StatusCode,
+    /// The token's `exp` claim, when it is a JWT
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The response's `Date` header
+    pub server_date: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for Unauthorised {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Token rejected by the API (HTTP {})", self.status)
+    }
+}
+
+impl std::error::Error for Unauthorised {}
+
+/// No complete response within the caller's deadline
+#[derive(Debug)]
+pub struct TimedOut(pub Duration);
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "No response from the ChatGPT API within {}s",
+            self.0.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Whether a request failed by running out of time, while connecting or
+/// while reading the body
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|r| r.is_timeout())
+            || cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|io| io.kind() == std::io::ErrorKind::TimedOut)
+    })
+}
+
+// ─── Response body limits ─────────────────────────────────────────────────────
+
+/// Default cap on the usage response body; the real payload is a few hundred bytes
+const DEFAULT_MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+/// How much of an error body to quote back to the user
+const ERROR_SNIPPET_CHARS: usize = 300;
+
+/// Body size cap, overridable via CODEX_USAGE_MAX_BODY (bytes)
+pub(crate) fn max_body_bytes() -> u64 {
+    std::env::var("CODEX_USAGE_MAX_BODY")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+/// How much of an HTML body to inspect when working out what it is
+const HTML_SNIFF_BYTES: u64 = 16 * 1024;
+
+/// Read up to `limit` bytes of a body, lossily decoded
+fn read_prefix(resp: impl std::io::Read, limit: u64) -> String {
+    use std::io::Read;
+    let mut buf = Vec::new();
+    let _ = resp.take(limit).read_to_end(&mut buf);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Read at most a few KB of an error body and trim it down for display
+pub(crate) fn read_snippet(resp: impl std::io::Read) -> String {
+    let text = read_prefix(resp, (ERROR_SNIPPET_CHARS * 4) as u64);
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > ERROR_SNIPPET_CHARS {
+        let cut: String = text.chars().take(ERROR_SNIPPET_CHARS).collect();
+        format!("{cut}… (truncated)")
+    } else {
+        text
+    }
+}
+
+/// `CappedReader`'s error, told apart from the connection's own
+#[derive(Debug)]
+struct BodyTooLarge;
+
+impl std::fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("response body too large")
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// Reader that fails once more than `limit` bytes have been pulled through it
+pub(crate) struct CappedReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R> CappedReader<R> {
+    pub(crate) fn new(inner: R, limit: u64) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for CappedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            // Probe for one more byte — a clean EOF at the limit is fine
+            let mut probe = [0u8; 1];
+            return match self.inner.read(&mut probe)? {
+                0 => Ok(0),
+                _ => Err(std::io::Error::other(BodyTooLarge)),
+            };
+        }
+        let max = buf.len().min(self.remaining as usize);
+        let n = self.inner.read(&mut buf[..max])?;
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+// ─── HTML / interstitial responses ────────────────────────────────────────────
+
+/// Explain an HTML response in terms of what the user is probably looking at
+fn describe_html_response(status: reqwest::StatusCode, body: &str) -> String {
+    let lower = body.to_ascii_lowercase();
+    let title = html_title(body).unwrap_or_default();
+    let title_lower = title.to_ascii_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+
+    if has(&[
+        "cf-chl",
+        "challenge-platform",
+        "cf-turnstile",
+        "just a moment",
+        "attention required",
+    ]) {
+        format!(
+            "The ChatGPT API answered with a Cloudflare challenge page (HTTP {status}) \
+             instead of usage data"
+        )
+    } else if has(&["captcha", "recaptcha", "hcaptcha"]) {
+        format!(
+            "The ChatGPT API answered with a captcha page (HTTP {status}) instead of \
+             usage data"
+        )
+    } else if has(&[
+        "samlrequest",
+        "okta",
+        "single sign-on",
+        "/sso/",
+        "adfs",
+        "login.microsoftonline",
+    ]) || title_lower.contains("sign in")
+        || title_lower.contains("log in")
+    {
+        format!(
+            "The request was intercepted by a sign-in / SSO page (HTTP {status}) \
+             instead of reaching the ChatGPT API"
+        )
+    } else {
+        let what = if title.is_empty() {
+            String::new()
+        } else {
+            format!(" titled \"{title}\"")
+        };
+        format!(
+            "Expected JSON from the usage endpoint but got an HTML page{what} \
+             (HTTP {status})"
+        )
+    }
+}
+
+/// Pull the <title> text out of an HTML document
+fn html_title(body: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets, so indices map back onto `body`
+    let lower = body.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let open_end = start + lower[start..].find('>')? + 1;
+    let close = open_end + lower[open_end..].find("</title")?;
+    let title = body[open_end..close].split_whitespace().collect::<Vec<_>>();
+    Some(title.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+    use reqwest::StatusCode;
+    use std::io::Read;
+
+    /// A connection that hands over `sent` and then fails with `kind`
+    struct Dropped {
+        sent: &'static [u8],
+        kind: std::io::ErrorKind,
+    }
+
+    impl Read for Dropped {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.sent.read(buf)? {
+                0 => Err(self.kind.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    fn read(body: impl Read) -> Result<WhamUsage> {
+        read_response(StatusCode::OK, &HeaderMap::new(), None, None, body)
+    }
+
+    #[test]
+    fn capped_reader_stops_past_its_limit() {
+        let mut out = Vec::new();
+        CappedReader::new(&b"{\"plan_type\":\"plus\"}"[..], 20)
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out.len(), 20);
+
+        let err = CappedReader::new(&b"{\"plan_type\":\"plus\"} "[..], 20)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert!(err.get_ref().unwrap().is::<BodyTooLarge>());
+    }
+
+    #[test]
+    fn body_errors_say_what_went_wrong() {
+        let ok = read(&br#"{"plan_type":"plus"}"#[..]).unwrap();
+        assert_eq!(ok.plan_type.as_deref(), Some("plus"));
+
+        // Only the cap is reported as the cap
+        let huge = std::io::repeat(b' ').take(DEFAULT_MAX_BODY_BYTES + 1);
+        let err = read(huge).unwrap_err().to_string();
+        assert!(err.starts_with("Usage response exceeded the 1048576 byte limit"));
+
+        let dropped = |kind| Dropped {
+            sent: b"{\"plan_type\":",
+            kind,
+        };
+        let err = read(dropped(std::io::ErrorKind::ConnectionReset)).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to reach ChatGPT API");
+        assert_eq!(crate::errors::classify(&err).code, "E004");
+
+        let err = read(dropped(std::io::ErrorKind::TimedOut)).unwrap_err();
+        assert!(is_timeout(&err));
+
+        let err = read(&b"{\"plan_type\":"[..]).unwrap_err().to_string();
+        assert!(err.starts_with("Failed to parse usage response"), "{err}");
+    }
+
+    #[test]
+    fn html_pages_are_named_for_what_they_are() {
+        let forbidden = StatusCode::FORBIDDEN;
+        let challenge = r#"<!DOCTYPE html><html><head><title>Just a moment...</title>
+            <script src="/cdn-cgi/challenge-platform/h/g/orchestrate/chl_page/v1"></script>"#;
+        assert_eq!(
+            describe_html_response(forbidden, challenge),
+            "The ChatGPT API answered with a Cloudflare challenge page (HTTP 403 Forbidden) \
+             instead of usage data"
+        );
+
+        let login = "<html><head><title>Sign in to your account</title></head></html>";
+        assert!(describe_html_response(StatusCode::OK, login)
+            .starts_with("The request was intercepted by a sign-in / SSO page (HTTP 200 OK)"));
+
+        let untitled = "<html><body><h1>Bad gateway</h1></body></html>";
+        assert_eq!(html_title(untitled), None);
+        assert_eq!(
+            describe_html_response(StatusCode::BAD_GATEWAY, untitled),
+            "Expected JSON from the usage endpoint but got an HTML page (HTTP 502 Bad Gateway)"
+        );
+
+        let shouting = "<HTML><HEAD><TITLE lang=en>\n  Proxy   Error\n</TITLE></HEAD></HTML>";
+        assert_eq!(html_title(shouting).as_deref(), Some("Proxy Error"));
+        assert!(
+            describe_html_response(StatusCode::OK, shouting).contains(" titled \"Proxy Error\" ")
+        );
+    }
+
+    fn rate_limit(json: serde_json::Value) -> RateLimit {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn soft_limited_only_below_every_window() {
+        let window = |pct: f64| serde_json::json!({ "used_percent": pct });
+        let soft = rate_limit(serde_json::json!({
+            "primary_window": window(41.0),
+            "secondary_window": window(63.0),
+            "limit_reached": true,
+            "allowed": false
+        }));
+        assert!(soft.soft_limited() && soft.exhausted());
+        assert_eq!(soft.extra["allowed"], false);
+
+        // A full window explains the flag; no flag, nothing to explain
+        let full = rate_limit(serde_json::json!({
+            "primary_window": window(100.0),
+            "secondary_window": window(63.0),
+            "limit_reached": true
+        }));
+        assert!(!full.soft_limited() && full.exhausted());
+        let fine = rate_limit(serde_json::json!({
+            "primary_window": window(41.0),
+            "limit_reached": false
+        }));
+        assert!(!fine.soft_limited() && !fine.exhausted());
+        // With no figures at all there is nothing to call soft
+        let bare = rate_limit(serde_json::json!({ "limit_reached": true }));
+        assert!(!bare.soft_limited() && bare.exhausted());
+    }
+
+    #[test]
+    fn feature_limits_come_from_the_list_and_keys() {
+        let window = serde_json::json!({ "primary_window": { "used_percent": 5.0 } });
+        let usage: WhamUsage = serde_json::from_value(serde_json::json!({
+            "plan_type": "pro",
+            "code_review_rate_limit": window,
+            "not_a_limit": { "primary_window": 1 },
+            "additional_rate_limits": [
+                { "limit_name": "cloud_tasks", "rate_limit": window },
+                { "metered_feature": "codex_web", "rate_limit": window },
+                { "rate_limit": window }
+            ]
+        }))
+        .unwrap();
+        let names: Vec<String> = usage
+            .feature_limits()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            ["cloud_tasks", "codex_web", "unnamed", "code_review"]
+        );
+
+        // Extra windows follow the two the schema names, flags don't count
+        let rl = rate_limit(serde_json::json!({
+            "daily_window": { "used_percent": 64.5 },
+            "primary_window": { "used_percent": 12.0 },
+            "allowed": true
+        }));
+        let windows: Vec<&str> = rl.windows().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(windows, ["primary", "daily"]);
+        assert!(rl.is_window_key("daily_window") && !rl.is_window_key("allowed"));
+    }
+}
@@ -0,0 +1,286 @@
+//! Credential discovery: env vars, auth.json files and the platform's
+//! secret store (see [`crate::keystore`]).
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// ─── Auth / credential types ──────────────────────────────────────────────────
+
+/// Represents the tokens block inside auth.json
+#[derive(Debug, Deserialize)]
+struct TokenBlock {
+    access_token: Option<String>,
+    account_id: Option<String>,
+    /// JWT with the account's email and plan
+    id_token: Option<String>,
+}
+
+/// Top-level auth.json schema used by the Codex CLI
+#[derive(Debug, Deserialize)]
+struct AuthDotJson {
+    /// OAuth flow credentials
+    tokens: Option<TokenBlock>,
+    /// Fallback: plain API key stored directly
+    #[serde(rename = "OPENAI_API_KEY")]
+    openai_api_key: Option<String>,
+    /// When Codex last refreshed the tokens (RFC 3339)
+    last_refresh: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Credentials {
+    pub access_token: String,
+    pub account_id: Option<String>,
+    /// true = OAuth (can hit /wham/usage); false = API key only
+    pub is_oauth: bool,
+    pub identity: Identity,
+}
+
+/// Who the token belongs to, worked out locally from auth.json and the
+/// tokens' claims; no request involved
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Identity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// `plus`, `pro`, `team`, … as the token states it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+    /// auth.json's `last_refresh`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refreshed_at: Option<DateTime<Utc>>,
+}
+
+impl Identity {
+    /// Email and plan from a JWT's claims (the id_token has both; Codex
+    /// access tokens carry them under OpenAI's namespaced claims)
+    pub fn from_jwt(token: &str) -> Identity {
+        let Some(claims) = crate::fleet::jwt_claims(token) else {
+            return Identity::default();
+        };
+        let text = |v: Option<&serde_json::Value>| {
+            v.and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+        };
+        Identity {
+            email: text(claims.get("email"))
+                .or_else(|| text(claims.pointer("/https:~1~1api.openai.com~1profile/email"))),
+            plan: text(claims.pointer("/https:~1~1api.openai.com~1auth/chatgpt_plan_type")),
+            refreshed_at: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Identity::default()
+    }
+}
+
+// ─── Credential discovery ─────────────────────────────────────────────────────
+
+/// Somewhere a token can come from
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// An OAuth token in an env var, with an optional account id
+    Env {
+        var: String,
+        account_id: Option<String>,
+    },
+    /// A plain API key in OPENAI_API_KEY
+    ApiKeyEnv,
+    /// An auth.json written by `codex login`
+    AuthFile(PathBuf),
+    /// The platform's secret store (service "Codex" and a few variants)
+    SecretStore,
+}
+
+impl Source {
+    pub fn label(&self) -> String {
+        match self {
+            Source::Env { var, .. } => format!("env {var}"),
+            Source::ApiKeyEnv => "env OPENAI_API_KEY".to_string(),
+            Source::AuthFile(path) => format!("file {}", path.display()),
+            Source::SecretStore => crate::keystore::NAME.to_string(),
+        }
+    }
+
+    /// What this source holds: `Ok(None)` when there is nothing there, `Err`
+    /// when something is there but can't be used
+    pub fn read(&self) -> Result<Option<Credentials>> {
+        let env = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Ok(match self {
+            Source::Env { var, account_id } => env(var).map(|token| Credentials {
+                identity: Identity::from_jwt(&token),
+                access_token: token,
+                account_id: account_id.clone(),
+                is_oauth: true,
+            }),
+            Source::ApiKeyEnv => env("OPENAI_API_KEY").map(|key| Credentials {
+                access_token: key,
+                account_id: None,
+                is_oauth: false,
+                identity: Identity::default(),
+            }),
+            Source::AuthFile(path) if !path.exists() => None,
+            Source::AuthFile(path) => Some(read_auth_json(path)?),
+            Source::SecretStore => crate::keystore::read()
+                .ok()
+                .flatten()
+                .map(|secret| read_stored_secret(&secret)),
+        })
+    }
+}
+
+/// Where `get_credentials` looks, in priority order:
+///   1. CODEX_ACCESS_TOKEN env var  (OAuth override)
+///   2. OPENAI_API_KEY env var
+///   3. ~/.codex/auth.json  (Codex CLI default location)
+///   4. ~/.config/codex/auth.json  (XDG alternative)
+///   5. the platform's secret store, entry "Codex" (when built in)
+pub fn discovery_order() -> Vec<Source> {
+    let home = std::env::var_os("HOME").unwrap_or_default();
+    let home = std::path::Path::new(&home);
+    vec![
+        Source::Env {
+            var: "CODEX_ACCESS_TOKEN".to_string(),
+            account_id: std::env::var("CODEX_ACCOUNT_ID").ok(),
+        },
+        Source::ApiKeyEnv,
+        Source::AuthFile(home.join(".codex").join("auth.json")),
+        Source::AuthFile(home.join(".config").join("codex").join("auth.json")),
+        Source::SecretStore,
+    ]
+}
+
+/// The first source in `sources` holding a usable token, and that token
+pub fn first_available(sources: Vec<Source>) -> Option<(Source, Credentials)> {
+    sources.into_iter().find_map(|source| match source.read() {
+        Ok(Some(creds)) => Some((source, creds)),
+        _ => None,
+    })
+}
+
+/// The first usable token in `discovery_order`, or why the first source
+/// that has something couldn't be used
+pub fn get_credentials() -> Result<Credentials> {
+    let mut unusable = None;
+    for source in discovery_order() {
+        match source.read() {
+            Ok(Some(creds)) => return Ok(creds),
+            Ok(None) => {}
+            Err(e) => {
+                unusable.get_or_insert(e);
+            }
+        }
+    }
+    // A file that's there but broken says more than "nothing found"
+    if let Some(e) = unusable {
+        return Err(e);
+    }
+
+    bail!(
+        "No OpenAI / Codex credentials found.\n\
+         Tried:\n\
+         • CODEX_ACCESS_TOKEN / OPENAI_API_KEY env vars\n\
+         • ~/.codex/auth.json\n\
+         • ~/.config/codex/auth.json\n\
+         • {} (service \"Codex\")\n\n\
+         Log in with:  codex login\n\
+         Or set:       export OPENAI_API_KEY=sk-...",
+        crate::keystore::NAME
+    )
+}
+
+pub fn read_auth_json(path: &std::path::Path) -> Result<Credentials> {
+    let raw = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let auth: AuthDotJson = crate::lenient::parse(&raw, path)?;
+    match extract_from_auth(auth) {
+        Some(creds) => Ok(creds),
+        None => bail!("auth.json found but contained no usable token"),
+    }
+}
+
+/// A secret from the store: an auth.json blob or a bare access token
+fn read_stored_secret(secret: &str) -> Credentials {
+    serde_json::from_str::<AuthDotJson>(secret)
+        .ok()
+        .and_then(extract_from_auth)
+        .unwrap_or_else(|| Credentials {
+            access_token: secret.to_string(),
+            account_id: None,
+            is_oauth: true,
+            identity: Identity::from_jwt(secret),
+        })
+}
+
+/// The OAuth token if there is one, else the API key
+fn extract_from_auth(auth: AuthDotJson) -> Option<Credentials> {
+    let refreshed_at = auth
+        .last_refresh
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc));
+    if let Some(tokens) = auth.tokens {
+        if let Some(access_token) = tokens.access_token {
+            if !access_token.is_empty() {
+                let identity = Identity {
+                    refreshed_at,
+                    ..Identity::from_jwt(tokens.id_token.as_deref().unwrap_or(&access_token))
+                };
+                return Some(Credentials {
+                    access_token,
+                    account_id: tokens.account_id,
+                    is_oauth: true,
+                    identity,
+                });
+            }
+        }
+    }
+    if let Some(key) = auth.openai_api_key {
+        if !key.is_empty() {
+            return Some(Credentials {
+                access_token: key,
+                account_id: None,
+                is_oauth: false,
+                identity: Identity {
+                    refreshed_at,
+                    ..Identity::default()
+                },
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_from_auth_json() {
+        // {"email":"ada@example.com","https://api.openai.com/auth":{"chatgpt_plan_type":"pro"}}
+        let id_token = "eyJhbGciOiJub25lIn0.eyJlbWFpbCI6ImFkYUBleGFtcGxlLmNvbSIsImh0dHBzOi8vYXBp\
+                        Lm9wZW5haS5jb20vYXV0aCI6eyJjaGF0Z3B0X3BsYW5fdHlwZSI6InBybyJ9fQ.";
+        let auth = format!(
+            r#"{{"tokens": {{"access_token": "tok", "id_token": "{id_token}"}},
+                "last_refresh": "2024-05-01T08:30:00.123456Z"}}"#
+        );
+        let creds = read_stored_secret(&auth);
+        assert_eq!(creds.access_token, "tok");
+        assert_eq!(
+            creds.identity,
+            Identity {
+                email: Some("ada@example.com".into()),
+                plan: Some("pro".into()),
+                refreshed_at: crate::clock::parse_timestamp("2024-05-01T08:30:00.123456Z").ok(),
+            }
+        );
+        assert!(read_stored_secret("opaque").identity.is_empty());
+    }
+}
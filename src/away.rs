@@ -0,0 +1,162 @@
+//! What changed while you were away: the first report after a gap of at
+//! least `GAP` opens with a few lines from the history recorded in between,
+//! by `record` or anything else that fetched, rather than a bare current
+//! number: each window's peak, the times the limit was hit, and the resets.
+//!
+//! "Last looked" is the last report rendered for a person to read, kept in
+//! its own file, since prompts and samplers fetch far more often than anyone
+//! looks.
+
+use crate::api::Snapshot;
+use crate::history::Mark;
+use crate::render::{label, window_rows};
+use crate::status::age;
+use chrono::{DateTime, Duration, Utc};
+use colored::Colorize;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// How long since the last report counts as away
+const GAP: Duration = Duration::hours(8);
+
+fn path() -> PathBuf {
+    crate::paths::data_dir().join("last_seen")
+}
+
+/// Note a report seen at `now`; returns when the one before it was, if that
+/// was at least `GAP` ago. Never fails the report: a marker that can't be
+/// read or written just means no digest.
+pub fn returned(now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let path = path();
+    let last = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| DateTime::parse_from_rfc3339(s.trim()).ok())
+        .map(|t| t.with_timezone(&Utc));
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(&path, now.to_rfc3339());
+    last.filter(|&t| now - t >= GAP)
+}
+
+/// The digest of `samples`, the history since `since`, in time order. Nothing
+/// when no sample was recorded while away besides the report's own.
+pub fn digest(samples: &[Snapshot], since: DateTime<Utc>, now: DateTime<Utc>) -> Option<String> {
+    if samples.len() < 2 {
+        return None;
+    }
+
+    // Peak of each window, in the order the report shows them
+    let mut peaks: Vec<(String, f64, DateTime<Utc>)> = Vec::new();
+    let mut limit_hits = Vec::new();
+    let mut was_limited = false;
+    let mut resets: Vec<(Mark, usize)> = Vec::new();
+    for snap in samples {
+        for row in window_rows(snap, snap.fetched_at) {
+            let Some(pct) = row.window.as_ref().and_then(|w| w.used_percent) else {
+                continue;
+            };
+            let name = label(&row);
+            match peaks.iter_mut().find(|(n, ..)| *n == name) {
+                Some(peak) if pct > peak.1 => *peak = (name, pct, snap.fetched_at),
+                Some(_) => {}
+                None => peaks.push((name, pct, snap.fetched_at)),
+            }
+        }
+        let limited = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .is_some_and(|r| r.limit_reached == Some(true));
+        if limited && !was_limited {
+            limit_hits.push(snap.fetched_at);
+        }
+        was_limited = limited;
+        for &mark in &snap.marks {
+            match resets.iter_mut().find(|(m, _)| *m == mark) {
+                Some((_, n)) => *n += 1,
+                None => resets.push((mark, 1)),
+            }
+        }
+    }
+
+    let ago = |t: DateTime<Utc>| format!("{} ago", age(now - t));
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "  {} {}",
+        "While you were away".bold(),
+        format!("({}, {} samples)", age(now - since), samples.len()).dimmed()
+    );
+    for (name, pct, at) in &peaks {
+        let _ = writeln!(out, "    {name} peaked at {pct:.0}% {}", ago(*at).dimmed());
+    }
+    let _ = match limit_hits.as_slice() {
+        [] => writeln!(out, "    Limit not reached"),
+        [at] => writeln!(out, "    Limit reached once {}", ago(*at).dimmed()),
+        [.., last] => writeln!(
+            out,
+            "    Limit reached {} times, last {}",
+            limit_hits.len(),
+            ago(*last).dimmed()
+        ),
+    };
+    if !resets.is_empty() {
+        let resets: Vec<String> = resets
+            .iter()
+            .map(|(mark, n)| match n {
+                1 => mark.label().to_string(),
+                n => format!("{} ×{n}", mark.label()),
+            })
+            .collect();
+        let _ = writeln!(out, "    Since then: {}", resets.join(", "));
+    }
+    out.push('\n');
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    fn sample(ts: &str, primary: f64, weekly: f64, reached: bool) -> Snapshot {
+        let usage = serde_json::from_value(serde_json::json!({
+            "plan_type": "plus",
+            "rate_limit": {
+                "primary_window": { "used_percent": primary, "limit_window_seconds": 18000 },
+                "secondary_window": { "used_percent": weekly, "limit_window_seconds": 604800 },
+                "limit_reached": reached
+            }
+        }))
+        .unwrap();
+        Snapshot::new(usage, parse_timestamp(ts).unwrap())
+    }
+
+    #[test]
+    fn digest_sums_up_the_gap() {
+        colored::control::set_override(false);
+        let at = |s| parse_timestamp(s).unwrap();
+        let mut samples = vec![
+            sample("2024-05-01T18:00:00Z", 60.0, 40.0, false),
+            sample("2024-05-01T20:00:00Z", 100.0, 55.0, true),
+            sample("2024-05-01T21:00:00Z", 100.0, 56.0, true),
+            sample("2024-05-02T02:00:00Z", 5.0, 56.0, false),
+            sample("2024-05-02T06:00:00Z", 100.0, 70.0, true),
+            sample("2024-05-02T09:00:00Z", 10.0, 71.0, false),
+        ];
+        samples[3].marks = vec![Mark::PrimaryReset];
+        samples[5].marks = vec![Mark::PrimaryReset];
+        let now = at("2024-05-02T09:00:00Z");
+        assert_eq!(
+            digest(&samples, at("2024-05-01T17:00:00Z"), now).unwrap(),
+            "  While you were away (16h 0m, 6 samples)\n\
+             \x20   5-hour session peaked at 100% 13h 0m ago\n\
+             \x20   7-day rolling peaked at 71% 0s ago\n\
+             \x20   Limit reached 2 times, last 3h 0m ago\n\
+             \x20   Since then: 5h window reset ×2\n\n"
+        );
+        // Only the report's own fetch: nothing recorded while away
+        assert_eq!(digest(&samples[5..], at("2024-05-01T17:00:00Z"), now), None);
+    }
+}
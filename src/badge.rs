@@ -0,0 +1,113 @@
+//! `badge`: a shields.io endpoint payload, printed, served, or pushed to a gist.
+//!
+//! Point shields at the JSON with
+//! `https://img.shields.io/endpoint?url=<raw gist or server URL>`.
+
+use crate::api::Snapshot;
+use crate::render::{CRIT_PERCENT, WARN_PERCENT};
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+/// File name used inside the gist unless overridden
+pub const DEFAULT_GIST_FILE: &str = "codex-usage-badge.json";
+
+/// shields.io "endpoint" schema
+pub fn shields(snap: &Snapshot) -> Value {
+    let rl = snap.usage.rate_limit.as_ref();
+    let pct = |w: Option<&crate::api::RateWindow>| w.and_then(|w| w.used_percent);
+    let primary = pct(rl.and_then(|r| r.primary_window.as_ref()));
+    let secondary = pct(rl.and_then(|r| r.secondary_window.as_ref()));
+    let limit_reached = rl.and_then(|r| r.limit_reached).unwrap_or(false);
+
+    // `5h 42% · 7d 12%`, each window labelled by its length
+    let part = |name: &str, w: Option<&crate::api::RateWindow>| {
+        let w = w?;
+        Some(format!("{} {:.0}%", w.span(name)?.short(), w.used_percent?))
+    };
+    let parts: Vec<String> = [
+        part("primary", rl.and_then(|r| r.primary_window.as_ref())),
+        part("secondary", rl.and_then(|r| r.secondary_window.as_ref())),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let message = match parts.is_empty() {
+        true => "unknown".to_string(),
+        false => parts.join(" · "),
+    };
+    let highest = primary.unwrap_or(0.0).max(secondary.unwrap_or(0.0));
+    let color = if limit_reached || highest >= CRIT_PERCENT {
+        "red"
+    } else if highest >= WARN_PERCENT {
+        "yellow"
+    } else if primary.is_none() && secondary.is_none() {
+        "lightgrey"
+    } else {
+        "brightgreen"
+    };
+
+    json!({
+        "schemaVersion": 1,
+        "label": "Codex quota",
+        "message": if limit_reached { "limit reached".to_string() } else { message },
+        "color": color,
+    })
+}
+
+/// Render in the requested style; only `shields` exists today
+pub fn render(style: &str, snap: &Snapshot) -> Result<Value> {
+    match style {
+        "shields" => Ok(shields(snap)),
+        other => bail!("Unknown badge style '{other}' (expected: shields)"),
+    }
+}
+
+/// Overwrite `file` in an existing gist with the badge JSON
+pub fn publish_gist(gist_id: &str, file: &str, token: &str, badge: &Value) -> Result<()> {
+    let resp = Client::new()
+        .patch(format!("https://api.github.com/gists/{gist_id}"))
+        .bearer_auth(token)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "codex-usage")
+        .json(&json!({ "files": { file: { "content": badge.to_string() } } }))
+        .send()
+        .context("Failed to reach the GitHub API")?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("GitHub returned HTTP {status} updating gist {gist_id}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn colour_and_message_follow_the_fullest_window() {
+        let badge = |five_hour, weekly| {
+            let snap = Snapshot::plus((five_hour, 3600), (weekly, 86400), Utc::now());
+            let badge = render("shields", &snap).unwrap();
+            (badge["message"].clone(), badge["color"].clone())
+        };
+        assert_eq!(
+            badge(42.0, 12.0),
+            (json!("5h 42% · 7d 12%"), json!("brightgreen"))
+        );
+        assert_eq!(badge(42.0, 80.0).1, "yellow");
+        assert_eq!(badge(95.0, 12.0).1, "red");
+        assert_eq!(badge(100.0, 12.0), (json!("limit reached"), json!("red")));
+
+        let mut empty = Snapshot::plus((0.0, 0), (0.0, 0), Utc::now());
+        empty.usage.rate_limit = None;
+        let badge = shields(&empty);
+        assert_eq!(
+            (badge["message"].as_str(), badge["color"].as_str()),
+            (Some("unknown"), Some("lightgrey"))
+        );
+        assert_eq!(badge["schemaVersion"], 1);
+        assert!(render("flat", &empty).is_err());
+    }
+}
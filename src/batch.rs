@@ -0,0 +1,289 @@
+//! A small work queue for sweeps over many accounts (`team report`,
+//! `fleet validate`): a few workers, a shared request rate limit, retries with
+//! backoff, and an optional checkpoint file so an interrupted sweep picks up
+//! where it stopped instead of querying every seat again.
+//!
+//! The checkpoint is JSON lines, one `{"key", "value"}` per finished item,
+//! appended as results arrive. It is deleted once a sweep finishes with no
+//! failures, so the next run starts fresh.
+
+use crate::api::Unauthorised;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default number of workers
+pub const DEFAULT_JOBS: usize = 8;
+
+/// Default requests per second across all workers
+pub const DEFAULT_RATE: f64 = 5.0;
+
+/// Default retries after a failed attempt
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Backoff before the first retry; doubles each time
+const FIRST_BACKOFF: Duration = Duration::from_millis(500);
+
+pub struct Batch {
+    pub jobs: usize,
+    /// Requests per second, shared by all workers
+    pub rate: f64,
+    pub retries: u32,
+    pub checkpoint: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    key: String,
+    value: T,
+}
+
+/// Finished work: results in input order, `Err` for items that still failed
+/// after every retry
+pub struct Outcome<T> {
+    pub results: Vec<(String, Result<T, String>)>,
+    /// How many results came from the checkpoint rather than a request
+    pub resumed: usize,
+}
+
+impl Batch {
+    /// Run `work` for each keyed item. Keys identify items in the checkpoint,
+    /// so they must be unique and stable between runs.
+    pub fn run<I, T>(
+        &self,
+        items: &[(String, I)],
+        work: impl Fn(&I) -> Result<T> + Sync,
+    ) -> Result<Outcome<T>>
+    where
+        I: Sync,
+        T: Serialize + DeserializeOwned + Send,
+    {
+        if self.checkpoint.is_some() {
+            let mut seen = std::collections::BTreeSet::new();
+            if let Some((key, _)) = items.iter().find(|(k, _)| !seen.insert(k)) {
+                anyhow::bail!("'{key}' appears twice; a checkpointed sweep needs unique names");
+            }
+        }
+        let mut done: BTreeMap<String, T> = match &self.checkpoint {
+            Some(path) => load_checkpoint(path)?,
+            None => BTreeMap::new(),
+        };
+        let resumed = items.iter().filter(|(k, _)| done.contains_key(k)).count();
+        let pending: Vec<usize> = (0..items.len())
+            .filter(|&i| !done.contains_key(&items[i].0))
+            .collect();
+
+        let checkpoint = match &self.checkpoint {
+            Some(path) => Some(Mutex::new(open_checkpoint(path)?)),
+            None => None,
+        };
+        let limiter = RateLimiter::new(self.rate);
+        let next = AtomicUsize::new(0);
+        let fresh: Mutex<BTreeMap<usize, Result<T, String>>> = Mutex::new(BTreeMap::new());
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.jobs.clamp(1, pending.len().max(1)) {
+                scope.spawn(|| {
+                    while let Some(&i) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let (key, item) = &items[i];
+                        let result = self.attempt(&limiter, || work(item));
+                        if let (Ok(value), Some(file)) = (&result, &checkpoint) {
+                            // A lost checkpoint line only costs a re-query later
+                            if let Ok(mut line) = serde_json::to_string(&Entry {
+                                key: key.clone(),
+                                value,
+                            }) {
+                                line.push('\n');
+                                let _ = file.lock().unwrap().write_all(line.as_bytes());
+                            }
+                        }
+                        fresh
+                            .lock()
+                            .unwrap()
+                            .insert(i, result.map_err(|e| format!("{e:#}")));
+                    }
+                });
+            }
+        });
+
+        let mut fresh = fresh.into_inner().unwrap();
+        let results: Vec<_> = items
+            .iter()
+            .enumerate()
+            .map(|(i, (key, _))| {
+                let result = match done.remove(key) {
+                    Some(v) => Ok(v),
+                    None => fresh
+                        .remove(&i)
+                        .unwrap_or_else(|| Err("not run".to_string())),
+                };
+                (key.clone(), result)
+            })
+            .collect();
+
+        if let Some(path) = &self.checkpoint {
+            if results.iter().all(|(_, r)| r.is_ok()) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        Ok(Outcome { results, resumed })
+    }
+
+    /// One item: wait for a rate-limit slot, retry with backoff on failure.
+    /// A rejected token won't get better by asking again.
+    fn attempt<T>(&self, limiter: &RateLimiter, work: impl Fn() -> Result<T>) -> Result<T> {
+        let mut backoff = FIRST_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            limiter.wait();
+            match work() {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt >= self.retries || e.is::<Unauthorised>() => return Err(e),
+                Err(_) => {
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Spaces requests evenly: each caller takes the next free slot
+struct RateLimiter {
+    gap: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_second: f64) -> Self {
+        RateLimiter {
+            gap: Duration::from_secs_f64(1.0 / per_second.max(0.001)),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.gap;
+            slot
+        };
+        std::thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Open for appending, first ending any torn line so the next entry starts
+/// on a line of its own
+fn open_checkpoint(path: &std::path::Path) -> Result<std::fs::File> {
+    let torn = std::fs::read(path).is_ok_and(|b| b.last().is_some_and(|&c| c != b'\n'));
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    if torn {
+        file.write_all(b"\n")?;
+    }
+    Ok(file)
+}
+
+/// Finished items from an earlier run. A torn last line (the run was killed
+/// mid-write) is ignored.
+fn load_checkpoint<T: DeserializeOwned>(path: &std::path::Path) -> Result<BTreeMap<String, T>> {
+    let raw = match std::fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}", path.display())),
+    };
+    Ok(raw
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Entry<T>>(l).ok())
+        .map(|e| (e.key, e.value))
+        .collect())
+}
+
+/// Parse a `--rate` such as `5`, `5/s` or `120/m`
+pub fn parse_rate(s: &str) -> Result<f64> {
+    let t = s.trim();
+    let (n, per) = match t.split_once('/') {
+        Some((n, unit)) => (
+            n,
+            match unit.trim() {
+                "s" | "sec" => 1.0,
+                "m" | "min" => 60.0,
+                _ => anyhow::bail!("Invalid --rate '{s}' (expected e.g. 5/s or 120/m)"),
+            },
+        ),
+        None => (t, 1.0),
+    };
+    match n.trim().parse::<f64>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v / per),
+        _ => anyhow::bail!("Invalid --rate '{s}' (expected e.g. 5/s or 120/m)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_and_resumes() {
+        let dir = std::env::temp_dir().join(format!("codex-usage-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let checkpoint = dir.join("sweep.jsonl");
+        let _ = std::fs::remove_file(&checkpoint);
+        let batch = Batch {
+            jobs: 2,
+            rate: 1000.0,
+            retries: 1,
+            checkpoint: Some(checkpoint.clone()),
+        };
+        let items: Vec<(String, u32)> = (0..4).map(|i| (format!("seat{i}"), i)).collect();
+
+        // seat3 always fails; seat1 fails once then succeeds on retry
+        let calls = AtomicUsize::new(0);
+        let flaky = AtomicUsize::new(0);
+        let out = batch
+            .run(&items, |&i| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                match i {
+                    1 if flaky.fetch_add(1, Ordering::Relaxed) == 0 => anyhow::bail!("blip"),
+                    3 => anyhow::bail!("down"),
+                    _ => Ok(i * 10),
+                }
+            })
+            .unwrap();
+        assert_eq!(out.results[1].1, Ok(10));
+        assert!(out.results[3].1.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 3 + 1 + 2);
+
+        // Only the failed seat is queried again, and the checkpoint goes away
+        // once everything has succeeded
+        let calls = AtomicUsize::new(0);
+        let out = batch
+            .run(&items, |&i| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Ok(i * 10)
+            })
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(out.resumed, 3);
+        assert!(!checkpoint.exists());
+        let _ = std::fs::remove_dir(&dir);
+    }
+
+    #[test]
+    fn rates() {
+        assert_eq!(parse_rate("5").unwrap(), 5.0);
+        assert_eq!(parse_rate("120/m").unwrap(), 2.0);
+        assert!(parse_rate("0").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! The hot cache: each profile's last successful snapshot, rewritten after
+//! every fetch so prompt segments and status bars can pass `--max-age` and
+//! skip the network while it is fresh.
+//!
+//! This file is read on every keystroke-driven prompt redraw, so it is CBOR
+//! rather than JSON, behind one version byte and read through a memory map.
+//! Anything people look at or export (history, `--json`, `status`) stays
+//! JSON.
+
+use crate::api::Snapshot;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::path::PathBuf;
+
+/// Bumped whenever the encoding changes; older files read as a miss
+const VERSION: u8 = 1;
+
+/// The cache file for `profile`
+pub fn path(profile: &str) -> PathBuf {
+    crate::paths::data_dir()
+        .join("cache")
+        .join(format!("{profile}.cbor"))
+}
+
+/// Store `snap` as `profile`'s latest
+pub fn write(profile: &str, snap: &Snapshot) -> Result<()> {
+    let path = path(profile);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create {}", dir.display()))?;
+    }
+    let mut buf = vec![VERSION];
+    ciborium::into_writer(snap, &mut buf)?;
+    // Replace by rename, never in place: a reader's map of the old file
+    // stays valid, and nobody sees half a file
+    let tmp = path.with_extension(format!("cbor.{}", std::process::id()));
+    std::fs::write(&tmp, &buf).with_context(|| format!("Could not write {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(())
+}
+
+/// `profile`'s cached snapshot; `None` if there is none yet or it was
+/// written by a different version
+pub fn read(profile: &str) -> Result<Option<Snapshot>> {
+    let path = path(profile);
+    let file = match std::fs::File::open(&path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Could not open {}", path.display())),
+    };
+    // SAFETY: the file is only ever replaced by rename, never truncated or
+    // rewritten in place, so the mapped bytes can't change under us
+    let map = unsafe { memmap2::Mmap::map(&file) }
+        .with_context(|| format!("Could not read {}", path.display()))?;
+    decode(&map).with_context(|| format!("Could not parse {}", path.display()))
+}
+
+fn decode(bytes: &[u8]) -> Result<Option<Snapshot>> {
+    match bytes.split_first() {
+        Some((&VERSION, body)) => Ok(Some(ciborium::from_reader(body)?)),
+        Some(_) => Ok(None),
+        None => bail!("empty file"),
+    }
+}
+
+/// The cached snapshot if it was fetched no more than `max_age` before `now`
+pub fn fresh(profile: &str, max_age: Duration, now: DateTime<Utc>) -> Option<Snapshot> {
+    read(profile)
+        .ok()
+        .flatten()
+        .filter(|s| now - s.fetched_at <= max_age)
+}
+
+/// How long a background refresh holds off the next, so a burst of prompt
+/// redraws starts one fetch rather than one each
+const REFRESH_BACKOFF: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Start this binary fetching `profile` in the background, unless another
+/// run did so within `REFRESH_BACKOFF`; `--profile` is passed on when the
+/// caller was given one. The child keeps none of our stdio, so whatever
+/// reads our output sees it end as soon as we exit.
+pub fn refresh_in_background(profile: &str, explicit: bool) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    let marker = path(profile).with_extension("refreshing");
+    let recent = std::fs::metadata(&marker)
+        .and_then(|m| m.modified())
+        .is_ok_and(|at| at.elapsed().is_ok_and(|age| age < REFRESH_BACKOFF));
+    if recent {
+        return Ok(());
+    }
+    if let Some(dir) = marker.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create {}", dir.display()))?;
+    }
+    std::fs::write(&marker, b"")
+        .with_context(|| format!("Could not write {}", marker.display()))?;
+
+    let mut child = Command::new(std::env::current_exe()?);
+    child.args(["--plain", "--quiet", "--no-tty"]);
+    if explicit {
+        child.args(["--profile", profile]);
+    }
+    child
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Its own process group, so a Ctrl-C at the prompt doesn't reach it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut child, 0);
+    child
+        .spawn()
+        .context("Could not start a background refresh")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_behind_a_version_byte() {
+        let usage = serde_json::from_str(
+            r#"{"plan_type":"plus","rate_limit":{"primary_window":{"used_percent":42.5}}}"#,
+        )
+        .unwrap();
+        let at = crate::clock::parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let mut snap = Snapshot::new(usage, at);
+        snap.command = Some("codex exec".into());
+
+        let mut buf = vec![VERSION];
+        ciborium::into_writer(&snap, &mut buf).unwrap();
+        let back = decode(&buf).unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&back).unwrap(),
+            serde_json::to_value(&snap).unwrap()
+        );
+
+        buf[0] = VERSION + 1;
+        assert!(decode(&buf).unwrap().is_none());
+        assert!(decode(&[]).is_err());
+    }
+}
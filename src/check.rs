@@ -0,0 +1,159 @@
+//! `--check`: a Nagios/Icinga plugin. One status line with perfdata on
+//! stdout, and the plugin exit codes: 0 OK, 1 WARNING, 2 CRITICAL,
+//! 3 UNKNOWN. Status goes by the fullest window of the main Codex limit, or
+//! CRITICAL outright when the API says the limit is reached; feature limits
+//! are in the perfdata only.
+
+use crate::api::Snapshot;
+use crate::render::{window_rows, CRIT_PERCENT, WARN_PERCENT};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+
+/// `--warn` and `--crit` when not given: where the bars change colour
+pub const DEFAULT_WARN: f64 = WARN_PERCENT;
+pub const DEFAULT_CRIT: f64 = CRIT_PERCENT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Critical => "CRITICAL",
+            Status::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// Percentages at which a window turns WARNING and CRITICAL
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub warn: f64,
+    pub crit: f64,
+}
+
+impl Thresholds {
+    pub fn new(warn: Option<f64>, crit: Option<f64>) -> Result<Thresholds> {
+        let t = Thresholds {
+            warn: warn.unwrap_or(DEFAULT_WARN),
+            crit: crit.unwrap_or(DEFAULT_CRIT),
+        };
+        if t.warn > t.crit {
+            bail!("--warn {} is above --crit {}", t.warn, t.crit);
+        }
+        Ok(t)
+    }
+
+    fn status(self, pct: f64) -> Status {
+        match pct {
+            p if p >= self.crit => Status::Critical,
+            p if p >= self.warn => Status::Warning,
+            _ => Status::Ok,
+        }
+    }
+}
+
+/// The status of `snap` and its plugin output line
+pub fn evaluate(snap: &Snapshot, now: DateTime<Utc>, t: Thresholds) -> (Status, String) {
+    let rows = window_rows(snap, now);
+    let mut status = None;
+    let mut summary = Vec::new();
+    let mut perfdata = Vec::new();
+    for row in &rows {
+        let Some(pct) = row.window.as_ref().and_then(|w| w.used_percent) else {
+            continue;
+        };
+        let label = match &row.feature {
+            Some(feature) => format!("{feature}_{}", row.name),
+            None => {
+                summary.push(format!("{} {pct}%", row.short_span()));
+                status = status.max(Some(t.status(pct)));
+                row.name.clone()
+            }
+        };
+        perfdata.push(format!("{label}={pct}%;{};{};0;100", t.warn, t.crit));
+    }
+
+    let rl = snap.usage.rate_limit.as_ref();
+    if rl.and_then(|r| r.limit_reached) == Some(true) {
+        status = Some(Status::Critical);
+        summary.push("limit reached".into());
+    }
+    match status {
+        Some(status) => {
+            let line = format!(
+                "CODEX {} - {} | {}",
+                status.label(),
+                summary.join(", "),
+                perfdata.join(" ")
+            );
+            (status, line)
+        }
+        None => unknown("the API reported no usage"),
+    }
+}
+
+/// UNKNOWN, for a check that couldn't be made
+pub fn unknown(why: &str) -> (Status, String) {
+    let first = why.lines().next().unwrap_or_default();
+    (Status::Unknown, format!("CODEX UNKNOWN - {first}"))
+}
+
+/// A check that ended other than OK: the exit code to leave with, once the
+/// status line is out
+#[derive(Debug)]
+pub struct Exit(pub Status);
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Check status {}", self.0.label())
+    }
+}
+
+impl std::error::Error for Exit {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    fn check(fixture: &str, warn: f64, crit: f64) -> (Status, String) {
+        let now = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let snap = Snapshot::new(serde_json::from_str(fixture).unwrap(), now);
+        let t = Thresholds::new(Some(warn), Some(crit)).unwrap();
+        evaluate(&snap, now, t)
+    }
+
+    #[test]
+    fn statuses_and_perfdata() {
+        let low = include_str!("../tests/fixtures/low.json");
+        let (status, line) = check(low, 70.0, 90.0);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(
+            line,
+            "CODEX OK - 5h 12%, 7d 4.5% | primary=12%;70;90;0;100 secondary=4.5%;70;90;0;100"
+        );
+        assert_eq!(check(low, 10.0, 90.0).0, Status::Warning);
+        assert_eq!(check(low, 5.0, 10.0).0, Status::Critical);
+
+        // Reached by the API's word, with every window under --warn
+        let reached = include_str!("../tests/fixtures/soft_limited.json");
+        let (status, line) = check(reached, 99.0, 99.5);
+        assert_eq!(status, Status::Critical);
+        assert!(line.contains("limit reached |"), "{line}");
+
+        assert!(Thresholds::new(Some(95.0), None).is_err());
+        assert_eq!(unknown("Failed to reach ChatGPT API\nmore").0.code(), 3);
+    }
+}
@@ -0,0 +1,82 @@
+//! CI runners. A personal `codex login` token that ends up in a pipeline's
+//! environment (a copied dotfile, a secret added "just to try") would be sent
+//! from shared infrastructure on every build, so when a CI runner is detected
+//! nothing that carries credentials leaves the machine unless
+//! `--allow-network` says it should. `--dry-run` keeps working as a smoke test.
+
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Variables CI services set on their runners, most specific first
+const VARS: &[&str] = &[
+    "GITHUB_ACTIONS",
+    "GITLAB_CI",
+    "BUILDKITE",
+    "CIRCLECI",
+    "TRAVIS",
+    "JENKINS_URL",
+    "TF_BUILD",
+    "TEAMCITY_VERSION",
+    "BITBUCKET_BUILD_NUMBER",
+    "CODEBUILD_BUILD_ID",
+    "DRONE",
+    "APPVEYOR",
+    "CI",
+];
+
+static ALLOWED: AtomicBool = AtomicBool::new(false);
+
+/// `--allow-network`: send credentials even on a CI runner
+pub fn allow() {
+    ALLOWED.store(true, Ordering::Relaxed);
+}
+
+/// The variable that gives a CI runner away, if any. `CI=false` (or `0`)
+/// is how some setups say they aren't one.
+pub fn detect(env: impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    VARS.iter().copied().find(|var| {
+        env(var).is_some_and(|v| {
+            let v = v.trim().to_ascii_lowercase();
+            !v.is_empty() && v != "false" && v != "0"
+        })
+    })
+}
+
+/// Fail before credentials are sent from a CI runner without
+/// `--allow-network`
+pub fn check() -> Result<()> {
+    if ALLOWED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if let Some(var) = detect(|k| std::env::var(k).ok()) {
+        bail!(
+            "Refusing to send credentials from a CI runner ({var} is set). Pass \
+             --allow-network if this pipeline should check usage, or use --dry-run \
+             to test without the network"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spots_runners() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |k: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == k)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(
+            detect(env(&[("CI", "true"), ("GITHUB_ACTIONS", "true")])),
+            Some("GITHUB_ACTIONS")
+        );
+        assert_eq!(detect(env(&[("CI", "1")])), Some("CI"));
+        assert_eq!(detect(env(&[("CI", "false")])), None);
+        assert_eq!(detect(env(&[("HOME", "/root")])), None);
+    }
+}
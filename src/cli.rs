@@ -0,0 +1,762 @@
+//! Command-line parsing. Deliberately hand-rolled — the surface is small.
+//!
+//! Flags live in one flat namespace and may appear anywhere; the positional
+//! words pick the subcommand.
+
+use crate::history::SessionMark;
+use crate::render::{Format, Sort};
+use anyhow::{bail, Context, Result};
+
+pub const USAGE: &str = "\
+Usage: codex-usage [OPTIONS]
+       codex-usage all [--timeout <SECS>] [--sort severity|name]
+       codex-usage doctor [--json]
+       codex-usage history replay [--speed <N>x] [--since <TIME>] [--until <TIME>]
+       codex-usage history export --parquet <FILE> | --csv <FILE> [--since <TIME>] [--until <TIME>]
+       codex-usage history plans [--json]
+       codex-usage query <SQL> | --schema
+       codex-usage team report --seats <FILE> [--sort severity|name] [SWEEP OPTIONS]
+       codex-usage fleet validate --tokens-file <FILE> [SWEEP OPTIONS]
+       codex-usage serve [--listen <ADDR>] [--label <KEY=VALUE>]... [--prometheus] [--adaptive]
+       codex-usage daemon [--socket <PATH>] [--dbus] [--adaptive]
+       codex-usage status [--json]
+       codex-usage notify test [--channel <NAME>] [--json]
+       codex-usage tui [--compare <PROFILE>] [--interval <SECS>] [--adaptive]
+       codex-usage tray [--interval <SECS>] [--adaptive]
+       codex-usage record [--interval <SECS>]
+       codex-usage session start <NAME> | stop | report [--json]
+       codex-usage badge [--style shields] [--gist <ID> [--gist-file <NAME>]]
+       codex-usage hook --command <CMD>
+       codex-usage shell-init bash|zsh|fish [--after-codex] [--no-prompt]
+       codex-usage env [fish] [--max-age <SECS>]
+       codex-usage advise
+       codex-usage explain [<CODE>]
+       codex-usage meta stats [--json]
+       codex-usage config env-docs [--json]
+
+Options:
+  --format <FORMAT>  Output format: fancy (default), plain, json, json-pretty,
+                     yaml (the JSON report as YAML), prometheus (gauges
+                     for node_exporter's textfile collector), influx
+                     (an InfluxDB line-protocol point), waybar (JSON for
+                     a Waybar custom module), polybar (one line with
+                     polybar colour tags), tmux (a status-line segment),
+                     i3blocks (full text, short text and colour), xbar
+                     (an xbar/SwiftBar menu-bar plugin), starship (a
+                     prompt module, answered from the cache at once),
+                     oh-my-posh (JSON with text, colours and tooltip),
+                     conky (both windows with conky colour variables),
+                     oneline, eww (flat JSON for an eww widget), prompt;
+                     piped with none given, oneline
+  -p, --plain        Same as --format plain
+  --oneline          Same as --format oneline: PLUS 5h:43%(2h13m) 7d:12%(4d2h)
+  --prompt           Same as --format prompt: CX 5h:42% 7d:12% reset 1h03m
+  --json             One line of JSON (schema in the README); errors are
+                     JSON on stderr too
+  --json-pretty      The same JSON, indented
+  --now <TIME>       Pretend it is TIME (RFC 3339 or unix seconds) when
+                     computing countdowns; also CODEX_USAGE_NOW
+  --profile <NAME>   Use a profile from the config file
+  --patterns         Vary bar patterns with severity as well as colour
+  --pace             Draw a thin bar under each window for how much of it
+                     has passed, to hold usage against
+  -q, --quiet        No progress spinner while fetching
+  --tty, --no-tty    Treat stdout as a terminal or not, overriding detection:
+                     colour and the progress line follow
+  --locale <TAG>     Decimal separator and date format for reports and CSV,
+                     e.g. de-DE; also CODEX_USAGE_LOCALE
+  --week <DAYS@TZ>   Also show the work week, e.g. mon-fri@Australia/Brisbane;
+                     overrides [week] in the config file
+  --no-history       Don't append this run to the local history
+  --max-age <SECS>   Reuse the last fetch if it is at most SECS old instead
+                     of calling the API; for prompts and status bars
+  --cached           Take the report from a running `daemon` instead of
+                     calling the API; --socket to pick one
+  --watch            Redraw the report in place until q or Ctrl-C, fetching
+                     every --interval seconds; r refreshes, p switches
+                     profile, a shows reset times
+  --interval <SECS>  Seconds between fetches with --watch, tui, tray or record
+                     (default 60, 900 for record; at least 10)
+  --adaptive         Pace fetches by usage (--watch, tui, tray, serve, daemon):
+                     slower while low, faster as a window fills or nears
+                     its reset, backing off while the limit is reached
+  --log-csv <FILE>   Append a row for this run (time, plan, both windows'
+                     percent and reset, limit flag, error code) to FILE;
+                     for building a record from cron
+  --log-ndjson <FILE>
+                     Append this run's --json report, with a timestamp, as
+                     one line of JSON to FILE; for log shippers
+  --influx-url <URL> Also write this run's point to InfluxDB or Telegraf,
+                     e.g. http://host:8086/api/v2/write?org=me&bucket=codex
+  --influx-token <T> Token for --influx-url; also INFLUX_TOKEN
+  --statsd <HOST:PORT>
+                     Also send this run's gauges to a StatsD daemon over UDP
+  --dogstatsd        Tag the gauges with plan and profile, DogStatsD style;
+                     alone, sends to the Datadog agent on 127.0.0.1:8125
+  --label <K=V>      Tag prometheus and influx output, --influx-url points
+                     and --dogstatsd gauges; repeatable, as for serve
+  --check            Act as a Nagios/Icinga plugin: one status line with
+                     perfdata, exit 0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN
+  --warn <PCT>       WARNING from this percent of any window (default 70)
+  --crit <PCT>       CRITICAL from this percent of any window (default 90)
+  --anonymize        Mask the plan, workspace, email, account id and home
+                     directory in what is shown, for screenshots; usage
+                     figures are kept
+  --har <FILE>       Save the HTTP exchanges as HAR (credentials redacted)
+  --timing           Print where the time went (credentials, HTTP client
+                     setup, request, parse, render) to stderr afterwards
+  --dry-run          Show the endpoint, redacted headers and proxy that would
+                     be used, without sending anything (also with `all`)
+  --allow-network    Send credentials even when a CI runner is detected (CI,
+                     GITHUB_ACTIONS, GITLAB_CI, …); refused by default
+  --lenient-json     Accept comments and trailing commas in auth.json
+  --explain <CODE>   Same as `explain <CODE>`
+  -h, --help         Show this help
+
+Profiles:
+  all                Every configured profile at once; slow ones are shown
+                     as timed out and filled in when a retry answers
+    --timeout <SECS> Per-attempt deadline for every profile (default 10,
+                     or timeout_seconds in the profile)
+    --sort <ORDER>   severity (default: limit reached, then fullest, first)
+                     or name; team report takes it too
+  doctor             Each profile's credential sources: found, valid,
+                     expiry; exits 1 unless every profile works (--json too)
+
+History:
+  history replay     Animate stored samples through the fancy renderer
+    --speed <N>x     Playback speed (default 60x)
+    --since <TIME>   Start from this time
+    --until <TIME>   Stop at this time
+  history export     Write history to a typed file for pandas / Polars
+    --parquet <FILE> Parquet output path
+    --csv <FILE>     CSV output path (- for stdout); see --locale
+  history plans      Every plan change on record and when it took effect
+                     (--json too)
+  record             Sample usage into the history every --interval seconds
+                     (default 900), on the clock; for systemd or launchd
+  session start <N>  Record a sample marking the start of work session N
+  session stop       Record a sample ending it, and say what it used
+  session report     Each session and the quota it used (--json too)
+  query <SQL>        Run read-only SQL against the `samples` and `windows` tables
+    --schema         Print the tables' schema
+
+Team:
+  team report        Weekly window as an error budget across seats
+    --seats <FILE>   JSON lines: {\"name\", \"access_token\", \"account_id\"}
+  fleet validate     Find expired or rejected seat tokens; exits 1 if any
+    --tokens-file <F>  Same format as --seats
+  Sweep options (team report, fleet validate):
+    --jobs <N>       Seats queried at once (default 8)
+    --rate <N>[/s|/m]  Request rate across all jobs (default 5/s)
+    --retries <N>    Retries per seat after a failure (default 2)
+    --checkpoint <F> Record finished seats here and skip them when re-run
+
+Server:
+  serve              Read-only JSON server: /usage/<profile>, bearer auth
+    --listen <ADDR>  Bind address (default 127.0.0.1:8787)
+    --label <K=V>    Add a label to every body served or pushed; repeatable,
+                     also CODEX_USAGE_LABELS=k=v,… and [server.labels]
+    --prometheus     Run as a Prometheus exporter: /metrics, default
+                     address 127.0.0.1:9187
+  daemon             Poll in the background and answer `--cached` clients
+                     over a Unix socket
+    --socket <PATH>  Socket path (default $XDG_RUNTIME_DIR/codex-usage.sock);
+                     also CODEX_USAGE_SOCKET
+    --dbus           Also publish org.codexusage.Monitor on the session bus
+                     (Linux, `dbus` feature); also CODEX_USAGE_DBUS=1
+  status             Active profile, token source and expiry, last fetch,
+                     whether `serve` is alive and how old its data is,
+                     recorders and integrations (--json too)
+  notify test        Send a test alert through every [alerting] channel and
+                     say how each went; exits 1 if any failed (--json too)
+    --channel <NAME> Only log, desktop, webhook, pagerduty or opsgenie
+  tui                Full-screen dashboard of --profile: gauges, plan, chart
+    --compare <P>    Show profile P in a column beside it; every refresh
+                     fetches both
+  tray               Usage as a tray or menu-bar icon, with a menu to
+                     refresh, open `tui` in a terminal, or quit (`tray`
+                     feature)
+
+Badge:
+  badge              Print a shields.io endpoint JSON payload
+    --style <STYLE>  Badge schema (default shields)
+    --gist <ID>      Write it to this gist instead (needs GITHUB_TOKEN)
+    --gist-file <F>  File name inside the gist (default codex-usage-badge.json)
+
+Shell integration:
+  hook               Record a history sample tagged with a command; meant
+                     to be called by a shell hook after each `codex` run
+    --command <CMD>  The command line that just finished
+  shell-init <SHELL> Print the bash, zsh or fish setup to eval from the rc
+                     file: completion and a usage segment in the prompt
+    --after-codex    Also print usage after each `codex` command, recording
+                     it with the command as `hook` does
+    --no-prompt      Leave the prompt alone
+  env [fish]         Print the last fetch as `export CODEX_USAGE_PRIMARY=43`
+                     lines (fish: `set -gx`) to eval, from the cache; fetches
+                     if the cache is empty or older than --max-age
+
+Agents:
+  advise             Print `proceed|slow|stop <wait_seconds>` for an agent
+                     wrapper to obey between tasks; the reason goes to stderr
+
+Errors:
+  explain <CODE>     What an error code such as E003 means, its likely causes
+                     and what to do; without a code, list them all
+
+Meta:
+  meta stats         How often codex-usage ran, by command, format and error
+                     code; recorded only with [stats] enabled = true or
+                     CODEX_USAGE_STATS=1, and never sent anywhere
+  config env-docs    Every environment variable read, with defaults and the
+                     config setting each overrides, as Markdown (--json too)";
+
+/// What to do, picked by the positional words
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Command {
+    /// Fetch and print the current usage (the default)
+    #[default]
+    Report,
+    All,
+    Doctor,
+    HistoryReplay,
+    HistoryExport,
+    HistoryPlans,
+    Query,
+    TeamReport,
+    FleetValidate,
+    Serve,
+    Daemon,
+    Status,
+    NotifyTest,
+    Tui,
+    Tray,
+    Record,
+    SessionStart,
+    SessionStop,
+    SessionReport,
+    Badge,
+    Hook,
+    ShellInit,
+    Env,
+    Advise,
+    Explain,
+    MetaStats,
+    ConfigEnvDocs,
+}
+
+impl Command {
+    pub const ALL: [Command; 27] = [
+        Command::Report,
+        Command::All,
+        Command::Doctor,
+        Command::HistoryReplay,
+        Command::HistoryExport,
+        Command::HistoryPlans,
+        Command::Query,
+        Command::TeamReport,
+        Command::FleetValidate,
+        Command::Serve,
+        Command::Daemon,
+        Command::Status,
+        Command::NotifyTest,
+        Command::Tui,
+        Command::Tray,
+        Command::Record,
+        Command::SessionStart,
+        Command::SessionStop,
+        Command::SessionReport,
+        Command::Badge,
+        Command::Hook,
+        Command::ShellInit,
+        Command::Env,
+        Command::Advise,
+        Command::Explain,
+        Command::MetaStats,
+        Command::ConfigEnvDocs,
+    ];
+
+    /// The subcommand as typed, for stats
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Report => "report",
+            Command::All => "all",
+            Command::Doctor => "doctor",
+            Command::HistoryReplay => "history replay",
+            Command::HistoryExport => "history export",
+            Command::HistoryPlans => "history plans",
+            Command::Query => "query",
+            Command::TeamReport => "team report",
+            Command::FleetValidate => "fleet validate",
+            Command::Serve => "serve",
+            Command::Daemon => "daemon",
+            Command::Status => "status",
+            Command::NotifyTest => "notify test",
+            Command::Tui => "tui",
+            Command::Tray => "tray",
+            Command::Record => "record",
+            Command::SessionStart => "session start",
+            Command::SessionStop => "session stop",
+            Command::SessionReport => "session report",
+            Command::Badge => "badge",
+            Command::Hook => "hook",
+            Command::ShellInit => "shell-init",
+            Command::Env => "env",
+            Command::Advise => "advise",
+            Command::Explain => "explain",
+            Command::MetaStats => "meta stats",
+            Command::ConfigEnvDocs => "config env-docs",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Args {
+    pub command: Command,
+    pub format: Format,
+    /// A format flag was given, so piping the report doesn't change it
+    pub format_given: bool,
+    pub now: Option<String>,
+    pub week: Option<String>,
+    pub no_history: bool,
+    pub dry_run: bool,
+    pub allow_network: bool,
+    pub lenient_json: bool,
+    pub timing: bool,
+    pub har: Option<String>,
+    pub speed: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub seats: Option<String>,
+    pub tokens_file: Option<String>,
+    pub jobs: Option<usize>,
+    pub timeout: Option<u64>,
+    /// Seconds a cached snapshot may be reused for
+    pub max_age: Option<u64>,
+    pub watch: bool,
+    pub interval: Option<u64>,
+    /// CSV file to append this run's row to
+    pub log_csv: Option<String>,
+    /// NDJSON file to append this run's line to
+    pub log_ndjson: Option<String>,
+    /// InfluxDB write endpoint to push this run's point to
+    pub influx_url: Option<String>,
+    pub influx_token: Option<String>,
+    /// Shell for `shell-init` and `env`
+    pub shell: Option<String>,
+    /// `shell-init --after-codex`
+    pub after_codex: bool,
+    /// `shell-init --no-prompt`
+    pub no_prompt: bool,
+    /// `--anonymize`: mask identifying details in the output
+    pub anonymize: bool,
+    /// `--check`: Nagios plugin output and exit codes
+    pub check: bool,
+    pub warn: Option<f64>,
+    pub crit: Option<f64>,
+    /// StatsD daemon to send this run's gauges to
+    pub statsd: Option<String>,
+    /// `--dogstatsd`: tagged gauges
+    pub dogstatsd: bool,
+    pub rate: Option<String>,
+    pub retries: Option<u32>,
+    pub checkpoint: Option<String>,
+    pub profile: Option<String>,
+    pub listen: Option<String>,
+    /// `--cached`: ask the daemon rather than the API
+    pub cached: bool,
+    pub socket: Option<String>,
+    /// `daemon --dbus`
+    pub dbus: bool,
+    /// `--adaptive`: pace polls by usage
+    pub adaptive: bool,
+    /// `notify test --channel`
+    pub channel: Option<String>,
+    /// `--label key=value` for `serve` and a run's metrics, in order
+    pub labels: Vec<String>,
+    /// `serve --prometheus`: exporter defaults
+    pub prometheus: bool,
+    pub style: Option<String>,
+    pub gist: Option<String>,
+    pub gist_file: Option<String>,
+    pub command_line: Option<String>,
+    /// The marker `session start` and `session stop` put on their sample
+    pub session: Option<SessionMark>,
+    pub parquet: Option<String>,
+    pub csv: Option<String>,
+    pub locale: Option<String>,
+    pub patterns: bool,
+    pub pace: bool,
+    pub sort: Sort,
+    /// `tui --compare`: the profile shown beside `--profile`
+    pub compare: Option<String>,
+    pub quiet: bool,
+    /// `--tty` / `--no-tty`; `None` means detect
+    pub tty: Option<bool>,
+    pub sql: Option<String>,
+    /// Error code for `explain`
+    pub code: Option<String>,
+    pub schema: bool,
+    pub json: bool,
+    pub help: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            command: Command::Report,
+            format: Format::Fancy,
+            format_given: false,
+            now: None,
+            week: None,
+            no_history: false,
+            dry_run: false,
+            allow_network: false,
+            lenient_json: false,
+            timing: false,
+            har: None,
+            speed: None,
+            since: None,
+            until: None,
+            seats: None,
+            tokens_file: None,
+            jobs: None,
+            timeout: None,
+            max_age: None,
+            watch: false,
+            interval: None,
+            log_csv: None,
+            log_ndjson: None,
+            influx_url: None,
+            influx_token: None,
+            shell: None,
+            after_codex: false,
+            no_prompt: false,
+            anonymize: false,
+            check: false,
+            warn: None,
+            crit: None,
+            statsd: None,
+            dogstatsd: false,
+            rate: None,
+            retries: None,
+            checkpoint: None,
+            profile: None,
+            listen: None,
+            cached: false,
+            socket: None,
+            dbus: false,
+            adaptive: false,
+            channel: None,
+            labels: Vec::new(),
+            prometheus: false,
+            style: None,
+            gist: None,
+            gist_file: None,
+            command_line: None,
+            session: None,
+            parquet: None,
+            csv: None,
+            locale: None,
+            patterns: false,
+            pace: false,
+            sort: Sort::Severity,
+            compare: None,
+            quiet: false,
+            tty: None,
+            sql: None,
+            code: None,
+            schema: false,
+            json: false,
+            help: false,
+        }
+    }
+}
+
+/// Whether the command line asks for JSON, judged before (or without) a
+/// successful parse so even argument errors can be reported as JSON
+pub fn wants_json(argv: impl IntoIterator<Item = String>) -> bool {
+    let argv: Vec<String> = argv.into_iter().collect();
+    argv.iter().enumerate().any(|(i, a)| {
+        let json =
+            |f: &str| f.eq_ignore_ascii_case("json") || f.eq_ignore_ascii_case("json-pretty");
+        a == "--json"
+            || a == "--json-pretty"
+            || a.strip_prefix("--format=").is_some_and(json)
+            || (a == "--format" && argv.get(i + 1).is_some_and(|f| json(f)))
+    })
+}
+
+/// A threshold from 0 to 100
+fn percent(flag: &str, v: &str) -> Result<f64> {
+    match v.trim_end_matches('%').parse::<f64>() {
+        Ok(p) if (0.0..=100.0).contains(&p) => Ok(p),
+        _ => bail!("Invalid {flag} '{v}' (expected a percentage from 0 to 100)"),
+    }
+}
+
+impl Args {
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.format = format;
+        self.format_given = true;
+    }
+
+    pub fn parse_from(argv: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut args = Args::default();
+        let mut words = Vec::new();
+        let mut argv = argv.into_iter();
+
+        while let Some(arg) = argv.next() {
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((f, v)) if f.starts_with("--") => (f.to_string(), Some(v.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = |name: &str| -> Result<String> {
+                match inline.clone() {
+                    Some(v) => Ok(v),
+                    None => argv.next().with_context(|| format!("{name} needs a value")),
+                }
+            };
+
+            match flag.as_str() {
+                "-p" | "--plain" => args.set_format(Format::Plain),
+                "--oneline" => args.set_format(Format::Oneline),
+                "--prompt" => args.set_format(Format::Prompt),
+                "--format" => args.set_format(value("--format")?.parse()?),
+                "--now" => args.now = Some(value("--now")?),
+                "--week" => args.week = Some(value("--week")?),
+                "--no-history" => args.no_history = true,
+                "--dry-run" => args.dry_run = true,
+                "--allow-network" => args.allow_network = true,
+                "--lenient-json" => args.lenient_json = true,
+                "--timing" => args.timing = true,
+                "--har" => args.har = Some(value("--har")?),
+                "--speed" => args.speed = Some(value("--speed")?),
+                "--since" => args.since = Some(value("--since")?),
+                "--until" => args.until = Some(value("--until")?),
+                "--seats" => args.seats = Some(value("--seats")?),
+                "--tokens-file" => args.tokens_file = Some(value("--tokens-file")?),
+                "--rate" => args.rate = Some(value("--rate")?),
+                "--checkpoint" => args.checkpoint = Some(value("--checkpoint")?),
+                "--retries" => {
+                    let v = value("--retries")?;
+                    args.retries = Some(v.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid --retries '{v}' (expected a number)")
+                    })?);
+                }
+                "--jobs" => {
+                    let v = value("--jobs")?;
+                    match v.parse::<usize>() {
+                        Ok(n) if n > 0 => args.jobs = Some(n),
+                        _ => bail!("Invalid --jobs '{v}' (expected a positive number)"),
+                    }
+                }
+                "--watch" => args.watch = true,
+                "--interval" => {
+                    let v = value("--interval")?;
+                    match v.parse::<u64>() {
+                        Ok(n) if n >= crate::watch::MIN_INTERVAL => args.interval = Some(n),
+                        _ => bail!(
+                            "Invalid --interval '{v}' (expected seconds, at least {})",
+                            crate::watch::MIN_INTERVAL
+                        ),
+                    }
+                }
+                "--timeout" => {
+                    let v = value("--timeout")?;
+                    match v.parse::<u64>() {
+                        Ok(n) if n > 0 => args.timeout = Some(n),
+                        _ => bail!("Invalid --timeout '{v}' (expected seconds)"),
+                    }
+                }
+                "--max-age" => {
+                    let v = value("--max-age")?;
+                    args.max_age = Some(v.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid --max-age '{v}' (expected seconds)")
+                    })?);
+                }
+                "--log-csv" => args.log_csv = Some(value("--log-csv")?),
+                "--log-ndjson" => args.log_ndjson = Some(value("--log-ndjson")?),
+                "--influx-url" => args.influx_url = Some(value("--influx-url")?),
+                "--influx-token" => args.influx_token = Some(value("--influx-token")?),
+                "--after-codex" => args.after_codex = true,
+                "--no-prompt" => args.no_prompt = true,
+                "--anonymize" => args.anonymize = true,
+                "--check" => args.check = true,
+                "--warn" => args.warn = Some(percent("--warn", &value("--warn")?)?),
+                "--crit" => args.crit = Some(percent("--crit", &value("--crit")?)?),
+                "--statsd" => args.statsd = Some(value("--statsd")?),
+                "--dogstatsd" => args.dogstatsd = true,
+                "--profile" => args.profile = Some(value("--profile")?),
+                "--listen" => args.listen = Some(value("--listen")?),
+                "--cached" => args.cached = true,
+                "--socket" => args.socket = Some(value("--socket")?),
+                "--dbus" => args.dbus = true,
+                "--adaptive" => args.adaptive = true,
+                "--channel" => args.channel = Some(value("--channel")?),
+                "--label" => args.labels.push(value("--label")?),
+                "--prometheus" => args.prometheus = true,
+                "--style" => args.style = Some(value("--style")?),
+                "--gist" => args.gist = Some(value("--gist")?),
+                "--gist-file" => args.gist_file = Some(value("--gist-file")?),
+                "--command" => args.command_line = Some(value("--command")?),
+                "--parquet" => args.parquet = Some(value("--parquet")?),
+                "--csv" => args.csv = Some(value("--csv")?),
+                "--locale" => args.locale = Some(value("--locale")?),
+                "--patterns" => args.patterns = true,
+                "--pace" => args.pace = true,
+                "--sort" => args.sort = value("--sort")?.parse()?,
+                "--compare" => args.compare = Some(value("--compare")?),
+                "-q" | "--quiet" => args.quiet = true,
+                "--tty" => args.tty = Some(true),
+                "--no-tty" => args.tty = Some(false),
+                "--schema" => args.schema = true,
+                "--explain" => args.code = Some(value("--explain")?),
+                "--json" => {
+                    args.json = true;
+                    args.set_format(Format::Json);
+                }
+                "--json-pretty" => {
+                    args.json = true;
+                    args.set_format(Format::JsonPretty);
+                }
+                "-h" | "--help" => args.help = true,
+                word if !word.starts_with('-') => words.push(arg),
+                other => bail!("Unrecognised argument '{other}'\n\n{USAGE}"),
+            }
+        }
+
+        args.command = match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] if args.code.is_some() => Command::Explain,
+            [] => Command::Report,
+            ["all"] => Command::All,
+            ["doctor"] => Command::Doctor,
+            ["history", "replay"] => Command::HistoryReplay,
+            ["history", "export"] => Command::HistoryExport,
+            ["history", "plans"] => Command::HistoryPlans,
+            ["query"] => Command::Query,
+            ["query", sql] => {
+                args.sql = Some(sql.to_string());
+                Command::Query
+            }
+            ["team", "report"] => Command::TeamReport,
+            ["fleet", "validate"] => Command::FleetValidate,
+            ["serve"] => Command::Serve,
+            ["daemon"] => Command::Daemon,
+            ["status"] => Command::Status,
+            ["notify", "test"] => Command::NotifyTest,
+            ["tui"] => Command::Tui,
+            ["tray"] => Command::Tray,
+            ["record"] => Command::Record,
+            ["session", "start"] => bail!(
+                "session start needs a name, e.g. `codex-usage session start \"refactor auth\"`"
+            ),
+            ["session", "start", name] => {
+                args.session = Some(SessionMark::Start(name.to_string()));
+                Command::SessionStart
+            }
+            ["session", "stop"] => {
+                args.session = Some(SessionMark::Stop);
+                Command::SessionStop
+            }
+            ["session", "report"] => Command::SessionReport,
+            ["badge"] => Command::Badge,
+            ["hook"] => Command::Hook,
+            ["shell-init"] => Command::ShellInit,
+            ["shell-init", shell] => {
+                args.shell = Some(shell.to_string());
+                Command::ShellInit
+            }
+            ["env"] => Command::Env,
+            ["env", shell] => {
+                args.shell = Some(shell.to_string());
+                Command::Env
+            }
+            ["advise"] => Command::Advise,
+            ["explain"] => Command::Explain,
+            ["meta", "stats"] => Command::MetaStats,
+            ["config", "env-docs"] => Command::ConfigEnvDocs,
+            ["explain", code] => {
+                args.code = Some(code.to_string());
+                Command::Explain
+            }
+            _ => bail!("Unknown command '{}'\n\n{USAGE}", words.join(" ")),
+        };
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(argv: &[&str]) -> Result<Args> {
+        Args::parse_from(argv.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn short_formats_have_their_own_flags() {
+        assert_eq!(parse(&["--oneline"]).unwrap().format, Format::Oneline);
+        assert_eq!(parse(&["--prompt"]).unwrap().format, Format::Prompt);
+        assert_eq!(parse(&["--format=prompt"]).unwrap().format, Format::Prompt);
+        // Too close to --oneline to say which was meant
+        assert!(parse(&["--one-line"]).is_err());
+    }
+
+    #[test]
+    fn json_flags_pick_the_layout() {
+        let line = parse(&["--json"]).unwrap();
+        assert!(line.json && line.format == Format::Json);
+        let pretty = parse(&["--json-pretty"]).unwrap();
+        assert!(pretty.json && pretty.format == Format::JsonPretty);
+    }
+
+    #[test]
+    fn sort_is_severity_unless_named() {
+        assert_eq!(parse(&["all"]).unwrap().sort, Sort::Severity);
+        assert_eq!(parse(&["all", "--sort", "NAME"]).unwrap().sort, Sort::Name);
+        let err = parse(&["all", "--sort", "usage"]).unwrap_err();
+        assert!(err.to_string().contains("Invalid --sort 'usage'"), "{err}");
+    }
+
+    #[test]
+    fn tui_compares_with_a_second_profile() {
+        let args = parse(&["tui", "--profile", "work", "--compare", "home"]).unwrap();
+        assert_eq!(args.command, Command::Tui);
+        assert_eq!(
+            (args.profile.as_deref(), args.compare.as_deref()),
+            (Some("work"), Some("home"))
+        );
+        assert!(parse(&["tui", "--compare"]).is_err());
+    }
+
+    #[test]
+    fn tty_can_be_forced_either_way() {
+        assert_eq!(parse(&[]).unwrap().tty, None);
+        assert_eq!(parse(&["--tty"]).unwrap().tty, Some(true));
+        assert_eq!(parse(&["--no-tty"]).unwrap().tty, Some(false));
+        // The last one given wins
+        assert_eq!(parse(&["--tty", "--no-tty"]).unwrap().tty, Some(false));
+    }
+
+    #[test]
+    fn history_plans_is_its_own_command() {
+        let args = parse(&["history", "plans", "--json"]).unwrap();
+        assert_eq!(args.command, Command::HistoryPlans);
+        assert!(args.json);
+    }
+
+    #[test]
+    fn hook_takes_the_command_that_ran() {
+        let args = parse(&["hook", "--command", "codex exec 'fix tests'"]).unwrap();
+        assert_eq!(args.command, Command::Hook);
+        assert_eq!(args.command_line.as_deref(), Some("codex exec 'fix tests'"));
+        assert!(parse(&["hook", "--command"]).is_err());
+    }
+}
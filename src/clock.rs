@@ -0,0 +1,43 @@
+//! Where "now" comes from. Everything time-dependent asks a `Clock` rather
+//! than calling `Utc::now()`, so `--now` / `CODEX_USAGE_NOW` can pin it.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Clock {
+    /// The real wall clock
+    System,
+    /// A pinned instant, for tests and reproducing past output
+    Fixed(DateTime<Utc>),
+}
+
+impl Clock {
+    /// Build a clock from `--now`, falling back to CODEX_USAGE_NOW
+    pub fn from_override(flag: Option<&str>) -> Result<Self> {
+        let env = std::env::var("CODEX_USAGE_NOW").ok();
+        match flag.or(env.as_deref()).map(str::trim) {
+            Some(s) if !s.is_empty() => Ok(Clock::Fixed(
+                parse_timestamp(s).context("Invalid --now / CODEX_USAGE_NOW value")?,
+            )),
+            _ => Ok(Clock::System),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Clock::System => Utc::now(),
+            Clock::Fixed(t) => *t,
+        }
+    }
+}
+
+/// Accept RFC 3339 (`2024-05-01T10:00:00Z`) or unix seconds
+pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(secs) = s.parse::<i64>() {
+        return DateTime::from_timestamp(secs, 0).context("timestamp out of range");
+    }
+    let t = DateTime::parse_from_rfc3339(s)
+        .with_context(|| format!("'{s}' is not an RFC 3339 timestamp or unix seconds"))?;
+    Ok(t.with_timezone(&Utc))
+}
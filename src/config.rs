@@ -0,0 +1,330 @@
+//! Optional config file: `~/.config/codex-usage/config.toml`, or the path in
+//! CODEX_USAGE_CONFIG. Every setting is optional — with no file at all the
+//! tool behaves exactly as it does out of the box.
+//!
+//! ```toml
+//! [profiles.work]
+//! auth_file = "~/.codex-work/auth.json"
+//!
+//! [profiles.personal]
+//! access_token_env = "PERSONAL_CODEX_TOKEN"
+//! account_id = "acc-123"
+//!
+//! [week]
+//! timezone = "Australia/Brisbane"
+//! days = "mon-fri"
+//!
+//! [plans]
+//! business = "ChatGPT Business"
+//!
+//! [server]
+//! listen = "127.0.0.1:8787"
+//!
+//! [server.labels]
+//! team = "platform"
+//!
+//! [[server.clients]]
+//! name = "kitchen-dashboard"
+//! token_env = "KITCHEN_DASHBOARD_TOKEN"
+//! profiles = ["personal"]
+//! ```
+
+use crate::auth::{self, Credentials, Source};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Profile name used when none is given and none is configured
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub profiles: BTreeMap<String, Profile>,
+    pub server: ServerConfig,
+    pub discord: DiscordConfig,
+    pub week: WeekConfig,
+    pub advise: AdviseConfig,
+    pub theme: ThemeConfig,
+    pub stats: StatsConfig,
+    /// Incident alerts raised by `serve`, by profile name
+    pub alerting: BTreeMap<String, AlertingConfig>,
+    /// Display names by raw plan code, e.g. `business = "ChatGPT Business"`
+    pub plans: BTreeMap<String, String>,
+}
+
+/// Where one account's credentials come from. An empty profile falls back to
+/// the normal discovery order (env vars, auth.json, secret store).
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// An auth.json written by `codex login`
+    pub auth_file: Option<PathBuf>,
+    /// Name of an env var holding an OAuth access token
+    pub access_token_env: Option<String>,
+    /// Sent as chatgpt-account-id alongside a token from `access_token_env`
+    pub account_id: Option<String>,
+    /// Per-attempt deadline in the `all` view (default 10)
+    pub timeout_seconds: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Address to bind, e.g. 127.0.0.1:8787
+    pub listen: Option<String>,
+    /// Seconds between polls of each profile
+    pub poll_seconds: Option<u64>,
+    /// Pace polls by usage, `poll_seconds` being the base
+    pub adaptive: bool,
+    /// Added to every body served, e.g. `team = "platform"`
+    pub labels: BTreeMap<String, String>,
+    pub clients: Vec<ServerClient>,
+}
+
+/// A dashboard allowed to read some profiles with its own bearer token
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ServerClient {
+    pub name: String,
+    /// The bearer token, inline…
+    pub token: Option<String>,
+    /// …or read from this env var
+    pub token_env: Option<String>,
+    /// Profiles this client may read; `"*"` means all
+    pub profiles: Vec<String>,
+}
+
+/// Discord Rich Presence, updated by `serve` whenever usage changes
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DiscordConfig {
+    /// Application id from the Discord developer portal; unset disables it
+    pub client_id: Option<String>,
+    /// Profile to show; defaults to the first one
+    pub profile: Option<String>,
+}
+
+/// Where `serve` sends one profile's limit alerts (see `alerting`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AlertingConfig {
+    /// PagerDuty Events API v2; the key is the integration's routing key
+    pub pagerduty: Option<AlertKey>,
+    /// Opsgenie Alert API; the key is an API integration's key
+    pub opsgenie: Option<AlertKey>,
+    /// A JSON POST to any URL, of our shape or a template's
+    pub webhook: Option<AlertWebhook>,
+    /// Also alert as any window passes these percentages, once per reset
+    pub thresholds: Vec<f64>,
+    /// Percentages, or the limit, routed to particular channels
+    pub escalation: Vec<EscalationStep>,
+    /// When alerts are logged rather than sent
+    pub quiet: Option<QuietConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct QuietConfig {
+    /// Every day, e.g. "22:00-07:00"; may run past midnight
+    pub hours: Option<String>,
+    /// Whole days, e.g. "sat,sun"
+    pub days: Option<String>,
+    /// IANA name the hours and days are in (default UTC)
+    pub timezone: Option<String>,
+    /// Hold back the limit's own alert too, not just thresholds
+    pub include_limit: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AlertKey {
+    /// The key, inline…
+    pub key: Option<String>,
+    /// …or read from this env var
+    pub key_env: Option<String>,
+    /// Opsgenie only: `eu` for accounts in the EU region
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AlertWebhook {
+    /// The URL, inline…
+    pub url: Option<String>,
+    /// …or read from this env var, as it usually holds a secret
+    pub url_env: Option<String>,
+    /// A Tera template for the body, instead of the built-in one; must
+    /// render JSON
+    pub template: Option<String>,
+}
+
+impl AlertWebhook {
+    pub fn resolve(&self) -> Option<String> {
+        self.url
+            .clone()
+            .or_else(|| self.url_env.as_ref().and_then(|v| std::env::var(v).ok()))
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+    }
+}
+
+/// `{ at = 90, notify = ["webhook"] }`
+#[derive(Debug, Deserialize)]
+pub struct EscalationStep {
+    pub at: StepAt,
+    /// log, desktop, webhook, pagerduty or opsgenie
+    pub notify: Vec<String>,
+}
+
+/// A percentage of any window, or `"limit"`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StepAt {
+    Percent(f64),
+    Named(String),
+}
+
+impl AlertKey {
+    pub fn resolve(&self) -> Option<String> {
+        self.key
+            .clone()
+            .or_else(|| self.key_env.as_ref().and_then(|v| std::env::var(v).ok()))
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    }
+}
+
+/// The user's work week, for the work-week view in reports (see `week`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WeekConfig {
+    /// IANA zone, e.g. "Australia/Brisbane"; defaults to UTC
+    pub timezone: Option<String>,
+    /// e.g. "mon-fri" (the default) or "sun-thu" or "mon,tue,thu"
+    pub days: Option<String>,
+}
+
+/// How the fancy report looks
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Encode severity in bar patterns as well as colour (same as --patterns)
+    pub patterns: bool,
+    /// Show how much of each window has passed (same as --pace)
+    pub pace: bool,
+}
+
+/// Local run statistics (see `stats`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+    /// Count runs, formats and failures into a local file; off by default
+    pub enabled: bool,
+}
+
+/// Thresholds for `advise`, as 0–100 of a window used
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AdviseConfig {
+    /// Start answering "slow" here (default 80)
+    pub slow_at: Option<f64>,
+    /// Answer "stop" from here (default 95)
+    pub stop_at: Option<f64>,
+}
+
+impl ServerClient {
+    pub fn resolve_token(&self) -> Option<String> {
+        self.token
+            .clone()
+            .or_else(|| self.token_env.as_ref().and_then(|v| std::env::var(v).ok()))
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    }
+
+    pub fn may_read(&self, profile: &str) -> bool {
+        self.profiles.iter().any(|p| p == "*" || p == profile)
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        match std::env::var_os("CODEX_USAGE_CONFIG").filter(|p| !p.is_empty()) {
+            Some(p) => PathBuf::from(p),
+            None => crate::paths::config_dir().join("config.toml"),
+        }
+    }
+
+    /// Load the config file; a missing file is an empty config
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        let mut config: Config = match std::fs::read_to_string(&path) {
+            Ok(raw) => toml::from_str(&raw)
+                .with_context(|| format!("Could not parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(e).with_context(|| format!("Could not read {}", path.display())),
+        };
+        // Server and daemon settings can come from the environment too
+        crate::envvars::apply(&mut config, |k| std::env::var(k).ok())?;
+        Ok(config)
+    }
+
+    /// Names of the configured profiles, or just "default" when there are none
+    pub fn profile_names(&self) -> Vec<String> {
+        if self.profiles.is_empty() {
+            vec![DEFAULT_PROFILE.to_string()]
+        } else {
+            self.profiles.keys().cloned().collect()
+        }
+    }
+
+    /// Look up a profile; "default" always exists
+    pub fn profile(&self, name: &str) -> Result<Profile> {
+        match self.profiles.get(name) {
+            Some(p) => Ok(p.clone()),
+            None if name == DEFAULT_PROFILE => Ok(Profile::default()),
+            None => bail!(
+                "No profile named '{name}' in {} (have: {})",
+                Self::path().display(),
+                self.profile_names().join(", ")
+            ),
+        }
+    }
+}
+
+impl Profile {
+    /// Where this profile's token can come from, in the order tried
+    pub fn sources(&self) -> Vec<Source> {
+        if let Some(var) = &self.access_token_env {
+            return vec![Source::Env {
+                var: var.clone(),
+                account_id: self.account_id.clone(),
+            }];
+        }
+        if let Some(path) = &self.auth_file {
+            return vec![Source::AuthFile(crate::paths::expand_tilde(path))];
+        }
+        auth::discovery_order()
+    }
+
+    pub fn credentials(&self) -> Result<Credentials> {
+        if let Some(var) = &self.access_token_env {
+            let token = std::env::var(var)
+                .ok()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .with_context(|| format!("Profile token env var {var} is not set"))?;
+            return Ok(Credentials {
+                identity: auth::Identity::from_jwt(&token),
+                access_token: token,
+                account_id: self.account_id.clone(),
+                is_oauth: true,
+            });
+        }
+        if let Some(path) = &self.auth_file {
+            return auth::read_auth_json(&crate::paths::expand_tilde(path));
+        }
+        auth::get_credentials()
+    }
+}
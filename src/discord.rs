@@ -0,0 +1,189 @@
+//! Discord Rich Presence over the local IPC socket — no Discord SDK needed.
+//!
+//! Frames are a little-endian `(opcode: u32, length: u32)` header followed by
+//! a JSON payload. Opcode 0 is the handshake, 1 a command.
+
+use crate::api::Snapshot;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+
+/// Largest frame we accept back from Discord
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+#[cfg(unix)]
+type Pipe = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Pipe = std::fs::File;
+
+pub struct Presence {
+    pipe: Pipe,
+    nonce: u64,
+}
+
+impl Presence {
+    /// Connect to the first Discord IPC socket that answers and handshake
+    pub fn connect(client_id: &str) -> Result<Self> {
+        let mut last_err = std::io::Error::from(std::io::ErrorKind::NotFound);
+        for n in 0..10 {
+            match open_pipe(n) {
+                Ok(pipe) => {
+                    let mut presence = Presence { pipe, nonce: 0 };
+                    presence.send(OP_HANDSHAKE, &json!({ "v": 1, "client_id": client_id }))?;
+                    let (op, reply) = presence.recv()?;
+                    if reply.get("evt").and_then(Value::as_str) != Some("READY") {
+                        bail!("Discord rejected the handshake (op {op}): {reply}");
+                    }
+                    return Ok(presence);
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err).context("Discord does not appear to be running")
+    }
+
+    /// Show `snap` as the current activity
+    pub fn update(&mut self, snap: &Snapshot) -> Result<()> {
+        let rl = snap.usage.rate_limit.as_ref();
+        let primary = rl.and_then(|r| r.primary_window.as_ref());
+        let secondary = rl.and_then(|r| r.secondary_window.as_ref());
+
+        let details = match primary.and_then(|w| Some((w.used_percent?, w.span("primary")?))) {
+            Some((p, span)) => format!("Codex: {p:.0}% of {} window", span.short()),
+            None => "Codex".to_string(),
+        };
+        let mut activity = json!({ "details": details });
+        if let Some((p, span)) =
+            secondary.and_then(|w| Some((w.used_percent?, w.span("secondary")?)))
+        {
+            let label = crate::render::feature_title(&span.adjective());
+            activity["state"] = json!(format!("{label} {p:.0}%"));
+        }
+        // Discord renders the countdown itself from an end timestamp
+        if let Some(secs) = primary.and_then(|w| w.resets_in(snap.fetched_at, Utc::now())) {
+            activity["timestamps"] = json!({ "end": Utc::now().timestamp() + secs as i64 });
+        }
+
+        self.nonce += 1;
+        self.send(
+            OP_FRAME,
+            &json!({
+                "cmd": "SET_ACTIVITY",
+                "args": { "pid": std::process::id(), "activity": activity },
+                "nonce": self.nonce.to_string(),
+            }),
+        )?;
+        let (_, reply) = self.recv()?;
+        if reply.get("evt").and_then(Value::as_str) == Some("ERROR") {
+            bail!("Discord refused the activity: {reply}");
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, op: u32, payload: &Value) -> Result<()> {
+        let body = payload.to_string();
+        let mut frame = Vec::with_capacity(8 + body.len());
+        frame.extend_from_slice(&op.to_le_bytes());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(body.as_bytes());
+        self.pipe
+            .write_all(&frame)
+            .context("Discord IPC write failed")
+    }
+
+    fn recv(&mut self) -> Result<(u32, Value)> {
+        let mut header = [0u8; 8];
+        self.pipe
+            .read_exact(&mut header)
+            .context("Discord IPC read failed")?;
+        let op = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        if len > MAX_FRAME_BYTES {
+            bail!("Discord IPC frame too large ({len} bytes)");
+        }
+        let mut body = vec![0u8; len];
+        self.pipe.read_exact(&mut body)?;
+        Ok((op, serde_json::from_slice(&body).unwrap_or(Value::Null)))
+    }
+}
+
+#[cfg(unix)]
+fn open_pipe(n: u8) -> std::io::Result<Pipe> {
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(|v| std::env::var_os(v).filter(|d| !d.is_empty()))
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| "/tmp".into());
+    Pipe::connect(dir.join(format!("discord-ipc-{n}")))
+}
+
+#[cfg(windows)]
+fn open_pipe(n: u8) -> std::io::Result<Pipe> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(format!(r"\\.\pipe\discord-ipc-{n}"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_the_activity_and_reads_refusals() {
+        let (ours, theirs) = Pipe::pair().unwrap();
+        let mut presence = Presence {
+            pipe: ours,
+            nonce: 0,
+        };
+        // Discord's end of the socket speaks the same framing
+        let discord = std::thread::spawn(move || {
+            let mut discord = Presence {
+                pipe: theirs,
+                nonce: 0,
+            };
+            let (op, first) = discord.recv().unwrap();
+            discord
+                .send(OP_FRAME, &json!({ "cmd": "SET_ACTIVITY", "evt": null }))
+                .unwrap();
+            let (_, second) = discord.recv().unwrap();
+            discord
+                .send(
+                    OP_FRAME,
+                    &json!({ "evt": "ERROR", "data": { "code": 4000 } }),
+                )
+                .unwrap();
+            // A frame past the limit is refused rather than allocated
+            discord.pipe.write_all(&OP_FRAME.to_le_bytes()).unwrap();
+            discord.pipe.write_all(&u32::MAX.to_le_bytes()).unwrap();
+            (op, first, second)
+        });
+
+        let snap = Snapshot::plus((42.0, 3600), (12.0, 6 * 86400), Utc::now());
+        presence.update(&snap).unwrap();
+        let refused = presence.update(&snap).unwrap_err().to_string();
+        assert!(
+            refused.contains("Discord refused the activity"),
+            "{refused}"
+        );
+        let too_large = presence.recv().unwrap_err().to_string();
+        assert!(too_large.contains("too large"), "{too_large}");
+
+        let (op, first, second) = discord.join().unwrap();
+        assert_eq!(op, OP_FRAME);
+        assert_eq!(first["cmd"], "SET_ACTIVITY");
+        assert_eq!(
+            (first["nonce"].as_str(), second["nonce"].as_str()),
+            (Some("1"), Some("2"))
+        );
+        let activity = &first["args"]["activity"];
+        assert_eq!(activity["details"], "Codex: 42% of 5h window");
+        assert_eq!(activity["state"], "Weekly 12%");
+        let end = activity["timestamps"]["end"].as_i64().unwrap();
+        assert!((end - (Utc::now().timestamp() + 3600)).abs() <= 2);
+    }
+}
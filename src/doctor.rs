@@ -0,0 +1,237 @@
+//! `doctor`: where every profile's token comes from and whether it works.
+//!
+//! For each configured profile, each credential source it would try is
+//! listed with what was found there — nothing, something unreadable, an API
+//! key, or an OAuth token and its expiry — and the one actually used gets a
+//! single request to confirm the API accepts it. `--json` prints the same
+//! matrix for fleet tooling.
+
+use crate::api::{self, Unauthorised};
+use crate::auth::Credentials;
+use crate::config::Config;
+use crate::fleet::{self, Status};
+use crate::render::layout::fit_width;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Columns reserved for source labels
+const SOURCE_WIDTH: usize = 36;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Found {
+    Missing,
+    /// Something is there but couldn't be parsed
+    Unreadable,
+    /// A plain API key, which can't see usage limits
+    ApiKey,
+    OauthExpired,
+    Oauth,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceCheck {
+    pub source: String,
+    pub found: Found,
+    /// Redacted token, e.g. `eyJh…k9Qs`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The source this profile actually uses: the first one with a token
+    pub in_use: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileHealth {
+    pub profile: String,
+    pub sources: Vec<SourceCheck>,
+    /// What the API made of the token in use; absent when there was no
+    /// OAuth token to try
+    pub api: Option<Status>,
+}
+
+impl ProfileHealth {
+    pub fn healthy(&self) -> bool {
+        self.api == Some(Status::Ok)
+    }
+}
+
+/// Check every configured profile
+pub fn check(config: &Config, now: DateTime<Utc>) -> Result<Vec<ProfileHealth>> {
+    let mut out = Vec::new();
+    for name in config.profile_names() {
+        let profile = config.profile(&name)?;
+        let mut in_use = None;
+        let sources = profile
+            .sources()
+            .iter()
+            .map(|source| {
+                let mut check = SourceCheck {
+                    source: source.label(),
+                    found: Found::Missing,
+                    token: None,
+                    expires_at: None,
+                    error: None,
+                    in_use: false,
+                };
+                match source.read() {
+                    Ok(None) => {}
+                    Err(e) => {
+                        check.found = Found::Unreadable;
+                        check.error = Some(format!("{e:#}"));
+                    }
+                    Ok(Some(creds)) => {
+                        check.token = Some(fleet::redact(&creds.access_token));
+                        check.expires_at = fleet::jwt_expiry(&creds.access_token);
+                        check.found = match (creds.is_oauth, check.expires_at) {
+                            (false, _) => Found::ApiKey,
+                            (true, Some(exp)) if exp <= now => Found::OauthExpired,
+                            (true, _) => Found::Oauth,
+                        };
+                        if in_use.is_none() {
+                            check.in_use = true;
+                            in_use = Some((creds, check.found, check.expires_at));
+                        }
+                    }
+                }
+                check
+            })
+            .collect();
+
+        let api = match in_use {
+            Some((_, Found::OauthExpired, exp)) => exp.map(Status::Expired),
+            Some((creds, Found::Oauth, _)) => Some(live_check(&creds)),
+            _ => None,
+        };
+        out.push(ProfileHealth {
+            profile: name,
+            sources,
+            api,
+        });
+    }
+    Ok(out)
+}
+
+/// One request with the token, read the way `fleet validate` reads it
+fn live_check(creds: &Credentials) -> Status {
+    match api::fetch_usage_within(creds, crate::all::DEFAULT_TIMEOUT) {
+        Ok(_) => Status::Ok,
+        Err(e) => match e.downcast_ref::<Unauthorised>() {
+            Some(Unauthorised { status, .. }) => {
+                Status::Invalid(format!("HTTP {}", status.as_u16()))
+            }
+            None => Status::Error(format!("{e:#}").lines().next().unwrap_or("").into()),
+        },
+    }
+}
+
+/// The matrix: one block per profile, the source in use marked `▸`
+pub fn render(reports: &[ProfileHealth], now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "\n  {} Credential health", "◆".cyan().bold());
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+    for (i, report) in reports.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let _ = writeln!(out, "  {}", report.profile.bold());
+        for s in &report.sources {
+            let marker = if s.in_use { "▸" } else { " " };
+            let token = s.token.as_deref().unwrap_or_default();
+            let expiry = |exp: DateTime<Utc>| {
+                let days = (exp - now).num_days();
+                let when = exp.format("%Y-%m-%d");
+                match days {
+                    _ if exp <= now => format!("expired {when}"),
+                    0 => format!("expires {when} (today)"),
+                    d => format!("expires {when} (in {d}d)"),
+                }
+            };
+            let found = match s.found {
+                Found::Missing => "not found".dimmed(),
+                Found::Unreadable => {
+                    let why = s.error.as_deref().unwrap_or_default();
+                    format!("unreadable: {}", why.lines().next().unwrap_or_default()).red()
+                }
+                Found::ApiKey => format!("API key {token} — can't see usage limits").yellow(),
+                Found::OauthExpired => match s.expires_at {
+                    Some(exp) => format!("OAuth {token} {}", expiry(exp)).red(),
+                    None => format!("OAuth {token} expired").red(),
+                },
+                Found::Oauth => match s.expires_at {
+                    Some(exp) => format!("OAuth {token} {}", expiry(exp)).green(),
+                    None => format!("OAuth {token}").green(),
+                },
+            };
+            let _ = writeln!(
+                out,
+                "    {marker} {} {found}",
+                fit_width(&s.source, SOURCE_WIDTH)
+            );
+        }
+        let api = match &report.api {
+            Some(Status::Ok) => format!("{} API accepts the token", "✓".green()),
+            Some(Status::Expired(_)) => format!("{} token expired — codex login", "✗".red()),
+            Some(Status::Invalid(why)) => format!("{} API rejected the token ({why})", "✗".red()),
+            Some(Status::Error(why)) => format!("{} API unreachable: {why}", "?".yellow()),
+            None => format!("{} no OAuth token to try", "✗".red()),
+        };
+        let _ = writeln!(out, "    {api}");
+    }
+    let healthy = reports.iter().filter(|r| r.healthy()).count();
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+    let _ = writeln!(out, "  {healthy} of {} profiles ready\n", reports.len());
+    out
+}
+
+/// Fail (exit 1) unless every profile has a token the API accepts
+pub fn ensure_healthy(reports: &[ProfileHealth]) -> Result<()> {
+    let broken = reports.iter().filter(|r| !r.healthy()).count();
+    if broken > 0 {
+        bail!(
+            "{broken} of {} profiles have no working credentials",
+            reports.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_the_source_in_use() {
+        colored::control::set_override(false);
+        let now = crate::clock::parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let source = |label: &str, found, in_use| SourceCheck {
+            source: label.into(),
+            found,
+            token: (found != Found::Missing).then(|| "eyJh…k9Qs".into()),
+            expires_at: Some(now + chrono::Duration::hours(75)),
+            error: None,
+            in_use,
+        };
+        let reports = vec![ProfileHealth {
+            profile: "work".into(),
+            sources: vec![
+                source("env CODEX_ACCESS_TOKEN", Found::Missing, false),
+                source("file ~/.codex/auth.json", Found::Oauth, true),
+            ],
+            api: Some(Status::Invalid("HTTP 401".into())),
+        }];
+        let out = render(&reports, now);
+        assert!(out.contains("  env CODEX_ACCESS_TOKEN"));
+        let used = out.lines().find(|l| l.contains("▸")).unwrap();
+        assert!(used.contains("auth.json") && used.contains("expires 2024-05-04 (in 3d)"));
+        assert!(out.contains("API rejected the token (HTTP 401)"));
+        assert!(out.contains("0 of 1 profiles ready"));
+        assert!(ensure_healthy(&reports).is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! Every environment variable codex-usage reads, in one table. Server and
+//! daemon settings can all come from the environment as well as the config
+//! file, so a container needs no config file at all; `config env-docs`
+//! prints this table, and a test keeps the README's copy identical, so
+//! deployment templates can't drift from the code.
+
+use crate::config::{Config, ServerClient};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt::Write;
+
+#[derive(Serialize)]
+pub struct EnvVar {
+    pub name: &'static str,
+    pub default: &'static str,
+    /// The config file setting it overrides, if any
+    pub config: Option<&'static str>,
+    pub effect: &'static str,
+    /// Applies the value on top of the config file
+    #[serde(skip)]
+    apply: Option<fn(&mut Config, &str) -> Result<()>>,
+}
+
+const fn var(name: &'static str, default: &'static str, effect: &'static str) -> EnvVar {
+    EnvVar {
+        name,
+        default,
+        config: None,
+        effect,
+        apply: None,
+    }
+}
+
+const fn setting(
+    name: &'static str,
+    default: &'static str,
+    config: &'static str,
+    effect: &'static str,
+    apply: fn(&mut Config, &str) -> Result<()>,
+) -> EnvVar {
+    EnvVar {
+        name,
+        default,
+        config: Some(config),
+        effect,
+        apply: Some(apply),
+    }
+}
+
+pub const VARS: &[EnvVar] = &[
+    // ─── Credentials ─────────────────────────────────────────────────────────
+    var(
+        "CODEX_ACCESS_TOKEN",
+        "—",
+        "OAuth access token, used before any auth.json",
+    ),
+    var(
+        "CODEX_ACCOUNT_ID",
+        "—",
+        "Sent as chatgpt-account-id with `CODEX_ACCESS_TOKEN`",
+    ),
+    var(
+        "OPENAI_API_KEY",
+        "—",
+        "Noticed only to explain that API keys can't show usage limits",
+    ),
+    var("GITHUB_TOKEN", "—", "Token for `badge --gist`"),
+    var(
+        "INFLUX_TOKEN",
+        "—",
+        "Token for `--influx-url` when `--influx-token` isn't given",
+    ),
+    // ─── Server and daemon ───────────────────────────────────────────────────
+    setting(
+        "CODEX_USAGE_LISTEN",
+        "`127.0.0.1:8787`",
+        "[server] listen",
+        "Address `serve` binds",
+        |c, v| {
+            c.server.listen = Some(v.to_string());
+            Ok(())
+        },
+    ),
+    setting(
+        "CODEX_USAGE_POLL_SECONDS",
+        "`60`",
+        "[server] poll_seconds",
+        "Seconds between polls of each profile",
+        |c, v| {
+            c.server.poll_seconds = Some(number("CODEX_USAGE_POLL_SECONDS", v)?);
+            Ok(())
+        },
+    ),
+    setting(
+        "CODEX_USAGE_ADAPTIVE",
+        "`0`",
+        "[server] adaptive",
+        "`1` to pace polls by usage, the poll interval being the base",
+        |c, v| {
+            c.server.adaptive = match v {
+                "1" | "true" => true,
+                "0" | "false" => false,
+                _ => bail!("Invalid CODEX_USAGE_ADAPTIVE '{v}' (expected 1 or 0)"),
+            };
+            Ok(())
+        },
+    ),
+    setting(
+        "CODEX_USAGE_SERVER_CLIENTS",
+        "—",
+        "[[server.clients]]",
+        "Bearer-token clients as `name:TOKEN_ENV:profile|profile,…` (`*` for all \
+         profiles); replaces the config file's list",
+        |c, v| {
+            c.server.clients = clients(v)?;
+            Ok(())
+        },
+    ),
+    setting(
+        "CODEX_USAGE_LABELS",
+        "—",
+        "[server.labels]",
+        "`key=value,…` labels added to everything `serve` serves and to metrics a run prints or pushes; `--label` flags win",
+        |c, v| {
+            for pair in v.split(',').filter(|p| !p.trim().is_empty()) {
+                let Some((k, v)) = pair.split_once('=').filter(|(k, _)| !k.trim().is_empty())
+                else {
+                    bail!("Invalid label '{pair}' in CODEX_USAGE_LABELS (expected key=value)");
+                };
+                c.server.labels.insert(k.trim().into(), v.trim().into());
+            }
+            Ok(())
+        },
+    ),
+    setting(
+        "CODEX_USAGE_DISCORD_CLIENT_ID",
+        "—",
+        "[discord] client_id",
+        "Discord application id; turns Rich Presence on while serving",
+        |c, v| {
+            c.discord.client_id = Some(v.to_string());
+            Ok(())
+        },
+    ),
+    setting(
+        "CODEX_USAGE_DISCORD_PROFILE",
+        "first profile",
+        "[discord] profile",
+        "Profile shown in Discord",
+        |c, v| {
+            c.discord.profile = Some(v.to_string());
+            Ok(())
+        },
+    ),
+    var(
+        "CODEX_USAGE_SOCKET",
+        "`$XDG_RUNTIME_DIR/codex-usage.sock`",
+        "Socket for `daemon` and `--cached`, same as `--socket`",
+    ),
+    var(
+        "CODEX_USAGE_DBUS",
+        "off",
+        "`1` to publish on the session bus from `daemon`, same as `--dbus`",
+    ),
+    // ─── Terminal ────────────────────────────────────────────────────────────
+    var("NO_COLOR", "—", "Set to anything to turn colour off"),
+    var("CLICOLOR", "—", "`0` turns colour off"),
+    var(
+        "CLICOLOR_FORCE",
+        "—",
+        "Anything but `0` keeps colour on when piped",
+    ),
+    var(
+        "FORCE_COLOR",
+        "—",
+        "Set to anything to keep colour on when piped",
+    ),
+    var(
+        "COLORTERM",
+        "—",
+        "`truecolor` or `24bit` picks the 24-bit palette",
+    ),
+    var(
+        "TERM",
+        "—",
+        "`*256color` picks the 256-colour palette; `dumb` turns colour off",
+    ),
+    var(
+        "TERMINAL",
+        "`x-terminal-emulator`",
+        "Terminal the tray's \"Open dashboard\" starts on Linux",
+    ),
+    // ─── Everything else ─────────────────────────────────────────────────────
+    var(
+        "CODEX_USAGE_CONFIG",
+        "`~/.config/codex-usage/config.toml`",
+        "Config file to read",
+    ),
+    var(
+        "CODEX_USAGE_ENDPOINT",
+        "`https://chatgpt.com/backend-api/wham/usage`",
+        "Usage endpoint, e.g. an internal relay or a test server",
+    ),
+    var(
+        "CODEX_USAGE_MAX_BODY",
+        "`1048576`",
+        "Maximum usage or account check response size in bytes; larger bodies are rejected",
+    ),
+    var("CODEX_USAGE_LOCALE", "—", "Default for `--locale`"),
+    var(
+        "CODEX_USAGE_HISTORY",
+        "on",
+        "`0` to stop appending samples to the local history",
+    ),
+    var(
+        "CODEX_USAGE_STATS",
+        "—",
+        "`1` or `0` to turn local run counters on or off, overriding `[stats]`",
+    ),
+    var(
+        "CODEX_USAGE_NOW",
+        "—",
+        "Pin \"now\" (RFC 3339 or unix seconds), same as `--now`; makes countdowns \
+         reproducible",
+    ),
+    var(
+        "XDG_DATA_HOME",
+        "`~/.local/share`",
+        "Parent of the data directory (history, cache, stats)",
+    ),
+    var(
+        "XDG_STATE_HOME",
+        "`~/.local/state`",
+        "Parent of the data directory when the usual one isn't writable",
+    ),
+    var(
+        "XDG_CONFIG_HOME",
+        "`~/.config`",
+        "Parent of the config directory",
+    ),
+    var(
+        "XDG_RUNTIME_DIR",
+        "—",
+        "Where the `daemon` socket goes by default and Discord's IPC socket is found",
+    ),
+    var(
+        "HOME",
+        "—",
+        "Home of `~/.codex`, the data and config directories",
+    ),
+    var(
+        "USER",
+        "—",
+        "Names the temp directory fallback, `codex-usage-$USER`",
+    ),
+    var("LOGNAME", "—", "Stands in for `USER` when it is unset"),
+    var(
+        "TMPDIR",
+        "`/tmp`",
+        "Temp directory for the last data fallback and Discord's IPC socket \
+         (`TMP` or `TEMP` on Windows)",
+    ),
+    var(
+        "CI",
+        "—",
+        "Set on a CI runner, like `GITHUB_ACTIONS`, `GITLAB_CI` and others: credentials \
+         aren't sent without `--allow-network`",
+    ),
+    var(
+        "HTTPS_PROXY",
+        "—",
+        "Proxy for the usage API (or `ALL_PROXY`); `--dry-run` shows the one in use",
+    ),
+    var("NO_PROXY", "—", "Hosts reached without the proxy"),
+];
+
+/// Lay the environment over `config`, for every variable that has a
+/// config file twin. Empty values count as unset.
+pub fn apply(config: &mut Config, env: impl Fn(&str) -> Option<String>) -> Result<()> {
+    for v in VARS {
+        if let (Some(apply), Some(value)) = (v.apply, env(v.name)) {
+            if !value.trim().is_empty() {
+                apply(config, value.trim())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn number(name: &str, v: &str) -> Result<u64> {
+    match v.parse() {
+        Ok(n) => Ok(n),
+        Err(_) => bail!("Invalid {name} '{v}' (expected a number)"),
+    }
+}
+
+fn clients(v: &str) -> Result<Vec<ServerClient>> {
+    v.split(',')
+        .filter(|e| !e.trim().is_empty())
+        .map(|entry| {
+            let parts: Vec<&str> = entry.trim().split(':').collect();
+            let [name, token_env, profiles] = parts[..] else {
+                bail!(
+                    "Invalid CODEX_USAGE_SERVER_CLIENTS entry '{entry}' \
+                     (expected name:TOKEN_ENV:profile|profile)"
+                );
+            };
+            Ok(ServerClient {
+                name: name.to_string(),
+                token: None,
+                token_env: Some(token_env.to_string()),
+                profiles: profiles.split('|').map(str::to_string).collect(),
+            })
+        })
+        .collect()
+}
+
+/// `config env-docs`: the table as Markdown
+pub fn markdown() -> String {
+    let mut out = String::from(
+        "| Variable | Default | Overrides | Effect |\n\
+         |----------|---------|-----------|--------|\n",
+    );
+    for v in VARS {
+        let config = v.config.map_or("—".to_string(), |key| format!("`{key}`"));
+        // A pipe inside a cell, even in backticks, would end it
+        let effect = v.effect.replace('|', "\\|");
+        let _ = writeln!(
+            out,
+            "| `{}` | {} | {config} | {effect} |",
+            v.name, v.default
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_config_and_readme_matches() {
+        let mut config: Config = toml::from_str(
+            "[server]\nlisten = \"127.0.0.1:9000\"\n[server.labels]\nteam = \"infra\"",
+        )
+        .unwrap();
+        let env = |k: &str| {
+            match k {
+                "CODEX_USAGE_LISTEN" => Some("0.0.0.0:8787"),
+                "CODEX_USAGE_POLL_SECONDS" => Some("30"),
+                "CODEX_USAGE_SERVER_CLIENTS" => {
+                    Some("grafana:GRAFANA_TOKEN:*,ci:CI_TOKEN:home|work")
+                }
+                "CODEX_USAGE_LABELS" => Some("pod=api-0"),
+                _ => None,
+            }
+            .map(String::from)
+        };
+        apply(&mut config, env).unwrap();
+        assert_eq!(config.server.listen.as_deref(), Some("0.0.0.0:8787"));
+        assert_eq!(config.server.poll_seconds, Some(30));
+        assert_eq!(config.server.clients[1].profiles, ["home", "work"]);
+        assert_eq!(
+            config.server.clients[1].token_env.as_deref(),
+            Some("CI_TOKEN")
+        );
+        assert_eq!(config.server.labels.len(), 2);
+
+        let bad = |k: &str| (k == "CODEX_USAGE_POLL_SECONDS").then(|| "soon".to_string());
+        assert!(apply(&mut config, bad).is_err());
+
+        assert!(
+            include_str!("../README.md").contains(&markdown()),
+            "README's variable table is out of date: paste `codex-usage config env-docs`"
+        );
+    }
+
+    /// Names in `env::var("…")`, `env::var_os("…")` and `env("…")` calls
+    /// outside tests, in every file under `dir`
+    fn read_in(dir: &std::path::Path, found: &mut Vec<(String, String)>) {
+        for entry in std::fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                read_in(&path, found);
+                continue;
+            }
+            if path.extension().is_none_or(|e| e != "rs") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let code = source.split("#[cfg(test)]\nmod tests").next().unwrap();
+            for call in ["env::var(\"", "env::var_os(\"", " env(\"", "(env(\""] {
+                for (i, _) in code.match_indices(call) {
+                    let rest = &code[i + call.len()..];
+                    let name = &rest[..rest.find('"').unwrap()];
+                    if name
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+                    {
+                        found.push((name.to_string(), path.display().to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_variable_read_is_listed() {
+        let mut found = Vec::new();
+        read_in(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut found,
+        );
+        assert!(found.iter().any(|(name, _)| name == "CODEX_USAGE_ENDPOINT"));
+        let missing: Vec<_> = found
+            .iter()
+            .filter(|(name, _)| !VARS.iter().any(|v| v.name == name))
+            .collect();
+        assert!(missing.is_empty(), "read but not in VARS: {missing:?}");
+    }
+}
@@ -0,0 +1,459 @@
+//! Error codes. Every failure maps to a stable code with a one-line hint
+//! (sharpened by [`crate::hints`] where it recognises the case) and whether
+//! retrying could help, so automation can branch on `code` instead of
+//! parsing prose. With `--json` the error goes to stderr as
+//! `{"code", "message", "hint", "retryable"}`.
+//!
+//! Classification looks at typed errors first (`Unauthorised`, `TimedOut`,
+//! the HTTP client's own errors), then at stable fragments of our own
+//! messages.
+
+use crate::api::{TimedOut, Unauthorised};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt::Write;
+
+pub struct Kind {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub hint: &'static str,
+    pub retryable: bool,
+    /// Long form for `explain`: causes and remedies
+    pub explanation: &'static str,
+}
+
+pub const KINDS: &[Kind] = &[
+    Kind {
+        code: "E000",
+        title: "unexpected error",
+        hint: "",
+        retryable: false,
+        explanation: "\
+A failure codex-usage has no code for yet. The message is passed through as
+it came from the library or system call that failed.
+
+If it keeps happening, please open an issue with the message and, for network
+failures, a capture made with `--har usage.har` (credentials are redacted).",
+    },
+    Kind {
+        code: "E001",
+        title: "no credentials",
+        hint: "Run `codex login`, or set CODEX_ACCESS_TOKEN",
+        retryable: false,
+        explanation: "\
+No token was found in any of the places the profile looks: CODEX_ACCESS_TOKEN,
+~/.codex/auth.json, ~/.config/codex/auth.json and the platform's secret store
+(Keychain, Secret Service or Credential Manager), or just
+the profile's own `access_token_env` / `auth_file` when it sets one.
+
+Likely causes:
+  - `codex login` has never been run as this user
+  - a profile's `access_token_env` names a variable that isn't exported
+  - auth.json exists but holds no usable token, or doesn't parse (the
+    message gives the line and column)
+
+What to do:
+  - run `codex login`, or export CODEX_ACCESS_TOKEN
+  - `codex-usage doctor` lists every source per profile and what was found",
+    },
+    Kind {
+        code: "E002",
+        title: "API key only",
+        hint: "Usage limits need an OAuth session: run `codex login`",
+        retryable: false,
+        explanation: "\
+The only credential found is a platform API key (`sk-…`). The usage endpoint
+belongs to ChatGPT plans and only accepts the OAuth session token that
+`codex login` stores; an API key can't see plan limits at all.
+
+What to do:
+  - run `codex login` and sign in with the ChatGPT account
+  - OPENAI_API_KEY is checked before auth.json, so if you have logged in
+    already, unset it for this command or point the profile at `auth_file`",
+    },
+    Kind {
+        code: "E003",
+        title: "token rejected",
+        hint: "Run `codex logout && codex login`",
+        retryable: false,
+        explanation: "\
+The usage API answered 401 or 403 to the token that was sent.
+
+Likely causes:
+  - the token expired; the error's hint gives the expiry when the token says it
+  - it was revoked, typically by logging in again on another machine
+  - this machine's clock is far enough off that a valid token looks expired
+    or not yet issued
+  - the account id sent with it belongs to a different workspace
+
+What to do:
+  - run `codex logout && codex login`
+  - if the hint mentions the clock, turn on time sync first
+  - `codex-usage doctor` shows which source each profile used",
+    },
+    Kind {
+        code: "E004",
+        title: "network failure",
+        hint: "Check the connection, DNS and proxy settings (see --dry-run)",
+        retryable: true,
+        explanation: "\
+The request never got an HTTP answer: DNS lookup, connecting or the TLS
+handshake failed.
+
+Likely causes:
+  - offline, or DNS can't resolve chatgpt.com
+  - a proxy is required but not set, or set but unreachable
+  - a proxy asked for a login (HTTP 407)
+  - TLS interception by a corporate proxy whose CA isn't trusted
+
+What to do:
+  - `codex-usage --dry-run` shows the endpoint and the proxy that would be used
+  - set HTTPS_PROXY (with user:password@ if the proxy needs it), or NO_PROXY
+    to bypass it",
+    },
+    Kind {
+        code: "E005",
+        title: "timed out",
+        hint: "Try again; raise --timeout for slow links",
+        retryable: true,
+        explanation: "\
+The API didn't answer within the deadline. The endpoint normally replies in
+well under a second, so this usually means a slow or lossy link, or a proxy
+holding the connection open.
+
+What to do:
+  - try again
+  - raise the deadline with `--timeout <SECS>` or `timeout_seconds` in the
+    profile",
+    },
+    Kind {
+        code: "E006",
+        title: "HTML instead of JSON",
+        hint: "A challenge page, portal or proxy is in the way; see the message",
+        retryable: true,
+        explanation: "\
+Something answered with a web page instead of usage data. The message says
+which kind of page it looked like.
+
+Likely causes:
+  - a Cloudflare challenge or captcha for this network's address
+  - a corporate SSO portal or proxy intercepting the request
+  - a captive portal (hotel or airport Wi-Fi) or an outage page
+
+What to do:
+  - challenges usually clear within minutes; otherwise try another network
+  - open https://chatgpt.com in a browser on the same network and complete
+    whatever it asks
+  - `--har usage.har` saves the page for a closer look",
+    },
+    Kind {
+        code: "E007",
+        title: "API error",
+        hint: "The usage API failed; try again shortly",
+        retryable: true,
+        explanation: "\
+The usage API answered with an HTTP error other than 401/403, typically a 5xx
+during an incident or a 429 when polled too often. The start of the response
+body is quoted in the message.
+
+What to do:
+  - wait a minute and try again; check https://status.openai.com
+  - if polling from a script, slow it down",
+    },
+    Kind {
+        code: "E008",
+        title: "response too large",
+        hint: "Raise CODEX_USAGE_MAX_BODY if the endpoint is trusted",
+        retryable: false,
+        explanation: "\
+The response was larger than the body limit (1 MiB by default). The real
+payload is a few hundred bytes, so this is almost always a proxy or a
+misconfigured CODEX_USAGE_ENDPOINT returning something else.
+
+What to do:
+  - check CODEX_USAGE_ENDPOINT and the proxy (`--dry-run`)
+  - for a trusted endpoint, raise the limit with CODEX_USAGE_MAX_BODY (bytes)",
+    },
+    Kind {
+        code: "E009",
+        title: "unreadable response",
+        hint: "The response format may have changed; capture it with --har",
+        retryable: false,
+        explanation: "\
+The response was JSON but not in the shape codex-usage expects. The parser is
+lenient about missing and extra fields, so this points at a changed API or a
+proxy rewriting the body.
+
+What to do:
+  - capture the exchange with `--har usage.har` and open an issue with it",
+    },
+    Kind {
+        code: "E010",
+        title: "configuration error",
+        hint: "Check the config file; `codex-usage doctor` lists profiles",
+        retryable: false,
+        explanation: "\
+The config file couldn't be used: it doesn't parse, names a profile that
+isn't defined, or has an invalid value such as an unknown time zone in
+[week].
+
+What to do:
+  - the message names the file and the offending setting
+  - `codex-usage doctor` lists the profiles that are defined",
+    },
+    Kind {
+        code: "E011",
+        title: "invalid arguments",
+        hint: "See `codex-usage --help`",
+        retryable: false,
+        explanation: "\
+The command line couldn't be understood: an unknown flag or subcommand, a
+flag missing its value, or a value of the wrong kind.
+
+What to do:
+  - `codex-usage --help` lists every command and flag",
+    },
+    Kind {
+        code: "E012",
+        title: "tokens need replacing",
+        hint: "Replace the tokens listed above",
+        retryable: false,
+        explanation: "\
+`fleet validate` or `doctor` found tokens that don't work: expired, rejected
+by the API, or missing. The command's output lists which.
+
+What to do:
+  - for each listed seat or profile, log in again and update the stored token
+  - re-run the command; it exits 0 once every token works",
+    },
+    Kind {
+        code: "E013",
+        title: "file access",
+        hint: "Check the path exists and is readable/writable",
+        retryable: false,
+        explanation: "\
+A file codex-usage needed to read or write couldn't be: a seats file, a
+checkpoint, an export target, the history or the HAR output.
+
+What to do:
+  - the message names the path; check it exists and its permissions",
+    },
+    Kind {
+        code: "E014",
+        title: "refused in CI",
+        hint: "Pass --allow-network if this pipeline should send credentials",
+        retryable: false,
+        explanation: "\
+A CI runner was detected (CI, GITHUB_ACTIONS, GITLAB_CI, BUILDKITE and similar
+variables), so codex-usage refused to send credentials from it. Tokens from
+`codex login` are personal; one that leaks into a pipeline's environment
+shouldn't be used from shared infrastructure without anyone deciding to.
+
+What to do:
+  - if the pipeline really should check usage, pass --allow-network
+  - to smoke-test the binary without the network, use --dry-run
+  - if this isn't CI, unset the variable named in the message (CI=false
+    also works)",
+    },
+];
+
+/// Fragments of our own messages' first lines, checked in order after the
+/// typed errors
+const SIGNATURES: &[(&str, &str)] = &[
+    ("No OpenAI / Codex credentials found", "E001"),
+    ("Profile token env var", "E001"),
+    ("auth.json found but", "E001"),
+    ("auth.json at line", "E001"),
+    ("auth.json: not UTF-8", "E001"),
+    ("Only an API key was found", "E002"),
+    ("Failed to reach ChatGPT API", "E004"),
+    ("Failed to reach InfluxDB", "E004"),
+    ("Failed to reach StatsD", "E004"),
+    ("The ChatGPT API answered with", "E006"),
+    ("The request was intercepted", "E006"),
+    ("Expected JSON from the usage endpoint", "E006"),
+    ("API returned HTTP", "E007"),
+    ("Usage response is", "E008"),
+    ("Usage response exceeded", "E008"),
+    ("Failed to parse usage response", "E009"),
+    ("No profile named", "E010"),
+    ("Unknown time zone", "E010"),
+    ("Unknown day", "E010"),
+    ("[week]", "E010"),
+    ("Server client", "E010"),
+    ("Unrecognised argument", "E011"),
+    ("Unknown error code", "E011"),
+    ("Unknown command", "E011"),
+    ("Unknown format", "E011"),
+    ("Unknown locale", "E011"),
+    ("Unknown badge style", "E011"),
+    ("Invalid --", "E011"),
+    ("Invalid label", "E011"),
+    ("Invalid CODEX_USAGE_", "E010"),
+    ("Only read-only statements", "E011"),
+    ("seat tokens need replacing", "E012"),
+    ("profiles have no working credentials", "E012"),
+    ("Refusing to send credentials from a CI", "E014"),
+    ("Could not parse", "E010"),
+    ("Could not read", "E013"),
+    ("Could not write", "E013"),
+    ("Could not open", "E013"),
+];
+
+pub fn kind(code: &str) -> Option<&'static Kind> {
+    KINDS.iter().find(|k| k.code.eq_ignore_ascii_case(code))
+}
+
+/// `explain <CODE>`: the long form, or the list of codes without one
+pub fn explain(code: Option<&str>) -> Result<String> {
+    let Some(code) = code else {
+        let mut out = String::from("Error codes (`codex-usage explain <CODE>` for more):\n\n");
+        for k in KINDS {
+            let _ = writeln!(out, "  {}  {}", k.code, k.title);
+        }
+        return Ok(out);
+    };
+    let Some(k) = kind(code) else {
+        bail!(
+            "Unknown error code '{code}' (codes run E000–E{:03})",
+            KINDS.len() - 1
+        );
+    };
+    let retry = match k.retryable {
+        true => "Retrying later can help.",
+        false => "Retrying won't help until the cause is fixed.",
+    };
+    Ok(format!(
+        "{}: {}\n\n{}\n\n{retry}\n",
+        k.code, k.title, k.explanation
+    ))
+}
+
+/// Which kind of failure `e` is
+pub fn classify(e: &anyhow::Error) -> &'static Kind {
+    let code = typed(e).unwrap_or_else(|| {
+        // Some messages append the usage text; only the first line is ours
+        let message = e.to_string();
+        let first = message.lines().next().unwrap_or_default();
+        SIGNATURES
+            .iter()
+            .find(|(sig, _)| first.contains(sig))
+            .map(|(_, code)| *code)
+            .or_else(|| needs_value(first).then_some("E011"))
+            .unwrap_or("E000")
+    });
+    kind(code).unwrap_or(&KINDS[0])
+}
+
+fn typed(e: &anyhow::Error) -> Option<&'static str> {
+    if e.is::<Unauthorised>() {
+        return Some("E003");
+    }
+    if e.is::<TimedOut>() {
+        return Some("E005");
+    }
+    e.chain().find_map(|cause| {
+        let http = cause.downcast_ref::<reqwest::Error>()?;
+        if http.is_timeout() {
+            Some("E005")
+        } else if http.is_connect() || http.is_request() {
+            Some("E004")
+        } else {
+            None
+        }
+    })
+}
+
+/// `… needs --seats <FILE>`, `… needs a SQL statement`
+fn needs_value(message: &str) -> bool {
+    message.contains(" needs --") || message.contains(" needs a ")
+}
+
+#[derive(Serialize)]
+pub struct JsonError {
+    pub code: &'static str,
+    pub message: String,
+    pub hint: Option<String>,
+    pub retryable: bool,
+}
+
+impl JsonError {
+    pub fn new(e: &anyhow::Error) -> Self {
+        let kind = classify(e);
+        JsonError {
+            code: kind.code,
+            message: format!("{e:#}")
+                .split("\n\nUsage:")
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            hint: crate::hints::hint(e)
+                .or_else(|| (!kind.hint.is_empty()).then(|| kind.hint.to_string())),
+            retryable: kind.retryable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        let code = |e: anyhow::Error| classify(&e).code;
+        assert_eq!(
+            code(
+                Unauthorised {
+                    status: reqwest::StatusCode::UNAUTHORIZED,
+                    expires_at: None,
+                    server_date: None,
+                }
+                .into()
+            ),
+            "E003"
+        );
+        assert_eq!(
+            code(anyhow::anyhow!("Only an API key was found — …")),
+            "E002"
+        );
+        assert_eq!(
+            code(anyhow::anyhow!("API returned HTTP 502 Bad Gateway: …")),
+            "E007"
+        );
+        assert_eq!(
+            code(anyhow::anyhow!("team report needs --seats <FILE>")),
+            "E011"
+        );
+        assert_eq!(code(anyhow::anyhow!("something new")), "E000");
+        assert_eq!(
+            code(anyhow::anyhow!(
+                "Unrecognised argument '-x'\n\n{}",
+                crate::cli::USAGE
+            )),
+            "E011"
+        );
+        let json = serde_json::to_value(JsonError::new(
+            &TimedOut(std::time::Duration::from_secs(3)).into(),
+        ))
+        .unwrap();
+        assert_eq!(json["code"], "E005");
+        assert_eq!(json["retryable"], true);
+        assert!(KINDS
+            .iter()
+            .enumerate()
+            .all(|(i, k)| k.code == format!("E{i:03}")));
+    }
+
+    #[test]
+    fn explanations() {
+        assert!(explain(Some("e003"))
+            .unwrap()
+            .starts_with("E003: token rejected\n"));
+        assert!(explain(Some("E999")).is_err());
+        let list = explain(None).unwrap();
+        assert!(KINDS.iter().all(|k| list.contains(k.code)));
+        assert!(KINDS
+            .iter()
+            .all(|k| !k.title.is_empty() && !k.explanation.is_empty()));
+    }
+}
@@ -0,0 +1,220 @@
+//! `fleet validate`: check many seat tokens at once and list the broken ones.
+//!
+//! Takes the same JSON-lines file as `team report`. Tokens are checked
+//! concurrently through a `Batch`; anything printed about a seat other than
+//! its name is redacted, so the output can be pasted into a ticket.
+
+use crate::api::{self, Unauthorised};
+use crate::batch::Batch;
+use crate::team::Seat;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Columns reserved for seat names
+const SEAT_WIDTH: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Status {
+    Ok,
+    /// The token's own `exp` claim is in the past
+    Expired(DateTime<Utc>),
+    /// The API refused the token
+    Invalid(String),
+    /// Couldn't tell: network trouble, API errors
+    Error(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Check {
+    pub name: String,
+    /// Redacted token, e.g. `eyJh…k9Qs`
+    pub token: String,
+    pub account_id: Option<String>,
+    pub status: Status,
+}
+
+/// Check every seat; results keep the file's order. Also returns how many
+/// came from the batch checkpoint.
+pub fn validate(seats: &[Seat], batch: &Batch, now: DateTime<Utc>) -> Result<(Vec<Check>, usize)> {
+    let items: Vec<(String, &Seat)> = seats.iter().map(|s| (s.name.clone(), s)).collect();
+    let outcome = batch.run(&items, |seat| check(seat, now))?;
+    let checks = outcome
+        .results
+        .into_iter()
+        .zip(seats)
+        .map(|((_, result), seat)| {
+            result.unwrap_or_else(|e| Check {
+                name: seat.name.clone(),
+                token: redact(&seat.access_token),
+                account_id: seat.account_id.as_deref().map(redact),
+                status: Status::Error(e.lines().next().unwrap_or("").to_string()),
+            })
+        })
+        .collect();
+    Ok((checks, outcome.resumed))
+}
+
+/// A definite answer for one seat; `Err` when the API couldn't give one, so
+/// the batch retries it
+fn check(seat: &Seat, now: DateTime<Utc>) -> Result<Check> {
+    // An expired JWT needs no round trip to diagnose
+    let status = match jwt_expiry(&seat.access_token) {
+        Some(exp) if exp <= now => Status::Expired(exp),
+        _ => match api::fetch_usage(&seat.credentials()) {
+            Ok(_) => Status::Ok,
+            Err(e) => match e.downcast_ref::<Unauthorised>() {
+                Some(Unauthorised { status, .. }) => {
+                    Status::Invalid(format!("HTTP {}", status.as_u16()))
+                }
+                None => return Err(e),
+            },
+        },
+    };
+    Ok(Check {
+        name: seat.name.clone(),
+        token: redact(&seat.access_token),
+        account_id: seat.account_id.as_deref().map(redact),
+        status,
+    })
+}
+
+/// Render the report. Only seats needing attention are listed.
+pub fn render(checks: &[Check], now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "\n  {} Codex fleet token check — {} seat{}",
+        "◆".cyan().bold(),
+        checks.len(),
+        if checks.len() == 1 { "" } else { "s" }
+    );
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+
+    let (mut ok, mut expired, mut invalid, mut errors) = (0, 0, 0, 0);
+    for c in checks {
+        let (mark, kind, detail) = match &c.status {
+            Status::Ok => {
+                ok += 1;
+                continue;
+            }
+            Status::Expired(at) => {
+                expired += 1;
+                let days = (now - *at).num_days();
+                let ago = if days > 0 {
+                    format!("expired {days}d ago")
+                } else {
+                    "expired today".to_string()
+                };
+                ("✗".red().bold(), "expired".red(), ago)
+            }
+            Status::Invalid(why) => {
+                invalid += 1;
+                ("✗".red().bold(), "invalid".red(), why.clone())
+            }
+            Status::Error(why) => {
+                errors += 1;
+                ("!".yellow().bold(), "error".yellow(), why.clone())
+            }
+        };
+        let ids = match &c.account_id {
+            Some(acc) => format!("{} {}", c.token, acc),
+            None => c.token.clone(),
+        };
+        let _ = writeln!(
+            out,
+            "  {} {} {:8} {}  {}",
+            mark,
+            crate::render::layout::fit_width(&c.name, SEAT_WIDTH),
+            kind,
+            ids.dimmed(),
+            detail
+        );
+    }
+    if ok == checks.len() {
+        let _ = writeln!(out, "  {} Every token works.", "✓".green());
+    }
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+    let _ = writeln!(
+        out,
+        "  {ok} ok · {expired} expired · {invalid} invalid · {errors} unreachable\n"
+    );
+    out
+}
+
+/// Fail the command when any token is broken, so CI can gate on it
+pub fn ensure_healthy(checks: &[Check]) -> Result<()> {
+    let broken = checks
+        .iter()
+        .filter(|c| matches!(c.status, Status::Expired(_) | Status::Invalid(_)))
+        .count();
+    if broken > 0 {
+        bail!("{broken} of {} seat tokens need replacing", checks.len());
+    }
+    Ok(())
+}
+
+/// Keep just enough of an identifier to match it up: `eyJh…k9Qs`
+pub fn redact(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= 12 {
+        return "…".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}…{tail}")
+}
+
+/// The `exp` claim of a JWT access token, if it is one
+pub fn jwt_expiry(token: &str) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(jwt_claims(token)?.get("exp")?.as_i64()?, 0)
+}
+
+/// A JWT's payload, unverified; only ever used to describe a token
+pub fn jwt_claims(token: &str) -> Option<serde_json::Value> {
+    let payload = token.split('.').nth(1)?;
+    serde_json::from_slice(&base64url_decode(payload)?).ok()
+}
+
+/// Unpadded base64url, as used in JWTs
+fn base64url_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for b in s.trim_end_matches('=').bytes() {
+        let v = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'-' | b'+' => 62,
+            b'_' | b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_and_redaction() {
+        // {"alg":"none"}.{"exp":1700000000}.
+        let token = "eyJhbGciOiJub25lIn0.eyJleHAiOjE3MDAwMDAwMDB9.";
+        assert_eq!(
+            jwt_expiry(token),
+            DateTime::from_timestamp(1_700_000_000, 0)
+        );
+        assert_eq!(jwt_expiry("not-a-jwt"), None);
+        assert_eq!(redact(token), "eyJh…DB9.");
+        assert_eq!(redact("short"), "…");
+    }
+}
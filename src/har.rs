@@ -0,0 +1,256 @@
+//! `--har <FILE>`: capture each API request and response in HAR 1.2, the
+//! format browser devtools and proxy debuggers read, for tracking down what a
+//! proxy or gateway did to the traffic. Credentials are replaced with
+//! `[redacted]` before anything is kept.
+//!
+//! Capture is process-wide: `start` switches it on, `api` and `workspace`
+//! record an entry per request, and `save` writes them all out at the end of
+//! the run.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Request and response headers whose values never reach the file
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "chatgpt-account-id",
+    "cookie",
+    "set-cookie",
+    "proxy-authorization",
+];
+
+static ENTRIES: Mutex<Option<Vec<Entry>>> = Mutex::new(None);
+
+/// Start capturing requests
+pub fn start() {
+    *ENTRIES.lock().unwrap() = Some(Vec::new());
+}
+
+pub fn capturing() -> bool {
+    ENTRIES.lock().unwrap().is_some()
+}
+
+pub fn record(entry: Entry) {
+    if let Some(entries) = ENTRIES.lock().unwrap().as_mut() {
+        entries.push(entry);
+    }
+}
+
+/// Write everything captured so far to `path`
+pub fn save(path: &Path) -> Result<()> {
+    let entries = ENTRIES.lock().unwrap().clone().unwrap_or_default();
+    let har = serde_json::json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "codex-usage", "version": env!("CARGO_PKG_VERSION") },
+            "entries": entries,
+        }
+    });
+    let json = serde_json::to_string_pretty(&har)?;
+    std::fs::write(path, json).with_context(|| format!("Could not write {}", path.display()))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    started_date_time: DateTime<Utc>,
+    /// Total milliseconds
+    time: f64,
+    request: Request,
+    response: Response,
+    cache: serde_json::Value,
+    timings: Timings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    method: &'static str,
+    url: String,
+    http_version: String,
+    cookies: Vec<()>,
+    headers: Vec<Header>,
+    query_string: Vec<()>,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    status: u16,
+    status_text: String,
+    http_version: String,
+    cookies: Vec<()>,
+    headers: Vec<Header>,
+    content: Content,
+    #[serde(rename = "redirectURL")]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    size: i64,
+    mime_type: String,
+    text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Header {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct Timings {
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
+
+fn header(name: &str, value: &str) -> Header {
+    let secret = SECRET_HEADERS.contains(&name.to_ascii_lowercase().as_str());
+    Header {
+        name: name.to_string(),
+        value: if secret {
+            "[redacted]".to_string()
+        } else {
+            value.to_string()
+        },
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+impl Entry {
+    /// A GET to `url` with `headers`, about to be sent
+    pub fn new(started: DateTime<Utc>, url: &str, headers: &[(&str, String)]) -> Self {
+        Entry {
+            started_date_time: started,
+            time: 0.0,
+            request: Request {
+                method: "GET",
+                url: url.to_string(),
+                http_version: "HTTP/1.1".to_string(),
+                cookies: Vec::new(),
+                headers: headers.iter().map(|(n, v)| header(n, v)).collect(),
+                query_string: Vec::new(),
+                headers_size: -1,
+                body_size: 0,
+            },
+            response: Response::default(),
+            cache: serde_json::json!({}),
+            timings: Timings::default(),
+            comment: None,
+        }
+    }
+
+    /// No response at all; HAR's convention is status 0
+    pub fn failed(mut self, error: &reqwest::Error, elapsed: Duration) -> Self {
+        self.time = ms(elapsed);
+        self.timings.wait = ms(elapsed);
+        self.response.headers_size = -1;
+        self.response.body_size = -1;
+        self.comment = Some(format!("No response: {error}"));
+        self
+    }
+
+    pub fn response(
+        mut self,
+        status: reqwest::StatusCode,
+        version: reqwest::Version,
+        headers: &reqwest::header::HeaderMap,
+        body: Vec<u8>,
+        wait: Duration,
+        total: Duration,
+    ) -> Self {
+        let version = format!("{version:?}");
+        self.request.http_version = version.clone();
+        let mime_type = headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        self.response = Response {
+            status: status.as_u16(),
+            status_text: status.canonical_reason().unwrap_or_default().to_string(),
+            http_version: version,
+            cookies: Vec::new(),
+            headers: headers
+                .iter()
+                .map(|(n, v)| header(n.as_str(), &String::from_utf8_lossy(v.as_bytes())))
+                .collect(),
+            content: Content {
+                size: body.len() as i64,
+                mime_type,
+                text: String::from_utf8_lossy(&body).into_owned(),
+            },
+            redirect_url: String::new(),
+            headers_size: -1,
+            body_size: body.len() as i64,
+        };
+        self.time = ms(total);
+        self.timings.wait = ms(wait);
+        self.timings.receive = ms(total.saturating_sub(wait));
+        self
+    }
+}
+
+/// A reader that keeps a copy of what passes through, when asked to
+pub struct Tee<R> {
+    inner: R,
+    copy: Option<Vec<u8>>,
+}
+
+impl<R> Tee<R> {
+    pub fn new(inner: R, keep: bool) -> Self {
+        Tee {
+            inner,
+            copy: keep.then(Vec::new),
+        }
+    }
+
+    pub fn into_captured(self) -> Vec<u8> {
+        self.copy.unwrap_or_default()
+    }
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(copy) = &mut self.copy {
+            copy.extend_from_slice(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_redacted() {
+        let headers = vec![
+            ("Authorization", "Bearer eyJhbGciOi.secret".to_string()),
+            ("Accept", "application/json".to_string()),
+        ];
+        let entry = Entry::new(Utc::now(), "https://example.test/usage", &headers);
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(!json.contains("secret"));
+        assert!(json.contains(r#"{"name":"Authorization","value":"[redacted]"}"#));
+        assert!(json.contains(r#""value":"application/json""#));
+    }
+}
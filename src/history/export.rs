@@ -0,0 +1,232 @@
+//! `history export`: flat, typed files for pandas / Polars / DuckDB, and CSV
+//! for spreadsheets.
+
+use crate::api::{RateWindow, Snapshot};
+use crate::locale::Locale;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// One history sample flattened into columns
+pub(super) struct Row<'a> {
+    pub fetched_at_ms: i64,
+    pub plan_type: Option<&'a str>,
+    pub primary: Option<&'a RateWindow>,
+    pub secondary: Option<&'a RateWindow>,
+    pub limit_reached: Option<bool>,
+    pub command: Option<&'a str>,
+    pub marks: Option<String>,
+}
+
+pub(super) fn rows(samples: &[Snapshot]) -> Vec<Row<'_>> {
+    samples
+        .iter()
+        .map(|s| {
+            let rl = s.usage.rate_limit.as_ref();
+            Row {
+                fetched_at_ms: s.fetched_at.timestamp_millis(),
+                plan_type: s.usage.plan_type.as_deref(),
+                primary: rl.and_then(|r| r.primary_window.as_ref()),
+                secondary: rl.and_then(|r| r.secondary_window.as_ref()),
+                limit_reached: rl.and_then(|r| r.limit_reached),
+                command: s.command.as_deref(),
+                marks: super::marks::joined(&s.marks),
+            }
+        })
+        .collect()
+}
+
+/// Parquet schema of the export; documented in the README
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "
+message codex_usage_history {
+    REQUIRED INT64 fetched_at (TIMESTAMP(MILLIS,true));
+    OPTIONAL BYTE_ARRAY plan_type (UTF8);
+    OPTIONAL DOUBLE primary_used_percent;
+    OPTIONAL INT64 primary_reset_after_seconds;
+    OPTIONAL DOUBLE secondary_used_percent;
+    OPTIONAL INT64 secondary_reset_after_seconds;
+    OPTIONAL BOOLEAN limit_reached;
+    OPTIONAL BYTE_ARRAY command (UTF8);
+    OPTIONAL BYTE_ARRAY marks (UTF8);
+}";
+
+#[cfg(feature = "parquet")]
+pub fn parquet(samples: &[Snapshot], path: &Path) -> Result<()> {
+    use parquet::basic::Compression;
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    /// Split optional values into (present values, definition levels)
+    fn levels<T>(values: impl Iterator<Item = Option<T>>) -> (Vec<T>, Vec<i16>) {
+        let mut present = Vec::new();
+        let mut defs = Vec::new();
+        for v in values {
+            defs.push(v.is_some() as i16);
+            present.extend(v);
+        }
+        (present, defs)
+    }
+
+    let rows = rows(samples);
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Could not create {}", path.display()))?;
+    let mut writer = SerializedFileWriter::new(file, schema, props)?;
+    let mut group = writer.next_row_group()?;
+
+    let text = |s: Option<&str>| s.map(|s| ByteArray::from(s.as_bytes().to_vec()));
+    let pct = |w: Option<&RateWindow>| w.and_then(|w| w.used_percent);
+    let reset = |w: Option<&RateWindow>| w.and_then(|w| w.reset_after_seconds).map(|s| s as i64);
+
+    // Columns must be written in schema order
+    let fetched: Vec<i64> = rows.iter().map(|r| r.fetched_at_ms).collect();
+    let mut col = group.next_column()?.context("schema/column mismatch")?;
+    col.typed::<Int64Type>().write_batch(&fetched, None, None)?;
+    col.close()?;
+
+    macro_rules! optional_column {
+        ($ty:ty, $values:expr) => {{
+            let (values, defs) = levels($values);
+            let mut col = group.next_column()?.context("schema/column mismatch")?;
+            col.typed::<$ty>().write_batch(&values, Some(&defs), None)?;
+            col.close()?;
+        }};
+    }
+    optional_column!(ByteArrayType, rows.iter().map(|r| text(r.plan_type)));
+    optional_column!(DoubleType, rows.iter().map(|r| pct(r.primary)));
+    optional_column!(Int64Type, rows.iter().map(|r| reset(r.primary)));
+    optional_column!(DoubleType, rows.iter().map(|r| pct(r.secondary)));
+    optional_column!(Int64Type, rows.iter().map(|r| reset(r.secondary)));
+    optional_column!(BoolType, rows.iter().map(|r| r.limit_reached));
+    optional_column!(ByteArrayType, rows.iter().map(|r| text(r.command)));
+    optional_column!(ByteArrayType, rows.iter().map(|r| text(r.marks.as_deref())));
+
+    group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+/// CSV with the same columns as the Parquet export. `locale` picks the
+/// decimal separator, timestamp layout and field separator; "-" is stdout.
+pub fn csv(samples: &[Snapshot], path: &Path, locale: &Locale) -> Result<()> {
+    let sep = locale.csv_separator;
+    let field = |s: &str| {
+        if s.contains([sep, '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let num = |v: Option<f64>| v.map(|v| locale.exact(v)).unwrap_or_default();
+    let int = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_default();
+
+    let mut out = [
+        "fetched_at",
+        "plan_type",
+        "primary_used_percent",
+        "primary_reset_after_seconds",
+        "secondary_used_percent",
+        "secondary_reset_after_seconds",
+        "limit_reached",
+        "command",
+        "marks",
+    ]
+    .join(&sep.to_string());
+    out.push('\n');
+    for (row, sample) in rows(samples).iter().zip(samples) {
+        let cells = [
+            locale.datetime(sample.fetched_at),
+            field(row.plan_type.unwrap_or("")),
+            num(row.primary.and_then(|w| w.used_percent)),
+            int(row.primary.and_then(|w| w.reset_after_seconds)),
+            num(row.secondary.and_then(|w| w.used_percent)),
+            int(row.secondary.and_then(|w| w.reset_after_seconds)),
+            row.limit_reached.map(|b| b.to_string()).unwrap_or_default(),
+            field(row.command.unwrap_or("")),
+            field(row.marks.as_deref().unwrap_or("")),
+        ];
+        out.push_str(&cells.join(&sep.to_string()));
+        out.push('\n');
+    }
+
+    if path == Path::new("-") {
+        use std::io::Write;
+        std::io::stdout().write_all(out.as_bytes())?;
+        return Ok(());
+    }
+    std::fs::write(path, out).with_context(|| format!("Could not write {}", path.display()))
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn parquet(_samples: &[Snapshot], _path: &Path) -> Result<()> {
+    anyhow::bail!("This build of codex-usage was compiled without the `parquet` feature")
+}
+
+#[cfg(all(test, feature = "parquet"))]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+    use crate::history::Mark;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    #[test]
+    fn parquet_keeps_every_column_and_gap() {
+        let path =
+            std::env::temp_dir().join(format!("codex-usage-export-{}.parquet", std::process::id()));
+        let at = |s| parse_timestamp(s).unwrap();
+        let mut tagged = Snapshot::plus((42.5, 3600), (12.0, 86400), at("2024-05-01T10:00:00Z"));
+        tagged.command = Some("codex exec".into());
+        let mut bare = Snapshot::plus((0.0, 0), (0.0, 0), at("2024-05-01T11:00:00Z"));
+        bare.usage.rate_limit = None;
+        bare.marks = vec![Mark::PlanChange];
+        parquet(&[tagged, bare], &path).unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let names: Vec<&str> = schema.columns().iter().map(|c| c.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "fetched_at",
+                "plan_type",
+                "primary_used_percent",
+                "primary_reset_after_seconds",
+                "secondary_used_percent",
+                "secondary_reset_after_seconds",
+                "limit_reached",
+                "command",
+                "marks",
+            ]
+        );
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(rows.len(), 2);
+        let first = &rows[0];
+        assert_eq!(
+            first.get_timestamp_millis(0).unwrap(),
+            at("2024-05-01T10:00:00Z").timestamp_millis()
+        );
+        assert_eq!(first.get_string(1).unwrap(), "plus");
+        assert_eq!(first.get_double(2).unwrap(), 42.5);
+        assert_eq!(first.get_long(3).unwrap(), 3600);
+        assert!(!first.get_bool(6).unwrap());
+        assert_eq!(first.get_string(7).unwrap(), "codex exec");
+        // What a sample lacks is null, not zero
+        let second = &rows[1];
+        assert!(second.get_double(2).is_err() && second.get_bool(6).is_err());
+        assert_eq!(second.get_string(8).unwrap(), "plan_change");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,258 @@
+//! Boundary markers: points in the history where a number moved for a reason
+//! other than usage — a window resetting or the plan changing — so a cliff in
+//! a chart reads as "weekly reset", not "I stopped working".
+
+use crate::api::{RateWindow, Snapshot};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mark {
+    /// The 5-hour window rolled over since the previous sample
+    PrimaryReset,
+    /// The weekly window rolled over since the previous sample
+    SecondaryReset,
+    /// plan_type differs from the previous sample (upgrade, renewal, lapse)
+    PlanChange,
+}
+
+impl Mark {
+    pub fn label(self) -> &'static str {
+        match self {
+            Mark::PrimaryReset => "5h window reset",
+            Mark::SecondaryReset => "weekly reset",
+            Mark::PlanChange => "plan changed",
+        }
+    }
+
+    /// Stable identifier, as stored in history and exports
+    pub fn name(self) -> &'static str {
+        match self {
+            Mark::PrimaryReset => "primary_reset",
+            Mark::SecondaryReset => "secondary_reset",
+            Mark::PlanChange => "plan_change",
+        }
+    }
+}
+
+/// Usage falling by less than this is treated as rounding noise
+const DROP_TOLERANCE: f64 = 0.5;
+
+/// Boundaries crossed between two consecutive samples
+pub fn between(prev: &Snapshot, next: &Snapshot) -> Vec<Mark> {
+    let mut marks = Vec::new();
+    if reset(
+        window(prev, false),
+        prev.fetched_at,
+        window(next, false),
+        next.fetched_at,
+    ) {
+        marks.push(Mark::PrimaryReset);
+    }
+    if reset(
+        window(prev, true),
+        prev.fetched_at,
+        window(next, true),
+        next.fetched_at,
+    ) {
+        marks.push(Mark::SecondaryReset);
+    }
+    if plan_change(prev, next).is_some() {
+        marks.push(Mark::PlanChange);
+    }
+    marks
+}
+
+// ─── Plan changes ─────────────────────────────────────────────────────────────
+
+/// Personal plans, cheapest first; moves along this are up- or downgrades.
+/// Anything else (team, business, enterprise, edu) is just "changed".
+const LADDER: &[&str] = &["free", "go", "plus", "pro"];
+
+/// plan_type moving between two samples: quotas follow the new plan from
+/// some point between `since` and `at`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanChange {
+    pub from: String,
+    pub to: String,
+    /// The last sample on the old plan
+    pub since: DateTime<Utc>,
+    /// The first sample on the new plan
+    pub at: DateTime<Utc>,
+}
+
+impl PlanChange {
+    /// `upgraded`, `downgraded` or `changed`
+    pub fn direction(&self) -> &'static str {
+        let rank = |p: &str| LADDER.iter().position(|l| p.eq_ignore_ascii_case(l));
+        match (rank(&self.from), rank(&self.to)) {
+            (Some(a), Some(b)) if b > a => "upgraded",
+            (Some(a), Some(b)) if b < a => "downgraded",
+            _ => "changed",
+        }
+    }
+
+    /// `plan upgraded Plus → Pro (between 09:00 and 09:15 UTC on 2024-05-01)`
+    pub fn describe(&self) -> String {
+        let day = |t: DateTime<Utc>| t.format("%Y-%m-%d").to_string();
+        let when = match day(self.since) == day(self.at) {
+            true => format!(
+                "between {} and {} UTC on {}",
+                self.since.format("%H:%M"),
+                self.at.format("%H:%M"),
+                day(self.at)
+            ),
+            false => format!(
+                "between {} and {} UTC",
+                self.since.format("%Y-%m-%d %H:%M"),
+                self.at.format("%Y-%m-%d %H:%M")
+            ),
+        };
+        format!(
+            "plan {} {} → {} ({when})",
+            self.direction(),
+            crate::plans::display(&self.from),
+            crate::plans::display(&self.to)
+        )
+    }
+}
+
+/// The plan change between two consecutive samples, if both name a plan
+pub fn plan_change(prev: &Snapshot, next: &Snapshot) -> Option<PlanChange> {
+    let (from, to) = (
+        prev.usage.plan_type.as_ref()?,
+        next.usage.plan_type.as_ref()?,
+    );
+    (from != to).then(|| PlanChange {
+        from: from.clone(),
+        to: to.clone(),
+        since: prev.fetched_at,
+        at: next.fetched_at,
+    })
+}
+
+/// Every plan change in `samples` (in time order), skipping samples that
+/// don't name a plan
+pub fn plan_changes(samples: &[Snapshot]) -> Vec<PlanChange> {
+    let named: Vec<&Snapshot> = samples
+        .iter()
+        .filter(|s| s.usage.plan_type.is_some())
+        .collect();
+    named
+        .windows(2)
+        .filter_map(|w| plan_change(w[0], w[1]))
+        .collect()
+}
+
+fn window(s: &Snapshot, secondary: bool) -> Option<&RateWindow> {
+    let rl = s.usage.rate_limit.as_ref()?;
+    if secondary {
+        rl.secondary_window.as_ref()
+    } else {
+        rl.primary_window.as_ref()
+    }
+}
+
+/// A window reset if its scheduled reset time has passed, or if usage fell —
+/// which nothing but a reset does
+fn reset(
+    prev: Option<&RateWindow>,
+    prev_at: DateTime<Utc>,
+    next: Option<&RateWindow>,
+    next_at: DateTime<Utc>,
+) -> bool {
+    let (Some(p), Some(n)) = (prev, next) else {
+        return false;
+    };
+    if let Some(left) = p.resets_in(prev_at, prev_at) {
+        if next_at >= prev_at + Duration::seconds(left as i64) {
+            return true;
+        }
+    }
+    matches!((p.used_percent, n.used_percent), (Some(a), Some(b)) if b + DROP_TOLERANCE < a)
+}
+
+/// Fill in marks for samples recorded before markers existed. `samples` must
+/// be in time order; samples that already carry marks are left alone.
+pub fn annotate(samples: &mut [Snapshot]) {
+    for i in 1..samples.len() {
+        if samples[i].marks.is_empty() {
+            samples[i].marks = between(&samples[i - 1], &samples[i]);
+        }
+    }
+}
+
+/// Comma-separated mark names, for flat exports
+pub fn joined(marks: &[Mark]) -> Option<String> {
+    if marks.is_empty() {
+        return None;
+    }
+    Some(marks.iter().map(|m| m.name()).collect::<Vec<_>>().join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    fn sample(ts: &str, plan: &str, primary: f64, reset_after: u64, weekly: f64) -> Snapshot {
+        let usage = serde_json::from_value(serde_json::json!({
+            "plan_type": plan,
+            "rate_limit": {
+                "primary_window": { "used_percent": primary, "reset_after_seconds": reset_after },
+                "secondary_window": { "used_percent": weekly }
+            }
+        }))
+        .unwrap();
+        Snapshot::new(usage, parse_timestamp(ts).unwrap())
+    }
+
+    #[test]
+    fn detects_resets_and_plan_changes() {
+        let a = sample("2024-05-01T10:00:00Z", "plus", 40.0, 600, 50.0);
+        // Reset time passed even though usage happens to be higher again
+        let b = sample("2024-05-01T10:20:00Z", "plus", 45.0, 17000, 52.0);
+        assert_eq!(between(&a, &b), [Mark::PrimaryReset]);
+
+        // Weekly usage fell; plan upgraded
+        let c = sample("2024-05-01T10:25:00Z", "pro", 46.0, 16700, 3.0);
+        assert_eq!(between(&b, &c), [Mark::SecondaryReset, Mark::PlanChange]);
+
+        // Ordinary growth is not a boundary
+        let d = sample("2024-05-01T10:30:00Z", "pro", 48.0, 16400, 3.4);
+        assert!(between(&c, &d).is_empty());
+
+        let e = sample("2024-05-02T08:00:00Z", "team", 1.0, 18000, 0.0);
+        let changes = plan_changes(&[a, b, c, d, e]);
+        assert_eq!(
+            changes.iter().map(PlanChange::describe).collect::<Vec<_>>(),
+            [
+                "plan upgraded Plus → Pro (between 10:20 and 10:25 UTC on 2024-05-01)",
+                "plan changed Pro → Team (between 2024-05-01 10:30 and 2024-05-02 08:00 UTC)",
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_changes_skip_unnamed_samples() {
+        let a = sample("2024-05-01T09:00:00Z", "pro", 10.0, 9000, 5.0);
+        // A response without plan_type says nothing either way
+        let mut gap = sample("2024-05-01T09:10:00Z", "pro", 11.0, 8400, 5.0);
+        gap.usage.plan_type = None;
+        let b = sample("2024-05-01T09:15:00Z", "plus", 12.0, 8100, 5.0);
+        assert_eq!(plan_change(&a, &gap), None);
+        let changes = plan_changes(&[a, gap, b]);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].direction(), "downgraded");
+        assert_eq!(
+            serde_json::to_value(&changes[0]).unwrap(),
+            serde_json::json!({
+                "from": "pro",
+                "to": "plus",
+                "since": "2024-05-01T09:00:00Z",
+                "at": "2024-05-01T09:15:00Z"
+            })
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Local usage history: one JSON `Snapshot` per line, appended after every
+//! successful fetch. Nothing here ever leaves the machine.
+
+pub mod export;
+mod marks;
+pub mod query;
+mod replay;
+pub mod sessions;
+
+use crate::api::Snapshot;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub use marks::{plan_changes, Mark, PlanChange};
+pub use replay::{parse_speed, replay};
+pub use sessions::SessionMark;
+
+/// How far back from the end of the file `record` looks for the last sample
+const TAIL_BYTES: u64 = 16 * 1024;
+
+/// history.jsonl under the data directory
+pub fn path() -> PathBuf {
+    crate::paths::data_dir().join("history.jsonl")
+}
+
+/// Recording is on unless CODEX_USAGE_HISTORY is set to 0/false/off
+pub fn enabled() -> bool {
+    !matches!(
+        std::env::var("CODEX_USAGE_HISTORY")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str(),
+        "0" | "false" | "off" | "no"
+    )
+}
+
+/// Append one sample to the history file, marking any boundaries crossed
+/// since the previous one. Returns the plan change since then, if any.
+pub fn record(snap: &Snapshot) -> Result<Option<PlanChange>> {
+    record_in(&path(), snap)
+}
+
+fn record_in(path: &Path, snap: &Snapshot) -> Result<Option<PlanChange>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create {}", dir.display()))?;
+    }
+    let mut snap = snap.clone();
+    let prev = last(path);
+    if let Some(prev) = &prev {
+        snap.marks = marks::between(prev, &snap);
+    }
+    let mut line = serde_json::to_string(&snap)?;
+    line.push('\n');
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    // One write per line keeps concurrent appends from interleaving
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(prev.and_then(|prev| marks::plan_change(&prev, &snap)))
+}
+
+/// Load samples in time order, optionally bounded. Malformed lines (e.g. a
+/// half-written tail after a crash) are skipped rather than failing the read.
+pub fn load(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Result<Vec<Snapshot>> {
+    load_from(&path(), since, until)
+}
+
+fn load_from(
+    path: &Path,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<Vec<Snapshot>> {
+    let file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Could not open {}", path.display())),
+    };
+    let mut samples: Vec<Snapshot> = std::io::BufReader::new(file)
+        .lines()
+        .map_while(|l| l.ok())
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect();
+    samples.sort_by_key(|s| s.fetched_at);
+    // Before bounding, so the first sample in range still has a predecessor
+    marks::annotate(&mut samples);
+    samples.retain(|s| {
+        since.is_none_or(|t| s.fetched_at >= t) && until.is_none_or(|t| s.fetched_at <= t)
+    });
+    Ok(samples)
+}
+
+/// The most recent well-formed sample, read from the tail of the file
+fn last(path: &Path) -> Option<Snapshot> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))
+        .ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    String::from_utf8_lossy(&tail)
+        .lines()
+        .rev()
+        .find_map(|l| serde_json::from_str(l).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    #[test]
+    fn records_marks_and_loads_in_order() {
+        let dir = std::env::temp_dir().join(format!("codex-usage-history-{}", std::process::id()));
+        let path = dir.join("history.jsonl");
+        let _ = std::fs::remove_dir_all(&dir);
+        let at = |s| parse_timestamp(s).unwrap();
+
+        let first = Snapshot::plus((80.0, 600), (30.0, 86400), at("2024-05-01T10:00:00Z"));
+        assert!(record_in(&path, &first).unwrap().is_none());
+        // The 5-hour window reset in between
+        let mut later = Snapshot::plus((5.0, 17000), (31.0, 85000), at("2024-05-01T10:20:00Z"));
+        later.usage.plan_type = Some("pro".into());
+        let change = record_in(&path, &later).unwrap().unwrap();
+        assert_eq!((change.from.as_str(), change.to.as_str()), ("plus", "pro"));
+        // A half-written line from a crash is skipped
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"usage\":\n").unwrap();
+
+        let samples = load_from(&path, None, None).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].marks, [Mark::PrimaryReset, Mark::PlanChange]);
+        let since = Some(at("2024-05-01T10:10:00Z"));
+        let bounded = load_from(&path, since, None).unwrap();
+        assert_eq!(bounded.len(), 1);
+        assert_eq!(bounded[0].fetched_at, later.fetched_at);
+        assert!(load_from(&dir.join("missing.jsonl"), None, None)
+            .unwrap()
+            .is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,269 @@
+//! `query`: ad-hoc, read-only SQL over the history.
+//!
+//! The JSON-lines history is loaded into an in-memory SQLite database with a
+//! `samples` table, a row per fetch, and a `windows` table, a row per window
+//! in each, so the on-disk format stays append-only text while still
+//! answering arbitrary questions. See SCHEMA for the columns.
+
+use super::export::rows;
+use crate::api::{assumed_length, RateWindow, Snapshot};
+use crate::render::layout::{display_width, fit_width};
+use crate::render::window_rows;
+use anyhow::{bail, Context, Result};
+use rusqlite::limits::Limit;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection};
+
+/// The documented table layout; also printed by `query --schema`
+pub const SCHEMA: &str = "\
+CREATE TABLE samples (
+    fetched_at                    TEXT NOT NULL,  -- RFC 3339, UTC
+    fetched_at_unix               INTEGER NOT NULL,
+    plan_type                     TEXT,
+    primary_used_percent          REAL,           -- 0-100; see windows for lengths
+    primary_reset_after_seconds   INTEGER,
+    secondary_used_percent        REAL,           -- 0-100
+    secondary_reset_after_seconds INTEGER,
+    limit_reached                 INTEGER,        -- 0/1
+    command                       TEXT,           -- set on `hook` samples
+    marks                         TEXT            -- e.g. 'primary_reset,plan_change'
+);
+CREATE TABLE windows (
+    sample                        INTEGER NOT NULL, -- samples.rowid
+    feature                       TEXT,           -- NULL for the main limit
+    name                          TEXT NOT NULL,  -- 'primary', 'secondary', …
+    window_seconds                INTEGER,        -- as the API gives it, else assumed
+    used_percent                  REAL,           -- 0-100
+    reset_after_seconds           INTEGER
+);";
+
+/// Widest a column is allowed to get in table output
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Result of a query: column names plus rows rendered as text
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Load `samples` into a fresh in-memory database
+pub fn open(samples: &[Snapshot]) -> Result<Connection> {
+    let mut conn = Connection::open_in_memory()?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+    {
+        let mut insert = tx.prepare("INSERT INTO samples VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
+        let mut window = tx.prepare("INSERT INTO windows VALUES (?, ?, ?, ?, ?, ?)")?;
+        let pct = |w: Option<&RateWindow>| w.and_then(|w| w.used_percent);
+        let reset =
+            |w: Option<&RateWindow>| w.and_then(|w| w.reset_after_seconds).map(|s| s as i64);
+        for (row, sample) in rows(samples).iter().zip(samples) {
+            insert.execute(params![
+                sample.fetched_at.to_rfc3339(),
+                row.fetched_at_ms / 1000,
+                row.plan_type,
+                pct(row.primary),
+                reset(row.primary),
+                pct(row.secondary),
+                reset(row.secondary),
+                row.limit_reached,
+                row.command,
+                row.marks,
+            ])?;
+            let id = tx.last_insert_rowid();
+            for w in window_rows(sample, sample.fetched_at) {
+                let Some(rate) = &w.window else { continue };
+                let seconds = rate
+                    .limit_window_seconds
+                    .or_else(|| assumed_length(&w.name))
+                    .map(|s| s as i64);
+                window.execute(params![
+                    id,
+                    w.feature,
+                    w.name,
+                    seconds,
+                    rate.used_percent,
+                    reset(Some(rate)),
+                ])?;
+            }
+        }
+    }
+    tx.commit()?;
+    // Belt and braces on top of the statement check in `run`; ATTACH counts as
+    // read-only to SQLite but would create files, so forbid it outright
+    conn.execute_batch("PRAGMA query_only = ON")?;
+    conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)?;
+    Ok(conn)
+}
+
+/// Run one read-only statement
+pub fn run(conn: &Connection, sql: &str) -> Result<Table> {
+    let mut stmt = conn.prepare(sql).context("Invalid SQL")?;
+    if !stmt.readonly() {
+        bail!("Only read-only statements (SELECT, WITH …) are allowed");
+    }
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let n = columns.len();
+    let rows = stmt
+        .query_map([], |r| {
+            (0..n)
+                .map(|i| {
+                    Ok(match r.get_ref(i)? {
+                        ValueRef::Null => String::new(),
+                        ValueRef::Integer(v) => v.to_string(),
+                        ValueRef::Real(v) => v.to_string(),
+                        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+                        ValueRef::Blob(b) => format!("<{} bytes>", b.len()),
+                    })
+                })
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Table { columns, rows })
+}
+
+impl Table {
+    /// Aligned columns with a header rule, for terminals
+    pub fn to_aligned(&self) -> String {
+        let widths: Vec<usize> = (0..self.columns.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .map(|r| display_width(&r[i]))
+                    .chain([display_width(&self.columns[i])])
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_COLUMN_WIDTH)
+            })
+            .collect();
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(c, &w)| fit_width(c, w))
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        };
+        let mut out = line(&self.columns);
+        out.push('\n');
+        let rule: Vec<String> = widths.iter().map(|&w| "─".repeat(w)).collect();
+        out.push_str(&rule.join("  "));
+        out.push('\n');
+        for row in &self.rows {
+            out.push_str(&line(row));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Tab-separated with a header line, for scripts
+    pub fn to_tsv(&self) -> String {
+        let mut out = self.columns.join("\t");
+        out.push('\n');
+        for row in &self.rows {
+            out.push_str(&row.join("\t"));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::WhamUsage;
+    use crate::clock::parse_timestamp;
+
+    fn sample(ts: &str, primary: f64) -> Snapshot {
+        let usage: WhamUsage = serde_json::from_value(serde_json::json!({
+            "plan_type": "plus",
+            "rate_limit": { "primary_window": { "used_percent": primary } }
+        }))
+        .unwrap();
+        Snapshot::new(usage, parse_timestamp(ts).unwrap())
+    }
+
+    #[test]
+    fn selects_and_rejects_writes() {
+        let conn = open(&[
+            sample("2024-05-01T10:00:00Z", 10.0),
+            sample("2024-05-01T11:00:00Z", 35.5),
+        ])
+        .unwrap();
+        let t = run(
+            &conn,
+            "SELECT max(primary_used_percent) AS peak FROM samples",
+        )
+        .unwrap();
+        assert_eq!(t.columns, ["peak"]);
+        assert_eq!(t.rows, [["35.5"]]);
+        assert!(run(&conn, "DELETE FROM samples").is_err());
+        assert!(run(&conn, "ATTACH ':memory:' AS other").is_err());
+    }
+
+    #[test]
+    fn hook_samples_group_by_command() {
+        let tagged = |ts, primary, command: Option<&str>| {
+            let mut s = sample(ts, primary);
+            s.command = command.map(str::to_string);
+            s
+        };
+        let conn = open(&[
+            tagged("2024-05-01T10:00:00Z", 10.0, Some("codex exec lint")),
+            tagged("2024-05-01T10:05:00Z", 14.0, None),
+            tagged("2024-05-01T10:10:00Z", 30.0, Some("codex exec refactor")),
+            tagged("2024-05-01T10:20:00Z", 40.0, Some("codex exec refactor")),
+        ])
+        .unwrap();
+        let t = run(
+            &conn,
+            "SELECT command, avg(primary_used_percent) FROM samples
+             WHERE command IS NOT NULL GROUP BY 1 ORDER BY 2 DESC",
+        )
+        .unwrap();
+        assert_eq!(
+            t.rows,
+            [["codex exec refactor", "35"], ["codex exec lint", "10"]]
+        );
+    }
+
+    #[test]
+    fn every_window_gets_a_row() {
+        let usage: WhamUsage = serde_json::from_value(serde_json::json!({
+            "plan_type": "pro",
+            "rate_limit": {
+                "primary_window": { "used_percent": 20.0, "limit_window_seconds": 10800 },
+                "secondary_window": { "used_percent": 9.0, "reset_after_seconds": 3600 }
+            },
+            "additional_rate_limits": [{
+                "limit_name": "code_review",
+                "rate_limit": { "primary_window": { "used_percent": 55.0 } }
+            }]
+        }))
+        .unwrap();
+        let at = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let conn = open(&[
+            sample("2024-05-01T09:00:00Z", 5.0),
+            Snapshot::new(usage, at),
+        ])
+        .unwrap();
+        let t = run(
+            &conn,
+            "SELECT s.fetched_at_unix, coalesce(w.feature, '-'), w.name, w.window_seconds,
+                    w.used_percent, w.reset_after_seconds
+             FROM windows w JOIN samples s ON s.rowid = w.sample ORDER BY w.rowid",
+        )
+        .unwrap();
+        // The secondary the first sample left out has no row; a length the
+        // API doesn't give is assumed
+        assert_eq!(
+            t.rows,
+            [
+                ["1714554000", "-", "primary", "18000", "5", ""],
+                ["1714557600", "-", "primary", "10800", "20", ""],
+                ["1714557600", "-", "secondary", "604800", "9", "3600"],
+                ["1714557600", "code_review", "primary", "18000", "55", ""],
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! `history replay`: play stored samples back through the fancy renderer.
+
+use crate::api::Snapshot;
+use crate::render::{Fancy, Render, BUFFER_CAPACITY};
+use anyhow::{bail, Result};
+use chrono::Duration;
+use colored::Colorize;
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// Real time between redraws
+const FRAME: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Idle gaps longer than this (in real playback seconds) are skipped
+const MAX_IDLE_SECS: f64 = 3.0;
+
+/// Parse a playback speed such as `60x`, `60` or `0.5x`
+pub fn parse_speed(s: &str) -> Result<f64> {
+    let n = s.trim().trim_end_matches(['x', 'X']);
+    match n.parse::<f64>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        _ => bail!("Invalid --speed '{s}' (expected e.g. 60x)"),
+    }
+}
+
+/// Markers for the boundaries between `prev` and `sample`, shown for as long
+/// as `sample` is on screen
+fn boundary_line(prev: Option<&Snapshot>, sample: &Snapshot) -> String {
+    let mut notes: Vec<String> = sample
+        .marks
+        .iter()
+        .map(|m| format!("↺ {}", m.label()))
+        .collect();
+    if prev.is_some_and(|p| p.fetched_at.date_naive() != sample.fetched_at.date_naive()) {
+        notes.push(format!("☾ {}", sample.fetched_at.format("%a %Y-%m-%d")));
+    }
+    if notes.is_empty() {
+        return String::new();
+    }
+    format!("  {}\n", notes.join(" · ").yellow())
+}
+
+/// Animate `samples` at `speed` times real time
+pub fn replay(samples: &[Snapshot], speed: f64) -> Result<()> {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        bail!(
+            "No history to replay yet — samples are recorded each time \
+             codex-usage fetches usage"
+        );
+    };
+
+    let step = Duration::milliseconds((FRAME.as_millis() as f64 * speed) as i64);
+    let mut now = first.fetched_at;
+    let mut idx = 0;
+    let mut out = std::io::stdout();
+    let fancy = Fancy::default();
+    let mut frame = String::with_capacity(BUFFER_CAPACITY);
+
+    loop {
+        while idx + 1 < samples.len() && samples[idx + 1].fetched_at <= now {
+            idx += 1;
+        }
+        let sample = &samples[idx];
+
+        let header = format!(
+            "{} · sample {}/{} · {}x",
+            now.format("%Y-%m-%d %H:%M:%S UTC"),
+            idx + 1,
+            samples.len(),
+            speed
+        );
+        let prev = idx.checked_sub(1).map(|i| &samples[i]);
+        // Clear screen, home cursor, then draw the frame in one write
+        frame.clear();
+        let _ = write!(
+            frame,
+            "\x1b[2J\x1b[H\n  {} {}\n{}",
+            "▶ Replay".cyan().bold(),
+            header.dimmed(),
+            boundary_line(prev, sample),
+        );
+        fancy.render_into(&mut frame, sample, now);
+        out.write_all(frame.as_bytes())?;
+        out.flush()?;
+
+        if now >= last.fetched_at {
+            break;
+        }
+        std::thread::sleep(FRAME);
+
+        now += step;
+        // Jump over long quiet stretches instead of animating nothing
+        if let Some(next) = samples.get(idx + 1) {
+            let gap_secs = (next.fetched_at - now).num_seconds() as f64 / speed;
+            if gap_secs > MAX_IDLE_SECS {
+                now = next.fetched_at;
+            }
+        }
+        now = now.min(last.fetched_at);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+    use crate::history::Mark;
+
+    #[test]
+    fn speeds_and_boundaries() {
+        assert_eq!(parse_speed("60x").unwrap(), 60.0);
+        assert_eq!(parse_speed(" 0.5X ").unwrap(), 0.5);
+        assert!(parse_speed("0").is_err() && parse_speed("fast").is_err());
+
+        colored::control::set_override(false);
+        let at = |s| parse_timestamp(s).unwrap();
+        let prev = Snapshot::plus((90.0, 600), (40.0, 86400), at("2024-05-01T23:50:00Z"));
+        let mut next = Snapshot::plus((2.0, 18000), (40.0, 85000), at("2024-05-02T00:10:00Z"));
+        assert_eq!(boundary_line(Some(&prev), &next), "  ☾ Thu 2024-05-02\n");
+        next.marks = vec![Mark::PrimaryReset];
+        assert_eq!(
+            boundary_line(Some(&prev), &next),
+            "  ↺ 5h window reset · ☾ Thu 2024-05-02\n"
+        );
+        assert_eq!(boundary_line(None, &prev), "");
+    }
+}
@@ -0,0 +1,213 @@
+//! Named work sessions: `session start` and `session stop` each record a
+//! sample carrying a marker, and the quota a session used is what its
+//! windows grew by across the samples between, counting a window that reset
+//! part-way from zero again. Samples recorded meanwhile, by `record` or
+//! anything else, only make the figures finer.
+
+use crate::api::Snapshot;
+use crate::render::{label, window_rows};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Usage falling by more than this between samples means the window reset
+const DROP_TOLERANCE: f64 = 0.5;
+
+/// The marker on a `session start` or `session stop` sample
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionMark {
+    Start(String),
+    Stop,
+}
+
+/// Percentage points of one window a session used
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Used {
+    pub window: String,
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Session {
+    pub name: String,
+    pub started: DateTime<Utc>,
+    /// `None` while it is still running
+    pub stopped: Option<DateTime<Utc>>,
+    /// So far, for one still running: up to the latest sample
+    pub used: Vec<Used>,
+}
+
+impl Session {
+    /// `5-hour session +12%, 7-day rolling +3%`
+    pub fn describe_used(&self) -> String {
+        if self.used.is_empty() {
+            return "no usage figures".to_string();
+        }
+        self.used
+            .iter()
+            .map(|u| format!("{} +{:.0}%", u.window, u.percent))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// `refactor auth: 09:00–10:20 UTC on 2024-05-01 (1h 20m)`, then what it
+    /// used on the next line
+    pub fn describe(&self, now: DateTime<Utc>) -> String {
+        let day = |t: DateTime<Utc>| t.format("%Y-%m-%d").to_string();
+        let when = match self.stopped {
+            Some(end) if day(end) == day(self.started) => format!(
+                "{}–{} UTC on {} ({})",
+                self.started.format("%H:%M"),
+                end.format("%H:%M"),
+                day(end),
+                crate::status::age(end - self.started)
+            ),
+            Some(end) => format!(
+                "{} – {} UTC ({})",
+                self.started.format("%Y-%m-%d %H:%M"),
+                end.format("%Y-%m-%d %H:%M"),
+                crate::status::age(end - self.started)
+            ),
+            None => format!(
+                "since {} UTC, running ({} so far)",
+                self.started.format("%Y-%m-%d %H:%M"),
+                crate::status::age(now - self.started)
+            ),
+        };
+        format!("{}: {when}\n  {}", self.name, self.describe_used())
+    }
+}
+
+/// Every session in `samples` (in time order). Starting one while another
+/// runs ends the first there.
+pub fn sessions(samples: &[Snapshot]) -> Vec<Session> {
+    let mut found = Vec::new();
+    let mut open: Option<(String, usize)> = None;
+    for (i, snap) in samples.iter().enumerate() {
+        let Some(mark) = &snap.session else {
+            continue;
+        };
+        if let Some((name, from)) = open.take() {
+            found.push(session(name, &samples[from..=i], true));
+        }
+        if let SessionMark::Start(name) = mark {
+            open = Some((name.clone(), i));
+        }
+    }
+    if let Some((name, from)) = open {
+        found.push(session(name, &samples[from..], false));
+    }
+    found
+}
+
+/// The session running at the end of `samples`, if any
+pub fn running(samples: &[Snapshot]) -> Option<Session> {
+    sessions(samples).pop().filter(|s| s.stopped.is_none())
+}
+
+fn session(name: String, samples: &[Snapshot], stopped: bool) -> Session {
+    let mut used: Vec<Used> = Vec::new();
+    for pair in samples.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        let before = window_rows(prev, prev.fetched_at);
+        for row in window_rows(next, next.fetched_at) {
+            let name = label(&row);
+            let Some(now) = row.window.as_ref().and_then(|w| w.used_percent) else {
+                continue;
+            };
+            let then = before.iter().find(|r| label(r) == name);
+            let grew = match then.and_then(|r| Some((r.window.as_ref()?.used_percent?, r))) {
+                Some((then, r)) => {
+                    let reset = r.resets_in.is_some_and(|secs| {
+                        next.fetched_at >= prev.fetched_at + Duration::seconds(secs as i64)
+                    });
+                    match reset || now + DROP_TOLERANCE < then {
+                        true => now,
+                        false => (now - then).max(0.0),
+                    }
+                }
+                // A window that appeared part-way: nothing to measure from
+                None => 0.0,
+            };
+            match used.iter_mut().find(|u| u.window == name) {
+                Some(u) => u.percent += grew,
+                None => used.push(Used {
+                    window: name,
+                    percent: grew,
+                }),
+            }
+        }
+    }
+    let first = samples.first().map(|s| s.fetched_at).unwrap_or_default();
+    Session {
+        name,
+        started: first,
+        stopped: stopped
+            .then(|| samples.last().map(|s| s.fetched_at))
+            .flatten(),
+        used,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    fn sample(ts: &str, primary: f64, reset_after: u64, weekly: f64) -> Snapshot {
+        let usage = serde_json::from_value(serde_json::json!({
+            "plan_type": "plus",
+            "rate_limit": {
+                "primary_window": {
+                    "used_percent": primary,
+                    "reset_after_seconds": reset_after,
+                    "limit_window_seconds": 18000
+                },
+                "secondary_window": { "used_percent": weekly, "limit_window_seconds": 604800 }
+            }
+        }))
+        .unwrap();
+        Snapshot::new(usage, parse_timestamp(ts).unwrap())
+    }
+
+    #[test]
+    fn sessions_add_up_growth_across_resets() {
+        let marked = |mut s: Snapshot, mark| {
+            s.session = Some(mark);
+            s
+        };
+        let samples = vec![
+            sample("2024-05-01T08:00:00Z", 10.0, 9000, 20.0),
+            marked(
+                sample("2024-05-01T09:00:00Z", 30.0, 5400, 22.0),
+                SessionMark::Start("refactor auth".into()),
+            ),
+            sample("2024-05-01T10:00:00Z", 80.0, 1800, 27.0),
+            // The 5-hour window reset at 10:30
+            marked(
+                sample("2024-05-01T10:45:00Z", 12.0, 17100, 28.0),
+                SessionMark::Stop,
+            ),
+            sample("2024-05-01T12:00:00Z", 20.0, 12600, 29.0),
+            marked(
+                sample("2024-05-01T13:00:00Z", 25.0, 9000, 30.0),
+                SessionMark::Start("fix tests".into()),
+            ),
+            sample("2024-05-01T13:30:00Z", 31.0, 7200, 30.4),
+        ];
+        let found = sessions(&samples);
+        let now = parse_timestamp("2024-05-01T13:40:00Z").unwrap();
+        assert_eq!(
+            found.iter().map(|s| s.describe(now)).collect::<Vec<_>>(),
+            [
+                "refactor auth: 09:00–10:45 UTC on 2024-05-01 (1h 45m)\n  \
+                 5-hour session +62%, 7-day rolling +6%",
+                "fix tests: since 2024-05-01 13:00 UTC, running (40m so far)\n  \
+                 5-hour session +6%, 7-day rolling +0%",
+            ]
+        );
+        assert_eq!(running(&samples).unwrap().name, "fix tests");
+        assert_eq!(running(&samples[..6]).unwrap().used, []);
+        assert_eq!(running(&samples[..4]), None);
+    }
+}
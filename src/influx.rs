@@ -0,0 +1,49 @@
+//! `--influx-url`: write each run's point (see `render::influx`) straight to
+//! InfluxDB, or to Telegraf's `influxdb_v2_listener`, which takes the same
+//! requests. The URL is the full write endpoint, query string included, so
+//! both the v2 API (`/api/v2/write?org=…&bucket=…`) and v1 (`/write?db=…`)
+//! work without more flags.
+
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+/// Longest we wait for the database before giving up on the write
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `--influx-token`, else INFLUX_TOKEN (the influx CLI's own variable)
+pub fn token(flag: Option<&str>) -> Option<String> {
+    flag.map(str::to_string)
+        .or_else(|| std::env::var("INFLUX_TOKEN").ok())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// POST `lines` to `url`, at nanosecond precision unless the URL says
+/// otherwise
+pub fn push(url: &str, token: Option<&str>, lines: &str) -> Result<()> {
+    let url = match url.contains("precision=") {
+        true => url.to_string(),
+        false if url.contains('?') => format!("{url}&precision=ns"),
+        false => format!("{url}?precision=ns"),
+    };
+    let mut request = Client::builder()
+        .timeout(TIMEOUT)
+        .build()?
+        .post(&url)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .header("User-Agent", "codex-usage")
+        .body(lines.to_string());
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Token {token}"));
+    }
+    let resp = request
+        .send()
+        .with_context(|| format!("Failed to reach InfluxDB at {url}"))?;
+    let status = resp.status();
+    if !status.is_success() {
+        let detail = crate::api::read_snippet(resp);
+        bail!("InfluxDB returned HTTP {status} for the write: {detail}");
+    }
+    Ok(())
+}
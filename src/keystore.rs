@@ -0,0 +1,149 @@
+//! The platform's secret store, where Codex may keep its login instead of
+//! auth.json. Each platform gets its own backend, compiled only for that
+//! target, so no build assumes another platform's tooling:
+//!
+//! - macOS: the login Keychain, through the `security` tool
+//! - Linux and the BSDs: the Secret Service (GNOME Keyring, KWallet),
+//!   through `secret-tool` from libsecret
+//! - Windows: Credential Manager, through the Win32 API
+//!
+//! Building without the default `keyring` feature leaves the store out
+//! entirely, for static or container builds where there is none.
+
+use anyhow::Result;
+
+/// Service names Codex has been seen to use, tried in order
+const SERVICES: &[&str] = &["Codex", "codex", "openai-codex", "Codex CLI"];
+
+/// What the store is called here, for labels and messages
+pub const NAME: &str = backend::NAME;
+
+/// The secret stored under the first service name that has one. `Ok(None)`
+/// when nothing is stored or there is no store on this machine (the tool
+/// isn't installed, no keyring daemon is running).
+pub fn read() -> Result<Option<String>> {
+    first_secret(backend::lookup)
+}
+
+fn first_secret(lookup: impl Fn(&str) -> Result<Option<String>>) -> Result<Option<String>> {
+    for service in SERVICES {
+        if let Some(secret) = lookup(service)? {
+            let secret = secret.trim();
+            if !secret.is_empty() {
+                return Ok(Some(secret.to_string()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// ─── Backends ─────────────────────────────────────────────────────────────────
+
+/// Run a lookup tool; a missing tool or a failed lookup both mean "nothing
+/// stored"
+#[cfg(all(feature = "keyring", unix))]
+fn run_tool(program: &str, args: &[&str]) -> Result<Option<String>> {
+    match std::process::Command::new(program).args(args).output() {
+        Ok(out) if out.status.success() => Ok(Some(String::from_utf8(out.stdout)?)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(all(feature = "keyring", target_os = "macos"))]
+mod backend {
+    pub const NAME: &str = "macOS Keychain";
+
+    pub fn lookup(service: &str) -> anyhow::Result<Option<String>> {
+        super::run_tool("security", &["find-generic-password", "-s", service, "-w"])
+    }
+}
+
+#[cfg(all(feature = "keyring", unix, not(target_os = "macos")))]
+mod backend {
+    pub const NAME: &str = "Secret Service";
+
+    pub fn lookup(service: &str) -> anyhow::Result<Option<String>> {
+        super::run_tool("secret-tool", &["lookup", "service", service])
+    }
+}
+
+#[cfg(all(feature = "keyring", windows))]
+mod backend {
+    use windows_sys::Win32::Security::Credentials::{
+        CredFree, CredReadW, CREDENTIALW, CRED_TYPE_GENERIC,
+    };
+
+    pub const NAME: &str = "Windows Credential Manager";
+
+    pub fn lookup(service: &str) -> anyhow::Result<Option<String>> {
+        let target: Vec<u16> = service.encode_utf16().chain([0]).collect();
+        let mut cred: *mut CREDENTIALW = std::ptr::null_mut();
+        // SAFETY: `target` is NUL-terminated and outlives the call; on
+        // success `cred` points at a buffer we own until `CredFree`
+        let blob = unsafe {
+            if CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut cred) == 0 {
+                return Ok(None);
+            }
+            let c = &*cred;
+            let blob = std::slice::from_raw_parts(c.CredentialBlob, c.CredentialBlobSize as usize)
+                .to_vec();
+            CredFree(cred as *const _);
+            blob
+        };
+        // Most tools store UTF-8; some store the UTF-16 the UI uses
+        Ok(Some(match String::from_utf8(blob) {
+            Ok(s) => s,
+            Err(e) => {
+                let wide: Vec<u16> = e
+                    .as_bytes()
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                String::from_utf16_lossy(&wide)
+            }
+        }))
+    }
+}
+
+/// Anywhere else, or with the feature off: no store to ask
+#[cfg(not(all(feature = "keyring", any(unix, windows))))]
+mod backend {
+    pub const NAME: &str = "secret store (not built in)";
+
+    pub fn lookup(_service: &str) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_service_with_a_secret_wins() {
+        let store = |stored: &'static [(&'static str, &'static str)]| {
+            move |service: &str| {
+                Ok(stored
+                    .iter()
+                    .find(|(s, _)| *s == service)
+                    .map(|(_, v)| v.to_string()))
+            }
+        };
+        // A blank entry under an earlier name doesn't hide a later one
+        let found = first_secret(store(&[("codex", " \n"), ("openai-codex", "tok\n")]));
+        assert_eq!(found.unwrap().as_deref(), Some("tok"));
+        assert_eq!(first_secret(store(&[])).unwrap(), None);
+        assert!(first_secret(|_| anyhow::bail!("locked")).is_err());
+    }
+
+    #[cfg(all(feature = "keyring", unix))]
+    #[test]
+    fn missing_or_failing_tools_mean_nothing_stored() {
+        assert_eq!(
+            run_tool("sh", &["-c", "printf tok"]).unwrap().as_deref(),
+            Some("tok")
+        );
+        assert_eq!(run_tool("sh", &["-c", "exit 44"]).unwrap(), None);
+        assert_eq!(run_tool("codex-usage-no-such-tool", &[]).unwrap(), None);
+    }
+}
@@ -0,0 +1,72 @@
+//! Extra labels stamped on every body the server serves, on the metrics and
+//! points a run prints or pushes (`--format prometheus`, `--format influx`,
+//! `--influx-url`, `--dogstatsd`), so a fleet of sidecars can be sliced by
+//! pod, namespace or team once their output is collected in one place.
+//!
+//! Three layers, later ones winning key by key: `[server.labels]` in the
+//! config file, CODEX_USAGE_LABELS (`team=platform,pod=api-7f9c`, which a
+//! Kubernetes manifest can fill from the downward API; laid over the config
+//! by [`crate::envvars`]), then `--label`.
+
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+pub type Labels = BTreeMap<String, String>;
+
+/// `--label` flags over the configured labels
+pub fn resolve(config: &Labels, flags: &[String]) -> Result<Labels> {
+    let mut labels = config.clone();
+    for pair in flags {
+        let (k, v) = parse(pair, "--label")?;
+        labels.insert(k, v);
+    }
+    Ok(labels)
+}
+
+/// `labels` less the keys a format sets itself, such as `plan`
+pub fn extra<'a>(
+    labels: &'a Labels,
+    reserved: &'a [&str],
+) -> impl Iterator<Item = (&'a str, &'a str)> {
+    labels
+        .iter()
+        .filter(|(k, _)| !reserved.contains(&k.as_str()))
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+}
+
+fn parse(pair: &str, from: &str) -> Result<(String, String)> {
+    match pair.split_once('=') {
+        Some((k, v)) if !k.trim().is_empty() && !k.trim().contains(char::is_whitespace) => {
+            Ok((k.trim().to_string(), v.trim().to_string()))
+        }
+        _ => bail!("Invalid label '{pair}' in {from} (expected key=value)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_layers_win() {
+        let config = Labels::from([
+            ("team".to_string(), "infra".to_string()),
+            ("env".to_string(), "prod".to_string()),
+        ]);
+        let labels = resolve(
+            &config,
+            &["pod=api-0".to_string(), "team=platform".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            labels,
+            Labels::from([
+                ("env".to_string(), "prod".to_string()),
+                ("pod".to_string(), "api-0".to_string()),
+                ("team".to_string(), "platform".to_string()),
+            ])
+        );
+        assert!(resolve(&Labels::new(), &["team".to_string()]).is_err());
+        assert!(resolve(&Labels::new(), &["=x".to_string()]).is_err());
+    }
+}
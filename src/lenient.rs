@@ -0,0 +1,172 @@
+//! Reading JSON files people and other tools write: auth.json saved by a
+//! Windows editor with a byte-order mark or as UTF-16, or hand-edited with a
+//! comment or a trailing comma. Encodings are always handled; comments and
+//! trailing commas only with `--lenient-json`, since a file that needs them
+//! isn't one the Codex CLI wrote. Parse errors point at the line and column.
+
+use anyhow::{bail, Result};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static LENIENT: AtomicBool = AtomicBool::new(false);
+
+/// `--lenient-json`: accept comments and trailing commas
+pub fn allow() {
+    LENIENT.store(true, Ordering::Relaxed);
+}
+
+/// Text from UTF-8 (with or without a BOM) or UTF-16 (with a BOM, or little
+/// endian without one, as Notepad used to save "Unicode")
+pub fn decode(bytes: &[u8]) -> Result<String> {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|b| from([b[0], b[1]])).collect();
+        String::from_utf16(&units).map_err(|_| anyhow::anyhow!("invalid UTF-16"))
+    };
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => Ok(std::str::from_utf8(rest)?.to_string()),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        // ASCII JSON in UTF-16LE has a zero after every character
+        [c, 0, ..] if c.is_ascii() => utf16(bytes, u16::from_le_bytes),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_string()),
+            Err(e) => bail!(
+                "not UTF-8 or UTF-16 text (invalid byte at offset {})",
+                e.valid_up_to()
+            ),
+        },
+    }
+}
+
+/// Blank out `//` and `/* */` comments and trailing commas, keeping every
+/// other character (and every newline) where it was so error positions
+/// still match the file
+pub fn relax(text: &str) -> String {
+    let mut out: Vec<char> = text.chars().collect();
+    // Comments first, so a comma before one is seen as trailing
+    let mut i = 0;
+    let mut in_string = false;
+    while i < out.len() {
+        let next = out.get(i + 1).copied();
+        match (in_string, out[i], next) {
+            (true, '\\', _) => i += 1,
+            (_, '"', _) => in_string = !in_string,
+            (false, '/', Some('/')) => {
+                while i < out.len() && out[i] != '\n' {
+                    out[i] = ' ';
+                    i += 1;
+                }
+            }
+            (false, '/', Some('*')) => {
+                let end = (i + 2..out.len().saturating_sub(1))
+                    .find(|&j| out[j] == '*' && out[j + 1] == '/')
+                    .map_or(out.len(), |j| j + 2);
+                for ch in &mut out[i..end] {
+                    if *ch != '\n' {
+                        *ch = ' ';
+                    }
+                }
+                i = end - 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let mut i = 0;
+    in_string = false;
+    while i < out.len() {
+        match (in_string, out[i]) {
+            (true, '\\') => i += 1,
+            (_, '"') => in_string = !in_string,
+            (false, ',') => {
+                let next = out[i + 1..].iter().find(|c| !c.is_whitespace());
+                if matches!(next, Some('}' | ']')) {
+                    out[i] = ' ';
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    out.into_iter().collect()
+}
+
+/// Parse `bytes` from `path`, decoding and (with `--lenient-json`) relaxing
+/// first. Errors name the line and column and quote the line.
+pub fn parse<T: DeserializeOwned>(bytes: &[u8], path: &Path) -> Result<T> {
+    let text = match decode(bytes) {
+        Ok(text) => text,
+        Err(e) => bail!("Could not parse {}: {e}", path.display()),
+    };
+    let lenient = LENIENT.load(Ordering::Relaxed);
+    let source = if lenient { relax(&text) } else { text.clone() };
+    match serde_json::from_str(&source) {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            let mut msg = format!(
+                "Could not parse {} at line {}, column {}: {}",
+                path.display(),
+                e.line(),
+                e.column(),
+                reason(&e)
+            );
+            if let Some(line) = text.lines().nth(e.line().saturating_sub(1)) {
+                msg.push_str(&format!(
+                    "\n    {line}\n    {:>width$}",
+                    "^",
+                    width = e.column().max(1)
+                ));
+            }
+            if !lenient && serde_json::from_str::<T>(&relax(&text)).is_ok() {
+                msg.push_str(
+                    "\nIt has comments or trailing commas; pass --lenient-json to accept them",
+                );
+            }
+            bail!(msg)
+        }
+    }
+}
+
+/// serde_json's message without its own "at line … column …" suffix
+fn reason(e: &serde_json::Error) -> String {
+    let full = e.to_string();
+    match full.rfind(" at line ") {
+        Some(at) => full[..at].to_string(),
+        None => full,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_encodings_and_hand_edits() {
+        let json = r#"{"tokens": {"access_token": "tok"}}"#;
+        let mut bom = vec![0xEF, 0xBB, 0xBF];
+        bom.extend_from_slice(json.as_bytes());
+        assert_eq!(decode(&bom).unwrap(), json);
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(json.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode(&utf16).unwrap(), json);
+        let bare: Vec<u8> = json.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(&bare).unwrap(), json);
+
+        let edited =
+            "{\n  // from codex login\n  \"tokens\": {\"access_token\": \"a//b\",},\n  /* x */\n}";
+        let relaxed: serde_json::Value = serde_json::from_str(&relax(edited)).unwrap();
+        assert_eq!(relaxed["tokens"]["access_token"], "a//b");
+        assert_eq!(relax(edited).lines().count(), edited.lines().count());
+
+        let path = Path::new("auth.json");
+        let err = parse::<serde_json::Value>(edited.as_bytes(), path).unwrap_err();
+        let msg = err.to_string();
+        assert!(
+            msg.starts_with("Could not parse auth.json at line 2, column 3: key must be a string")
+        );
+        assert!(msg.contains("\n      // from codex login\n      ^"));
+        assert!(msg.ends_with("pass --lenient-json to accept them"));
+    }
+}
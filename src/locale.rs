@@ -0,0 +1,129 @@
+//! `--locale`: decimal separators and date formats for reports and CSV, so a
+//! CSV opened in a German Excel gets numbers rather than text. A small fixed
+//! table rather than full CLDR data — enough for the separators that matter.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    pub tag: &'static str,
+    pub decimal: char,
+    /// chrono format for timestamps
+    pub datetime: &'static str,
+    /// Field separator Excel expects in this locale: `;` wherever `,` is the
+    /// decimal separator
+    pub csv_separator: char,
+}
+
+/// No localisation: `.` decimals, RFC 3339 timestamps, `,`-separated CSV.
+/// What every output uses unless `--locale` is given.
+pub const DEFAULT: Locale = Locale {
+    tag: "iso",
+    decimal: '.',
+    datetime: "%Y-%m-%dT%H:%M:%SZ",
+    csv_separator: ',',
+};
+
+const fn locale(tag: &'static str, decimal: char, datetime: &'static str) -> Locale {
+    Locale {
+        tag,
+        decimal,
+        datetime,
+        csv_separator: if decimal == ',' { ';' } else { ',' },
+    }
+}
+
+const KNOWN: &[Locale] = &[
+    DEFAULT,
+    locale("en-US", '.', "%m/%d/%Y %H:%M:%S"),
+    locale("en-GB", '.', "%d/%m/%Y %H:%M:%S"),
+    locale("en-AU", '.', "%d/%m/%Y %H:%M:%S"),
+    locale("de-DE", ',', "%d.%m.%Y %H:%M:%S"),
+    locale("de-AT", ',', "%d.%m.%Y %H:%M:%S"),
+    locale("de-CH", '.', "%d.%m.%Y %H:%M:%S"),
+    locale("fr-FR", ',', "%d/%m/%Y %H:%M:%S"),
+    locale("es-ES", ',', "%d/%m/%Y %H:%M:%S"),
+    locale("it-IT", ',', "%d/%m/%Y %H:%M:%S"),
+    locale("nl-NL", ',', "%d-%m-%Y %H:%M:%S"),
+    locale("pt-BR", ',', "%d/%m/%Y %H:%M:%S"),
+    locale("pl-PL", ',', "%d.%m.%Y %H:%M:%S"),
+    locale("sv-SE", ',', "%Y-%m-%d %H:%M:%S"),
+    locale("da-DK", ',', "%d-%m-%Y %H:%M:%S"),
+    locale("fi-FI", ',', "%d.%m.%Y %H:%M:%S"),
+    locale("ja-JP", '.', "%Y/%m/%d %H:%M:%S"),
+];
+
+impl Default for Locale {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+impl Locale {
+    /// `v` to `places` decimals with this locale's separator
+    pub fn num(&self, v: f64, places: usize) -> String {
+        self.localise(format!("{v:.places$}"))
+    }
+
+    /// `v` at full precision, for data files
+    pub fn exact(&self, v: f64) -> String {
+        self.localise(v.to_string())
+    }
+
+    fn localise(&self, s: String) -> String {
+        if self.decimal == '.' {
+            s
+        } else {
+            s.replace('.', &self.decimal.to_string())
+        }
+    }
+
+    /// Timestamps are always UTC; only the layout changes
+    pub fn datetime(&self, t: DateTime<Utc>) -> String {
+        t.format(self.datetime).to_string()
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = anyhow::Error;
+
+    /// Exact tags first (`de-CH`), then the language alone (`de` → `de-DE`);
+    /// `_` and POSIX suffixes (`de_DE.UTF-8`) are accepted
+    fn from_str(s: &str) -> Result<Self> {
+        let tag = s.split('.').next().unwrap_or(s).replace('_', "-");
+        if let Some(l) = KNOWN.iter().find(|l| l.tag.eq_ignore_ascii_case(&tag)) {
+            return Ok(*l);
+        }
+        let lang = tag.split('-').next().unwrap_or("");
+        if let Some(l) = KNOWN.iter().find(|l| {
+            l.tag
+                .split('-')
+                .next()
+                .is_some_and(|t| t.eq_ignore_ascii_case(lang))
+        }) {
+            return Ok(*l);
+        }
+        let tags: Vec<&str> = KNOWN.iter().map(|l| l.tag).collect();
+        bail!("Unknown locale '{s}' (known: {})", tags.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    #[test]
+    fn separators_and_dates() {
+        let de: Locale = "de_DE.UTF-8".parse().unwrap();
+        assert_eq!(de.num(71.24, 1), "71,2");
+        assert_eq!(de.exact(71.24), "71,24");
+        assert_eq!(de.csv_separator, ';');
+        let t = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        assert_eq!(de.datetime(t), "01.05.2024 10:00:00");
+        assert_eq!("fr".parse::<Locale>().unwrap().tag, "fr-FR");
+        assert_eq!(DEFAULT.datetime(t), "2024-05-01T10:00:00Z");
+        assert!("xx-YY".parse::<Locale>().is_err());
+    }
+}
@@ -1,436 +1,841 @@
-use anyhow::{bail, Context, Result};
+mod advise;
+mod alerting;
+mod all;
+mod anonymize;
+mod api;
+mod auth;
+mod away;
+mod badge;
+mod batch;
+mod cache;
+mod check;
+mod ci;
+mod cli;
+mod clock;
+mod config;
+mod discord;
+mod doctor;
+mod dryrun;
+mod envvars;
+mod errors;
+mod fleet;
+mod har;
+mod hints;
+mod history;
+mod influx;
+mod keystore;
+mod labels;
+mod lenient;
+mod locale;
+mod pacing;
+mod paths;
+mod plans;
+mod progress;
+mod record;
+mod render;
+mod runlog;
+mod server;
+mod shell;
+mod stats;
+mod statsd;
+mod status;
+mod team;
+mod term;
+mod timing;
+mod tray;
+mod tui;
+mod watch;
+mod week;
+mod workspace;
+
+use anyhow::{Context, Result};
+use api::Snapshot;
+use cli::{Args, Command};
+use clock::{parse_timestamp, Clock};
 use colored::Colorize;
-use reqwest::blocking::Client;
-use serde::Deserialize;
-use std::process::Command;
-
-// ─── Auth / credential types ──────────────────────────────────────────────────
-
-/// Represents the tokens block inside auth.json
-#[derive(Debug, Deserialize)]
-struct TokenBlock {
-    access_token: Option<String>,
-    account_id: Option<String>,
-}
-
-/// Top-level auth.json schema used by the Codex CLI
-#[derive(Debug, Deserialize)]
-struct AuthDotJson {
-    /// OAuth flow credentials
-    tokens: Option<TokenBlock>,
-    /// Fallback: plain API key stored directly
-    #[serde(rename = "OPENAI_API_KEY")]
-    openai_api_key: Option<String>,
-}
-
-#[derive(Debug)]
-struct Credentials {
-    access_token: String,
-    account_id: Option<String>,
-    /// true = OAuth (can hit /wham/usage); false = API key only
-    is_oauth: bool,
-}
-
-// ─── API response types ───────────────────────────────────────────────────────
-
-#[derive(Debug, Deserialize, Clone)]
-struct RateWindow {
-    /// 0–100 percent used
-    used_percent: Option<f64>,
-    /// seconds until window resets
-    reset_after_seconds: Option<u64>,
-}
-
-#[derive(Debug, Deserialize)]
-struct RateLimit {
-    primary_window: Option<RateWindow>,   // 5-hour window
-    secondary_window: Option<RateWindow>, // 7-day window
-    limit_reached: Option<bool>,
-}
-
-#[derive(Debug, Deserialize)]
-struct WhamUsage {
-    plan_type: Option<String>,
-    rate_limit: Option<RateLimit>,
-}
-
-// ─── Credential discovery ─────────────────────────────────────────────────────
-
-/// Try to find a usable token, in priority order:
-///   1. OPENAI_API_KEY env var
-///   2. CODEX_ACCESS_TOKEN env var  (OAuth override)
-///   3. ~/.codex/auth.json  (Codex CLI default location)
-///   4. ~/.config/codex/auth.json  (XDG alternative)
-///   5. macOS Keychain entry "Codex" (if security tool available)
-fn get_credentials() -> Result<Credentials> {
-    // 1. Env-var overrides
-    if let Ok(token) = std::env::var("CODEX_ACCESS_TOKEN") {
-        let token = token.trim().to_string();
-        if !token.is_empty() {
-            let account_id = std::env::var("CODEX_ACCOUNT_ID").ok();
-            return Ok(Credentials {
-                access_token: token,
-                account_id,
-                is_oauth: true,
-            });
+use config::Config;
+use std::io::Write;
+
+// ─── Entry points ─────────────────────────────────────────────────────────────
+
+fn main() {
+    if let Err(e) = run() {
+        // --check has printed its status line; only the exit code is left
+        if let Some(check::Exit(status)) = e.downcast_ref() {
+            std::process::exit(status.code());
         }
-    }
-    if let Ok(key) = std::env::var("OPENAI_API_KEY") {
-        let key = key.trim().to_string();
-        if !key.is_empty() {
-            return Ok(Credentials {
-                access_token: key,
-                account_id: None,
-                is_oauth: false,
-            });
+        // Even a bad command line must answer in the plugin's terms
+        if std::env::args().any(|a| a == "--check") {
+            println!("{}", check::unknown(&e.to_string()).1);
+            std::process::exit(check::Status::Unknown.code());
         }
-    }
-
-    // 2. auth.json file locations
-    let home = std::env::var_os("HOME").unwrap_or_default();
-    let home = std::path::Path::new(&home);
-    let candidates = [
-        home.join(".codex").join("auth.json"),
-        home.join(".config").join("codex").join("auth.json"),
-    ];
-    for path in &candidates {
-        if path.exists() {
-            if let Ok(creds) = read_auth_json(path) {
-                return Ok(creds);
+        if cli::wants_json(std::env::args().skip(1)) {
+            let json = serde_json::to_string(&errors::JsonError::new(&e)).unwrap_or_default();
+            eprintln!("{json}");
+        } else {
+            let kind = errors::classify(&e);
+            let label = match kind.code {
+                "E000" => "Error:".to_string(),
+                code => format!("Error[{code}]:"),
+            };
+            eprintln!("\n  {} {}", label.red().bold(), e);
+            if let Some(hint) = hints::hint(&e) {
+                eprintln!("  {} {hint}", "→".cyan());
+            }
+            if kind.code != "E000" {
+                let more = format!("More: codex-usage explain {}", kind.code);
+                eprintln!("  {}", more.dimmed());
             }
+            eprintln!();
         }
+        std::process::exit(1);
     }
+}
 
-    // 3. macOS Keychain (service "Codex")
-    if let Ok(creds) = read_keychain() {
-        return Ok(creds);
+fn run() -> Result<()> {
+    let mut args = Args::parse()?;
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if args.timing {
+        timing::start();
+    }
+    // Before anything is printed, so every command gets the same palette
+    timing::measure("terminal", || term::init(args.tty));
+    // Piped with no format asked for: a line suits a log or another tool
+    // better than a dashboard with its colour stripped. A real pipe only;
+    // `--no-tty` on a terminal still gets the dashboard, `--tty` anywhere.
+    let piped = !std::io::IsTerminal::is_terminal(&std::io::stdout());
+    if args.command == Command::Report && !args.format_given && piped && args.tty != Some(true) {
+        args.format = render::Format::Oneline;
+    }
+    // The dashboard owns the screen; a note on stderr would land inside it.
+    // The tray icon has no terminal to speak of; failures go in its tooltip.
+    if matches!(args.command, Command::Tui | Command::Tray) {
+        args.quiet = true;
     }
 
-    bail!(
-        "No OpenAI / Codex credentials found.\n\
-         Tried:\n\
-         • CODEX_ACCESS_TOKEN / OPENAI_API_KEY env vars\n\
-         • ~/.codex/auth.json\n\
-         • ~/.config/codex/auth.json\n\
-         • macOS Keychain (service \"Codex\")\n\n\
-         Log in with:  codex login\n\
-         Or set:       export OPENAI_API_KEY=sk-..."
-    )
+    if args.har.is_some() {
+        har::start();
+    }
+    if args.allow_network {
+        ci::allow();
+    }
+    if args.lenient_json {
+        lenient::allow();
+    }
+    if args.anonymize {
+        anonymize::enable();
+    }
+    // A broken config file is reported by whichever command needs it
+    if let Ok(config) = Config::load() {
+        plans::init(&config.plans);
+    }
+
+    let result = match args.command {
+        Command::Report => report(&args),
+        Command::All => all(&args),
+        Command::Doctor => doctor(&args),
+        Command::HistoryReplay => history_replay(&args),
+        Command::HistoryExport => history_export(&args),
+        Command::HistoryPlans => history_plans(&args),
+        Command::Query => query(&args),
+        Command::TeamReport => team_report(&args),
+        Command::FleetValidate => fleet_validate(&args),
+        Command::Serve => server::serve(
+            server_config(&args)?,
+            args.listen.as_deref(),
+            &args.labels,
+            args.prometheus,
+        ),
+        Command::Daemon => server::socket::daemon(
+            server_config(&args)?,
+            args.socket.as_deref().map(std::path::Path::new),
+            args.dbus,
+        ),
+        Command::Badge => badge(&args),
+        Command::Hook => hook(&args),
+        Command::ShellInit => shell_init(&args),
+        Command::Env => env(&args),
+        Command::Advise => advise(&args),
+        Command::Explain => explain(&args),
+        Command::MetaStats => meta_stats(&args),
+        Command::ConfigEnvDocs => config_env_docs(&args),
+        Command::Status => status(&args),
+        Command::NotifyTest => notify_test(&args),
+        Command::Tui => tui(&args),
+        Command::Tray => tray(&args),
+        Command::Record => record(&args),
+        Command::SessionStart => session_start(&args),
+        Command::SessionStop => session_stop(&args),
+        Command::SessionReport => session_report(&args),
+    };
+    stats::record(&args, result.as_ref().err());
+    if let Some(report) = timing::report() {
+        eprint!("{report}");
+    }
+
+    // Written even when the command failed — that's when it's wanted most
+    match &args.har {
+        Some(path) => result.and(har::save(std::path::Path::new(path))),
+        None => result,
+    }
 }
 
-fn read_auth_json(path: &std::path::Path) -> Result<Credentials> {
-    let raw = std::fs::read_to_string(path)
-        .with_context(|| format!("Could not read {}", path.display()))?;
-    let auth: AuthDotJson = serde_json::from_str(raw.trim())
-        .with_context(|| format!("Could not parse {}", path.display()))?;
-
-    // Prefer OAuth tokens over plain API key
-    if let Some(ref tokens) = auth.tokens {
-        if let Some(ref access_token) = tokens.access_token {
-            if !access_token.is_empty() {
-                return Ok(Credentials {
-                    access_token: access_token.clone(),
-                    account_id: tokens.account_id.clone(),
-                    is_oauth: true,
-                });
-            }
-        }
+fn all(args: &Args) -> Result<()> {
+    let config = Config::load()?;
+    if args.dry_run {
+        let profiles = config
+            .profile_names()
+            .into_iter()
+            .map(|name| Ok((name.clone(), config.profile(&name)?)))
+            .collect::<Result<Vec<_>>>()?;
+        print!("{}", dryrun::render(&profiles));
+        return Ok(());
     }
-    if let Some(key) = auth.openai_api_key {
-        if !key.is_empty() {
-            return Ok(Credentials {
-                access_token: key,
-                account_id: None,
-                is_oauth: false,
-            });
-        }
+    let opts = render::Options {
+        locale: locale(args)?,
+        patterns: args.patterns || config.theme.patterns,
+        color: term::capability().clone(),
+        sort: args.sort,
+        ..Default::default()
+    };
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let timeout = args.timeout.map(std::time::Duration::from_secs);
+    all::run(&config, &clock, timeout, &opts)
+}
+
+fn doctor(args: &Args) -> Result<()> {
+    let now = Clock::from_override(args.now.as_deref())?.now();
+    let reports = doctor::check(&Config::load()?, now)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        print!("{}", doctor::render(&reports, now));
     }
-    bail!("auth.json found but contained no usable token")
-}
-
-fn read_keychain() -> Result<Credentials> {
-    // Try a few plausible macOS Keychain service names used by Codex CLI
-    let service_names = ["Codex", "codex", "openai-codex", "Codex CLI"];
-    for service in &service_names {
-        let output = Command::new("security")
-            .args(["find-generic-password", "-s", service, "-w"])
-            .output();
-        if let Ok(out) = output {
-            if out.status.success() {
-                let raw = String::from_utf8_lossy(&out.stdout);
-                let raw = raw.trim();
-                if !raw.is_empty() {
-                    // The value might be a JSON blob or a bare token
-                    if let Ok(auth) = serde_json::from_str::<AuthDotJson>(raw) {
-                        if let Ok(creds) = extract_from_auth(auth) {
-                            return Ok(creds);
-                        }
-                    }
-                    // Treat as raw access token
-                    return Ok(Credentials {
-                        access_token: raw.to_string(),
-                        account_id: None,
-                        is_oauth: true,
-                    });
-                }
-            }
+    doctor::ensure_healthy(&reports)
+}
+
+/// Resolve credentials for the selected profile, fetch, and record history
+fn fetch_snapshot(args: &Args, clock: &Clock) -> Result<Snapshot> {
+    fetch_profile(args, clock, args.profile.as_deref())
+}
+
+/// Like `fetch_snapshot`, for `profile` rather than `--profile`
+fn fetch_profile(args: &Args, clock: &Clock, profile: Option<&str>) -> Result<Snapshot> {
+    let fetched = (|| {
+        let creds = timing::measure("credentials", || match profile {
+            Some(name) => Config::load()?.profile(name)?.credentials(),
+            None => auth::get_credentials(),
+        })?;
+        let mut snap = Snapshot::new(api::fetch_usage(&creds)?, clock.now());
+        if workspace::applies(snap.usage.plan_type.as_deref()) {
+            // Only a header detail — never fail the report over it
+            snap.workspace = timing::measure("workspace", || workspace::fetch(&creds))
+                .ok()
+                .flatten();
+        }
+        Ok(snap)
+    })();
+    let profile = profile.unwrap_or(config::DEFAULT_PROFILE);
+    status::record_fetch(profile, &fetched);
+    let mut snap = fetched?;
+    snap.command = args.command_line.clone();
+    snap.session = args.session.clone();
+    // Like history, the cache must never fail the report
+    let _ = timing::measure("cache", || cache::write(profile, &snap));
+
+    if !args.no_history && history::enabled() {
+        // History is a nicety — never fail the report over it
+        let recorded = timing::measure("history", || history::record(&snap));
+        // Quotas follow the plan, so a change is worth saying out loud,
+        // though not in a screenshot
+        if let (Ok(Some(change)), false) = (recorded, args.quiet || args.anonymize) {
+            eprintln!("note: {}", change.describe());
         }
     }
-    bail!("Codex credentials not found in macOS Keychain")
+    Ok(snap)
 }
 
-fn extract_from_auth(auth: AuthDotJson) -> Result<Credentials> {
-    if let Some(tokens) = auth.tokens {
-        if let Some(access_token) = tokens.access_token {
-            if !access_token.is_empty() {
-                return Ok(Credentials {
-                    access_token,
-                    account_id: tokens.account_id,
-                    is_oauth: true,
-                });
-            }
-        }
+/// `--week`, else `[week]` from the config file, else none
+fn work_week(args: &Args) -> Result<Option<week::WorkWeek>> {
+    match &args.week {
+        Some(w) => w.parse().map(Some),
+        None => week::WorkWeek::from_config(&Config::load()?.week),
     }
-    if let Some(key) = auth.openai_api_key {
-        if !key.is_empty() {
-            return Ok(Credentials {
-                access_token: key,
-                account_id: None,
-                is_oauth: false,
-            });
-        }
+}
+
+/// `--locale`, else CODEX_USAGE_LOCALE, else unlocalised
+fn locale(args: &Args) -> Result<locale::Locale> {
+    match args
+        .locale
+        .clone()
+        .or_else(|| std::env::var("CODEX_USAGE_LOCALE").ok())
+        .filter(|l| !l.trim().is_empty())
+    {
+        Some(tag) => tag.parse(),
+        None => Ok(locale::DEFAULT),
     }
-    bail!("no usable token in auth structure")
 }
 
-// ─── API call ─────────────────────────────────────────────────────────────────
+fn report(args: &Args) -> Result<()> {
+    if args.dry_run {
+        let name = args.profile.as_deref().unwrap_or(config::DEFAULT_PROFILE);
+        let profile = match &args.profile {
+            Some(name) => Config::load()?.profile(name)?,
+            None => config::Profile::default(),
+        };
+        print!("{}", dryrun::render(&[(name.to_string(), profile)]));
+        return Ok(());
+    }
+    if args.check {
+        return check(args);
+    }
+    let cached = args.format.renderer(render::Options::default());
+    if cached.reads_cache() {
+        return from_cache(args, cached.as_ref());
+    }
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let color = term::capability().clone();
+    let profile = args.profile.as_deref().unwrap_or(config::DEFAULT_PROFILE);
+    let config = Config::load()?;
+    let theme = &config.theme;
+    let labels = labels::resolve(&config.server.labels, &args.labels)?;
+    let opts = render::Options {
+        week: work_week(args)?,
+        locale: locale(args)?,
+        // Without colour, the glyphs are what's left to show severity
+        patterns: args.patterns || theme.patterns || color.notice.is_some(),
+        pace: args.pace || theme.pace,
+        color,
+        profile: Some(profile.to_string()),
+        labels: labels.clone(),
+        sort: args.sort,
+        reset_clock: None,
+    };
 
-fn fetch_usage(creds: &Credentials) -> Result<WhamUsage> {
-    if !creds.is_oauth {
-        bail!(
-            "Only an API key was found — Codex usage limits are only visible \
-             via an OAuth session token.\n\
-             Log in with:  codex login"
+    if args.watch {
+        // `p` steps through the profiles, starting from this one
+        let mut profiles = config.profile_names();
+        if !profiles.iter().any(|p| p == profile) {
+            profiles.insert(0, profile.to_string());
+        }
+        return watch::run(
+            args.format,
+            opts,
+            &profiles,
+            pacing(args),
+            term::capability().tty,
+            || clock.now(),
+            |name| fetch_profile(args, &clock, Some(name)),
         );
     }
+    let renderer = args.format.renderer(opts);
+
+    let cached = match args.cached {
+        true => Some(timing::measure("daemon", || {
+            let socket = match &args.socket {
+                Some(path) => std::path::PathBuf::from(path),
+                None => server::socket::default_path(),
+            };
+            server::socket::query(&socket, profile)
+        })?),
+        false => args.max_age.and_then(|secs| {
+            timing::measure("cache", || {
+                cache::fresh(profile, chrono::Duration::seconds(secs as i64), clock.now())
+            })
+        }),
+    };
 
-    let client = Client::new();
-    let mut req = client
-        .get("https://chatgpt.com/backend-api/wham/usage")
-        .header("Authorization", format!("Bearer {}", creds.access_token))
-        .header("Content-Type", "application/json")
-        .header("Accept", "application/json")
-        .header(
-            "User-Agent",
-            "Mozilla/5.0 (compatible; codex-usage/0.1)",
-        );
+    if renderer.shows_progress() {
+        println!();
+    }
+    let snap = match cached {
+        Some(snap) => Ok(snap),
+        None => {
+            let progress = match renderer.shows_progress() && !args.quiet {
+                true => progress::Progress::start("Fetching usage data"),
+                false => progress::Progress::hidden(),
+            };
+            let snap = fetch_snapshot(args, &clock);
+            progress.finish();
+            snap
+        }
+    };
+    // Failed runs are logged too; the fetch error still wins over a log error
+    let mut logged = Ok(());
+    if let Some(path) = &args.log_csv {
+        let row = runlog::csv_row(profile, &snap, clock.now());
+        logged = logged.and(runlog::append(
+            std::path::Path::new(path),
+            Some(runlog::CSV_HEADER),
+            &row,
+        ));
+    }
+    if let Some(path) = &args.log_ndjson {
+        let line = runlog::ndjson_line(profile, &snap, clock.now());
+        logged = logged.and(runlog::append(std::path::Path::new(path), None, &line));
+    }
+    let snap = snap?;
+
+    let mut out = String::with_capacity(render::BUFFER_CAPACITY);
+    // Only a report someone reads counts as having looked; prompts and
+    // status bars fetch far too often
+    if renderer.shows_progress() && !args.quiet && !args.anonymize {
+        if let Some(since) = away::returned(clock.now()) {
+            let samples = history::load(Some(since), Some(clock.now())).unwrap_or_default();
+            out.extend(away::digest(&samples, since, clock.now()));
+        }
+    }
+    timing::measure("render", || {
+        renderer.render_into(&mut out, &anonymize::snapshot(&snap), clock.now())
+    });
+    // One write to a locked stdout, so runs sharing a terminal or a status
+    // bar's pipe can't interleave their lines
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(out.as_bytes())?;
+    stdout.flush()?;
+    drop(stdout);
+    // After the report, so a slow database doesn't hold it up
+    if let Some(url) = &args.influx_url {
+        let token = influx::token(args.influx_token.as_deref());
+        let point = render::influx::line(Some(profile), &labels, &snap);
+        logged = logged.and(influx::push(url, token.as_deref(), &point));
+    }
+    let statsd_addr = args
+        .statsd
+        .as_deref()
+        .or(args.dogstatsd.then_some(statsd::DEFAULT_ADDR));
+    if let Some(addr) = statsd_addr {
+        let packet = statsd::packet(profile, &labels, &snap, clock.now(), args.dogstatsd);
+        logged = logged.and(statsd::send(addr, &packet));
+    }
+    logged
+}
 
-    if let Some(ref account_id) = creds.account_id {
-        req = req.header("chatgpt-account-id", account_id);
+/// How old the cache may get before a format that reads it (`--format
+/// starship`) refreshes it, when `--max-age` isn't given
+const CACHE_MAX_AGE: u64 = 60;
+
+/// A format that [reads the cache](render::Render::reads_cache): whatever
+/// the cache holds, straight away, with a fetch started in the background
+/// once it is older than `--max-age`. The prompt is a redraw behind, never a
+/// request behind; with no cache yet it shows nothing.
+fn from_cache(args: &Args, renderer: &dyn render::Render) -> Result<()> {
+    let now = Clock::from_override(args.now.as_deref())?.now();
+    let profile = args.profile.as_deref().unwrap_or(config::DEFAULT_PROFILE);
+    let max_age = chrono::Duration::seconds(args.max_age.unwrap_or(CACHE_MAX_AGE) as i64);
+    let cached = cache::read(profile).ok().flatten();
+    if cached
+        .as_ref()
+        .is_none_or(|snap| now - snap.fetched_at > max_age)
+    {
+        // A failed refresh just leaves the old figure up
+        let _ = cache::refresh_in_background(profile, args.profile.is_some());
     }
+    if let Some(snap) = cached {
+        let mut out = String::new();
+        renderer.render_into(&mut out, &anonymize::snapshot(&snap), now);
+        print!("{out}");
+    }
+    Ok(())
+}
 
-    let resp = req.send().context("Failed to reach ChatGPT API")?;
-    let status = resp.status();
+/// `--check`: the plugin line on stdout; a failed fetch is UNKNOWN rather
+/// than an error, as the plugin contract asks
+fn check(args: &Args) -> Result<()> {
+    let thresholds = check::Thresholds::new(args.warn, args.crit)?;
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let profile = args.profile.as_deref().unwrap_or(config::DEFAULT_PROFILE);
+    let cached = args.max_age.and_then(|secs| {
+        cache::fresh(profile, chrono::Duration::seconds(secs as i64), clock.now())
+    });
+    let snap = match cached {
+        Some(snap) => Ok(snap),
+        None => fetch_snapshot(args, &clock),
+    };
+    let (status, line) = match &snap {
+        Ok(snap) => check::evaluate(snap, clock.now(), thresholds),
+        Err(e) => check::unknown(&e.to_string()),
+    };
+    println!("{line}");
+    match status {
+        check::Status::Ok => Ok(()),
+        status => Err(check::Exit(status).into()),
+    }
+}
 
-    if status.as_u16() == 401 || status.as_u16() == 403 {
-        bail!(
-            "Token expired or unauthorised (HTTP {status}).\n\
-             Try:  codex logout && codex login"
+fn history_replay(args: &Args) -> Result<()> {
+    let speed = match &args.speed {
+        Some(s) => history::parse_speed(s)?,
+        None => 60.0,
+    };
+    let since = args.since.as_deref().map(parse_timestamp).transpose()?;
+    let until = args.until.as_deref().map(parse_timestamp).transpose()?;
+    let samples = history::load(since, until)?;
+    history::replay(&samples, speed)
+}
+
+fn history_export(args: &Args) -> Result<()> {
+    if args.parquet.is_none() && args.csv.is_none() {
+        anyhow::bail!(
+            "history export needs an output, e.g. --parquet usage.parquet or --csv usage.csv"
         );
     }
-    if !status.is_success() {
-        let body = resp.text().unwrap_or_default();
-        bail!("API returned HTTP {status}: {body}");
+    let since = args.since.as_deref().map(parse_timestamp).transpose()?;
+    let until = args.until.as_deref().map(parse_timestamp).transpose()?;
+    let samples = history::load(since, until)?;
+    if let Some(out) = &args.parquet {
+        history::export::parquet(&samples, std::path::Path::new(out))?;
+        eprintln!("Wrote {} samples to {out}", samples.len());
     }
-
-    // Parse — be lenient; the schema may evolve
-    let text = resp.text().context("Failed to read response body")?;
-    serde_json::from_str::<WhamUsage>(&text)
-        .with_context(|| format!("Failed to parse usage response: {text}"))
+    if let Some(out) = &args.csv {
+        history::export::csv(&samples, std::path::Path::new(out), &locale(args)?)?;
+        if out != "-" {
+            eprintln!("Wrote {} samples to {out}", samples.len());
+        }
+    }
+    Ok(())
 }
 
-// ─── Display helpers ──────────────────────────────────────────────────────────
-
-fn usage_bar(pct: f64, width: usize) -> colored::ColoredString {
-    let filled = ((pct / 100.0) * width as f64).round() as usize;
-    let filled = filled.min(width);
-    let empty = width - filled;
-    let bar = format!("{}{}", "█".repeat(filled), "░".repeat(empty));
-    if pct >= 90.0 {
-        bar.red().bold()
-    } else if pct >= 70.0 {
-        bar.yellow()
+fn history_plans(args: &Args) -> Result<()> {
+    let changes = history::plan_changes(&history::load(None, None)?);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else if changes.is_empty() {
+        println!("No plan changes in the history");
     } else {
-        bar.green()
+        for change in &changes {
+            println!("{}", change.describe());
+        }
     }
+    Ok(())
 }
 
-fn format_reset(reset_secs: Option<u64>) -> String {
-    let Some(secs) = reset_secs else {
-        return "—".dimmed().to_string();
+fn query(args: &Args) -> Result<()> {
+    if args.schema {
+        println!("{}", history::query::SCHEMA);
+        return Ok(());
+    }
+    let Some(sql) = &args.sql else {
+        anyhow::bail!("query needs a SQL statement, or --schema to see the table");
     };
-    if secs == 0 {
-        return "now".green().to_string();
-    }
-    let mins = secs / 60;
-    let hours = mins / 60;
-    let days = hours / 24;
-    if days > 0 {
-        format!("in {}d {}h", days, hours % 24).normal().to_string()
-    } else if hours > 0 {
-        format!("in {}h {}m", hours, mins % 60)
-            .normal()
-            .to_string()
-    } else {
-        format!("in {}m", mins).yellow().to_string()
+    let conn = history::query::open(&history::load(None, None)?)?;
+    let table = history::query::run(&conn, sql)?;
+    match args.format {
+        render::Format::Plain => print!("{}", table.to_tsv()),
+        _ => print!("{}", table.to_aligned()),
     }
+    Ok(())
 }
 
-fn pct_coloured(pct: f64) -> colored::ColoredString {
-    let s = format!("{:5.1}%", pct);
-    if pct >= 90.0 {
-        s.red().bold()
-    } else if pct >= 70.0 {
-        s.yellow()
-    } else {
-        s.green()
+/// Queue settings shared by the multi-seat commands
+fn sweep(args: &Args) -> Result<batch::Batch> {
+    Ok(batch::Batch {
+        jobs: args.jobs.unwrap_or(batch::DEFAULT_JOBS),
+        rate: match &args.rate {
+            Some(r) => batch::parse_rate(r)?,
+            None => batch::DEFAULT_RATE,
+        },
+        retries: args.retries.unwrap_or(batch::DEFAULT_RETRIES),
+        checkpoint: args.checkpoint.as_ref().map(std::path::PathBuf::from),
+    })
+}
+
+fn team_report(args: &Args) -> Result<()> {
+    let Some(seats) = &args.seats else {
+        anyhow::bail!("team report needs --seats <FILE>");
+    };
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let seats = team::load_seats(std::path::Path::new(seats))?;
+    team::report(
+        &seats,
+        &clock,
+        work_week(args)?.as_ref(),
+        &sweep(args)?,
+        &locale(args)?,
+        args.sort,
+    )
+}
+
+fn fleet_validate(args: &Args) -> Result<()> {
+    let Some(file) = &args.tokens_file else {
+        anyhow::bail!("fleet validate needs --tokens-file <FILE>");
+    };
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let seats = team::load_seats(std::path::Path::new(file))?;
+    let (checks, resumed) = fleet::validate(&seats, &sweep(args)?, clock.now())?;
+    if resumed > 0 {
+        eprintln!(
+            "Resumed {resumed} of {} seats from the checkpoint",
+            seats.len()
+        );
     }
+    print!("{}", fleet::render(&checks, clock.now()));
+    fleet::ensure_healthy(&checks)
 }
 
-fn print_window_fancy(label: &str, window: &Option<RateWindow>, bar_width: usize) {
-    match window {
-        None => {
-            println!("  {:<18} {}", label, "not available".dimmed());
+fn badge(args: &Args) -> Result<()> {
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let snap = fetch_snapshot(args, &clock)?;
+    let badge = badge::render(args.style.as_deref().unwrap_or("shields"), &snap)?;
+
+    match &args.gist {
+        Some(gist) => {
+            let token = std::env::var("GITHUB_TOKEN")
+                .ok()
+                .filter(|t| !t.trim().is_empty())
+                .context("Publishing to a gist needs GITHUB_TOKEN (with the gist scope)")?;
+            let file = args
+                .gist_file
+                .as_deref()
+                .unwrap_or(badge::DEFAULT_GIST_FILE);
+            badge::publish_gist(gist, file, token.trim(), &badge)?;
+            eprintln!("Updated {file} in gist {gist}");
         }
-        Some(w) => {
-            let pct_used = w.used_percent.unwrap_or(0.0).min(100.0);
-            let bar = usage_bar(pct_used, bar_width);
-            let pct_str = pct_coloured(pct_used);
-            println!(
-                "  {:<18} {} {} resets {}",
-                label.bold(),
-                bar,
-                pct_str,
-                format_reset(w.reset_after_seconds)
-            );
+        None => println!("{badge}"),
+    }
+    Ok(())
+}
+
+/// Quietly record a sample tagged with the shell command that just ran
+fn hook(args: &Args) -> Result<()> {
+    if args.command_line.is_none() {
+        anyhow::bail!("hook needs --command <CMD>");
+    }
+    if args.no_history || !history::enabled() {
+        return Ok(());
+    }
+    let clock = Clock::from_override(args.now.as_deref())?;
+    fetch_snapshot(args, &clock).map(drop)
+}
+
+/// History is where sessions live, so both ends need it on
+fn ensure_history(args: &Args) -> Result<()> {
+    if args.no_history || !history::enabled() {
+        anyhow::bail!("Sessions are kept in the history, which is turned off (--no-history or CODEX_USAGE_HISTORY)");
+    }
+    Ok(())
+}
+
+fn session_start(args: &Args) -> Result<()> {
+    ensure_history(args)?;
+    let clock = Clock::from_override(args.now.as_deref())?;
+    if let Some(running) = history::sessions::running(&history::load(None, None)?) {
+        anyhow::bail!(
+            "Session '{}' is still running; `codex-usage session stop` it first",
+            running.name
+        );
+    }
+    let snap = fetch_snapshot(args, &clock)?;
+    if let (Some(history::SessionMark::Start(name)), false) = (&args.session, args.quiet) {
+        let now = render::window_rows(&snap, snap.fetched_at)
+            .iter()
+            .filter_map(|row| {
+                let pct = row.window.as_ref()?.used_percent?;
+                Some(format!("{} {pct:.0}%", render::label(row)))
+            })
+            .collect::<Vec<_>>();
+        println!("Started '{name}' at {}", now.join(", "));
+    }
+    Ok(())
+}
+
+fn session_stop(args: &Args) -> Result<()> {
+    ensure_history(args)?;
+    let clock = Clock::from_override(args.now.as_deref())?;
+    if history::sessions::running(&history::load(None, None)?).is_none() {
+        anyhow::bail!("No session is running; start one with `codex-usage session start <NAME>`");
+    }
+    fetch_snapshot(args, &clock)?;
+    if let Some(session) = history::sessions::sessions(&history::load(None, None)?).pop() {
+        if !args.quiet {
+            println!("Stopped {}", session.describe(clock.now()));
         }
     }
+    Ok(())
 }
 
-fn print_window_plain(label: &str, window: &Option<RateWindow>) {
-    match window {
-        None => println!("{}: N/A", label),
-        Some(w) => {
-            let pct = w.used_percent.unwrap_or(0.0).min(100.0);
-            let reset = w
-                .reset_after_seconds
-                .map(|s| format!("{}s", s))
-                .unwrap_or_else(|| "—".to_string());
-            println!("{}: {:.1}% used  Resets in: {}", label, pct, reset);
+fn session_report(args: &Args) -> Result<()> {
+    let now = Clock::from_override(args.now.as_deref())?.now();
+    let sessions = history::sessions::sessions(&history::load(None, None)?);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+    } else if sessions.is_empty() {
+        println!("No sessions in the history; start one with `codex-usage session start <NAME>`");
+    } else {
+        for session in &sessions {
+            println!("{}", session.describe(now));
         }
     }
+    Ok(())
 }
 
-// ─── Entry points ─────────────────────────────────────────────────────────────
+fn shell_init(args: &Args) -> Result<()> {
+    let Some(shell) = &args.shell else {
+        anyhow::bail!("shell-init needs a shell: bash, zsh or fish");
+    };
+    let parts = shell::Parts {
+        prompt: !args.no_prompt,
+        after_codex: args.after_codex,
+    };
+    print!("{}", shell::init(shell.parse()?, parts));
+    Ok(())
+}
 
-fn main() {
-    if let Err(e) = run() {
-        eprintln!("\n  {} {}\n", "Error:".red().bold(), e);
-        std::process::exit(1);
+/// The cache as shell assignments, fetching only when it has nothing (or
+/// nothing within --max-age)
+fn env(args: &Args) -> Result<()> {
+    let shell = args.shell.as_deref().map(str::parse).transpose()?;
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let profile = args.profile.as_deref().unwrap_or(config::DEFAULT_PROFILE);
+    let cached = match args.max_age {
+        Some(secs) => cache::fresh(profile, chrono::Duration::seconds(secs as i64), clock.now()),
+        None => cache::read(profile).ok().flatten(),
+    };
+    let snap = match cached {
+        Some(snap) => snap,
+        None => fetch_snapshot(args, &clock)?,
+    };
+    print!("{}", shell::env(shell, &snap, clock.now()));
+    Ok(())
+}
+
+/// One stable line on stdout for agent wrappers; the why on stderr
+fn advise(args: &Args) -> Result<()> {
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let limits = advise::Thresholds::from_config(&Config::load()?.advise);
+    let snap = fetch_snapshot(args, &clock)?;
+    let now = clock.now();
+    // Projection is best-effort; no history just means no projection
+    let recent =
+        history::load(Some(now - advise::PROJECTION_WINDOW), Some(now)).unwrap_or_default();
+    let advice = advise::advise(&snap, &recent, limits, now);
+    println!("{} {}", advice.verdict.as_str(), advice.wait_seconds);
+    eprintln!("{}", advice.reason);
+    Ok(())
+}
+
+fn explain(args: &Args) -> Result<()> {
+    print!("{}", errors::explain(args.code.as_deref())?);
+    Ok(())
+}
+
+fn meta_stats(args: &Args) -> Result<()> {
+    let counts = stats::load()?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&counts)?);
+    } else {
+        let today = Clock::from_override(args.now.as_deref())?
+            .now()
+            .date_naive();
+        print!("{}", stats::render(&counts, stats::enabled(), today));
     }
+    Ok(())
 }
 
-fn run() -> Result<()> {
-    let plain = std::env::args().any(|a| a == "--plain" || a == "-p");
+fn config_env_docs(args: &Args) -> Result<()> {
+    match args.json {
+        true => println!("{}", serde_json::to_string_pretty(envvars::VARS)?),
+        false => print!("{}", envvars::markdown()),
+    }
+    Ok(())
+}
 
-    if !plain {
-        println!();
-        print!("  {} Fetching usage data... ", "◆".cyan());
-        // flush so the user sees it immediately
-        use std::io::Write;
-        let _ = std::io::stdout().flush();
-    }
-
-    let creds = get_credentials()?;
-    let usage = fetch_usage(&creds)?;
-
-    // Extract windows
-    let rl = usage.rate_limit.as_ref();
-    let primary = rl.and_then(|r| r.primary_window.as_ref());
-    let secondary = rl.and_then(|r| r.secondary_window.as_ref());
-    let limit_reached = rl.and_then(|r| r.limit_reached).unwrap_or(false);
-
-    // ── Plain output ──────────────────────────────────────────────────────────
-    if plain {
-        let plan = usage.plan_type.as_deref().unwrap_or("unknown");
-        println!("Plan: {}", plan.to_uppercase());
-        print_window_plain("5hr window", &rl.and_then(|r| r.primary_window.clone()));
-        print_window_plain("7day window", &rl.and_then(|r| r.secondary_window.clone()));
-        if limit_reached {
-            println!("Status: LIMIT REACHED");
+/// `--interval` and `--adaptive` for `--watch` and `tui`
+fn pacing(args: &Args) -> pacing::Pacing {
+    pacing::Pacing {
+        base: std::time::Duration::from_secs(args.interval.unwrap_or(watch::DEFAULT_INTERVAL)),
+        adaptive: args.adaptive,
+    }
+}
+
+/// The config for `serve` and `daemon`, with `--adaptive` laid over it
+fn server_config(args: &Args) -> Result<Config> {
+    let mut config = Config::load()?;
+    config.server.adaptive |= args.adaptive;
+    Ok(config)
+}
+
+fn tui(args: &Args) -> Result<()> {
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let profile = args.profile.as_deref().unwrap_or(config::DEFAULT_PROFILE);
+    let mut profiles = vec![profile];
+    if let Some(other) = args.compare.as_deref() {
+        if other == profile {
+            anyhow::bail!("--compare {other} is the profile already shown; name another");
         }
-        return Ok(());
+        // Check it exists before the first fetch spends a request
+        Config::load()?.profile(other)?;
+        profiles.push(other);
     }
+    let since = clock.now() - chrono::Duration::hours(tui::REACH_HOURS);
+    // Only the chart's backfill; it fills in from the fetches without it
+    let history = history::load(Some(since), None).unwrap_or_default();
+    tui::run(
+        &profiles,
+        pacing(args),
+        || clock.now(),
+        history,
+        |name| fetch_profile(args, &clock, Some(name)),
+    )
+}
 
-    // ── Fancy output ──────────────────────────────────────────────────────────
-    // Clear the "fetching" line
-    print!("\r{}\r", " ".repeat(55));
+fn tray(args: &Args) -> Result<()> {
+    let clock = Clock::from_override(args.now.as_deref())?;
+    tray::run(
+        args.profile.as_deref(),
+        pacing(args),
+        move || clock.now(),
+        || fetch_snapshot(args, &clock),
+    )
+}
 
-    let plan = usage
-        .plan_type
-        .as_deref()
-        .unwrap_or("unknown")
-        .to_uppercase();
-
-    println!(
-        "  {} OpenAI {} Plan — Codex Usage Limits",
-        "◆".cyan().bold(),
-        plan.yellow().bold()
-    );
-    println!("  {}", "─".repeat(67).dimmed());
-
-    let bar_width = 28;
-    print_window_fancy(
-        "5-hour session",
-        &rl.and_then(|r| r.primary_window.clone()),
-        bar_width,
-    );
-    print_window_fancy(
-        "7-day rolling",
-        &rl.and_then(|r| r.secondary_window.clone()),
-        bar_width,
-    );
-
-    println!("  {}", "─".repeat(67).dimmed());
-
-    // Summary hint
-    let highest = [primary, secondary]
-        .iter()
-        .filter_map(|w| w.map(|w| w.used_percent.unwrap_or(0.0)))
-        .fold(0.0_f64, f64::max);
-
-    if limit_reached || highest >= 100.0 {
-        println!(
-            "\n  {} Limit reached — check your reset time above.",
-            "✗".red().bold()
-        );
-    } else if highest >= 90.0 {
-        println!(
-            "\n  {} Nearly at your limit — check reset time above.",
-            "⚠".red().bold()
-        );
-    } else if highest >= 70.0 {
-        println!(
-            "\n  {} Usage is elevated — consider pacing your session.",
-            "△".yellow()
+fn record(args: &Args) -> Result<()> {
+    if args.no_history || !history::enabled() {
+        anyhow::bail!("`record` only writes history, which is turned off (--no-history or CODEX_USAGE_HISTORY)");
+    }
+    let clock = Clock::from_override(args.now.as_deref())?;
+    let every = args.interval.unwrap_or(record::DEFAULT_INTERVAL);
+    if !args.quiet {
+        eprintln!(
+            "codex-usage recording every {} to {}",
+            status::age(chrono::Duration::seconds(every as i64)),
+            history::path().display()
         );
+    }
+    record::run(
+        std::time::Duration::from_secs(every),
+        args.quiet,
+        || clock.now(),
+        || fetch_snapshot(args, &clock),
+    )
+}
+
+fn notify_test(args: &Args) -> Result<()> {
+    let config = Config::load()?;
+    let names = config.profile_names();
+    let mut alerts = alerting::configure(&config.alerting, &names.iter().collect::<Vec<_>>())?;
+    if let Some(profile) = &args.profile {
+        alerts.retain(|a| &a.profile == profile);
+    }
+    let deliveries = alerting::test(&alerts, args.channel.as_deref())?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&deliveries)?);
     } else {
-        println!(
-            "\n  {} Looking good — plenty of capacity remaining.",
-            "✓".green()
-        );
+        print!("{}", alerting::render_test(&deliveries));
     }
+    alerting::ensure_delivered(&deliveries)
+}
 
-    println!();
+fn status(args: &Args) -> Result<()> {
+    let now = Clock::from_override(args.now.as_deref())?.now();
+    let status = status::gather(args.profile.as_deref(), now)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else {
+        print!("{}", status::render(&status, now));
+    }
     Ok(())
 }
@@ -0,0 +1,102 @@
+//! `--adaptive` (or `[server] adaptive`): how long to wait before the next
+//! poll, for `--watch`, `tui` and the server's pollers. The interval is a
+//! base that stretches while every window is comfortably low and shrinks as
+//! one fills, backs right off while the limit is reached, and never runs
+//! past a window's reset, so the poll that sees capacity return comes just
+//! after it.
+
+use crate::api::Snapshot;
+use crate::render::{window_rows, Level};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Below this in every window, polls slow to twice the base
+const LOW_PERCENT: f64 = 50.0;
+
+/// How much longer than the base to wait while the limit is reached, the
+/// longest adaptive wait
+pub const LIMIT_BACKOFF: u32 = 10;
+
+/// Left after a reset before polling, so the API has rolled the window over
+const RESET_GRACE: Duration = Duration::from_secs(5);
+
+/// Shortest wait; never shorter than the base either
+const FLOOR: Duration = Duration::from_secs(crate::watch::MIN_INTERVAL);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pacing {
+    pub base: Duration,
+    pub adaptive: bool,
+}
+
+impl Pacing {
+    /// The wait before the poll after `latest`; the base when there is
+    /// nothing current to go on, such as after a failed poll
+    pub fn after(&self, latest: Option<&Snapshot>, now: DateTime<Utc>) -> Duration {
+        let Some(snap) = latest.filter(|_| self.adaptive) else {
+            return self.base;
+        };
+        let rows = window_rows(snap, now);
+        let fullest = rows
+            .iter()
+            .filter_map(|r| r.window.as_ref()?.used_percent)
+            .fold(0.0, f64::max);
+        let limit_reached = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .is_some_and(|r| r.limit_reached == Some(true));
+
+        let wait = match Level::of(fullest) {
+            _ if limit_reached => self.base * LIMIT_BACKOFF,
+            Level::Critical => self.base / 4,
+            Level::Warning => self.base / 2,
+            Level::Normal if fullest < LOW_PERCENT => self.base * 2,
+            Level::Normal => self.base,
+        };
+        let wait = match rows.iter().filter_map(|r| r.resets_in).min() {
+            Some(secs) => wait.min(Duration::from_secs(secs) + RESET_GRACE),
+            None => wait,
+        };
+        wait.max(FLOOR.min(self.base))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(pct: f64, reset: u64, now: DateTime<Utc>) -> Snapshot {
+        Snapshot::plus((pct, reset), (20.0, 5 * 86400), now)
+    }
+
+    #[test]
+    fn waits_follow_usage_and_resets() {
+        let now = Utc::now();
+        let pacing = Pacing {
+            base: Duration::from_secs(60),
+            adaptive: true,
+        };
+        let after = |pct, reset| {
+            pacing
+                .after(Some(&snapshot(pct, reset, now)), now)
+                .as_secs()
+        };
+        assert_eq!(after(10.0, 9000), 120);
+        assert_eq!(after(60.0, 9000), 60);
+        assert_eq!(after(75.0, 9000), 30);
+        assert_eq!(after(95.0, 9000), 15);
+        assert_eq!(after(100.0, 9000), 600);
+        // At the limit, the poll after the reset still comes promptly
+        assert_eq!(after(100.0, 100), 105);
+        assert_eq!(after(30.0, 1), 10);
+
+        assert_eq!(pacing.after(None, now).as_secs(), 60);
+        let fixed = Pacing {
+            adaptive: false,
+            ..pacing
+        };
+        let snap = snapshot(95.0, 9000, now);
+        assert_eq!(fixed.after(Some(&snap), now).as_secs(), 60);
+    }
+}
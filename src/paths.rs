@@ -0,0 +1,223 @@
+//! Where codex-usage keeps its own files (never the Codex CLI's).
+//!
+//! Hardened containers often mount home read-only. Rather than fail on the
+//! first write, the data directory falls back to $XDG_STATE_HOME, then the
+//! temp directory, and as a last resort to keeping nothing at all: every
+//! write is best-effort, so a run still works, it just doesn't remember.
+//!
+//! The temp directory is shared, so a directory there is made 0700 and only
+//! used while this user owns it and no one else can get in: anyone could
+//! have created `codex-usage-$USER` first.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+struct DataDir {
+    dir: PathBuf,
+    /// Set when the preferred directory wasn't writable
+    fallback: Option<String>,
+}
+
+static DATA_DIR: OnceLock<DataDir> = OnceLock::new();
+
+/// Data directory: $XDG_DATA_HOME/codex-usage, else
+/// ~/.local/share/codex-usage — or the first writable fallback, chosen once
+/// per run with a warning on stderr
+pub fn data_dir() -> PathBuf {
+    resolved().dir.clone()
+}
+
+/// Why the data directory isn't the usual one, if it isn't
+pub fn data_fallback() -> Option<&'static str> {
+    resolved().fallback.as_deref()
+}
+
+fn resolved() -> &'static DataDir {
+    DATA_DIR.get_or_init(|| {
+        let env = |k: &str| std::env::var_os(k).filter(|v| !v.is_empty());
+        let candidates = data_candidates(env);
+        let preferred = candidates[0].clone();
+        let resolved = match candidates.iter().position(|d| usable(d)) {
+            Some(0) => DataDir {
+                dir: preferred,
+                fallback: None,
+            },
+            Some(i) => DataDir {
+                fallback: Some(format!(
+                    "{} isn't writable; using {}",
+                    preferred.display(),
+                    candidates[i].display()
+                )),
+                dir: candidates[i].clone(),
+            },
+            None => DataDir {
+                fallback: Some(format!(
+                    "{} isn't writable and neither is any fallback; nothing is \
+                     saved (history, cache, stats)",
+                    preferred.display()
+                )),
+                dir: preferred,
+            },
+        };
+        if let Some(why) = &resolved.fallback {
+            eprintln!("warning: {why}");
+        }
+        resolved
+    })
+}
+
+/// Where data may go, best first: the XDG data dir, the XDG state dir, then
+/// a per-user directory under the temp dir
+fn data_candidates(env: impl Fn(&str) -> Option<std::ffi::OsString>) -> Vec<PathBuf> {
+    let home = PathBuf::from(env("HOME").unwrap_or_default());
+    let data = match env("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home.join(".local").join("share"),
+    };
+    let state = match env("XDG_STATE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home.join(".local").join("state"),
+    };
+    let user = env("USER")
+        .or_else(|| env("LOGNAME"))
+        .map(|u| format!("codex-usage-{}", u.to_string_lossy()))
+        .unwrap_or_else(|| "codex-usage".into());
+    vec![
+        data.join("codex-usage"),
+        state.join("codex-usage"),
+        std::env::temp_dir().join(user),
+    ]
+}
+
+/// Whether data can go in `dir`: a directory directly under the shared
+/// temp dir must also be private
+fn usable(dir: &Path) -> bool {
+    let shared = dir.parent() == Some(std::env::temp_dir().as_path());
+    match shared {
+        true => private(dir),
+        false => writable(dir),
+    }
+}
+
+/// Whether files can be created in `dir`, creating it if needed; making it
+/// is proof enough. One that exists is asked about rather than written to,
+/// since a read-only mount still shows writable permission bits.
+fn writable(dir: &Path) -> bool {
+    if dir.is_dir() {
+        return may_write(dir);
+    }
+    std::fs::create_dir_all(dir).is_ok()
+}
+
+#[cfg(unix)]
+fn may_write(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // Unlike the mode bits, access(2) also fails on a read-only filesystem.
+    // SAFETY: the path is a valid NUL-terminated string for the call
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// Windows has no read-only mounts to speak of, so creating a file is the
+/// only check there is
+#[cfg(not(unix))]
+fn may_write(dir: &Path) -> bool {
+    let probe = dir.join(format!(".probe.{}", std::process::id()));
+    let ok = std::fs::File::create(&probe).is_ok();
+    let _ = std::fs::remove_file(&probe);
+    ok
+}
+
+/// `dir` made 0700 if it's missing, and whether it is then a real
+/// directory this user owns that no one else can read or write
+#[cfg(unix)]
+fn private(dir: &Path) -> bool {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    let _ = std::fs::DirBuilder::new().mode(0o700).create(dir);
+    let Ok(meta) = std::fs::symlink_metadata(dir) else {
+        return false;
+    };
+    // SAFETY: getuid takes nothing and can't fail
+    let uid = unsafe { libc::getuid() };
+    meta.is_dir() && meta.uid() == uid && meta.mode() & 0o077 == 0 && may_write(dir)
+}
+
+/// The temp dir is already per user on Windows
+#[cfg(not(unix))]
+fn private(dir: &Path) -> bool {
+    writable(dir)
+}
+
+/// Config directory: $XDG_CONFIG_HOME/codex-usage, else ~/.config/codex-usage
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir).join("codex-usage");
+    }
+    home().join(".config").join("codex-usage")
+}
+
+/// $HOME, or an empty path if it is unset
+pub fn home() -> PathBuf {
+    PathBuf::from(std::env::var_os("HOME").unwrap_or_default())
+}
+
+/// Expand a leading `~/` against $HOME
+pub fn expand_tilde(path: &std::path::Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_state_then_temp() {
+        let env = |k: &str| match k {
+            "HOME" => Some("/home/ada".into()),
+            "XDG_STATE_HOME" => Some("/run/state".into()),
+            "USER" => Some("ada".into()),
+            _ => None,
+        };
+        let dirs = data_candidates(env);
+        assert_eq!(dirs[0], Path::new("/home/ada/.local/share/codex-usage"));
+        assert_eq!(dirs[1], Path::new("/run/state/codex-usage"));
+        assert_eq!(dirs[2], std::env::temp_dir().join("codex-usage-ada"));
+
+        // A path under a regular file can never be created
+        let file = std::env::temp_dir().join(format!("codex-usage-test.{}", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        assert!(!writable(&file.join("data")));
+        assert!(writable(&std::env::temp_dir()));
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shared_temp_dirs_must_be_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("codex-usage-test-{}", std::process::id()));
+        let mode = |d: &Path| std::fs::metadata(d).unwrap().permissions().mode() & 0o777;
+        assert!(usable(&dir));
+        assert_eq!(mode(&dir), 0o700);
+
+        // Opened up, say by someone else who made it first
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(!usable(&dir));
+        let _ = std::fs::remove_dir(&dir);
+
+        // Nor through a link to somewhere else
+        let target =
+            std::env::temp_dir().join(format!("codex-usage-elsewhere-{}", std::process::id()));
+        std::fs::create_dir(&target).unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o700)).unwrap();
+        std::os::unix::fs::symlink(&target, &dir).unwrap();
+        assert!(!usable(&dir));
+        let _ = std::fs::remove_file(&dir);
+        let _ = std::fs::remove_dir(&target);
+    }
+}
@@ -0,0 +1,74 @@
+//! Plan names for people. The API reports plans as raw codes (`plus`,
+//! `business`, `edu`, …) that change as OpenAI adds plans; shown uppercased
+//! they read as codes. Known plans get their product name, unknown ones are
+//! title-cased from the code, and `[plans]` in the config file overrides
+//! either, e.g. to follow a renamed plan before a release catches up or to
+//! show names in another language. Machine formats keep the raw code.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Product names for the codes seen so far
+const NAMES: &[(&str, &str)] = &[
+    ("free", "Free"),
+    ("go", "Go"),
+    ("plus", "Plus"),
+    ("pro", "Pro"),
+    ("team", "Team"),
+    ("business", "Business"),
+    ("enterprise", "Enterprise"),
+    ("edu", "Edu"),
+    ("education", "Edu"),
+];
+
+static OVERRIDES: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Use `[plans]` from the config file for the rest of the run
+pub fn init(overrides: &BTreeMap<String, String>) {
+    let lowered = overrides
+        .iter()
+        .map(|(k, v)| (k.to_ascii_lowercase(), v.clone()))
+        .collect();
+    let _ = OVERRIDES.set(lowered);
+}
+
+/// What to call plan `raw`
+pub fn display(raw: &str) -> String {
+    name(raw, OVERRIDES.get())
+}
+
+fn name(raw: &str, overrides: Option<&BTreeMap<String, String>>) -> String {
+    let key = raw.trim().to_ascii_lowercase();
+    if let Some(name) = overrides.and_then(|o| o.get(&key)) {
+        return name.clone();
+    }
+    if let Some((_, name)) = NAMES.iter().find(|(code, _)| *code == key) {
+        return name.to_string();
+    }
+    key.split(['_', '-', ' '])
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_unknown_and_overridden() {
+        assert_eq!(display("plus"), "Plus");
+        assert_eq!(display("EDU"), "Edu");
+        assert_eq!(display("chatgpt_business-trial"), "Chatgpt Business Trial");
+        let overrides = BTreeMap::from([("pro".to_string(), "Pro (200)".to_string())]);
+        assert_eq!(name("PRO", Some(&overrides)), "Pro (200)");
+        assert_eq!(name("plus", Some(&overrides)), "Plus");
+    }
+}
@@ -0,0 +1,99 @@
+//! The spinner shown while a request runs. Drawn only when stdout is a
+//! terminal (see `term`) and redrawn in place from a background thread; once
+//! the request is slow it shows the time waited so far. Dropping a `Progress`
+//! erases the line, so an error never prints after a stale "Fetching…".
+
+use crate::term;
+use colored::Colorize;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Time between redraws
+const TICK: Duration = Duration::from_millis(100);
+
+/// Show the elapsed time once a request has taken this long
+const SLOW_AFTER: Duration = Duration::from_secs(2);
+
+pub struct Progress {
+    spinner: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl Progress {
+    /// Spin next to `message` until finished, if stdout is a terminal
+    pub fn start(message: &str) -> Self {
+        if !term::capability().tty {
+            return Self::hidden();
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let message = message.to_string();
+        let handle = std::thread::spawn({
+            let done = done.clone();
+            move || {
+                let started = Instant::now();
+                for frame in FRAMES.iter().cycle() {
+                    if done.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    print!("\r\x1b[2K{}", line(frame, &message, started.elapsed()));
+                    let _ = std::io::stdout().flush();
+                    std::thread::sleep(TICK);
+                }
+            }
+        });
+        Progress {
+            spinner: Some((done, handle)),
+        }
+    }
+
+    /// A progress indicator that shows nothing
+    pub fn hidden() -> Self {
+        Progress { spinner: None }
+    }
+
+    /// Stop and erase the spinner, ready for the real output
+    pub fn finish(self) {}
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some((done, handle)) = self.spinner.take() {
+            done.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+            // Back to column 0, then erase the whole line
+            print!("\r\x1b[2K");
+            let _ = std::io::stdout().flush();
+        }
+    }
+}
+
+/// One frame of the spinner line
+fn line(frame: &str, message: &str, elapsed: Duration) -> String {
+    let waited = match elapsed >= SLOW_AFTER {
+        true => format!(" {}", format!("({}s)", elapsed.as_secs()).dimmed()),
+        false => String::new(),
+    };
+    format!("  {} {message}…{waited}", frame.cyan())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_only_when_slow() {
+        colored::control::set_override(false);
+        assert_eq!(
+            line("⠋", "Fetching", Duration::from_millis(300)),
+            "  ⠋ Fetching…"
+        );
+        assert_eq!(
+            line("⠙", "Fetching", Duration::from_secs(7)),
+            "  ⠙ Fetching… (7s)"
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! `record`: a sampler meant to run under systemd or launchd, fetching every
+//! `--interval` seconds (15 minutes by default) and appending each sample to
+//! the history, so trends, forecasts and exports have a steady series to
+//! work from whether or not anyone runs a report. Samples land on the clock
+//! (:00, :15, :30, …) so series from several machines line up.
+//!
+//! A failed fetch is logged and the sampler carries on: a network that comes
+//! back or a token refreshed by `codex login` fixes the next one.
+
+use crate::api::Snapshot;
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use std::time::Duration;
+
+/// Seconds between samples when `--interval` isn't given
+pub const DEFAULT_INTERVAL: u64 = 900;
+
+/// Fetch, which records the sample, at each slot from now on. Never returns
+/// unless stopped from outside, which systemd and launchd do by signal.
+pub fn run(
+    every: Duration,
+    quiet: bool,
+    now: impl Fn() -> DateTime<Utc>,
+    mut fetch: impl FnMut() -> Result<Snapshot>,
+) -> Result<()> {
+    loop {
+        match fetch() {
+            Ok(snap) if !quiet => eprintln!("record: sampled at {}", snap.fetched_at.to_rfc3339()),
+            Ok(_) => {}
+            Err(e) => eprintln!("record: {e:#}"),
+        }
+        let at = now();
+        let wait = (next_slot(at, every) - at).to_std().unwrap_or_default();
+        std::thread::sleep(wait);
+    }
+}
+
+/// The first multiple of `every` since the epoch after `now`, so samples
+/// land on round times rather than drifting with each fetch's latency
+fn next_slot(now: DateTime<Utc>, every: Duration) -> DateTime<Utc> {
+    let every = every.as_secs().max(1) as i64;
+    let next = (now.timestamp().div_euclid(every) + 1) * every;
+    Utc.timestamp_opt(next, 0).single().unwrap_or(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_land_on_round_times() {
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let quarter = Duration::from_secs(900);
+        assert_eq!(
+            next_slot(at("2024-05-01T10:07:31Z"), quarter),
+            at("2024-05-01T10:15:00Z")
+        );
+        // Exactly on a slot: the next one, not the same again
+        assert_eq!(
+            next_slot(at("2024-05-01T10:15:00Z"), quarter),
+            at("2024-05-01T10:30:00Z")
+        );
+        assert_eq!(
+            next_slot(at("2024-05-01T23:59:59Z"), Duration::from_secs(3600)),
+            at("2024-05-02T00:00:00Z")
+        );
+    }
+}
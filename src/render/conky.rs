@@ -0,0 +1,41 @@
+//! `--format conky`: both main windows with conky colour variables around
+//! each figure, `5h ${color green}42%${color} 7d ${color green}12%${color}`,
+//! for `execpi`. Each figure changes colour where the fancy view's bars do.
+
+use super::{window_rows, Level, Render};
+use crate::api::Snapshot;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+pub struct Conky;
+
+impl Render for Conky {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let figures: Vec<String> = window_rows(snap, now)
+            .iter()
+            .filter(|row| row.feature.is_none())
+            .take(2)
+            .map(
+                |row| match row.window.as_ref().and_then(|w| w.used_percent) {
+                    Some(pct) => format!(
+                        "{} ${{color {}}}{}%${{color}}",
+                        row.short_span(),
+                        colour(Level::of(pct)),
+                        pct.round()
+                    ),
+                    None => format!("{} —", row.short_span()),
+                },
+            )
+            .collect();
+        let _ = writeln!(out, "{}", figures.join(" "));
+    }
+}
+
+/// Colour names conky knows without a palette entry
+fn colour(level: Level) -> &'static str {
+    match level {
+        Level::Normal => "green",
+        Level::Warning => "yellow",
+        Level::Critical => "red",
+    }
+}
@@ -0,0 +1,82 @@
+//! `--format eww`: one flat JSON object for an eww `defpoll`, every value
+//! ready to bind — fractions for `progress` and `scale`, hex colours, and
+//! countdowns already written out — so the widget needs no string munging.
+
+use super::{Level, Render};
+use crate::api::{RateWindow, Snapshot};
+use crate::plans;
+use crate::status::age;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+pub struct Eww;
+
+#[derive(Serialize)]
+struct Widget {
+    plan: String,
+    limit_reached: bool,
+    /// The worse window's colour, red once the limit is reached
+    color: String,
+    five_hour_percent: f64,
+    /// 0.0–1.0
+    five_hour_fraction: f64,
+    five_hour_color: String,
+    /// `2h 13m`, or empty when unknown
+    five_hour_resets_in: String,
+    seven_day_percent: f64,
+    seven_day_fraction: f64,
+    seven_day_color: String,
+    seven_day_resets_in: String,
+}
+
+impl Render for Eww {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let rl = snap.usage.rate_limit.as_ref();
+        let reached = rl.is_some_and(|r| r.limit_reached == Some(true));
+        let pct = |w: Option<&RateWindow>| {
+            w.and_then(|w| w.used_percent)
+                .unwrap_or(0.0)
+                .clamp(0.0, 100.0)
+        };
+        let resets_in = |w: Option<&RateWindow>| {
+            w.and_then(|w| w.resets_in(snap.fetched_at, now))
+                .map(|secs| age(chrono::Duration::seconds(secs as i64)))
+                .unwrap_or_default()
+        };
+        let five = rl.and_then(|r| r.primary_window.as_ref());
+        let seven = rl.and_then(|r| r.secondary_window.as_ref());
+        let worst = match reached {
+            true => Level::Critical,
+            false => Level::of(pct(five).max(pct(seven))),
+        };
+
+        let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+        let widget = Widget {
+            plan: plans::display(plan),
+            limit_reached: reached,
+            color: hex(worst),
+            five_hour_percent: pct(five),
+            five_hour_fraction: fraction(pct(five)),
+            five_hour_color: hex(Level::of(pct(five))),
+            five_hour_resets_in: resets_in(five),
+            seven_day_percent: pct(seven),
+            seven_day_fraction: fraction(pct(seven)),
+            seven_day_color: hex(Level::of(pct(seven))),
+            seven_day_resets_in: resets_in(seven),
+        };
+        // A struct of strings, numbers and bools always serialises
+        out.push_str(&serde_json::to_string(&widget).unwrap_or_default());
+        out.push('\n');
+    }
+}
+
+/// `pct` as 0.0–1.0, to four places so `71.2` is `0.712` and not a float's
+/// nearest neighbour
+fn fraction(pct: f64) -> f64 {
+    (pct * 100.0).round() / 10_000.0
+}
+
+fn hex(level: Level) -> String {
+    let (r, g, b) = level.rgb();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
@@ -0,0 +1,342 @@
+//! The default coloured report: header, one bar per window, and a summary hint.
+
+use super::layout::{display_width, fit_width, truncate_to_width};
+use super::{
+    feature_title, window_rows, Level, Options, Render, WindowRow, CRIT_PERCENT, WARN_PERCENT,
+};
+use crate::api::{RateLimit, RateWindow, Snapshot, Span, Unit};
+use crate::locale::Locale;
+use crate::plans;
+use crate::term::ColorLevel;
+use crate::week::WorkWeek;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use colored::Colorize;
+use std::fmt::Write;
+
+/// Columns reserved for window labels
+const LABEL_WIDTH: usize = 18;
+
+/// Minimum width of the horizontal rules
+const RULE_WIDTH: usize = 67;
+
+/// Columns taken by the header around the plan name:
+/// `◆ OpenAI  Plan — Codex Usage Limits`
+const TITLE_WIDTH: usize = 35;
+
+/// Widest plan name shown in the header before it gets truncated
+const PLAN_NAME_WIDTH: usize = 32;
+
+/// Width of the usage bars, in columns
+const BAR_WIDTH: usize = 28;
+
+/// Bar fill by severity with `--patterns`: solid, striped, cross-hatched
+const PATTERN_FILLS: [&str; 3] = ["█", "▤", "▦"];
+
+/// The `--pace` bar: time passed, then time left
+const PACE_FILLS: [&str; 2] = ["━", "─"];
+
+#[derive(Default)]
+pub struct Fancy {
+    pub opts: Options,
+}
+
+impl Render for Fancy {
+    fn shows_progress(&self) -> bool {
+        true
+    }
+
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let usage = &snap.usage;
+        let resets_in = |w: &RateWindow| w.resets_in(snap.fetched_at, now);
+        let reset = |secs| format_reset(secs, now, self.opts.reset_clock);
+        let rl = usage.rate_limit.as_ref();
+        let secondary = rl.and_then(|r| r.secondary_window.as_ref());
+        let limit_reached = rl.and_then(|r| r.limit_reached).unwrap_or(false);
+        let soft_limited = rl.is_some_and(|r| r.soft_limited());
+
+        let plan = truncate_to_width(
+            &plans::display(usage.plan_type.as_deref().unwrap_or("unknown")),
+            PLAN_NAME_WIDTH,
+        );
+
+        // Which account this is, for people with a personal and a work login
+        let workspace = snap
+            .workspace
+            .as_ref()
+            .and_then(|w| w.describe())
+            .map(|w| format!(" · {}", truncate_to_width(&w, PLAN_NAME_WIDTH)))
+            .unwrap_or_default();
+
+        let title_width = display_width(&plan) + display_width(&workspace) + TITLE_WIDTH;
+        let rule = "─".repeat(RULE_WIDTH.max(title_width));
+        let _ = writeln!(
+            out,
+            "  {} OpenAI {} Plan — Codex Usage Limits{}",
+            "◆".cyan().bold(),
+            plan.yellow().bold(),
+            workspace.dimmed()
+        );
+        let _ = writeln!(out, "  {}", rule.dimmed());
+
+        let locale = &self.opts.locale;
+        let (main, feature_rows): (Vec<_>, Vec<_>) = window_rows(snap, now)
+            .into_iter()
+            .partition(|row| row.feature.is_none());
+        for row in &main {
+            write_window(out, row, &self.opts, now);
+        }
+        if let Some(week) = &self.opts.week {
+            write_work_week(out, week, secondary, snap.fetched_at, now, locale);
+        }
+        for row in &feature_rows {
+            write_window(out, row, &self.opts, now);
+        }
+
+        let _ = writeln!(out, "  {}", rule.dimmed());
+
+        let highest = rl.map_or(0.0, |r| r.highest());
+        let features = usage.feature_limits();
+        match soft_limited {
+            true => write_soft_limit(out),
+            false => write_summary(out, limit_reached, highest),
+        }
+        for (name, rl) in &features {
+            write_feature_alert(out, name, rl, &resets_in, &reset);
+        }
+        if let Some(updated) = super::updated(snap, now) {
+            let _ = writeln!(out, "\n  {}", format!("updated {updated}").dimmed());
+        }
+        if let Some(why) = &self.opts.color.notice {
+            let _ = writeln!(out, "\n  Colour off ({why}); bar patterns show severity.");
+        }
+
+        out.push('\n');
+    }
+}
+
+/// `5-hour session`, `7-day rolling`, `Code review 7d`, …
+pub(crate) fn label(row: &WindowRow) -> String {
+    if let Some(feature) = &row.feature {
+        return format!("{} {}", feature_title(feature), row.short_span());
+    }
+    match row.span {
+        Some(Span {
+            count,
+            unit: Unit::Day,
+        }) => format!("{count}-day rolling"),
+        Some(span) => format!("{} session", span.adjective()),
+        None => format!("{} window", feature_title(&row.name)),
+    }
+}
+
+fn write_window(out: &mut String, row: &WindowRow, opts: &Options, now: DateTime<Utc>) {
+    let label = fit_width(&label(row), LABEL_WIDTH);
+    match &row.window {
+        None => {
+            let _ = writeln!(out, "  {} {}", label, "not available".dimmed());
+        }
+        Some(w) => {
+            let pct_used = w.used_percent.unwrap_or(0.0).min(100.0);
+            let bar = usage_bar(pct_used, BAR_WIDTH, opts);
+            let pct_str = severity(
+                format!("{:>5}%", opts.locale.num(pct_used, 1)),
+                pct_used,
+                opts.color.color,
+            );
+            let _ = writeln!(
+                out,
+                "  {} {} {} resets {}",
+                label.bold(),
+                bar,
+                pct_str,
+                format_reset(row.resets_in, now, opts.reset_clock)
+            );
+            if opts.pace {
+                write_pace(out, row, w, &opts.locale);
+            }
+        }
+    }
+}
+
+/// A thin bar under a window's usage bar for how much of the window has
+/// passed, so being ahead of or behind pace shows at a glance. Left out
+/// when the window's length or reset isn't known.
+fn write_pace(out: &mut String, row: &WindowRow, w: &RateWindow, locale: &Locale) {
+    let (Some(length), Some(left)) = (w.length(&row.name), row.resets_in) else {
+        return;
+    };
+    let elapsed = 100.0 * length.saturating_sub(left) as f64 / length as f64;
+    let filled = ((elapsed / 100.0) * BAR_WIDTH as f64).round() as usize;
+    let filled = filled.min(BAR_WIDTH);
+    let _ = writeln!(
+        out,
+        "  {} {}{} {}",
+        " ".repeat(LABEL_WIDTH),
+        PACE_FILLS[0].repeat(filled).cyan(),
+        PACE_FILLS[1].repeat(BAR_WIDTH - filled).dimmed(),
+        format!("{:>5}% elapsed", locale.num(elapsed, 1)).dimmed()
+    );
+}
+
+/// How far through the calendar work week we are, and what the weekly budget
+/// left allows per remaining work day
+fn write_work_week(
+    out: &mut String,
+    week: &WorkWeek,
+    weekly: Option<&RateWindow>,
+    fetched_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    locale: &Locale,
+) {
+    let v = week.view(weekly, fetched_at, now);
+    let filled = ((v.elapsed / 100.0) * BAR_WIDTH as f64).round() as usize;
+    let bar = format!(
+        "{}{}",
+        "█".repeat(filled.min(BAR_WIDTH)),
+        "░".repeat(BAR_WIDTH - filled.min(BAR_WIDTH))
+    );
+    let _ = writeln!(
+        out,
+        "  {} {} {:>5}% elapsed {}",
+        fit_width("Work week", LABEL_WIDTH).bold(),
+        bar.cyan(),
+        locale.num(v.elapsed, 1),
+        format!("({})", week.label()).dimmed()
+    );
+    let budget = match (v.days_left, v.per_day) {
+        (Some(days), Some(per_day)) => format!(
+            "{}% of the weekly budget per work day · {} work days until reset",
+            locale.num(per_day, 1),
+            locale.num(days, 1)
+        ),
+        (Some(_), None) => "No work days left before the weekly reset".to_string(),
+        _ => "Weekly reset time unknown".to_string(),
+    };
+    let _ = writeln!(out, "  {} {}", " ".repeat(LABEL_WIDTH), budget.dimmed());
+}
+
+fn write_summary(out: &mut String, limit_reached: bool, highest: f64) {
+    if limit_reached || highest >= 100.0 {
+        let _ = writeln!(
+            out,
+            "\n  {} Limit reached — check your reset time above.",
+            "✗".red().bold()
+        );
+    } else if highest >= CRIT_PERCENT {
+        let _ = writeln!(
+            out,
+            "\n  {} Nearly at your limit — check reset time above.",
+            "⚠".red().bold()
+        );
+    } else if highest >= WARN_PERCENT {
+        let _ = writeln!(
+            out,
+            "\n  {} Usage is elevated — consider pacing your session.",
+            "△".yellow()
+        );
+    } else {
+        let _ = writeln!(
+            out,
+            "\n  {} Looking good — plenty of capacity remaining.",
+            "✓".green()
+        );
+    }
+}
+
+/// A line under the summary for a feature limit that's full or nearly so
+fn write_feature_alert(
+    out: &mut String,
+    name: &str,
+    rl: &RateLimit,
+    resets_in: &dyn Fn(&RateWindow) -> Option<u64>,
+    reset: &dyn Fn(Option<u64>) -> String,
+) {
+    let name = feature_title(name);
+    if rl.exhausted() {
+        // The longest wait among the full windows is the one that matters
+        let wait = rl
+            .windows()
+            .iter()
+            .filter(|(_, w)| w.used_percent.unwrap_or(0.0) >= 100.0)
+            .filter_map(|(_, w)| resets_in(w))
+            .max();
+        let _ = writeln!(
+            out,
+            "  {} {name} limit reached — resets {}",
+            "✗".red().bold(),
+            reset(wait)
+        );
+    } else if rl.highest() >= CRIT_PERCENT {
+        let _ = writeln!(out, "  {} {name} nearly at its limit", "⚠".red().bold());
+    }
+}
+
+fn write_soft_limit(out: &mut String) {
+    let _ = writeln!(
+        out,
+        "\n  {} Soft-limited — the API reports the limit reached though no window\n    \
+         is at 100%. Requests may be refused until a reset.",
+        "◐".red().bold()
+    );
+}
+
+/// A bar `width` columns wide, filled to `pct` and coloured by severity
+pub(crate) fn usage_bar(pct: f64, width: usize, opts: &Options) -> colored::ColoredString {
+    let filled = ((pct / 100.0) * width as f64).round() as usize;
+    let filled = filled.min(width);
+    let empty = width - filled;
+    let fill = match (opts.patterns, pct) {
+        (false, _) => "█",
+        (true, p) if p >= CRIT_PERCENT => PATTERN_FILLS[2],
+        (true, p) if p >= WARN_PERCENT => PATTERN_FILLS[1],
+        (true, _) => PATTERN_FILLS[0],
+    };
+    let bar = format!("{}{}", fill.repeat(filled), "░".repeat(empty));
+    severity(bar, pct, opts.color.color)
+}
+
+/// `in 2h 5m`, or with `clock` the time of day it happens there, `at
+/// 14:30`, with the weekday when it isn't today
+fn format_reset(reset_secs: Option<u64>, now: DateTime<Utc>, clock: Option<FixedOffset>) -> String {
+    let Some(secs) = reset_secs else {
+        return "—".dimmed().to_string();
+    };
+    if secs == 0 {
+        return "now".green().to_string();
+    }
+    let mins = secs / 60;
+    let hours = mins / 60;
+    let days = hours / 24;
+    let text = match clock {
+        Some(offset) => {
+            let at = (now + Duration::seconds(secs as i64)).with_timezone(&offset);
+            match at.date_naive() == now.with_timezone(&offset).date_naive() {
+                true => at.format("at %H:%M").to_string(),
+                false => at.format("at %a %H:%M").to_string(),
+            }
+        }
+        None if days > 0 => format!("in {}d {}h", days, hours % 24),
+        None if hours > 0 => format!("in {}h {}m", hours, mins % 60),
+        None => format!("in {}m", mins),
+    };
+    match hours {
+        0 => text.yellow().to_string(),
+        _ => text.normal().to_string(),
+    }
+}
+
+/// Colour `s` by how close `pct` is to the limit. Truecolor terminals get a
+/// softer palette; everything else the standard red/yellow/green, which any
+/// colour terminal renders legibly. Same thresholds either way.
+fn severity(s: String, pct: f64, level: ColorLevel) -> colored::ColoredString {
+    let truecolor = level == ColorLevel::TrueColor;
+    let shade = Level::of(pct);
+    let (r, g, b) = shade.rgb();
+    match (shade, truecolor) {
+        (Level::Critical, true) => s.truecolor(r, g, b).bold(),
+        (Level::Critical, false) => s.red().bold(),
+        (_, true) => s.truecolor(r, g, b),
+        (Level::Warning, false) => s.yellow(),
+        (Level::Normal, false) => s.green(),
+    }
+}
@@ -0,0 +1,45 @@
+//! `--format i3blocks`: the three-line blocklet protocol — full text, short
+//! text, colour — so the binary can be a blocklet's `command` as it is.
+//! The full text has each main window, the short text only the fullest, and
+//! the colour is the fancy view's for the worst level, or red once the limit
+//! is reached.
+
+use super::{window_rows, Level, Render};
+use crate::api::Snapshot;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+pub struct I3blocks;
+
+impl Render for I3blocks {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let main: Vec<_> = window_rows(snap, now)
+            .into_iter()
+            .filter(|row| row.feature.is_none())
+            .filter_map(|row| Some((row.short_span(), row.window?.used_percent?)))
+            .collect();
+        let reached = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .is_some_and(|r| r.limit_reached == Some(true));
+
+        let Some(highest) = main.iter().map(|&(_, pct)| pct).reduce(f64::max) else {
+            // i3blocks keeps the bar's own colour for an empty third line
+            let _ = write!(out, "CX —\nCX —\n\n");
+            return;
+        };
+        let full: Vec<String> = main
+            .iter()
+            .map(|(span, pct)| format!("{span} {}%", pct.round()))
+            .collect();
+        let level = match reached {
+            true => Level::Critical,
+            false => Level::of(highest),
+        };
+        let (r, g, b) = level.rgb();
+        let _ = writeln!(out, "CX {}", full.join(" · "));
+        let _ = writeln!(out, "CX {}%", highest.round());
+        let _ = writeln!(out, "#{r:02x}{g:02x}{b:02x}");
+    }
+}
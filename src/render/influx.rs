@@ -0,0 +1,69 @@
+//! `--format influx`: the sample as one InfluxDB line-protocol point, for
+//! Telegraf's `exec` input or `--influx-url`. The point is stamped with the
+//! fetch time and its countdowns are taken then too, so writing the same
+//! cached sample twice stores the same point rather than a new one.
+
+use super::Render;
+use crate::api::{RateWindow, Snapshot};
+use crate::labels::{self, Labels};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+pub const MEASUREMENT: &str = "codex_usage";
+
+pub struct Influx {
+    /// Tagged as `profile` when known
+    pub profile: Option<String>,
+    /// More tags, after those
+    pub labels: Labels,
+}
+
+impl Render for Influx {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, _now: DateTime<Utc>) {
+        out.push_str(&line(self.profile.as_deref(), &self.labels, snap));
+        out.push('\n');
+    }
+}
+
+/// The point for `snap`, without the newline: plan, profile and `labels` as
+/// tags, each window's percentage and reset countdown and the limit flag as
+/// fields, nanosecond timestamp
+pub fn line(profile: Option<&str>, labels: &Labels, snap: &Snapshot) -> String {
+    let rl = snap.usage.rate_limit.as_ref();
+    let mut out = String::from(MEASUREMENT);
+    let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+    let _ = write!(out, ",plan={}", escape_tag(plan));
+    if let Some(profile) = profile {
+        let _ = write!(out, ",profile={}", escape_tag(profile));
+    }
+    for (k, v) in labels::extra(labels, &["plan", "profile"]) {
+        let _ = write!(out, ",{}={}", escape_tag(k), escape_tag(v));
+    }
+
+    let mut fields = Vec::new();
+    let mut window = |prefix: &str, w: Option<&RateWindow>| {
+        let Some(w) = w else { return };
+        if let Some(pct) = w.used_percent {
+            // Always written with a decimal point, so the field stays a float
+            fields.push(format!("{prefix}_percent={pct:?}"));
+        }
+        if let Some(secs) = w.resets_in(snap.fetched_at, snap.fetched_at) {
+            fields.push(format!("{prefix}_resets_in_seconds={secs}i"));
+        }
+    };
+    window("five_hour", rl.and_then(|r| r.primary_window.as_ref()));
+    window("seven_day", rl.and_then(|r| r.secondary_window.as_ref()));
+    let reached = rl.and_then(|r| r.limit_reached).unwrap_or(false);
+    fields.push(format!("limit_reached={reached}"));
+
+    let nanos = snap.fetched_at.timestamp_nanos_opt().unwrap_or_default();
+    let _ = write!(out, " {} {nanos}", fields.join(","));
+    out
+}
+
+/// Tag keys and values escape commas, equals signs and spaces
+fn escape_tag(v: &str) -> String {
+    v.replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
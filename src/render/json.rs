@@ -0,0 +1,138 @@
+//! `--json` / `--json-pretty`: a small, versioned summary for scripts and
+//! `jq`. Every key is always present (`null` when the API left it out), and
+//! `schema` only changes if an existing key's meaning does; new keys may be
+//! added. `--json` is one line, `--json-pretty` the same object indented.
+//! `--format yaml` and `--log-ndjson` serialise the same [`Report`], so they
+//! can't drift.
+
+use super::Render;
+use crate::api::{RateLimit, RateWindow, Snapshot};
+use crate::workspace::Workspace;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Bumped only for incompatible changes
+pub const SCHEMA: u32 = 1;
+
+pub struct Json {
+    pub pretty: bool,
+}
+
+#[derive(Serialize)]
+pub struct Report<'a> {
+    schema: u32,
+    /// The raw plan code, as the API sent it
+    plan: Option<&'a str>,
+    /// What the other formats call it (built in, or `[plans]` in the config)
+    plan_display: Option<String>,
+    /// The workspace a team or business token acts in, and the seat's role
+    workspace: Option<&'a Workspace>,
+    fetched_at: DateTime<Utc>,
+    /// Seconds between `fetched_at` and the moment of output
+    age_seconds: i64,
+    limit_reached: bool,
+    /// `limit_reached` while every window reads under 100%
+    soft_limited: bool,
+    /// The rate limit's flags exactly as the API sent them
+    flags: BTreeMap<&'a str, serde_json::Value>,
+    five_hour: Option<Window>,
+    seven_day: Option<Window>,
+    /// Every window as a list: the main limit's (`codex`) then each
+    /// feature's (`code_review`, …)
+    limits: Vec<Limit>,
+}
+
+#[derive(Serialize)]
+struct Limit {
+    name: String,
+    /// `primary`, `secondary`, …, as the API names it
+    window: String,
+    window_seconds: Option<u64>,
+    /// The flag of the limit this window belongs to
+    limit_reached: bool,
+    #[serde(flatten)]
+    usage: Window,
+}
+
+#[derive(Serialize)]
+pub struct Window {
+    used_percent: Option<f64>,
+    /// Counted down to the moment of rendering, like the other formats
+    resets_in_seconds: Option<u64>,
+    resets_at: Option<DateTime<Utc>>,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(snap: &'a Snapshot, now: DateTime<Utc>) -> Self {
+        let rl = snap.usage.rate_limit.as_ref();
+        let usage = |w: &RateWindow| {
+            let resets_in = w.resets_in(snap.fetched_at, now);
+            Window {
+                used_percent: w.used_percent,
+                resets_in_seconds: resets_in,
+                resets_at: resets_in.map(|s| (now + Duration::seconds(s as i64)).trunc_subsecs(0)),
+            }
+        };
+        let window = |w: Option<&RateWindow>| w.map(usage);
+        let main = rl.map(|rl| ("codex".to_string(), rl.clone()));
+        let limits = main
+            .into_iter()
+            .chain(snap.usage.feature_limits())
+            .flat_map(|(name, rl)| {
+                rl.windows()
+                    .into_iter()
+                    .map(|(which, w)| Limit {
+                        name: name.clone(),
+                        window: which.to_string(),
+                        window_seconds: w.limit_window_seconds,
+                        limit_reached: rl.limit_reached.unwrap_or(false),
+                        usage: usage(&w),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Report {
+            schema: SCHEMA,
+            plan: snap.usage.plan_type.as_deref(),
+            plan_display: snap.usage.plan_type.as_deref().map(crate::plans::display),
+            workspace: snap.workspace.as_ref(),
+            fetched_at: snap.fetched_at,
+            age_seconds: (now - snap.fetched_at).num_seconds().max(0),
+            limit_reached: rl.and_then(|r| r.limit_reached).unwrap_or(false),
+            soft_limited: rl.is_some_and(|r| r.soft_limited()),
+            flags: rl.map(flags).unwrap_or_default(),
+            five_hour: window(rl.and_then(|r| r.primary_window.as_ref())),
+            seven_day: window(rl.and_then(|r| r.secondary_window.as_ref())),
+            limits,
+        }
+    }
+}
+
+/// `limit_reached` (if sent) and every key the schema doesn't know, other
+/// than extra windows, which are in `limits`
+fn flags(rl: &RateLimit) -> BTreeMap<&str, serde_json::Value> {
+    let mut flags: BTreeMap<&str, serde_json::Value> = rl
+        .extra
+        .iter()
+        .filter(|(k, _)| !rl.is_window_key(k))
+        .map(|(k, v)| (k.as_str(), v.clone()))
+        .collect();
+    if let Some(reached) = rl.limit_reached {
+        flags.insert("limit_reached", reached.into());
+    }
+    flags
+}
+
+impl Render for Json {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let report = Report::new(snap, now);
+        // Serialising plain data can't fail
+        let json = match self.pretty {
+            true => serde_json::to_string_pretty(&report),
+            false => serde_json::to_string(&report),
+        };
+        out.push_str(&json.unwrap_or_default());
+        out.push('\n');
+    }
+}
@@ -0,0 +1,36 @@
+//! Width-aware text helpers — alignment is done in terminal columns, not chars.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Terminal columns occupied by `s` — wide (CJK, emoji) characters count as two
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// Truncate `s` to at most `width` columns, marking the cut with an ellipsis
+pub fn truncate_to_width(s: &str, width: usize) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for ch in s.chars() {
+        let w = ch.width().unwrap_or(0);
+        // leave one column for the ellipsis
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(ch);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+/// Truncate or right-pad `s` so it occupies exactly `width` columns
+pub fn fit_width(s: &str, width: usize) -> String {
+    let mut out = truncate_to_width(s, width);
+    let pad = width.saturating_sub(display_width(&out));
+    out.extend(std::iter::repeat_n(' ', pad));
+    out
+}
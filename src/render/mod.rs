@@ -0,0 +1,401 @@
+//! Output renderers. Each `--format` maps to one `Render` implementation, so
+//! adding a format means adding a module here rather than touching `run()`.
+
+mod conky;
+mod eww;
+mod fancy;
+mod i3blocks;
+pub mod influx;
+mod json;
+pub mod layout;
+mod ohmyposh;
+mod oneline;
+mod plain;
+mod polybar;
+pub mod prometheus;
+mod prompt;
+mod starship;
+#[cfg(test)]
+mod tests;
+mod tmux;
+mod waybar;
+mod xbar;
+mod yaml;
+
+use crate::api::{assumed_length, RateWindow, Snapshot, Span};
+use crate::locale::Locale;
+use crate::term::Capability;
+use crate::week::WorkWeek;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+
+pub use conky::Conky;
+pub use eww::Eww;
+pub use fancy::Fancy;
+pub(crate) use fancy::{label, usage_bar};
+pub use i3blocks::I3blocks;
+pub use influx::Influx;
+pub use json::{Json, Report};
+pub use ohmyposh::OhMyPosh;
+pub use oneline::Oneline;
+pub use plain::Plain;
+pub use polybar::Polybar;
+pub use prometheus::Prometheus;
+pub use prompt::Prompt;
+pub use starship::Starship;
+pub use tmux::Tmux;
+pub use waybar::Waybar;
+pub use xbar::Xbar;
+pub use yaml::Yaml;
+
+/// Usage at which a window is shown as elevated, and as nearly full. Colours,
+/// bar patterns, badges and status classes all change here.
+pub const WARN_PERCENT: f64 = 70.0;
+pub const CRIT_PERCENT: f64 = 90.0;
+
+/// Where a percentage stands against those thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    pub fn of(pct: f64) -> Level {
+        match pct {
+            p if p >= CRIT_PERCENT => Level::Critical,
+            p if p >= WARN_PERCENT => Level::Warning,
+            _ => Level::Normal,
+        }
+    }
+
+    /// `normal`, `warning`, `critical`
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Normal => "normal",
+            Level::Warning => "warning",
+            Level::Critical => "critical",
+        }
+    }
+
+    /// Its colour in the truecolor palette
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Level::Normal => (102, 187, 106),
+            Level::Warning => (255, 179, 0),
+            Level::Critical => (229, 57, 53),
+        }
+    }
+}
+
+/// Something that can turn a usage response into output
+pub trait Render {
+    /// Whether to show a spinner while the request runs. Machine formats
+    /// leave it off so nothing but the report reaches stdout.
+    fn shows_progress(&self) -> bool {
+        false
+    }
+
+    /// Whether the report is answered from the cache, whatever its age, with
+    /// a refresh left to the background, for prompts that mustn't wait on
+    /// the network
+    fn reads_cache(&self) -> bool {
+        false
+    }
+
+    /// Append the report for `snap` as seen at `now` to `out`. Pure — callers
+    /// decide where the output goes and which clock supplies `now`.
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>);
+
+    /// The report as a fresh string, for tests
+    #[cfg(test)]
+    fn render(&self, snap: &Snapshot, now: DateTime<Utc>) -> String {
+        let mut out = String::with_capacity(BUFFER_CAPACITY);
+        self.render_into(&mut out, snap, now);
+        out
+    }
+}
+
+/// `code_review` → `Code review`, for feature limit labels
+pub(crate) fn feature_title(name: &str) -> String {
+    let words = name.replace(['_', '-'], " ");
+    let mut chars = words.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// How long before `now` `snap` was fetched, `12s ago`, if it wasn't just
+/// now — a cache hit, a replayed sample, a poller that has stalled — so
+/// old data can't pass for live
+pub(crate) fn updated(snap: &Snapshot, now: DateTime<Utc>) -> Option<String> {
+    let age = now - snap.fetched_at;
+    (age.num_seconds() >= 1).then(|| format!("{} ago", crate::status::age(age)))
+}
+
+// ─── Windows ──────────────────────────────────────────────────────────────────
+
+/// One usage window as the text renderers list it. Rows are built from
+/// whatever windows the API sent, so a new one is shown without a renderer
+/// having to name it; renderers only choose how to label a row.
+#[derive(Debug, Clone)]
+pub(crate) struct WindowRow {
+    /// The feature limit it belongs to; `None` for the main Codex limit
+    pub feature: Option<String>,
+    /// As the API names it: `primary`, `secondary`, …
+    pub name: String,
+    /// Length, from the API or assumed from `name`
+    pub span: Option<Span>,
+    /// `None` for a main window the API left out
+    pub window: Option<RateWindow>,
+    pub resets_in: Option<u64>,
+}
+
+impl WindowRow {
+    /// `5h`, `7d`, `30m`, or the window's name when its length is unknown
+    pub fn short_span(&self) -> String {
+        match self.span {
+            Some(span) => span.short(),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Every window in `snap` as seen at `now`: the main limit's (its primary
+/// and secondary always, even if missing), then each feature limit's
+pub(crate) fn window_rows(snap: &Snapshot, now: DateTime<Utc>) -> Vec<WindowRow> {
+    let row = |feature: Option<&str>, name: &str, window: Option<RateWindow>| WindowRow {
+        feature: feature.map(str::to_string),
+        name: name.to_string(),
+        span: match &window {
+            Some(w) => w.span(name),
+            None => assumed_length(name).and_then(Span::from_seconds),
+        },
+        resets_in: window
+            .as_ref()
+            .and_then(|w| w.resets_in(snap.fetched_at, now)),
+        window,
+    };
+    let rl = snap.usage.rate_limit.as_ref();
+    let mut rows = vec![
+        row(None, "primary", rl.and_then(|r| r.primary_window.clone())),
+        row(
+            None,
+            "secondary",
+            rl.and_then(|r| r.secondary_window.clone()),
+        ),
+    ];
+    for (name, w) in rl.map(|r| r.windows()).unwrap_or_default() {
+        if !matches!(name, "primary" | "secondary") {
+            rows.push(row(None, name, Some(w)));
+        }
+    }
+    for (feature, rl) in snap.usage.feature_limits() {
+        for (name, w) in rl.windows() {
+            rows.push(row(Some(&feature), name, Some(w)));
+        }
+    }
+    rows
+}
+
+// ─── Ordering ─────────────────────────────────────────────────────────────────
+
+/// How views of several profiles or seats order their rows (`--sort`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sort {
+    /// Limit reached first, then the fullest, so what needs attention is on
+    /// top of a long list
+    #[default]
+    Severity,
+    Name,
+}
+
+impl Sort {
+    /// Put `rows` in this order. `key` gives a row's name and, when it has
+    /// usage, whether its limit is reached and how full it is; rows without
+    /// usage go last, by name.
+    pub fn apply<T>(self, rows: &mut [T], key: impl Fn(&T) -> (&str, Option<(bool, f64)>)) {
+        rows.sort_by(|a, b| {
+            let ((a_name, a_usage), (b_name, b_usage)) = (key(a), key(b));
+            let by_severity = match (self, a_usage, b_usage) {
+                (Sort::Name, _, _) => std::cmp::Ordering::Equal,
+                (Sort::Severity, Some(a), Some(b)) => b.0.cmp(&a.0).then(b.1.total_cmp(&a.1)),
+                (Sort::Severity, Some(_), None) => std::cmp::Ordering::Less,
+                (Sort::Severity, None, Some(_)) => std::cmp::Ordering::Greater,
+                (Sort::Severity, None, None) => std::cmp::Ordering::Equal,
+            };
+            by_severity.then_with(|| a_name.cmp(b_name))
+        });
+    }
+}
+
+impl std::str::FromStr for Sort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "severity" => Ok(Sort::Severity),
+            "name" => Ok(Sort::Name),
+            other => bail!("Invalid --sort '{other}' (expected severity or name)"),
+        }
+    }
+}
+
+/// Room for a whole report, colour codes included, so rendering one doesn't
+/// have to grow the buffer
+pub const BUFFER_CAPACITY: usize = 2048;
+
+/// Settings that shape a report beyond its format
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Adds the work-week view
+    pub week: Option<WorkWeek>,
+    /// Number formatting; `plain` ignores it to stay script-stable
+    pub locale: Locale,
+    /// Encode severity in the bar glyphs as well as the colour
+    pub patterns: bool,
+    /// A thin bar under each window for the share of it that has passed
+    pub pace: bool,
+    /// What the terminal can show; picks the palette and explains a
+    /// colourless report
+    pub color: Capability,
+    /// The profile reported on, for formats that label samples with it
+    pub profile: Option<String>,
+    /// Extra labels for the formats that tag samples: prometheus and influx
+    pub labels: crate::labels::Labels,
+    /// Row order for views of several profiles
+    pub sort: Sort,
+    /// Resets as the time of day in this offset rather than a countdown;
+    /// the fancy view only, toggled with `a` under `--watch`
+    pub reset_clock: Option<chrono::FixedOffset>,
+}
+
+/// Output formats selectable with `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Fancy,
+    Plain,
+    Json,
+    JsonPretty,
+    Yaml,
+    Prometheus,
+    Influx,
+    Waybar,
+    Polybar,
+    Tmux,
+    I3blocks,
+    Xbar,
+    Starship,
+    OhMyPosh,
+    Conky,
+    Oneline,
+    Eww,
+    Prompt,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &[
+        "fancy",
+        "plain",
+        "json",
+        "json-pretty",
+        "yaml",
+        "prometheus",
+        "influx",
+        "waybar",
+        "polybar",
+        "tmux",
+        "i3blocks",
+        "xbar",
+        "starship",
+        "oh-my-posh",
+        "conky",
+        "oneline",
+        "eww",
+        "prompt",
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Fancy => "fancy",
+            Format::Plain => "plain",
+            Format::Json => "json",
+            Format::JsonPretty => "json-pretty",
+            Format::Yaml => "yaml",
+            Format::Prometheus => "prometheus",
+            Format::Influx => "influx",
+            Format::Waybar => "waybar",
+            Format::Polybar => "polybar",
+            Format::Tmux => "tmux",
+            Format::I3blocks => "i3blocks",
+            Format::Xbar => "xbar",
+            Format::Starship => "starship",
+            Format::OhMyPosh => "oh-my-posh",
+            Format::Conky => "conky",
+            Format::Oneline => "oneline",
+            Format::Eww => "eww",
+            Format::Prompt => "prompt",
+        }
+    }
+
+    pub fn renderer(self, opts: Options) -> Box<dyn Render> {
+        match self {
+            Format::Fancy => Box::new(Fancy { opts }),
+            Format::Plain => Box::new(Plain { week: opts.week }),
+            Format::Json => Box::new(Json { pretty: false }),
+            Format::JsonPretty => Box::new(Json { pretty: true }),
+            Format::Yaml => Box::new(Yaml),
+            Format::Prometheus => Box::new(Prometheus {
+                labels: opts.labels,
+            }),
+            Format::Influx => Box::new(Influx {
+                profile: opts.profile,
+                labels: opts.labels,
+            }),
+            Format::Waybar => Box::new(Waybar),
+            Format::Polybar => Box::new(Polybar),
+            Format::Tmux => Box::new(Tmux),
+            Format::I3blocks => Box::new(I3blocks),
+            Format::Xbar => Box::new(Xbar),
+            Format::Starship => Box::new(Starship),
+            Format::OhMyPosh => Box::new(OhMyPosh),
+            Format::Conky => Box::new(Conky),
+            Format::Oneline => Box::new(Oneline),
+            Format::Eww => Box::new(Eww),
+            Format::Prompt => Box::new(Prompt),
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fancy" => Ok(Format::Fancy),
+            "plain" => Ok(Format::Plain),
+            "json" => Ok(Format::Json),
+            "json-pretty" => Ok(Format::JsonPretty),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "prometheus" => Ok(Format::Prometheus),
+            "influx" => Ok(Format::Influx),
+            "waybar" => Ok(Format::Waybar),
+            "polybar" => Ok(Format::Polybar),
+            "tmux" => Ok(Format::Tmux),
+            "i3blocks" => Ok(Format::I3blocks),
+            "xbar" | "swiftbar" => Ok(Format::Xbar),
+            "starship" => Ok(Format::Starship),
+            "oh-my-posh" | "omp" => Ok(Format::OhMyPosh),
+            "conky" => Ok(Format::Conky),
+            "oneline" => Ok(Format::Oneline),
+            "eww" => Ok(Format::Eww),
+            "prompt" => Ok(Format::Prompt),
+            other => bail!(
+                "Unknown format '{other}' (expected one of: {})",
+                Format::NAMES.join(", ")
+            ),
+        }
+    }
+}
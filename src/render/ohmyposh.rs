@@ -0,0 +1,62 @@
+//! `--format oh-my-posh`: one line of JSON for an oh-my-posh prompt theme —
+//! the segment's text, colours to draw it in, and the Waybar tooltip's
+//! lines. oh-my-posh can't run a command into a segment's colours itself,
+//! so the README's PowerShell hook copies the fields into environment
+//! variables for a `text` segment to read.
+
+use super::{waybar, window_rows, Level, Render};
+use crate::api::Snapshot;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+pub struct OhMyPosh;
+
+#[derive(Serialize)]
+struct Segment {
+    text: String,
+    /// Readable over `background`
+    foreground: String,
+    /// The fancy view's colour for the fullest window's level
+    background: String,
+    tooltip: String,
+}
+
+impl Render for OhMyPosh {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let rows = window_rows(snap, now);
+        let highest = rows
+            .iter()
+            .filter(|row| row.feature.is_none())
+            .filter_map(|row| row.window.as_ref()?.used_percent)
+            .reduce(f64::max);
+        let reached = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .is_some_and(|r| r.limit_reached == Some(true));
+
+        let level = match (reached, highest) {
+            (true, _) => Level::Critical,
+            (false, Some(pct)) => Level::of(pct),
+            (false, None) => Level::Normal,
+        };
+        let (r, g, b) = level.rgb();
+        let segment = Segment {
+            text: match highest {
+                Some(pct) => format!("CX {}%", pct.clamp(0.0, 100.0).round()),
+                None => "CX —".into(),
+            },
+            // Amber is too light for white text
+            foreground: match level {
+                Level::Warning => "#000000",
+                _ => "#ffffff",
+            }
+            .into(),
+            background: format!("#{r:02x}{g:02x}{b:02x}"),
+            tooltip: waybar::tooltip(snap, &rows, now).join("\n"),
+        };
+        // A struct of strings always serialises
+        out.push_str(&serde_json::to_string(&segment).unwrap_or_default());
+        out.push('\n');
+    }
+}
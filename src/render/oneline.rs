@@ -0,0 +1,45 @@
+//! `--oneline`: `PLUS 5h:43%(2h13m) 7d:12%(4d2h)`, for logs, `watch -n` and
+//! other tools' output. Also what a report piped elsewhere gets when no
+//! format was asked for. Only figures past the warning threshold, and a
+//! reached limit, are coloured.
+
+use super::{window_rows, Level, Render};
+use crate::api::Snapshot;
+use crate::status::age;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use std::fmt::Write;
+
+pub struct Oneline;
+
+impl Render for Oneline {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+        out.extend(plan.chars().flat_map(char::to_uppercase));
+        for row in window_rows(snap, now) {
+            if row.feature.is_some() {
+                continue;
+            }
+            let _ = write!(out, " {}:", row.short_span());
+            let Some(pct) = row.window.as_ref().and_then(|w| w.used_percent) else {
+                out.push('—');
+                continue;
+            };
+            let figure = format!("{}%", pct.round());
+            let _ = match Level::of(pct) {
+                Level::Normal => write!(out, "{figure}"),
+                Level::Warning => write!(out, "{}", figure.yellow()),
+                Level::Critical => write!(out, "{}", figure.red()),
+            };
+            if let Some(secs) = row.resets_in {
+                let wait = age(chrono::Duration::seconds(secs as i64));
+                let _ = write!(out, "({})", wait.replace(' ', ""));
+            }
+        }
+        let rl = snap.usage.rate_limit.as_ref();
+        if rl.is_some_and(|r| r.limit_reached == Some(true)) {
+            let _ = write!(out, " {}", "LIMIT REACHED".red().bold());
+        }
+        out.push('\n');
+    }
+}
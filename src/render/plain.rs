@@ -0,0 +1,105 @@
+//! `--plain`: stable line-oriented text for scripts and `watch`.
+
+use super::{window_rows, Render, WindowRow};
+use crate::api::{Snapshot, Span, Unit};
+use crate::week::WorkWeek;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+#[derive(Default)]
+pub struct Plain {
+    pub week: Option<WorkWeek>,
+}
+
+impl Render for Plain {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let usage = &snap.usage;
+        let rl = usage.rate_limit.as_ref();
+        let limit_reached = rl.and_then(|r| r.limit_reached).unwrap_or(false);
+
+        let plan = usage.plan_type.as_deref().unwrap_or("unknown");
+        out.push_str("Plan: ");
+        out.extend(plan.chars().flat_map(char::to_uppercase));
+        out.push('\n');
+        if let Some(workspace) = snap.workspace.as_ref().and_then(|w| w.describe()) {
+            out.push_str("Workspace: ");
+            out.push_str(&workspace);
+            out.push('\n');
+        }
+        for row in window_rows(snap, now) {
+            write_window(out, &row);
+        }
+        if let Some(week) = &self.week {
+            let v = week.view(
+                rl.and_then(|r| r.secondary_window.as_ref()),
+                snap.fetched_at,
+                now,
+            );
+            let _ = write!(
+                out,
+                "Work week: {:.1}% elapsed  Work days before reset: ",
+                v.elapsed
+            );
+            write_num(out, v.days_left);
+            out.push_str("  Per work day: ");
+            write_num(out, v.per_day);
+            let _ = writeln!(out, "%  ({})", week.label().replace('–', "-"));
+        }
+        if limit_reached {
+            out.push_str("Status: LIMIT REACHED\n");
+        }
+        // A line of its own, so scripts matching LIMIT REACHED still do
+        if rl.is_some_and(|r| r.soft_limited()) {
+            out.push_str("Status: SOFT LIMITED (windows below 100%)\n");
+        }
+        // Named, so scripts matching the main LIMIT REACHED line don't trip
+        for (name, rl) in &usage.feature_limits() {
+            if rl.exhausted() {
+                let _ = writeln!(out, "Status: {name} LIMIT REACHED");
+            }
+        }
+        if let Some(updated) = super::updated(snap, now) {
+            let _ = writeln!(out, "Updated: {updated}");
+        }
+    }
+}
+
+/// `5hr window`, `7day window`, `code_review 7d window`, …
+fn label(row: &WindowRow) -> String {
+    if let Some(feature) = &row.feature {
+        return format!("{feature} {} window", row.short_span());
+    }
+    let Some(Span { count, unit }) = row.span else {
+        return format!("{} window", row.name);
+    };
+    let unit = match unit {
+        Unit::Minute => "min",
+        Unit::Hour => "hr",
+        Unit::Day => "day",
+    };
+    format!("{count}{unit} window")
+}
+
+fn write_window(out: &mut String, row: &WindowRow) {
+    let label = label(row);
+    match &row.window {
+        None => {
+            let _ = writeln!(out, "{}: N/A", label);
+        }
+        Some(w) => {
+            let pct = w.used_percent.unwrap_or(0.0).min(100.0);
+            let _ = write!(out, "{}: {:.1}% used  Resets in: ", label, pct);
+            let _ = match row.resets_in {
+                Some(s) => writeln!(out, "{}s", s),
+                None => writeln!(out, "—"),
+            };
+        }
+    }
+}
+
+fn write_num(out: &mut String, v: Option<f64>) {
+    let _ = match v {
+        Some(v) => write!(out, "{v:.1}"),
+        None => write!(out, "—"),
+    };
+}
@@ -0,0 +1,46 @@
+//! `--format polybar`: one compact line for a polybar `custom/script`
+//! module, `⬤ 42%|12%` (5-hour, then 7-day), coloured with polybar's own
+//! `%{F#rrggbb}` tags rather than ANSI codes. Each figure takes the fancy
+//! view's colour for its level; the dot takes the worst, or red once the
+//! limit is reached.
+
+use super::{Level, Render};
+use crate::api::{RateWindow, Snapshot};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+pub struct Polybar;
+
+impl Render for Polybar {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, _now: DateTime<Utc>) {
+        let rl = snap.usage.rate_limit.as_ref();
+        let windows = [
+            rl.and_then(|r| r.primary_window.as_ref()),
+            rl.and_then(|r| r.secondary_window.as_ref()),
+        ];
+        let pct = |w: Option<&RateWindow>| w.and_then(|w| w.used_percent);
+
+        let worst = match rl.is_some_and(|r| r.limit_reached == Some(true)) {
+            true => Level::Critical,
+            false => windows
+                .iter()
+                .filter_map(|&w| pct(w).map(Level::of))
+                .max()
+                .unwrap_or(Level::Normal),
+        };
+        let figures: Vec<String> = windows
+            .iter()
+            .map(|&w| match pct(w) {
+                Some(p) => colour(Level::of(p), &format!("{}%", p.round())),
+                None => "—".to_string(),
+            })
+            .collect();
+        let _ = writeln!(out, "{} {}", colour(worst, "⬤"), figures.join("|"));
+    }
+}
+
+/// `text` in `level`'s colour, reset after
+fn colour(level: Level, text: &str) -> String {
+    let (r, g, b) = level.rgb();
+    format!("%{{F#{r:02x}{g:02x}{b:02x}}}{text}%{{F-}}")
+}
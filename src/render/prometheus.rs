@@ -0,0 +1,192 @@
+//! `--format prometheus`: gauges in the Prometheus text exposition format,
+//! for node_exporter's textfile collector, and the body of `serve`'s
+//! `/metrics`. Values the API left out are left out here too, rather than
+//! reported as 0.
+
+use super::Render;
+use crate::api::{RateWindow, Snapshot};
+use crate::labels::{self, Labels};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+pub struct Prometheus {
+    /// On every sample, ahead of the metrics' own
+    pub labels: Labels,
+}
+
+impl Render for Prometheus {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let extra = labels::extra(&self.labels, RESERVED_LABELS).collect();
+        write_metrics(out, &[(extra, snap)], now);
+    }
+}
+
+/// Label names the metrics set themselves
+pub const RESERVED_LABELS: &[&str] = &["profile", "plan", "window", "feature"];
+
+/// A metric's own labels and value, one entry per sample
+type Samples = Vec<(Vec<(&'static str, String)>, Option<f64>)>;
+
+type Metric = (&'static str, Help, fn(&Snapshot, DateTime<Utc>) -> Samples);
+
+/// A family's HELP text
+enum Help {
+    Fixed(&'static str),
+    /// `Percent of the 5-hour window used`, for the window the API calls this,
+    /// named by how long it is
+    WindowUsed(&'static str),
+}
+
+impl Help {
+    fn text(&self, series: &[(Vec<(&str, &str)>, &Snapshot)]) -> String {
+        let which = match self {
+            Help::Fixed(text) => return text.to_string(),
+            Help::WindowUsed(which) => *which,
+        };
+        let mut spans = series.iter().filter_map(|(_, snap)| {
+            let windows = snap.usage.rate_limit.as_ref()?.windows();
+            let (_, w) = windows.into_iter().find(|(name, _)| *name == which)?;
+            w.span(which)
+        });
+        let first = spans
+            .next()
+            .or_else(|| crate::api::assumed_length(which).and_then(crate::api::Span::from_seconds));
+        // Profiles whose windows differ in length share one family
+        let name = match first {
+            Some(span) if spans.all(|s| s == span) => span.hyphenated(),
+            _ => which.to_string(),
+        };
+        format!("Percent of the {name} window used")
+    }
+}
+
+const METRICS: &[Metric] = &[
+    (
+        "codex_usage_primary_used_percent",
+        Help::WindowUsed("primary"),
+        |snap, _| {
+            let w = snap
+                .usage
+                .rate_limit
+                .as_ref()
+                .and_then(|r| r.primary_window.as_ref());
+            vec![(Vec::new(), w.and_then(|w| w.used_percent))]
+        },
+    ),
+    (
+        "codex_usage_secondary_used_percent",
+        Help::WindowUsed("secondary"),
+        |snap, _| {
+            let w = snap
+                .usage
+                .rate_limit
+                .as_ref()
+                .and_then(|r| r.secondary_window.as_ref());
+            vec![(Vec::new(), w.and_then(|w| w.used_percent))]
+        },
+    ),
+    (
+        "codex_usage_reset_seconds",
+        Help::Fixed("Seconds until the window resets"),
+        |snap, now| {
+            let resets = |w: &RateWindow| w.resets_in(snap.fetched_at, now).map(|s| s as f64);
+            snap.usage
+                .rate_limit
+                .iter()
+                .flat_map(|r| r.windows())
+                .map(|(which, w)| (vec![("window", which.to_string())], resets(&w)))
+                .collect()
+        },
+    ),
+    (
+        "codex_usage_limit_reached",
+        Help::Fixed("1 if the API reports the usage limit reached, else 0"),
+        |snap, _| {
+            let reached = snap.usage.rate_limit.as_ref().and_then(|r| r.limit_reached);
+            vec![(
+                Vec::new(),
+                Some(f64::from(u8::from(reached.unwrap_or(false)))),
+            )]
+        },
+    ),
+    (
+        "codex_usage_feature_used_percent",
+        Help::Fixed("Percent used of a feature's own limit, such as code review"),
+        |snap, _| {
+            let mut samples = Vec::new();
+            for (name, rl) in snap.usage.feature_limits() {
+                for (which, w) in rl.windows() {
+                    let labels = vec![("feature", name.clone()), ("window", which.to_string())];
+                    samples.push((labels, w.used_percent));
+                }
+            }
+            samples
+        },
+    ),
+    (
+        "codex_usage_fetched_timestamp_seconds",
+        Help::Fixed("When the usage was fetched, as a Unix time"),
+        |snap, _| vec![(Vec::new(), Some(snap.fetched_at.timestamp() as f64))],
+    ),
+];
+
+/// Every gauge for each snapshot in `series`, its samples carrying that
+/// snapshot's labels ahead of their own. Families stay whole, as the format
+/// requires, however many snapshots there are.
+pub fn write_metrics(
+    out: &mut String,
+    series: &[(Vec<(&str, &str)>, &Snapshot)],
+    now: DateTime<Utc>,
+) {
+    for (name, help, samples) in METRICS {
+        let mut rows = Vec::new();
+        for (labels, snap) in series {
+            let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+            for (own, value) in samples(snap, now) {
+                let mut all = labels.clone();
+                all.push(("plan", plan));
+                all.extend(own.iter().map(|(k, v)| (*k, v.as_str())));
+                rows.push((label_set(&all), value));
+            }
+        }
+        gauge(out, name, &help.text(series), rows);
+    }
+}
+
+/// One metric family from `(label set, value)` rows. Rows without a value
+/// are skipped, and the whole family when none has one.
+pub fn gauge(out: &mut String, name: &str, help: &str, rows: Vec<(String, Option<f64>)>) {
+    if rows.iter().all(|(_, v)| v.is_none()) {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    for (labels, value) in rows {
+        if let Some(value) = value {
+            let _ = writeln!(out, "{name}{labels} {value}");
+        }
+    }
+}
+
+/// `{k="v",…}`. Names are reduced to the characters Prometheus allows;
+/// backslashes, quotes and newlines are escaped in values.
+pub fn label_set(labels: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| {
+            let mut k: String = k
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            if k.starts_with(|c: char| c.is_ascii_digit()) {
+                k.insert(0, '_');
+            }
+            let v = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{k}=\"{v}\"")
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
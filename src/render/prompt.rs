@@ -0,0 +1,55 @@
+//! `--prompt`: `CX 5h:42% 7d:12% reset 1h03m`, short enough for PS1 or a
+//! fish prompt. The reset is the fullest window's, the one that will stop
+//! work first. Figures are coloured past the warning threshold when colour
+//! is on, which in a prompt's `$(…)` takes `--tty`.
+
+use super::{window_rows, Level, Render};
+use crate::api::Snapshot;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use std::fmt::Write;
+
+pub struct Prompt;
+
+impl Render for Prompt {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let rows: Vec<_> = window_rows(snap, now)
+            .into_iter()
+            .filter(|row| row.feature.is_none())
+            .collect();
+        out.push_str("CX");
+        for row in &rows {
+            let _ = match row.window.as_ref().and_then(|w| w.used_percent) {
+                Some(pct) => {
+                    let figure = format!("{}%", pct.round());
+                    match Level::of(pct) {
+                        Level::Normal => write!(out, " {}:{figure}", row.short_span()),
+                        Level::Warning => write!(out, " {}:{}", row.short_span(), figure.yellow()),
+                        Level::Critical => write!(out, " {}:{}", row.short_span(), figure.red()),
+                    }
+                }
+                None => write!(out, " {}:—", row.short_span()),
+            };
+        }
+        let fullest = rows
+            .iter()
+            .filter_map(|row| Some((row.window.as_ref()?.used_percent?, row.resets_in?)))
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, secs)) = fullest {
+            let _ = write!(out, " reset {}", compact(secs));
+        }
+        out.push('\n');
+    }
+}
+
+/// `1h03m`, `4d2h`, `25m`, `now`; minutes padded after hours so the width holds
+/// steady as the countdown runs
+fn compact(secs: u64) -> String {
+    let (mins, hours, days) = (secs / 60, secs / 3600, secs / 86_400);
+    match () {
+        _ if secs == 0 => "now".to_string(),
+        _ if days > 0 => format!("{days}d{}h", hours % 24),
+        _ if hours > 0 => format!("{hours}h{:02}m", mins % 60),
+        _ => format!("{mins}m"),
+    }
+}
@@ -0,0 +1,32 @@
+//! `--format starship`: the text of a Starship custom module, `󰚩 42%` — the
+//! fullest window of the main limit and nothing else. Colour is left to the
+//! module's `style`. `report` answers it from the cache whatever its age and
+//! refreshes in the background, so a prompt never waits on the network.
+
+use super::{window_rows, Render};
+use crate::api::Snapshot;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+/// The Nerd Font robot, `nf-md-robot`
+const ICON: char = '\u{f06a9}';
+
+pub struct Starship;
+
+impl Render for Starship {
+    fn reads_cache(&self) -> bool {
+        true
+    }
+
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let highest = window_rows(snap, now)
+            .iter()
+            .filter(|row| row.feature.is_none())
+            .filter_map(|row| row.window.as_ref()?.used_percent)
+            .reduce(f64::max);
+        let _ = match highest {
+            Some(pct) => writeln!(out, "{ICON} {}%", pct.clamp(0.0, 100.0).round()),
+            None => writeln!(out, "{ICON} —"),
+        };
+    }
+}
@@ -0,0 +1,588 @@
+//! Snapshot tests for every renderer across representative responses.
+//! Review changes with `cargo insta review`.
+
+use super::{Format, Options};
+use crate::api::{Snapshot, WhamUsage};
+use crate::clock::parse_timestamp;
+use crate::labels::Labels;
+use chrono::{DateTime, Duration, Utc};
+
+const FIXTURES: &[(&str, &str)] = &[
+    ("low", include_str!("../../tests/fixtures/low.json")),
+    ("high", include_str!("../../tests/fixtures/high.json")),
+    (
+        "limit_reached",
+        include_str!("../../tests/fixtures/limit_reached.json"),
+    ),
+    (
+        "missing_windows",
+        include_str!("../../tests/fixtures/missing_windows.json"),
+    ),
+    (
+        "unknown_plan",
+        include_str!("../../tests/fixtures/unknown_plan.json"),
+    ),
+    (
+        "soft_limited",
+        include_str!("../../tests/fixtures/soft_limited.json"),
+    ),
+    (
+        "feature_limits",
+        include_str!("../../tests/fixtures/feature_limits.json"),
+    ),
+    (
+        "extra_windows",
+        include_str!("../../tests/fixtures/extra_windows.json"),
+    ),
+];
+
+fn fixture(json: &str) -> Snapshot {
+    Snapshot::new(
+        serde_json::from_str::<WhamUsage>(json).expect("fixture parses"),
+        fetched_at(),
+    )
+}
+
+fn fetched_at() -> DateTime<Utc> {
+    parse_timestamp("2024-05-01T10:00:00Z").unwrap()
+}
+
+fn render_all(format: Format, name: &str) {
+    // Snapshots hold plain text; colour is exercised by hand
+    colored::control::set_override(false);
+    let renderer = format.renderer(Options::default());
+    for (fixture_name, json) in FIXTURES {
+        let snap = fixture(json);
+        insta::assert_snapshot!(
+            format!("{name}_{fixture_name}"),
+            renderer.render(&snap, fetched_at())
+        );
+    }
+}
+
+#[test]
+fn fancy_snapshots() {
+    render_all(Format::Fancy, "fancy");
+}
+
+#[test]
+fn plain_snapshots() {
+    render_all(Format::Plain, "plain");
+}
+
+#[test]
+fn json_snapshots() {
+    render_all(Format::JsonPretty, "json");
+    // --json is the same object on one line
+    let snap = fixture(FIXTURES[0].1);
+    let line = Format::Json
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    let pretty = Format::JsonPretty
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    assert_eq!(line.lines().count(), 1);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+        serde_json::from_str::<serde_json::Value>(&pretty).unwrap()
+    );
+}
+
+#[test]
+fn json_keys_never_come_and_go() {
+    let keys = |v: &serde_json::Value| {
+        let mut keys: Vec<String> = v.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    };
+    let report = |json: &str, now| {
+        let out = Format::Json
+            .renderer(Options::default())
+            .render(&fixture(json), now);
+        serde_json::from_str::<serde_json::Value>(&out).unwrap()
+    };
+    let full = report(FIXTURES[0].1, fetched_at());
+    assert_eq!(full["schema"], 1);
+    for (name, json) in FIXTURES {
+        assert_eq!(keys(&report(json, fetched_at())), keys(&full), "{name}");
+    }
+    // What the API left out is null rather than missing
+    let missing = report(
+        include_str!("../../tests/fixtures/missing_windows.json"),
+        fetched_at(),
+    );
+    assert!(missing["seven_day"].is_null());
+
+    // Countdowns are as of the moment of output; the reset moment doesn't move
+    let later = report(FIXTURES[0].1, fetched_at() + Duration::seconds(600));
+    let five_hour = |r: &serde_json::Value, key: &str| r["five_hour"][key].clone();
+    assert_eq!(
+        five_hour(&full, "resets_in_seconds").as_u64().unwrap() - 600,
+        five_hour(&later, "resets_in_seconds").as_u64().unwrap()
+    );
+    assert_eq!(
+        five_hour(&full, "resets_at"),
+        five_hour(&later, "resets_at")
+    );
+    assert_eq!(later["age_seconds"], 600);
+}
+
+#[test]
+fn json_flags_are_as_sent() {
+    let flags = |json: &str| {
+        let out = Format::Json
+            .renderer(Options::default())
+            .render(&fixture(json), fetched_at());
+        serde_json::from_str::<serde_json::Value>(&out).unwrap()
+    };
+    let soft = flags(include_str!("../../tests/fixtures/soft_limited.json"));
+    assert_eq!(soft["soft_limited"], true);
+    assert_eq!(
+        soft["flags"],
+        serde_json::json!({ "allowed": false, "limit_reached": true })
+    );
+    // Extra windows are limits, not flags
+    let extra = flags(include_str!("../../tests/fixtures/extra_windows.json"));
+    assert_eq!(extra["soft_limited"], false);
+    assert!(extra["flags"].get("daily_window").is_none());
+    assert!(extra["limits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|l| l["window"] == "daily"));
+}
+
+#[test]
+fn feature_limits_carry_their_own_flag() {
+    colored::control::set_override(false);
+    let snap = fixture(include_str!("../../tests/fixtures/feature_limits.json"));
+    let out = Format::Json
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    let report: serde_json::Value = serde_json::from_str(&out).unwrap();
+    let limits: Vec<(&str, &str, bool)> = report["limits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| {
+            (
+                l["name"].as_str().unwrap(),
+                l["window"].as_str().unwrap(),
+                l["limit_reached"].as_bool().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        limits,
+        [
+            ("codex", "primary", false),
+            ("codex", "secondary", false),
+            ("cloud_tasks", "primary", false),
+            ("cloud_tasks", "secondary", false),
+            ("code_review", "primary", true),
+        ]
+    );
+    // The main limit is fine, so only the features warn
+    assert_eq!(report["limit_reached"], false);
+    let fancy = Format::Fancy
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    assert!(fancy.contains("✓ Looking good"));
+    assert!(fancy.contains("⚠ Cloud tasks nearly at its limit"));
+    assert!(fancy.contains("✗ Code review limit reached — resets in 2d 0h"));
+}
+
+#[test]
+fn yaml_snapshots() {
+    render_all(Format::Yaml, "yaml");
+    // The same document as --json
+    let snap = fixture(FIXTURES[0].1);
+    let yaml = Format::Yaml
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    let json = Format::Json
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    assert_eq!(
+        serde_yaml_ng::from_str::<serde_json::Value>(&yaml).unwrap(),
+        serde_json::from_str::<serde_json::Value>(&json).unwrap()
+    );
+}
+
+#[test]
+fn prometheus_snapshots() {
+    render_all(Format::Prometheus, "prometheus");
+    // Every sample line is `name{labels} value`, under its own TYPE line
+    let snap = fixture(FIXTURES[0].1);
+    let out = Format::Prometheus
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    for line in out.lines().filter(|l| !l.starts_with('#')) {
+        let (series, value) = line.rsplit_once(' ').unwrap();
+        let name = &series[..series.find('{').unwrap()];
+        assert!(out.contains(&format!("# TYPE {name} gauge\n")));
+        assert!(value.parse::<f64>().is_ok(), "{line}");
+    }
+
+    // Labels go on every sample, ahead of the metrics' own
+    let opts = Options {
+        labels: Labels::from([
+            ("pod".to_string(), "api-0".to_string()),
+            ("window".to_string(), "ignored".to_string()),
+        ]),
+        ..Default::default()
+    };
+    let labelled = Format::Prometheus
+        .renderer(opts)
+        .render(&snap, fetched_at());
+    let samples = labelled.lines().filter(|l| !l.starts_with('#'));
+    assert!(samples.clone().all(|l| l.contains("{pod=\"api-0\",plan=")));
+    assert!(!labelled.contains("ignored"));
+    assert_eq!(
+        samples.count(),
+        out.lines().filter(|l| !l.starts_with('#')).count()
+    );
+
+    // HELP names a window by its length, or by name when profiles differ
+    let help = |series: &[(Vec<(&str, &str)>, &Snapshot)]| {
+        let mut out = String::new();
+        super::prometheus::write_metrics(&mut out, series, fetched_at());
+        out.lines()
+            .filter(|l| l.starts_with("# HELP codex_usage_primary_"))
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    let three_hour = fixture(include_str!("../../tests/fixtures/extra_windows.json"));
+    assert_eq!(
+        help(&[(vec![("profile", "a")], &three_hour)]),
+        ["# HELP codex_usage_primary_used_percent Percent of the 3-hour window used"]
+    );
+    assert_eq!(
+        help(&[
+            (vec![("profile", "a")], &three_hour),
+            (vec![("profile", "b")], &snap)
+        ]),
+        ["# HELP codex_usage_primary_used_percent Percent of the primary window used"]
+    );
+}
+
+#[test]
+fn influx_snapshots() {
+    render_all(Format::Influx, "influx");
+    // Tag values escape the protocol's separators
+    let snap = fixture(FIXTURES[0].1);
+    let line = super::influx::line(Some("work laptop,2"), &Labels::new(), &snap);
+    assert!(line.starts_with("codex_usage,plan=plus,profile=work\\ laptop\\,2 "));
+
+    // Labels follow as tags, for the printed point and the pushed one alike
+    let labels = Labels::from([
+        ("team".to_string(), "platform eng".to_string()),
+        ("profile".to_string(), "other".to_string()),
+    ]);
+    let opts = Options {
+        profile: Some("work".into()),
+        labels: labels.clone(),
+        ..Default::default()
+    };
+    let out = Format::Influx.renderer(opts).render(&snap, fetched_at());
+    assert!(out.starts_with("codex_usage,plan=plus,profile=work,team=platform\\ eng "));
+    assert_eq!(
+        out.trim_end(),
+        super::influx::line(Some("work"), &labels, &snap)
+    );
+}
+
+#[test]
+fn waybar_snapshots() {
+    render_all(Format::Waybar, "waybar");
+}
+
+#[test]
+fn polybar_snapshots() {
+    render_all(Format::Polybar, "polybar");
+}
+
+#[test]
+fn tmux_snapshots() {
+    render_all(Format::Tmux, "tmux");
+}
+
+#[test]
+fn i3blocks_snapshots() {
+    render_all(Format::I3blocks, "i3blocks");
+}
+
+#[test]
+fn xbar_snapshots() {
+    render_all(Format::Xbar, "xbar");
+}
+
+#[test]
+fn starship_snapshots() {
+    render_all(Format::Starship, "starship");
+    // It answers from the cache, so a prompt never waits on a fetch
+    assert!(Format::Starship.renderer(Options::default()).reads_cache());
+    assert!(!Format::Prompt.renderer(Options::default()).reads_cache());
+}
+
+#[test]
+fn oh_my_posh_snapshots() {
+    render_all(Format::OhMyPosh, "oh_my_posh");
+}
+
+#[test]
+fn oneline_snapshots() {
+    render_all(Format::Oneline, "oneline");
+}
+
+#[test]
+fn eww_snapshots() {
+    render_all(Format::Eww, "eww");
+}
+
+#[test]
+fn prompt_snapshots() {
+    render_all(Format::Prompt, "prompt");
+}
+
+#[test]
+fn conky_snapshots() {
+    render_all(Format::Conky, "conky");
+}
+
+#[test]
+fn every_format_name_selects_its_renderer() {
+    colored::control::set_override(false);
+    let snap = fixture(FIXTURES[0].1);
+    for name in Format::NAMES {
+        let format: Format = name.parse().unwrap();
+        assert_eq!(format.name(), *name);
+        let out = format
+            .renderer(Options::default())
+            .render(&snap, fetched_at());
+        assert!(!out.trim().is_empty(), "{name}");
+    }
+    for (alias, format) in [
+        ("YML", Format::Yaml),
+        ("swiftbar", Format::Xbar),
+        ("omp", Format::OhMyPosh),
+    ] {
+        assert_eq!(alias.parse::<Format>().unwrap(), format);
+    }
+    let err = "html".parse::<Format>().unwrap_err().to_string();
+    assert!(err.contains("Unknown format 'html'") && err.contains("json-pretty"));
+}
+
+#[test]
+fn reports_append_into_one_buffer() {
+    colored::control::set_override(false);
+    for name in Format::NAMES {
+        let renderer = name.parse::<Format>().unwrap().renderer(Options::default());
+        for (fixture_name, json) in FIXTURES {
+            let mut out = String::with_capacity(super::BUFFER_CAPACITY);
+            out.push_str("before\n");
+            renderer.render_into(&mut out, &fixture(json), fetched_at());
+            // Appended, not replacing what was there, and never regrown
+            assert!(out.starts_with("before\n") && out.len() > 7);
+            assert_eq!(
+                out.capacity(),
+                super::BUFFER_CAPACITY,
+                "{name} {fixture_name}"
+            );
+        }
+    }
+}
+
+#[test]
+fn wide_names_keep_columns_aligned() {
+    colored::control::set_override(false);
+    let width = super::layout::display_width;
+    let mut snap = fixture(include_str!("../../tests/fixtures/feature_limits.json"));
+    snap.usage.plan_type = Some("企業向け".repeat(10));
+    snap.usage.additional_rate_limits[0].limit_name = Some("クラウドタスクの処理".into());
+    let out = Format::Fancy
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    let lines: Vec<&str> = out.lines().collect();
+
+    // The plan is cut to 32 columns, and the rules reach past the title
+    assert!(lines[0].contains("企業向…"), "{out}");
+    assert_eq!(width(lines[0]), 2 + 35 + 31, "{out}");
+    assert!(width(lines[1]) >= width(lines[0]), "{out}");
+    // Every bar starts in the same column, wide label or not
+    let bars: Vec<usize> = lines
+        .iter()
+        .filter_map(|l| Some(width(&l[..l.find(['█', '░'])?])))
+        .collect();
+    assert_eq!(bars.len(), 5, "{out}");
+    assert!(bars.iter().all(|&b| b == 2 + 18 + 1), "{out}");
+}
+
+#[test]
+fn countdown_follows_the_clock() {
+    let snap = fixture(FIXTURES[0].1);
+    let later = fetched_at() + Duration::seconds(520);
+    let out = Format::Plain
+        .renderer(Options::default())
+        .render(&snap, later);
+    assert!(
+        out.contains("5hr window: 12.0% used  Resets in: 11000s"),
+        "{out}"
+    );
+    // Data that old says so
+    assert!(out.ends_with("Updated: 8m ago\n"), "{out}");
+    let fresh = Format::Plain
+        .renderer(Options::default())
+        .render(&snap, fetched_at());
+    assert!(!fresh.contains("Updated"), "{fresh}");
+}
+
+#[test]
+fn work_week_view() {
+    colored::control::set_override(false);
+    let snap = fixture(include_str!("../../tests/fixtures/high.json"));
+    let opts = Options {
+        week: "mon-fri@Australia/Brisbane".parse().ok(),
+        ..Options::default()
+    };
+    for (name, format) in [("fancy", Format::Fancy), ("plain", Format::Plain)] {
+        insta::assert_snapshot!(
+            format!("{name}_work_week"),
+            format.renderer(opts.clone()).render(&snap, fetched_at())
+        );
+    }
+}
+
+#[test]
+fn pattern_bars() {
+    colored::control::set_override(false);
+    let opts = Options {
+        patterns: true,
+        ..Options::default()
+    };
+    let renderer = Format::Fancy.renderer(opts);
+    for fixture_name in ["low", "high"] {
+        let (_, json) = FIXTURES.iter().find(|(n, _)| *n == fixture_name).unwrap();
+        insta::assert_snapshot!(
+            format!("fancy_patterns_{fixture_name}"),
+            renderer.render(&fixture(json), fetched_at())
+        );
+    }
+}
+
+#[test]
+fn pace_bars() {
+    colored::control::set_override(false);
+    let opts = Options {
+        pace: true,
+        ..Options::default()
+    };
+    let snap = fixture(include_str!("../../tests/fixtures/high.json"));
+    insta::assert_snapshot!(
+        "fancy_pace_high",
+        Format::Fancy.renderer(opts).render(&snap, fetched_at())
+    );
+}
+
+#[test]
+fn workspace_header() {
+    colored::control::set_override(false);
+    let mut snap = fixture(include_str!("../../tests/fixtures/extra_windows.json"));
+    snap.workspace = Some(crate::workspace::Workspace {
+        name: Some("Acme Corp".into()),
+        role: Some("admin".into()),
+    });
+    for (name, format) in [("fancy", Format::Fancy), ("plain", Format::Plain)] {
+        insta::assert_snapshot!(
+            format!("{name}_workspace"),
+            format
+                .renderer(Options::default())
+                .render(&snap, fetched_at())
+        );
+    }
+}
+
+#[test]
+fn windows_are_listed_and_labelled_by_their_length() {
+    let labels = |json| {
+        let snap = fixture(json);
+        super::window_rows(&snap, fetched_at())
+            .iter()
+            .map(|row| {
+                (
+                    super::fancy::label(row),
+                    row.window.is_some(),
+                    row.resets_in,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    // Main windows first, then any others the API sent by key, named by
+    // length when it is given and by name when not
+    assert_eq!(
+        labels(include_str!("../../tests/fixtures/extra_windows.json")),
+        [
+            ("3-hour session".to_string(), true, Some(4000)),
+            ("7-day rolling".to_string(), true, Some(250000)),
+            ("Burst window".to_string(), true, Some(90)),
+            ("1-day rolling".to_string(), true, Some(30000)),
+        ]
+    );
+    // Both main windows are always listed, assumed lengths for missing ones
+    assert_eq!(
+        labels(include_str!("../../tests/fixtures/missing_windows.json")),
+        [
+            ("5-hour session".to_string(), true, None),
+            ("7-day rolling".to_string(), false, None),
+        ]
+    );
+    // Feature limits follow by name, each window under the feature's; a
+    // secondary window without a length is assumed to be weekly
+    let features = labels(include_str!("../../tests/fixtures/feature_limits.json"));
+    assert_eq!(
+        features
+            .iter()
+            .map(|(l, ..)| l.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "5-hour session",
+            "7-day rolling",
+            "Cloud tasks 5h",
+            "Cloud tasks 7d",
+            "Code review 7d",
+        ]
+    );
+}
+
+#[test]
+fn old_data_says_how_old_in_every_format() {
+    colored::control::set_override(false);
+    let snap = fixture(include_str!("../../tests/fixtures/low.json"));
+    let later = fetched_at() + Duration::seconds(95);
+    let render = |format: Format, now| format.renderer(Options::default()).render(&snap, now);
+
+    assert!(render(Format::Fancy, later).contains("\n  updated 1m ago\n"));
+    assert!(render(Format::Xbar, later).contains("Updated 1m ago | color=gray\n---\n"));
+    let tooltip = |now| {
+        let out: serde_json::Value = serde_json::from_str(&render(Format::Waybar, now)).unwrap();
+        out["tooltip"].as_str().unwrap().to_string()
+    };
+    assert!(
+        tooltip(later).ends_with("Updated 1m ago"),
+        "{}",
+        tooltip(later)
+    );
+    assert!(!tooltip(fetched_at()).contains("Updated"));
+    for format in [Format::Fancy, Format::Xbar] {
+        assert!(!render(format, fetched_at()).contains("pdated"));
+    }
+
+    let age = |now| {
+        let out: serde_json::Value = serde_json::from_str(&render(Format::Json, now)).unwrap();
+        out["age_seconds"].clone()
+    };
+    assert_eq!(age(later), 95);
+    assert_eq!(age(fetched_at()), 0);
+    // A clock behind the fetch doesn't make the data younger than new
+    assert_eq!(age(fetched_at() - Duration::seconds(30)), 0);
+}
@@ -0,0 +1,50 @@
+//! `--format tmux`: a status-line segment, `#[fg=green]CX 42%#[default]`,
+//! with tmux's own style tags instead of ANSI codes. The figure is the
+//! fullest window of the main limit; the colour changes where the fancy
+//! view's does, and is red once the limit is reached.
+
+use super::{window_rows, Level, Render};
+use crate::api::Snapshot;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+pub struct Tmux;
+
+impl Render for Tmux {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let highest = window_rows(snap, now)
+            .iter()
+            .filter(|row| row.feature.is_none())
+            .filter_map(|row| row.window.as_ref()?.used_percent)
+            .reduce(f64::max);
+        let reached = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .is_some_and(|r| r.limit_reached == Some(true));
+
+        let Some(pct) = highest else {
+            let _ = writeln!(out, "CX —");
+            return;
+        };
+        let level = match reached {
+            true => Level::Critical,
+            false => Level::of(pct),
+        };
+        let _ = writeln!(
+            out,
+            "#[fg={}]CX {}%#[default]",
+            colour(level),
+            pct.clamp(0.0, 100.0).round()
+        );
+    }
+}
+
+/// tmux's names, so the segment follows the terminal's palette
+fn colour(level: Level) -> &'static str {
+    match level {
+        Level::Normal => "green",
+        Level::Warning => "yellow",
+        Level::Critical => "red",
+    }
+}
@@ -0,0 +1,89 @@
+//! `--format waybar`: one line of JSON for a Waybar `custom` module with
+//! `"return-type": "json"`. The text is the fullest window's percentage, the
+//! tooltip lists every window, and the class changes where the fancy view's
+//! colours do, so the bar can be styled from CSS.
+
+use super::{label, window_rows, Level, Render, WindowRow};
+use crate::api::Snapshot;
+use crate::plans;
+use crate::status::age;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+pub struct Waybar;
+
+#[derive(Serialize)]
+struct Module {
+    text: String,
+    tooltip: String,
+    /// `normal`, `warning` or `critical`
+    class: &'static str,
+    /// 0–100, for format-icons
+    percentage: u8,
+}
+
+impl Render for Waybar {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let rows = window_rows(snap, now);
+        let highest = rows
+            .iter()
+            .filter(|row| row.feature.is_none())
+            .filter_map(|row| row.window.as_ref()?.used_percent)
+            .reduce(f64::max);
+        let reached = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .is_some_and(|r| r.limit_reached == Some(true));
+
+        let pct = highest.unwrap_or(0.0).clamp(0.0, 100.0);
+        let shown = pct.round();
+        let module = Module {
+            text: match highest {
+                Some(_) => format!("{shown}%"),
+                None => "—".into(),
+            },
+            tooltip: tooltip(snap, &rows, now).join("\n"),
+            class: match reached {
+                true => Level::Critical.name(),
+                false => Level::of(pct).name(),
+            },
+            percentage: shown as u8,
+        };
+        // A struct of strings and numbers always serialises
+        out.push_str(&serde_json::to_string(&module).unwrap_or_default());
+        out.push('\n');
+    }
+}
+
+/// The plan, every window with its reset, and anything worth a warning, one
+/// line each; prompt formats with a tooltip share it
+pub(super) fn tooltip(snap: &Snapshot, rows: &[WindowRow], now: DateTime<Utc>) -> Vec<String> {
+    let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+    let mut lines = vec![format!("OpenAI {} Plan", plans::display(plan))];
+    for row in rows {
+        let line = match &row.window {
+            None => format!("{}: not available", label(row)),
+            Some(w) => {
+                let pct = w.used_percent.unwrap_or(0.0);
+                let reset = match row.resets_in {
+                    Some(secs) => format!(
+                        ", resets in {}",
+                        age(chrono::Duration::seconds(secs as i64))
+                    ),
+                    None => String::new(),
+                };
+                format!("{}: {pct:.1}%{reset}", label(row))
+            }
+        };
+        lines.push(line);
+    }
+    let rl = snap.usage.rate_limit.as_ref();
+    if rl.is_some_and(|r| r.limit_reached == Some(true)) {
+        lines.push("Limit reached".into());
+    }
+    if let Some(updated) = super::updated(snap, now) {
+        lines.push(format!("Updated {updated}"));
+    }
+    lines
+}
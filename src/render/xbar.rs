@@ -0,0 +1,82 @@
+//! `--format xbar`: an xbar/SwiftBar plugin. The menu bar shows the fullest
+//! window of the main limit; the dropdown below `---` has the plan, every
+//! window with its reset, and a refresh item. Lines turn amber or red where
+//! the fancy view's bars do and otherwise keep the menu's own colour.
+
+use super::fancy::label;
+use super::{window_rows, Level, Render};
+use crate::api::Snapshot;
+use crate::plans;
+use crate::status::age;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+pub struct Xbar;
+
+impl Render for Xbar {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        let rows = window_rows(snap, now);
+        let highest = rows
+            .iter()
+            .filter(|row| row.feature.is_none())
+            .filter_map(|row| row.window.as_ref()?.used_percent)
+            .reduce(f64::max);
+        let reached = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .is_some_and(|r| r.limit_reached == Some(true));
+
+        let _ = match highest {
+            Some(pct) => {
+                let level = match reached {
+                    true => Level::Critical,
+                    false => Level::of(pct),
+                };
+                writeln!(out, "CX {}%{}", pct.round(), style(level))
+            }
+            None => writeln!(out, "CX —"),
+        };
+        out.push_str("---\n");
+
+        let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+        let _ = writeln!(out, "OpenAI {} Plan", plans::display(plan));
+        for row in &rows {
+            let _ = match &row.window {
+                None => writeln!(out, "{}: not available | color=gray", label(row)),
+                Some(w) => {
+                    let pct = w.used_percent.unwrap_or(0.0);
+                    let reset = match row.resets_in {
+                        Some(secs) => format!(
+                            ", resets in {}",
+                            age(chrono::Duration::seconds(secs as i64))
+                        ),
+                        None => String::new(),
+                    };
+                    writeln!(
+                        out,
+                        "{}: {pct:.1}%{reset}{}",
+                        label(row),
+                        style(Level::of(pct))
+                    )
+                }
+            };
+        }
+        if reached {
+            let _ = writeln!(out, "Limit reached{}", style(Level::Critical));
+        }
+        if let Some(updated) = super::updated(snap, now) {
+            let _ = writeln!(out, "Updated {updated} | color=gray");
+        }
+        out.push_str("---\nRefresh | refresh=true\n");
+    }
+}
+
+/// The parameters that colour a line at `level`; none when it is normal
+fn style(level: Level) -> String {
+    let (r, g, b) = level.rgb();
+    match level {
+        Level::Normal => String::new(),
+        _ => format!(" | color=#{r:02x}{g:02x}{b:02x}"),
+    }
+}
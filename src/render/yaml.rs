@@ -0,0 +1,16 @@
+//! `--format yaml`: the `--json` report as YAML, for Ansible facts and other
+//! YAML-native tools. Same keys, same `schema`, same nulls.
+
+use super::json::Report;
+use super::Render;
+use crate::api::Snapshot;
+use chrono::{DateTime, Utc};
+
+pub struct Yaml;
+
+impl Render for Yaml {
+    fn render_into(&self, out: &mut String, snap: &Snapshot, now: DateTime<Utc>) {
+        // Serialising plain data can't fail; the output ends with a newline
+        out.push_str(&serde_yaml_ng::to_string(&Report::new(snap, now)).unwrap_or_default());
+    }
+}
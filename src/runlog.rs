@@ -0,0 +1,165 @@
+//! Run logs: a line per run appended to a file, so calling codex-usage from
+//! cron builds a usage record with no other setup.
+//!
+//! - `--log-csv <FILE>`: a CSV row, under a header written when the file is new
+//! - `--log-ndjson <FILE>`: one JSON object per line for log shippers (vector,
+//!   fluent-bit): the `--json` report plus `timestamp`, `profile` and `error`
+//!
+//! Unlike `history export`, the files are never rewritten and the formats
+//! ignore `--locale`: RFC 3339 UTC times and `.` decimals, whatever the
+//! machine. A failed run still gets a line, with its error code, so gaps are
+//! explained.
+
+use crate::api::{RateWindow, Snapshot};
+use crate::render::Report;
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+pub const CSV_HEADER: &str =
+    "timestamp,profile,plan,five_hour_percent,five_hour_resets_in_seconds,\
+     seven_day_percent,seven_day_resets_in_seconds,limit_reached,error";
+
+/// The CSV row for one run: its snapshot, or the error it ended with
+pub fn csv_row(profile: &str, outcome: &Result<Snapshot>, now: DateTime<Utc>) -> String {
+    let field = |s: &str| match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    };
+    let mut cells = vec![
+        now.to_rfc3339_opts(SecondsFormat::Secs, true),
+        field(profile),
+    ];
+    match outcome {
+        Ok(snap) => {
+            let rl = snap.usage.rate_limit.as_ref();
+            let window = |w: Option<&RateWindow>| {
+                [
+                    w.and_then(|w| w.used_percent)
+                        .map(|p| p.to_string())
+                        .unwrap_or_default(),
+                    w.and_then(|w| w.resets_in(snap.fetched_at, now))
+                        .map(|s| s.to_string())
+                        .unwrap_or_default(),
+                ]
+            };
+            cells.push(field(snap.usage.plan_type.as_deref().unwrap_or("")));
+            cells.extend(window(rl.and_then(|r| r.primary_window.as_ref())));
+            cells.extend(window(rl.and_then(|r| r.secondary_window.as_ref())));
+            cells.push(
+                rl.and_then(|r| r.limit_reached)
+                    .map(|b| b.to_string())
+                    .unwrap_or_default(),
+            );
+            cells.push(String::new());
+        }
+        Err(e) => {
+            cells.extend(std::iter::repeat_n(String::new(), 6));
+            cells.push(crate::errors::classify(e).code.to_string());
+        }
+    }
+    cells.join(",")
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    timestamp: DateTime<Utc>,
+    profile: &'a str,
+    #[serde(flatten)]
+    report: Option<Report<'a>>,
+    error: Option<LineError>,
+}
+
+#[derive(Serialize)]
+struct LineError {
+    code: &'static str,
+    message: String,
+}
+
+/// The NDJSON line for one run, without the newline
+pub fn ndjson_line(profile: &str, outcome: &Result<Snapshot>, now: DateTime<Utc>) -> String {
+    let line = match outcome {
+        Ok(snap) => Line {
+            timestamp: now.trunc_subsecs(0),
+            profile,
+            report: Some(Report::new(snap, now)),
+            error: None,
+        },
+        Err(e) => Line {
+            timestamp: now.trunc_subsecs(0),
+            profile,
+            report: None,
+            error: Some(LineError {
+                code: crate::errors::classify(e).code,
+                message: format!("{e:#}").lines().next().unwrap_or("").into(),
+            }),
+        },
+    };
+    // Serialising plain data can't fail
+    serde_json::to_string(&line).unwrap_or_default()
+}
+
+/// Append `line` to `path`, writing `header` first if the file is new or
+/// empty. One write per line, so overlapping cron runs can't interleave.
+pub fn append(path: &Path, header: Option<&str>, line: &str) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    let mut out = String::new();
+    if let Some(header) = header {
+        if file.metadata().map(|m| m.len() == 0).unwrap_or(false) {
+            out.push_str(header);
+            out.push('\n');
+        }
+    }
+    out.push_str(line);
+    out.push('\n');
+    file.write_all(out.as_bytes())
+        .with_context(|| format!("Could not write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    #[test]
+    fn appends_lines_in_both_formats() {
+        let now = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let usage = serde_json::from_str(include_str!("../tests/fixtures/high.json")).unwrap();
+        let snap = Ok(Snapshot::new(usage, now - chrono::Duration::seconds(100)));
+        let failed = Err(anyhow::anyhow!("Failed to reach ChatGPT API"));
+
+        let ok = csv_row("work", &snap, now);
+        assert_eq!(
+            ok,
+            "2024-05-01T10:00:00Z,work,pro,95,1400,71.2,199900,false,"
+        );
+        let bad = csv_row("work", &failed, now);
+        assert_eq!(bad, "2024-05-01T10:00:00Z,work,,,,,,,E004");
+
+        let line: serde_json::Value =
+            serde_json::from_str(&ndjson_line("work", &snap, now)).unwrap();
+        assert_eq!(line["timestamp"], "2024-05-01T10:00:00Z");
+        assert_eq!(line["schema"], 1);
+        assert_eq!(line["five_hour"]["resets_in_seconds"], 1400);
+        assert!(line["error"].is_null());
+        let line: serde_json::Value =
+            serde_json::from_str(&ndjson_line("work", &failed, now)).unwrap();
+        assert_eq!(line["error"]["code"], "E004");
+        assert!(line.get("five_hour").is_none());
+
+        let dir = std::env::temp_dir().join(format!("codex-usage-runlog-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("usage.csv");
+        append(&path, Some(CSV_HEADER), &ok).unwrap();
+        append(&path, Some(CSV_HEADER), &bad).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, format!("{CSV_HEADER}\n{ok}\n{bad}\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
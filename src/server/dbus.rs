@@ -0,0 +1,209 @@
+//! `daemon --dbus`: the daemon's figures on the session bus as
+//! `org.codexusage.Monitor`, for desktop widgets. Each profile is an object
+//! at `/org/codexusage/Monitor/<profile>` with read-only properties, a
+//! `Refresh()` method that polls it early, and a `Changed` signal (with
+//! `PropertiesChanged`) whenever its usage does.
+
+use super::State;
+use anyhow::Result;
+use std::sync::Arc;
+
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+pub use bus::publish;
+
+#[cfg(not(all(target_os = "linux", feature = "dbus")))]
+pub fn publish(_state: &Arc<State>) -> Result<()> {
+    anyhow::bail!("This build of codex-usage was compiled without the `dbus` feature (Linux only)")
+}
+
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+mod bus {
+    use super::*;
+    use crate::api::{RateWindow, Snapshot};
+    use anyhow::Context;
+    use std::collections::HashMap;
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::Value;
+
+    const BUS_NAME: &str = "org.codexusage.Monitor";
+    const INTERFACE: &str = "org.codexusage.Monitor";
+    const ROOT: &str = "/org/codexusage/Monitor";
+
+    /// Every property, for `PropertiesChanged` to invalidate at once
+    const PROPERTIES: &[&str] = &[
+        "Profile",
+        "Plan",
+        "FiveHourPercent",
+        "SevenDayPercent",
+        "FiveHourResetsAt",
+        "SevenDayResetsAt",
+        "LimitReached",
+        "FetchedAt",
+        "Error",
+    ];
+
+    struct Monitor {
+        state: Arc<State>,
+        profile: String,
+    }
+
+    /// Numbers that aren't known yet read as -1 (percentages) or 0 (times),
+    /// as D-Bus has no null
+    #[zbus::interface(name = "org.codexusage.Monitor")]
+    impl Monitor {
+        /// Poll this profile now rather than at the next interval
+        fn refresh(&self) {
+            self.state.refresh(std::slice::from_ref(&self.profile));
+        }
+
+        /// Usage changed; read the properties again
+        #[zbus(signal)]
+        async fn changed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn profile(&self) -> String {
+            self.profile.clone()
+        }
+
+        #[zbus(property)]
+        fn plan(&self) -> String {
+            self.snapshot()
+                .and_then(|s| s.usage.plan_type)
+                .map(|p| crate::plans::display(&p))
+                .unwrap_or_default()
+        }
+
+        #[zbus(property)]
+        fn five_hour_percent(&self) -> f64 {
+            self.percent(|s| s.usage.rate_limit?.primary_window)
+        }
+
+        #[zbus(property)]
+        fn seven_day_percent(&self) -> f64 {
+            self.percent(|s| s.usage.rate_limit?.secondary_window)
+        }
+
+        /// Unix seconds
+        #[zbus(property)]
+        fn five_hour_resets_at(&self) -> i64 {
+            self.resets_at(|s| s.usage.rate_limit?.primary_window)
+        }
+
+        /// Unix seconds
+        #[zbus(property)]
+        fn seven_day_resets_at(&self) -> i64 {
+            self.resets_at(|s| s.usage.rate_limit?.secondary_window)
+        }
+
+        #[zbus(property)]
+        fn limit_reached(&self) -> bool {
+            self.snapshot()
+                .and_then(|s| s.usage.rate_limit?.limit_reached)
+                .unwrap_or(false)
+        }
+
+        /// Unix seconds
+        #[zbus(property)]
+        fn fetched_at(&self) -> i64 {
+            self.snapshot().map_or(0, |s| s.fetched_at.timestamp())
+        }
+
+        /// The last poll's failure; empty while polls succeed
+        #[zbus(property)]
+        fn error(&self) -> String {
+            let latest = self.state.latest.lock().unwrap();
+            latest
+                .get(&self.profile)
+                .and_then(|l| l.error.clone())
+                .unwrap_or_default()
+        }
+    }
+
+    impl Monitor {
+        fn snapshot(&self) -> Option<Snapshot> {
+            let latest = self.state.latest.lock().unwrap();
+            latest.get(&self.profile)?.snapshot.clone()
+        }
+
+        fn percent(&self, window: impl Fn(Snapshot) -> Option<RateWindow>) -> f64 {
+            self.snapshot()
+                .and_then(window)
+                .and_then(|w| w.used_percent)
+                .unwrap_or(-1.0)
+        }
+
+        fn resets_at(&self, window: impl Fn(Snapshot) -> Option<RateWindow>) -> i64 {
+            let Some(snap) = self.snapshot() else {
+                return 0;
+            };
+            let fetched_at = snap.fetched_at;
+            window(snap)
+                .and_then(|w| w.resets_in(fetched_at, fetched_at))
+                .map_or(0, |secs| fetched_at.timestamp() + secs as i64)
+        }
+    }
+
+    /// Take the bus name, serve an object per profile, and signal each of
+    /// them as its usage changes
+    pub fn publish(state: &Arc<State>) -> Result<()> {
+        let mut builder = zbus::blocking::connection::Builder::session()?.name(BUS_NAME)?;
+        for profile in state.profiles.keys() {
+            let monitor = Monitor {
+                state: Arc::clone(state),
+                profile: profile.clone(),
+            };
+            builder = builder.serve_at(object_path(profile), monitor)?;
+        }
+        let conn = builder
+            .build()
+            .with_context(|| format!("Could not publish {BUS_NAME} on the session bus"))?;
+
+        let updates = state.subscribe();
+        std::thread::spawn(move || {
+            for update in updates {
+                let path = object_path(&update.profile);
+                let _ = conn.emit_signal(None::<&str>, path.as_str(), INTERFACE, "Changed", &());
+                let changed: HashMap<&str, Value> = HashMap::new();
+                let _ = conn.emit_signal(
+                    None::<&str>,
+                    path.as_str(),
+                    "org.freedesktop.DBus.Properties",
+                    "PropertiesChanged",
+                    &(INTERFACE, changed, PROPERTIES),
+                );
+            }
+        });
+        Ok(())
+    }
+
+    /// `ROOT/<profile>`, with anything an object path can't hold written as
+    /// `_xx` hex, as systemd does
+    fn object_path(profile: &str) -> String {
+        let mut path = format!("{ROOT}/");
+        for b in profile.bytes() {
+            match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => path.push(b as char),
+                _ => path.push_str(&format!("_{b:02x}")),
+            }
+        }
+        if profile.is_empty() {
+            path.push('_');
+        }
+        path
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn profile_object_paths() {
+            assert_eq!(object_path("work"), "/org/codexusage/Monitor/work");
+            assert_eq!(
+                object_path("my-team.2"),
+                "/org/codexusage/Monitor/my_2dteam_2e2"
+            );
+            assert_eq!(object_path(""), "/org/codexusage/Monitor/_");
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! The daemon's state file. While `serve` runs it rewrites `daemon.json`
+//! every few seconds with a heartbeat and each profile's last poll, so
+//! `codex-usage status` can tell a live daemon from one that died or hung,
+//! and say why a status bar fed by it stopped moving.
+
+use super::State;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often the state file is rewritten
+pub const EVERY: Duration = Duration::from_secs(10);
+
+/// Heartbeats this many intervals old mean the daemon is gone
+pub const MISSED_BEATS: u32 = 3;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub pid: u32,
+    pub listen: String,
+    pub started_at: DateTime<Utc>,
+    pub beat_at: DateTime<Utc>,
+    pub poll_seconds: u64,
+    /// Polls paced by usage, `poll_seconds` being only the base
+    #[serde(default)]
+    pub adaptive: bool,
+    pub profiles: BTreeMap<String, ProfileBeat>,
+}
+
+/// What the poller last saw for one profile
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProfileBeat {
+    /// When the data being served was fetched
+    pub fetched_at: Option<DateTime<Utc>>,
+    pub polled_at: Option<DateTime<Utc>>,
+    /// The last poll's error, if it failed
+    pub error: Option<String>,
+}
+
+pub fn path() -> PathBuf {
+    crate::paths::data_dir().join("daemon.json")
+}
+
+/// Rewrite the state file every [`EVERY`] for as long as the server runs.
+/// A failed write is retried on the next beat rather than stopping the
+/// server.
+pub fn run(state: &State, listen: &str) {
+    let started_at = Utc::now();
+    loop {
+        let beat = Heartbeat {
+            pid: std::process::id(),
+            listen: listen.to_string(),
+            started_at,
+            beat_at: Utc::now(),
+            poll_seconds: state.pacing.base.as_secs(),
+            adaptive: state.pacing.adaptive,
+            profiles: state
+                .latest
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, latest)| {
+                    let beat = ProfileBeat {
+                        fetched_at: latest.snapshot.as_ref().map(|s| s.fetched_at),
+                        polled_at: latest.polled_at,
+                        error: latest.error.clone(),
+                    };
+                    (name.clone(), beat)
+                })
+                .collect(),
+        };
+        let _ = write(&beat);
+        std::thread::sleep(EVERY);
+    }
+}
+
+fn write(beat: &Heartbeat) -> Result<()> {
+    let path = path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Replace by rename so `status` never reads half a file
+    let tmp = path.with_extension(format!("json.{}", std::process::id()));
+    std::fs::write(&tmp, serde_json::to_vec_pretty(beat)?)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// The last heartbeat written, if a daemon has ever run
+pub fn read() -> Result<Option<Heartbeat>> {
+    let path = path();
+    match std::fs::read(&path) {
+        Ok(raw) => serde_json::from_slice(&raw)
+            .map(Some)
+            .with_context(|| format!("Could not parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Could not read {}", path.display())),
+    }
+}
+
+impl Heartbeat {
+    /// Whether the daemon was still beating at `now`
+    pub fn alive(&self, now: DateTime<Utc>) -> bool {
+        let limit = chrono::Duration::from_std(EVERY * MISSED_BEATS).unwrap_or_default();
+        now - self.beat_at <= limit
+    }
+
+    /// Whether a profile's data is older than two polls should allow
+    pub fn stale(&self, beat: &ProfileBeat, now: DateTime<Utc>) -> bool {
+        let longest = match self.adaptive {
+            true => self.poll_seconds * crate::pacing::LIMIT_BACKOFF as u64,
+            false => self.poll_seconds,
+        };
+        let allowed = chrono::Duration::seconds(2 * longest as i64 + 30);
+        beat.fetched_at.is_none_or(|at| now - at > allowed)
+    }
+}
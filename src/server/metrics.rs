@@ -0,0 +1,102 @@
+//! `/metrics`: the profiles a caller may read, in the Prometheus text format,
+//! so `serve` doubles as an exporter. Each sample is labelled with its
+//! profile and the server's labels. A failed poll keeps the last snapshot, so
+//! `codex_usage_up` says whether the latest poll of each profile worked.
+
+use super::Latest;
+use crate::labels::{self, Labels};
+use crate::render::prometheus::{gauge, label_set, write_metrics, RESERVED_LABELS};
+use chrono::{DateTime, Utc};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub fn render(labels: &Labels, profiles: &[(String, Latest)], now: DateTime<Utc>) -> String {
+    let with_profile = |name| profile_labels(labels, name);
+
+    let mut out = String::new();
+    let series: Vec<_> = profiles
+        .iter()
+        .filter_map(|(name, latest)| Some((with_profile(name), latest.snapshot.as_ref()?)))
+        .collect();
+    write_metrics(&mut out, &series, now);
+
+    let polled: Vec<_> = profiles
+        .iter()
+        .filter_map(|(name, latest)| {
+            Some((label_set(&with_profile(name)), latest.polled_at?, latest))
+        })
+        .collect();
+    gauge(
+        &mut out,
+        "codex_usage_up",
+        "1 if the last poll of the profile succeeded, else 0",
+        polled
+            .iter()
+            .map(|(l, _, latest)| (l.clone(), Some(f64::from(u8::from(latest.error.is_none())))))
+            .collect(),
+    );
+    gauge(
+        &mut out,
+        "codex_usage_last_poll_timestamp_seconds",
+        "When the profile was last polled, as a Unix time",
+        polled
+            .iter()
+            .map(|(l, at, _)| (l.clone(), Some(at.timestamp() as f64)))
+            .collect(),
+    );
+    out
+}
+
+/// `profile` and then the server's labels, less any the metrics set
+fn profile_labels<'a>(labels: &'a Labels, name: &'a str) -> Vec<(&'a str, &'a str)> {
+    let mut all = vec![("profile", name)];
+    all.extend(labels::extra(labels, RESERVED_LABELS));
+    all
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Snapshot;
+    use crate::clock::parse_timestamp;
+
+    #[test]
+    fn one_family_across_profiles() {
+        let now = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let usage = serde_json::from_str(include_str!("../../tests/fixtures/high.json")).unwrap();
+        let ok = Latest {
+            snapshot: Some(Snapshot::new(usage, now)),
+            error: None,
+            polled_at: Some(now),
+        };
+        let failing = Latest {
+            error: Some("Failed to reach ChatGPT API".into()),
+            ..ok.clone()
+        };
+        let labels = Labels::from([
+            ("team".to_string(), "platform".to_string()),
+            ("plan".to_string(), "ignored".to_string()),
+        ]);
+        let out = render(
+            &labels,
+            &[
+                ("personal".into(), ok),
+                ("work".into(), failing),
+                ("new".into(), Latest::default()),
+            ],
+            now,
+        );
+        assert_eq!(
+            out.matches("# TYPE codex_usage_primary_used_percent gauge")
+                .count(),
+            1
+        );
+        assert!(out.contains(
+            "codex_usage_primary_used_percent{profile=\"personal\",team=\"platform\",plan=\"pro\"} 95\n\
+             codex_usage_primary_used_percent{profile=\"work\",team=\"platform\",plan=\"pro\"} 95\n"
+        ));
+        assert!(out.contains("codex_usage_up{profile=\"work\",team=\"platform\"} 0\n"));
+        assert!(!out.contains("profile=\"new\""));
+        assert!(!out.contains("ignored"));
+    }
+}
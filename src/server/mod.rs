@@ -0,0 +1,862 @@
+//! `serve`: a small read-only HTTP server exposing usage as JSON.
+//!
+//! A background poller per profile keeps the latest snapshot fresh; routes
+//! only ever read that state, apart from `POST /refresh`, which nudges the
+//! pollers to run early. Clients listed under `[[server.clients]]`
+//! authenticate with their own bearer token and only see the profiles they
+//! are granted. `/metrics` serves the same state to Prometheus. A heartbeat
+//! in the state file lets `status` check on it.
+
+// Only the Unix socket daemon publishes on the bus
+#[cfg(unix)]
+mod dbus;
+pub mod heartbeat;
+mod http;
+mod metrics;
+mod overlay;
+pub mod socket;
+mod sse;
+mod ws;
+
+use crate::api::{self, Snapshot};
+use crate::config::{Config, Profile, ServerClient};
+use crate::labels::{self, Labels};
+use crate::pacing::Pacing;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use http::{Request, Response};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8787";
+
+/// Default address with `--prometheus`, the port exporters conventionally use
+pub const DEFAULT_METRICS_LISTEN: &str = "127.0.0.1:9187";
+
+/// Default seconds between polls of each profile
+const DEFAULT_POLL_SECS: u64 = 60;
+
+/// Nudged polls of one profile are spaced at least this far apart, so a busy
+/// webhook can't hammer the usage API
+const MIN_REFRESH_GAP: Duration = Duration::from_secs(5);
+
+/// Connections handled at once, websockets and event streams included;
+/// beyond this a new one is turned away with 503
+const MAX_CONNECTIONS: usize = 128;
+
+/// Longest a client may take to send its whole request head and body
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the poller last saw for one profile
+#[derive(Debug, Default, Clone)]
+struct Latest {
+    /// Most recent successful snapshot
+    snapshot: Option<Snapshot>,
+    /// Error from the most recent poll, if it failed
+    error: Option<String>,
+    polled_at: Option<chrono::DateTime<Utc>>,
+}
+
+/// A snapshot pushed to live subscribers
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub profile: String,
+    pub snapshot: Snapshot,
+    pub body: serde_json::Value,
+}
+
+pub struct State {
+    profiles: BTreeMap<String, Profile>,
+    /// Added to every profile body, e.g. pod, namespace, team
+    labels: Labels,
+    /// (bearer token, client) pairs; empty means unauthenticated loopback mode
+    clients: Vec<(String, ServerClient)>,
+    pacing: Pacing,
+    latest: Mutex<BTreeMap<String, Latest>>,
+    subscribers: Mutex<Vec<Sender<Update>>>,
+    /// Profiles asked to re-poll early by `POST /refresh`
+    nudged: Mutex<BTreeSet<String>>,
+    nudge: Condvar,
+}
+
+pub fn serve(
+    mut config: Config,
+    listen: Option<&str>,
+    label_flags: &[String],
+    prometheus: bool,
+) -> Result<()> {
+    let listen = listen
+        .map(str::to_string)
+        .or_else(|| config.server.listen.clone())
+        .unwrap_or_else(|| match prometheus {
+            true => DEFAULT_METRICS_LISTEN.to_string(),
+            false => DEFAULT_LISTEN.to_string(),
+        });
+    let labels = labels::resolve(&config.server.labels, label_flags)?;
+
+    let mut clients = Vec::new();
+    for client in std::mem::take(&mut config.server.clients) {
+        let Some(token) = client.resolve_token() else {
+            bail!("Server client '{}' has no token configured", client.name);
+        };
+        clients.push((token, client));
+    }
+    if clients.is_empty() && !is_loopback(&listen) {
+        bail!(
+            "Refusing to serve usage on {listen} without authentication.\n\
+             Add [[server.clients]] entries to {}, or listen on 127.0.0.1",
+            Config::path().display()
+        );
+    }
+
+    let listener =
+        TcpListener::bind(&listen).with_context(|| format!("Could not listen on {listen}"))?;
+    let state = start(config, labels, clients, &listen)?;
+    eprintln!(
+        "codex-usage serving {} profile(s) on http://{listen}/{}",
+        state.profiles.len(),
+        if prometheus {
+            "metrics"
+        } else {
+            "usage/<profile>"
+        }
+    );
+
+    let open = Arc::new(AtomicUsize::new(0));
+    for mut stream in listener.incoming().flatten() {
+        let Some(slot) = Slot::take(&open) else {
+            let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+            let _ = Response::json(503, &json!({ "error": "too many connections" }))
+                .with_header("Retry-After", "5")
+                .write_to(&mut stream, false);
+            continue;
+        };
+        let state = Arc::clone(&state);
+        std::thread::spawn(move || {
+            handle_connection(stream, &state);
+            drop(slot);
+        });
+    }
+    Ok(())
+}
+
+/// One of the `MAX_CONNECTIONS`, given back when its connection ends
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(open: &Arc<AtomicUsize>) -> Option<Slot> {
+        if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            open.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Slot(Arc::clone(open)))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Start polling every configured profile in the background, with the
+/// heartbeat, Discord and alerting that ride on it. `listen` is where the
+/// heartbeat says the daemon can be reached.
+fn start(
+    config: Config,
+    labels: Labels,
+    clients: Vec<(String, ServerClient)>,
+    listen: &str,
+) -> Result<Arc<State>> {
+    let mut profiles = config.profiles;
+    if profiles.is_empty() {
+        profiles.insert(
+            crate::config::DEFAULT_PROFILE.to_string(),
+            Profile::default(),
+        );
+    }
+    let alerts =
+        crate::alerting::configure(&config.alerting, &profiles.keys().collect::<Vec<_>>())?;
+
+    let state = Arc::new(State {
+        latest: Mutex::new(
+            profiles
+                .keys()
+                .map(|p| (p.clone(), Latest::default()))
+                .collect(),
+        ),
+        profiles,
+        labels,
+        clients,
+        pacing: Pacing {
+            base: Duration::from_secs(
+                config
+                    .server
+                    .poll_seconds
+                    .unwrap_or(DEFAULT_POLL_SECS)
+                    .max(1),
+            ),
+            adaptive: config.server.adaptive,
+        },
+        subscribers: Mutex::new(Vec::new()),
+        nudged: Mutex::new(BTreeSet::new()),
+        nudge: Condvar::new(),
+    });
+
+    if let Some(client_id) = config.discord.client_id {
+        let profile = config
+            .discord
+            .profile
+            .unwrap_or_else(|| state.profiles.keys().next().cloned().unwrap_or_default());
+        // Subscribe before the pollers start so the first snapshot is seen
+        let updates = state.subscribe();
+        std::thread::spawn(move || discord_loop(&client_id, &profile, updates));
+    }
+    if !alerts.is_empty() {
+        let updates = state.subscribe();
+        std::thread::spawn(move || crate::alerting::run(&alerts, updates));
+    }
+
+    {
+        let state = Arc::clone(&state);
+        let listen = listen.to_string();
+        std::thread::spawn(move || heartbeat::run(&state, &listen));
+    }
+    for name in state.profiles.keys() {
+        let name = name.clone();
+        let state = Arc::clone(&state);
+        std::thread::spawn(move || poll_loop(&state, &name));
+    }
+    Ok(state)
+}
+
+// ─── Polling ──────────────────────────────────────────────────────────────────
+
+fn poll_loop(state: &State, name: &str) {
+    loop {
+        let polled_at = Instant::now();
+        poll_once(state, name);
+        state.wait_for_poll(name, polled_at);
+    }
+}
+
+fn poll_once(state: &State, name: &str) {
+    let result = state.profiles[name]
+        .credentials()
+        .and_then(|creds| api::fetch_usage(&creds))
+        .map(|usage| Snapshot::new(usage, Utc::now()));
+
+    let changed = {
+        let mut latest = state.latest.lock().unwrap();
+        let entry = latest.entry(name.to_string()).or_default();
+        entry.polled_at = Some(Utc::now());
+        match result {
+            Ok(snap) => {
+                let changed = entry
+                    .snapshot
+                    .as_ref()
+                    .is_none_or(|prev| !prev.usage.same_state(&snap.usage));
+                entry.snapshot = Some(snap);
+                entry.error = None;
+                changed.then(|| entry.snapshot.clone()).flatten()
+            }
+            Err(e) => {
+                entry.error = Some(e.to_string());
+                None
+            }
+        }
+    };
+
+    if let Some(snap) = changed {
+        broadcast(
+            state,
+            Update {
+                profile: name.to_string(),
+                body: state.profile_json(name, &snap),
+                snapshot: snap,
+            },
+        );
+    }
+}
+
+/// Push an update to every live subscriber, dropping the ones that have gone
+fn broadcast(state: &State, update: Update) {
+    state
+        .subscribers
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(update.clone()).is_ok());
+}
+
+impl State {
+    /// Register for pushed updates
+    pub fn subscribe(&self) -> std::sync::mpsc::Receiver<Update> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Sleep until the next poll of `name` is due: the interval after
+    /// `polled_at` as paced by what it found, or sooner if a refresh is
+    /// requested
+    fn wait_for_poll(&self, name: &str, polled_at: Instant) {
+        let current = {
+            let latest = self.latest.lock().unwrap();
+            latest
+                .get(name)
+                .filter(|l| l.error.is_none())
+                .and_then(|l| l.snapshot.clone())
+        };
+        let deadline = polled_at + self.pacing.after(current.as_ref(), Utc::now());
+        let mut nudged = self.nudged.lock().unwrap();
+        loop {
+            if nudged.remove(name) {
+                drop(nudged);
+                let earliest = polled_at + MIN_REFRESH_GAP;
+                std::thread::sleep(earliest.saturating_duration_since(Instant::now()));
+                return;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return;
+            }
+            nudged = self.nudge.wait_timeout(nudged, left).unwrap().0;
+        }
+    }
+
+    /// Ask the pollers for `names` to run now
+    pub fn refresh(&self, names: &[String]) {
+        self.nudged.lock().unwrap().extend(names.iter().cloned());
+        self.nudge.notify_all();
+    }
+
+    /// The JSON body served for one profile
+    fn profile_json(&self, name: &str, snap: &Snapshot) -> serde_json::Value {
+        let mut body = json!({
+            "profile": name,
+            "fetched_at": snap.fetched_at,
+            "usage": snap.usage,
+        });
+        if !self.labels.is_empty() {
+            body["labels"] = json!(self.labels);
+        }
+        body
+    }
+
+    /// Current snapshot bodies for the given profiles
+    pub fn current(&self, names: &[String]) -> Vec<Update> {
+        let latest = self.latest.lock().unwrap();
+        names
+            .iter()
+            .filter_map(|n| {
+                let snap = latest.get(n)?.snapshot.as_ref()?;
+                Some(Update {
+                    profile: n.clone(),
+                    body: self.profile_json(n, snap),
+                    snapshot: snap.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Mirror one profile into Discord Rich Presence, reconnecting as Discord
+/// comes and goes
+fn discord_loop(client_id: &str, profile: &str, updates: std::sync::mpsc::Receiver<Update>) {
+    let mut presence: Option<crate::discord::Presence> = None;
+    for update in updates {
+        if update.profile != profile {
+            continue;
+        }
+        if presence.is_none() {
+            presence = crate::discord::Presence::connect(client_id).ok();
+        }
+        if let Some(p) = presence.as_mut() {
+            if p.update(&update.snapshot).is_err() {
+                presence = None;
+            }
+        }
+    }
+}
+
+// ─── Routing ──────────────────────────────────────────────────────────────────
+
+fn handle_connection(stream: TcpStream, state: &State) {
+    let Ok(read_half) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(http::Deadline::new(read_half, READ_TIMEOUT));
+    let Ok(request) = Request::read(&mut reader) else {
+        return;
+    };
+    let mut stream = stream;
+
+    let head_only = request.method == "HEAD";
+    let response = match dispatch(&request, state) {
+        Reply::Response(response) => response,
+        // The stream takes over the connection from here
+        Reply::Websocket(names) => return ws::serve(stream, &request, state, names),
+        Reply::Events { profiles, cors } => return sse::serve(stream, state, profiles, cors),
+    };
+    let _ = response.write_to(&mut stream, head_only);
+}
+
+/// What a request gets: a response, or its connection turned into a stream
+/// of these profiles' updates
+enum Reply {
+    Response(Response),
+    Websocket(Vec<String>),
+    /// `cors` when a client token was presented, so pages elsewhere may read
+    Events {
+        profiles: Vec<String>,
+        cors: bool,
+    },
+}
+
+fn dispatch(request: &Request, state: &State) -> Reply {
+    let response = match (request.method.as_str(), request.path.as_str()) {
+        ("GET" | "HEAD", "/healthz") => Response::text(200, "ok"),
+        ("GET", "/ws") => match authorise(request, state) {
+            Err(resp) => resp,
+            Ok(client) => {
+                let names = readable_profiles(state, client, request.query_param("profile"));
+                return Reply::Websocket(names);
+            }
+        },
+        ("GET", "/events") => match authorise(request, state) {
+            Err(resp) => resp,
+            Ok(client) => {
+                let profiles = readable_profiles(state, client, request.query_param("profile"));
+                let cors = client.is_some();
+                return Reply::Events { profiles, cors };
+            }
+        },
+        ("GET" | "HEAD", path) => match authorise(request, state) {
+            Err(resp) => resp,
+            Ok(client) => route(path, request, client, state),
+        },
+        ("POST", "/refresh") => match authorise(request, state) {
+            Err(resp) => resp,
+            Ok(client) => {
+                let names = readable_profiles(state, client, request.query_param("profile"));
+                // Unknown and forbidden look the same, so names can't be probed
+                if names.is_empty() {
+                    Response::json(403, &json!({ "error": "not allowed to refresh that" }))
+                } else {
+                    state.refresh(&names);
+                    Response::new(204, "text/plain", "")
+                }
+            }
+        },
+        _ => Response::json(
+            405,
+            &json!({ "error": "read-only server: use GET (or POST /refresh)" }),
+        ),
+    };
+    Reply::Response(response)
+}
+
+fn route(path: &str, request: &Request, client: Option<&ServerClient>, state: &State) -> Response {
+    match path.trim_end_matches('/') {
+        p if p == "/badge" || p.starts_with("/badge/") => {
+            let names = readable_profiles(state, client, None);
+            let name = match p.strip_prefix("/badge/") {
+                Some(n) => n.to_string(),
+                None => names.first().cloned().unwrap_or_default(),
+            };
+            if !names.contains(&name) {
+                return Response::json(404, &json!({ "error": "no such profile" }));
+            }
+            match state
+                .latest
+                .lock()
+                .unwrap()
+                .get(&name)
+                .and_then(|l| l.snapshot.as_ref())
+            {
+                Some(snap) => Response::json(200, &crate::badge::shields(snap)),
+                None => Response::json(503, &json!({ "error": "not fetched yet" })),
+            }
+        }
+        "/overlay" => {
+            let names = readable_profiles(state, client, request.query_param("profile"));
+            if names.is_empty() {
+                return Response::json(404, &json!({ "error": "no such profile" }));
+            }
+            let latest = state.latest.lock().unwrap();
+            let snaps: Vec<_> = names
+                .into_iter()
+                .map(|n| {
+                    let snap = latest.get(&n).and_then(|l| l.snapshot.clone());
+                    (n, snap)
+                })
+                .collect();
+            let key = request.query_param("key").unwrap_or("green");
+            let html = overlay::render(&snaps, key, state.pacing.base.as_secs().max(5));
+            Response::new(200, "text/html; charset=utf-8", html)
+                .with_header("Cache-Control", "no-store")
+        }
+        "/metrics" => {
+            let names = readable_profiles(state, client, request.query_param("profile"));
+            let profiles: Vec<_> = {
+                let latest = state.latest.lock().unwrap();
+                names
+                    .into_iter()
+                    .map(|n| {
+                        let l = latest.get(&n).cloned().unwrap_or_default();
+                        (n, l)
+                    })
+                    .collect()
+            };
+            let body = metrics::render(&state.labels, &profiles, Utc::now());
+            Response::new(200, metrics::CONTENT_TYPE, body)
+        }
+        "/usage" => {
+            let names = readable_profiles(state, client, None);
+            Response::json(200, &json!({ "profiles": names }))
+        }
+        p if p.starts_with("/usage/") => {
+            let name = &p["/usage/".len()..];
+            // Unknown and forbidden look the same, so names can't be probed
+            if !readable_profiles(state, client, None)
+                .iter()
+                .any(|n| n == name)
+            {
+                return Response::json(404, &json!({ "error": "no such profile" }));
+            }
+            let latest = state
+                .latest
+                .lock()
+                .unwrap()
+                .get(name)
+                .cloned()
+                .unwrap_or_default();
+            match (latest.snapshot, latest.error) {
+                (Some(snap), _) => Response::json(200, &state.profile_json(name, &snap)),
+                (None, Some(e)) => Response::json(502, &json!({ "error": e })),
+                (None, None) => Response::json(503, &json!({ "error": "not fetched yet" }))
+                    .with_header("Retry-After", "5"),
+            }
+        }
+        _ => Response::json(404, &json!({ "error": "not found" })),
+    }
+}
+
+/// Profiles `client` may read, optionally narrowed to one
+fn readable_profiles(
+    state: &State,
+    client: Option<&ServerClient>,
+    only: Option<&str>,
+) -> Vec<String> {
+    state
+        .profiles
+        .keys()
+        .filter(|p| client.is_none_or(|c| c.may_read(p)))
+        .filter(|p| only.is_none_or(|o| o == p.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Resolve the caller to a client. `Ok(None)` means open loopback mode,
+/// where only this machine's own pages may call: browsers send `Origin` with
+/// websocket upgrades, event streams and POSTs, and without the check any
+/// site could read usage off localhost or fire refreshes. `Host` must name
+/// this machine too, or a site could rebind its own name to 127.0.0.1 and
+/// call as a same-origin page, sending no `Origin` at all. Browsers can't
+/// set headers on WebSocket requests, so `?access_token=` is accepted as
+/// well as `Authorization: Bearer`.
+fn authorise<'a>(
+    request: &Request,
+    state: &'a State,
+) -> Result<Option<&'a ServerClient>, Response> {
+    if state.clients.is_empty() {
+        if !request.header("Host").is_some_and(local_host) {
+            return Err(Response::json(
+                403,
+                &json!({ "error": "Host must be localhost or a loopback address" }),
+            ));
+        }
+        if request.header("Origin").is_some_and(|o| !local_origin(o)) {
+            return Err(Response::json(
+                403,
+                &json!({ "error": "cross-origin requests need a [[server.clients]] token" }),
+            ));
+        }
+        return Ok(None);
+    }
+    let presented = request
+        .header("Authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| request.query_param("access_token"))
+        .map(str::trim);
+    let Some(presented) = presented else {
+        return Err(
+            Response::json(401, &json!({ "error": "bearer token required" }))
+                .with_header("WWW-Authenticate", "Bearer"),
+        );
+    };
+    state
+        .clients
+        .iter()
+        .find(|(token, _)| constant_time_eq(token.as_bytes(), presented.as_bytes()))
+        .map(|(_, client)| Some(client))
+        .ok_or_else(|| Response::json(403, &json!({ "error": "unknown token" })))
+}
+
+/// Whether an `Origin` such as `http://127.0.0.1:8787` is on this machine;
+/// `null`, sent by sandboxed pages and files, is not
+fn local_origin(origin: &str) -> bool {
+    local_host(origin.split_once("://").map_or(origin, |(_, rest)| rest))
+}
+
+/// Whether a `Host` such as `localhost:8787` or `[::1]` is this machine
+fn local_host(host: &str) -> bool {
+    let host = match host.trim().strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn is_loopback(listen: &str) -> bool {
+    listen
+        .parse::<std::net::SocketAddr>()
+        .map(|a| a.ip().is_loopback())
+        .unwrap_or_else(|_| listen.starts_with("localhost:"))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+impl State {
+    /// `profiles`, none polled yet, served to `clients`
+    fn for_tests(profiles: &[&str], clients: Vec<(String, ServerClient)>) -> State {
+        State {
+            profiles: profiles
+                .iter()
+                .map(|p| (p.to_string(), Profile::default()))
+                .collect(),
+            labels: Default::default(),
+            clients,
+            pacing: Pacing {
+                base: Duration::from_secs(60),
+                adaptive: false,
+            },
+            latest: Mutex::new(
+                profiles
+                    .iter()
+                    .map(|p| (p.to_string(), Latest::default()))
+                    .collect(),
+            ),
+            subscribers: Mutex::new(Vec::new()),
+            nudged: Mutex::new(BTreeSet::new()),
+            nudge: Condvar::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `head`, addressed to this machine unless it names another `Host`
+    fn request(head: &str) -> Request {
+        let host = match head.contains("\r\nHost:") {
+            true => "",
+            false => "\r\nHost: 127.0.0.1:8787",
+        };
+        let raw = format!("{head}{host}\r\n\r\n");
+        Request::read(&mut BufReader::new(raw.as_bytes())).unwrap()
+    }
+
+    fn client(name: &str, profiles: &[&str]) -> ServerClient {
+        ServerClient {
+            name: name.into(),
+            profiles: profiles.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn status(reply: Reply) -> u16 {
+        match reply {
+            Reply::Response(r) => r.status,
+            _ => 101,
+        }
+    }
+
+    #[test]
+    fn clients_read_only_their_profiles() {
+        let state = State::for_tests(
+            &["work", "home"],
+            vec![
+                ("tok-work".into(), client("kitchen", &["work"])),
+                ("tok-all".into(), client("ops", &["*"])),
+            ],
+        );
+        let get = |head: &str| match dispatch(&request(head), &state) {
+            Reply::Response(r) => (r.status, String::from_utf8(r.body).unwrap()),
+            _ => panic!("not a plain response"),
+        };
+        assert_eq!(get("GET /usage HTTP/1.1").0, 401);
+        assert_eq!(
+            get("GET /usage HTTP/1.1\r\nAuthorization: Bearer tok-wor").0,
+            403
+        );
+        assert_eq!(
+            get("GET /usage HTTP/1.1\r\nAuthorization: Bearer tok-work2").0,
+            403
+        );
+        assert_eq!(
+            get("GET /usage HTTP/1.1\r\nAuthorization: Bearer tok-work"),
+            (200, r#"{"profiles":["work"]}"#.into())
+        );
+        assert_eq!(
+            get("GET /usage?access_token=tok-all HTTP/1.1"),
+            (200, r#"{"profiles":["home","work"]}"#.into())
+        );
+        // Forbidden reads as missing; allowed but not yet polled is 503
+        assert_eq!(get("GET /usage/home?access_token=tok-work HTTP/1.1").0, 404);
+        assert_eq!(get("GET /usage/work?access_token=tok-work HTTP/1.1").0, 503);
+        assert_eq!(get("GET /healthz HTTP/1.1").0, 200);
+
+        // No clients: open to everything on this machine
+        let open = State::for_tests(&["work", "home"], Vec::new());
+        match dispatch(&request("GET /usage HTTP/1.1"), &open) {
+            Reply::Response(r) => assert_eq!(r.body, br#"{"profiles":["home","work"]}"#),
+            _ => panic!("not a plain response"),
+        }
+    }
+
+    #[test]
+    fn refresh_needs_a_token_for_the_profile() {
+        let state = State::for_tests(
+            &["work", "home"],
+            vec![("tok-work".into(), client("ci", &["work"]))],
+        );
+        let post = |head: &str| status(dispatch(&request(head), &state));
+        assert_eq!(post("POST /refresh HTTP/1.1"), 401);
+        assert_eq!(
+            post("POST /refresh HTTP/1.1\r\nAuthorization: Bearer nope"),
+            403
+        );
+        assert_eq!(
+            post("POST /refresh?profile=home HTTP/1.1\r\nAuthorization: Bearer tok-work"),
+            403
+        );
+        assert_eq!(
+            post("POST /refresh?profile=nope HTTP/1.1\r\nAuthorization: Bearer tok-work"),
+            403
+        );
+        assert!(state.nudged.lock().unwrap().is_empty());
+        assert_eq!(
+            post("POST /refresh HTTP/1.1\r\nAuthorization: Bearer tok-work"),
+            204
+        );
+        assert_eq!(
+            *state.nudged.lock().unwrap(),
+            BTreeSet::from(["work".to_string()])
+        );
+
+        // Open mode: this machine's pages and tools may, other sites may not
+        let open = State::for_tests(&["work"], Vec::new());
+        let post = |head: &str| status(dispatch(&request(head), &open));
+        assert_eq!(post("POST /refresh HTTP/1.1"), 204);
+        assert_eq!(
+            post("POST /refresh HTTP/1.1\r\nOrigin: https://evil.example"),
+            403
+        );
+    }
+
+    #[test]
+    fn open_mode_refuses_other_sites() {
+        let state = State::for_tests(&["work"], Vec::new());
+        let from = |origin: &str| {
+            dispatch(
+                &request(&format!("GET /ws HTTP/1.1\r\nOrigin: {origin}")),
+                &state,
+            )
+        };
+        assert!(
+            matches!(from("http://localhost:8787"), Reply::Websocket(names) if names == ["work"])
+        );
+        assert!(matches!(from("http://[::1]:8787"), Reply::Websocket(_)));
+        for origin in [
+            "https://evil.example",
+            "http://127.0.0.1.evil.example",
+            "null",
+        ] {
+            assert!(
+                matches!(from(origin), Reply::Response(r) if r.status == 403),
+                "{origin}"
+            );
+        }
+        // Not a browser: no Origin at all
+        assert!(matches!(
+            dispatch(&request("GET /ws HTTP/1.1"), &state),
+            Reply::Websocket(_)
+        ));
+    }
+
+    #[test]
+    fn open_mode_refuses_other_hosts() {
+        let state = State::for_tests(&["work"], Vec::new());
+        let to = |host: &str, head: &str| {
+            status(dispatch(
+                &request(&format!("{head}\r\nHost: {host}")),
+                &state,
+            ))
+        };
+        for host in [
+            "localhost",
+            "LOCALHOST:8787",
+            "127.0.0.1:8787",
+            "[::1]:8787",
+            "[::1]",
+        ] {
+            assert_eq!(to(host, "GET /usage HTTP/1.1"), 200, "{host}");
+        }
+        // A rebound name reaches the loopback address, but its pages are
+        // still someone else's, and send no Origin to catch them by
+        for host in [
+            "attacker.example:18788",
+            "127.0.0.1.attacker.example",
+            "192.168.1.20:8787",
+        ] {
+            assert_eq!(to(host, "GET /usage/work HTTP/1.1"), 403, "{host}");
+            assert_eq!(to(host, "POST /refresh HTTP/1.1"), 403, "{host}");
+        }
+        assert!(state.nudged.lock().unwrap().is_empty());
+        // No Host at all is no proof of being local either
+        let bare = "GET /usage HTTP/1.1\r\n\r\n";
+        let bare = Request::read(&mut BufReader::new(bare.as_bytes())).unwrap();
+        assert_eq!(status(dispatch(&bare, &state)), 403);
+
+        // With clients, a token is what counts, whatever the name used
+        let state = State::for_tests(&["work"], vec![("tok".into(), client("ci", &["work"]))]);
+        let head = "GET /usage HTTP/1.1\r\nAuthorization: Bearer tok\r\nHost: usage.example";
+        assert_eq!(status(dispatch(&request(head), &state)), 200);
+    }
+
+    #[test]
+    fn connections_are_capped() {
+        let open = Arc::new(AtomicUsize::new(0));
+        let slots: Vec<Slot> = (0..MAX_CONNECTIONS)
+            .map(|_| Slot::take(&open).unwrap())
+            .collect();
+        assert!(Slot::take(&open).is_none());
+        drop(slots);
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+        assert!(Slot::take(&open).is_some());
+    }
+}
@@ -0,0 +1,154 @@
+//! `/overlay`: a self-refreshing HTML fragment for OBS browser sources.
+//! The page background is a flat chroma-key colour so the bars can be keyed
+//! straight onto a stream; bar colours avoid the key colour.
+
+use crate::api::{RateWindow, Snapshot};
+use crate::render::{CRIT_PERCENT, WARN_PERCENT};
+use chrono::Utc;
+use std::fmt::Write;
+
+/// Chroma-key backgrounds selectable with `?key=`
+fn key_colour(key: &str) -> &'static str {
+    match key {
+        "blue" => "#0000ff",
+        "magenta" => "#ff00ff",
+        "transparent" => "transparent",
+        _ => "#00ff00",
+    }
+}
+
+/// Severity colour for a bar; none of these sit near a key colour
+fn bar_colour(pct: f64) -> &'static str {
+    if pct >= CRIT_PERCENT {
+        "#e03131"
+    } else if pct >= WARN_PERCENT {
+        "#f59f00"
+    } else {
+        "#1c7ed6"
+    }
+}
+
+pub fn render(profiles: &[(String, Option<Snapshot>)], key: &str, refresh_secs: u64) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"{refresh_secs}\">\
+         <title>Codex usage</title><style>\
+         body{{margin:0;padding:12px;background:{bg};font:600 18px system-ui,sans-serif;\
+         color:#fff;text-shadow:0 1px 2px #000}}\
+         .row{{display:flex;align-items:center;gap:10px;margin:6px 0}}\
+         .label{{width:150px}}\
+         .bar{{width:260px;height:16px;background:#222;border:2px solid #fff;border-radius:4px;overflow:hidden}}\
+         .fill{{height:100%}}\
+         .profile{{font-size:14px;opacity:.85;margin-top:8px}}\
+         .updated{{font-size:12px;opacity:.7}}\
+         </style></head><body>",
+        bg = key_colour(key)
+    );
+
+    let now = Utc::now();
+    let many = profiles.len() > 1;
+    for (name, snap) in profiles {
+        if many {
+            let _ = write!(html, "<div class=\"profile\">{}</div>", escape(name));
+        }
+        let Some(snap) = snap else {
+            html.push_str("<div class=\"row\">waiting for data…</div>");
+            continue;
+        };
+        let windows = snap
+            .usage
+            .rate_limit
+            .as_ref()
+            .map(|r| r.windows())
+            .unwrap_or_default();
+        for (name, w) in &windows {
+            // `Weekly`, `Daily`, else `5h window`
+            let label = match w.span(name) {
+                Some(span) => match span.period() {
+                    Some(period) => crate::render::feature_title(period),
+                    None => format!("{} window", span.short()),
+                },
+                None => format!("{} window", crate::render::feature_title(name)),
+            };
+            write_row(&mut html, &label, w, snap, now);
+        }
+        // A stalled poller shows as an age that keeps growing
+        if let Some(updated) = crate::render::updated(snap, now) {
+            let _ = write!(html, "<div class=\"updated\">updated {updated}</div>");
+        }
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+fn write_row(
+    html: &mut String,
+    label: &str,
+    w: &RateWindow,
+    snap: &Snapshot,
+    now: chrono::DateTime<Utc>,
+) {
+    let pct = w.used_percent.unwrap_or(0.0).clamp(0.0, 100.0);
+    let reset = w
+        .resets_in(snap.fetched_at, now)
+        .map(|s| match s {
+            s if s >= 86_400 => format!(" · {}d {}h", s / 86_400, (s % 86_400) / 3600),
+            s => format!(" · {}h {:02}m", s / 3600, (s % 3600) / 60),
+        })
+        .unwrap_or_default();
+    let _ = write!(
+        html,
+        "<div class=\"row\"><span class=\"label\">{label}</span>\
+         <div class=\"bar\"><div class=\"fill\" style=\"width:{pct:.1}%;background:{}\"></div></div>\
+         <span>{pct:.0}%{reset}</span></div>",
+        bar_colour(pct)
+    );
+}
+
+/// Minimal HTML escaping for profile names
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn keyed_page_with_a_row_per_window() {
+        let snap = Snapshot::plus((95.0, 2 * 3600 + 630), (40.0, 3 * 86400), Utc::now());
+        let html = render(
+            &[("<work>".into(), Some(snap)), ("home".into(), None)],
+            "blue",
+            30,
+        );
+        assert!(html.contains("content=\"30\"") && html.contains("background:#0000ff"));
+        assert!(html.contains("<div class=\"profile\">&lt;work&gt;</div>"));
+        assert!(html.contains("<span class=\"label\">5h window</span>"));
+        assert!(html.contains("width:95.0%;background:#e03131"));
+        assert!(html.contains("<span>95% · 2h 10m</span>"));
+        assert!(html.contains("<span class=\"label\">Weekly</span>"));
+        assert!(html.contains("width:40.0%;background:#1c7ed6"));
+        assert!(html.contains("waiting for data…"));
+        assert!(!html.contains("<div class=\"updated\">"));
+
+        // A poller that has stalled shows how old its figures are
+        let stale = Snapshot::plus(
+            (10.0, 3600),
+            (5.0, 86400),
+            Utc::now() - Duration::minutes(5),
+        );
+        let html = render(&[("work".into(), Some(stale))], "green", 30);
+        assert!(html.contains("<div class=\"updated\">updated 5m ago</div>"));
+
+        // One profile needs no heading; an unknown key falls back to green
+        let html = render(&[("home".into(), None)], "plaid", 30);
+        assert!(!html.contains("class=\"profile\"") && html.contains("background:#00ff00"));
+    }
+}
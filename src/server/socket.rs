@@ -0,0 +1,261 @@
+//! `daemon`: the server's pollers behind a Unix socket rather than HTTP,
+//! for status bars and prompts on the same machine. A client writes a
+//! profile name and a newline and reads back one line of JSON, either
+//! `{"snapshot": …}` or `{"error": "…"}`. `--cached` is that client, so a
+//! prompt redrawn many times a minute never calls the API itself.
+
+#[cfg(unix)]
+use super::State;
+use crate::api::Snapshot;
+use crate::config::Config;
+#[cfg(unix)]
+use anyhow::Context;
+use anyhow::{bail, Result};
+#[cfg(unix)]
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::time::Duration;
+
+/// Longest either side waits on the other; an answer is one local read
+#[cfg(unix)]
+const IO_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// CODEX_USAGE_SOCKET, else `$XDG_RUNTIME_DIR/codex-usage.sock`, else
+/// `daemon.sock` beside the history
+pub fn default_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("CODEX_USAGE_SOCKET").filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("codex-usage.sock"),
+        None => crate::paths::data_dir().join("daemon.sock"),
+    }
+}
+
+/// Answer one request: the latest snapshot of the profile named on the
+/// first line
+#[cfg(unix)]
+fn answer(state: &State, request: &str) -> Value {
+    let profile = request.trim();
+    let latest = state.latest.lock().unwrap();
+    match latest.get(profile) {
+        None => json!({ "error": format!("The daemon isn't polling a profile named '{profile}'") }),
+        Some(l) => match (&l.snapshot, &l.error) {
+            (Some(snap), _) => json!({ "snapshot": snap }),
+            (None, Some(e)) => json!({ "error": e }),
+            (None, None) => json!({ "error": format!("The daemon hasn't polled '{profile}' yet") }),
+        },
+    }
+}
+
+#[cfg(unix)]
+pub fn daemon(config: Config, socket: Option<&Path>, dbus: bool) -> Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+
+    let path = socket.map(Path::to_path_buf).unwrap_or_else(default_path);
+    if UnixStream::connect(&path).is_ok() {
+        bail!(
+            "A codex-usage daemon is already answering on {}",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create {}", dir.display()))?;
+    }
+    let listener =
+        bind_private(&path).with_context(|| format!("Could not listen on {}", path.display()))?;
+
+    let listen = path.display().to_string();
+    let state = super::start(config, Default::default(), Vec::new(), &listen)?;
+    if dbus || dbus_from_env() {
+        super::dbus::publish(&state)?;
+    }
+    eprintln!(
+        "codex-usage daemon polling {} profile(s), answering on {listen}",
+        state.profiles.len()
+    );
+
+    for stream in listener.incoming().flatten() {
+        let state = Arc::clone(&state);
+        std::thread::spawn(move || {
+            let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+            let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                return;
+            }
+            let mut line = answer(&state, &request).to_string();
+            line.push('\n');
+            let _ = (&stream).write_all(line.as_bytes());
+        });
+    }
+    Ok(())
+}
+
+/// CODEX_USAGE_DBUS set to 1/true/on/yes, as `--dbus` is for a unit file
+/// that can't change the command line
+#[cfg(unix)]
+fn dbus_from_env() -> bool {
+    matches!(
+        std::env::var("CODEX_USAGE_DBUS")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str(),
+        "1" | "true" | "on" | "yes"
+    )
+}
+
+/// Listen on `path`, which no one else may connect to at any point: the
+/// socket is bound in a fresh 0700 directory beside it, made 0600, and only
+/// then moved into place. Only a socket already there, left behind by a
+/// daemon that didn't shut down cleanly, is replaced; anything else at a
+/// mistyped `--socket` is not ours to destroy.
+#[cfg(unix)]
+fn bind_private(path: &Path) -> Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if !meta.file_type().is_socket() => {
+            bail!("{} exists and isn't a socket", path.display())
+        }
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let name = path
+        .file_name()
+        .context("The socket path has no file name")?;
+    let dir = path.with_file_name(format!(".codex-usage.{}", std::process::id()));
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let staged = dir.join(name);
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        // Usage is the owner's business only
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&dir);
+    Ok(bound?)
+}
+
+#[cfg(not(unix))]
+pub fn daemon(_config: Config, _socket: Option<&Path>, _dbus: bool) -> Result<()> {
+    bail!("`codex-usage daemon` needs Unix sockets; use `codex-usage serve` instead")
+}
+
+/// Ask the daemon on `socket` for `profile`'s latest snapshot
+#[cfg(unix)]
+pub fn query(socket: &Path, profile: &str) -> Result<Snapshot> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket).with_context(|| {
+        format!(
+            "No codex-usage daemon is answering on {}; start one with `codex-usage daemon`",
+            socket.display()
+        )
+    })?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    stream.write_all(format!("{profile}\n").as_bytes())?;
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .context("The codex-usage daemon didn't answer")?;
+    let mut reply: Value =
+        serde_json::from_str(&reply).context("The codex-usage daemon sent a malformed reply")?;
+    if let Some(e) = reply.get("error").and_then(Value::as_str) {
+        bail!("{e}");
+    }
+    Ok(serde_json::from_value(reply["snapshot"].take())?)
+}
+
+#[cfg(not(unix))]
+pub fn query(_socket: &Path, _profile: &str) -> Result<Snapshot> {
+    bail!("--cached needs Unix sockets; use --max-age instead")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::server::Latest;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn answers_by_profile() {
+        let snap = Snapshot::plus((12.0, 3600), (4.5, 86400), chrono::Utc::now());
+        let latest = |snapshot: Option<Snapshot>, error: Option<&str>| Latest {
+            snapshot,
+            error: error.map(str::to_string),
+            polled_at: None,
+        };
+        let state = State::for_tests(&[], Vec::new());
+        *state.latest.lock().unwrap() = BTreeMap::from([
+            ("home".to_string(), latest(Some(snap), Some("HTTP 502"))),
+            ("work".to_string(), latest(None, Some("HTTP 401"))),
+            ("new".to_string(), latest(None, None)),
+        ]);
+        // The last good snapshot wins over a later failed poll
+        assert_eq!(
+            answer(&state, "home\n")["snapshot"]["usage"]["plan_type"],
+            "plus"
+        );
+        assert_eq!(answer(&state, "work\n")["error"], "HTTP 401");
+        assert_eq!(
+            answer(&state, "new\n")["error"],
+            "The daemon hasn't polled 'new' yet"
+        );
+        assert!(answer(&state, "nope\n")["error"]
+            .as_str()
+            .unwrap()
+            .contains("isn't polling"));
+    }
+
+    #[test]
+    fn sockets_are_never_open_to_others() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("codex-usage-sock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("daemon.sock");
+        let _listener = bind_private(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+        // Nothing is left of the staging directory
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_a_stale_socket_is_replaced() {
+        let dir = std::env::temp_dir().join(format!("codex-usage-stale-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A daemon that died leaves its socket behind
+        let path = dir.join("daemon.sock");
+        drop(bind_private(&path).unwrap());
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_err());
+        let _listener = bind_private(&path).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+
+        // A file at a mistyped path survives
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "keep me").unwrap();
+        let err = bind_private(&notes).unwrap_err();
+        assert!(err.to_string().contains("isn't a socket"), "{err}");
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "keep me");
+        // Nor is a symlink followed to replace what it points at
+        let link = dir.join("link.sock");
+        std::os::unix::fs::symlink(&notes, &link).unwrap();
+        assert!(bind_private(&link).is_err());
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "keep me");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
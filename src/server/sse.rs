@@ -0,0 +1,96 @@
+//! `/events`: the `/ws` pushes as Server-Sent Events, for web pages that
+//! would rather use `EventSource` than a websocket. Each profile's JSON is
+//! a `usage` event, sent on connecting and whenever the poller sees a change.
+
+use super::http::Response;
+use super::{State, Update};
+use serde_json::json;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+/// A comment line is sent after this long without an event, so proxies
+/// keep the stream open and a client that has gone is noticed
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// How long `EventSource` waits before reconnecting after a drop
+const RETRY_MS: u64 = 5000;
+
+/// Stream updates until the client goes away. With `cors`, pages on any
+/// origin may read the stream: the caller presented a client token, which a
+/// page can only have been given.
+pub fn serve(mut stream: TcpStream, state: &State, profiles: Vec<String>, cors: bool) {
+    if profiles.is_empty() {
+        let _ = Response::json(404, &json!({ "error": "no such profile" }))
+            .write_to(&mut stream, false);
+        return;
+    }
+    if stream.write_all(head(cors).as_bytes()).is_err() {
+        return;
+    }
+
+    let updates = state.subscribe();
+    for update in state.current(&profiles) {
+        if stream.write_all(event(&update).as_bytes()).is_err() {
+            return;
+        }
+    }
+    loop {
+        let sent = match updates.recv_timeout(KEEPALIVE) {
+            Ok(update) if profiles.contains(&update.profile) => {
+                stream.write_all(event(&update).as_bytes())
+            }
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => stream.write_all(b": keepalive\n\n"),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if sent.and_then(|()| stream.flush()).is_err() {
+            return;
+        }
+    }
+}
+
+/// The response head, and how long to wait before reconnecting
+fn head(cors: bool) -> String {
+    let allow = match cors {
+        true => "Access-Control-Allow-Origin: *\r\n",
+        false => "",
+    };
+    format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/event-stream\r\n\
+         Cache-Control: no-store\r\n\
+         {allow}\
+         Connection: keep-alive\r\n\r\n\
+         retry: {RETRY_MS}\n\n"
+    )
+}
+
+/// One `usage` event; the body is a single line of JSON, so one `data:` line
+fn event(update: &Update) -> String {
+    format!("event: usage\ndata: {}\n\n", update.body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Snapshot;
+
+    #[test]
+    fn one_event_per_update() {
+        let update = Update {
+            profile: "work".into(),
+            snapshot: Snapshot::plus((12.0, 3600), (4.5, 86400), chrono::Utc::now()),
+            body: json!({ "profile": "work", "usage": { "plan_type": "plus" } }),
+        };
+        assert_eq!(
+            event(&update),
+            "event: usage\n\
+             data: {\"profile\":\"work\",\"usage\":{\"plan_type\":\"plus\"}}\n\n"
+        );
+        // Only a token-bearing caller is open to other origins
+        assert!(head(true).contains("Access-Control-Allow-Origin: *\r\n"));
+        assert!(!head(false).contains("Access-Control"));
+    }
+}
@@ -0,0 +1,95 @@
+//! `/ws`: pushes each readable profile's JSON whenever the poller sees a
+//! change, with ping/pong keepalive so dead dashboards are noticed.
+
+use super::http::{Request, Response};
+use super::State;
+use serde_json::json;
+use std::io::{ErrorKind, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+/// How often the socket is checked for incoming frames and queued updates
+const TICK: Duration = Duration::from_millis(500);
+
+/// Interval between keepalive pings
+const PING_EVERY: Duration = Duration::from_secs(30);
+
+/// Drop the connection when nothing (not even a pong) arrives for this long
+const SILENCE_LIMIT: Duration = Duration::from_secs(75);
+
+/// Upgrade the connection and stream updates until the client goes away
+pub fn serve(mut stream: TcpStream, request: &Request, state: &State, profiles: Vec<String>) {
+    let is_upgrade = request
+        .header("Upgrade")
+        .is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+    let Some(key) = request.header("Sec-WebSocket-Key").filter(|_| is_upgrade) else {
+        let _ = Response::json(400, &json!({ "error": "expected a websocket upgrade" }))
+            .write_to(&mut stream, false);
+        return;
+    };
+    if profiles.is_empty() {
+        let _ = Response::json(404, &json!({ "error": "no such profile" }))
+            .write_to(&mut stream, false);
+        return;
+    }
+
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    if stream.write_all(handshake.as_bytes()).is_err() {
+        return;
+    }
+    let _ = stream.set_read_timeout(Some(TICK));
+    let mut ws = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    // Subscribe before sending the current state so no change slips between
+    let updates = state.subscribe();
+    for update in state.current(&profiles) {
+        if ws.send(Message::text(update.body.to_string())).is_err() {
+            return;
+        }
+    }
+
+    let mut last_ping = Instant::now();
+    let mut last_heard = Instant::now();
+    loop {
+        match ws.read() {
+            // Pings are answered by tungstenite itself on the next flush
+            Ok(Message::Close(_)) => break,
+            Ok(_) => last_heard = Instant::now(),
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return,
+        }
+
+        while let Ok(update) = updates.try_recv() {
+            if profiles.contains(&update.profile)
+                && ws.send(Message::text(update.body.to_string())).is_err()
+            {
+                return;
+            }
+        }
+
+        if last_heard.elapsed() > SILENCE_LIMIT {
+            break;
+        }
+        if last_ping.elapsed() >= PING_EVERY {
+            if ws.send(Message::Ping(Default::default())).is_err() {
+                return;
+            }
+            last_ping = Instant::now();
+        }
+        if ws.flush().is_err() {
+            return;
+        }
+    }
+    let _ = ws.close(None);
+    let _ = ws.flush();
+}
@@ -0,0 +1,339 @@
+//! `shell-init bash|zsh|fish`: everything a shell needs, in one snippet to
+//! `eval` from its rc file — tab completion, a usage segment in the prompt,
+//! and with `--after-codex` a usage line after each `codex` command. The
+//! completion words come from the command table and the help text, so they
+//! can't fall behind the flags.
+//!
+//! `env`: the latest usage as variable assignments, for scripts and direnv
+//! layouts that would rather not parse JSON.
+
+use crate::api::Snapshot;
+use crate::cli::{Command, USAGE};
+use crate::render::Format;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl std::str::FromStr for Shell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Shell> {
+        match s.to_ascii_lowercase().as_str() {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => bail!("Unknown shell '{s}' (expected bash, zsh or fish)"),
+        }
+    }
+}
+
+impl Shell {
+    fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+/// What to wire up besides completion
+#[derive(Debug, Clone, Copy)]
+pub struct Parts {
+    pub prompt: bool,
+    pub after_codex: bool,
+}
+
+/// `CX 5h:12% 7d:48% reset 1h03m`. Prompt redraws answer from the cache,
+/// fetching at most every 5 minutes.
+const SEGMENT_RUN: &str = "command codex-usage --prompt --max-age 300 --no-history -q 2>/dev/null";
+
+/// After a `codex` command: a live fetch, recorded in history tagged with it
+const AFTER_RUN: &str = r#"command codex-usage --oneline -q --command "$cmd" 2>/dev/null"#;
+
+/// A shell's snippets. `@NAME@` marks are filled in by [`init`]; `NESTED`
+/// is repeated for each subcommand that takes a second word.
+struct Script {
+    completion: &'static str,
+    nested: &'static str,
+    prompt: &'static str,
+    after_codex: &'static str,
+}
+
+const BASH: Script = Script {
+    completion: r#"_codex_usage() {
+  local cur=${COMP_WORDS[COMP_CWORD]} prev=${COMP_WORDS[COMP_CWORD-1]} words
+  if [[ $prev == --format ]]; then words="@FORMATS@"
+  elif [[ $cur == -* ]]; then words="@FLAGS@"
+  elif ((COMP_CWORD == 1)); then words="@TOP@"
+  else
+    case ${COMP_WORDS[1]} in
+@NESTED@    esac
+  fi
+  COMPREPLY=($(compgen -W "$words" -- "$cur"))
+}
+complete -F _codex_usage codex-usage
+"#,
+    nested: "      @FIRST@) words=\"@REST@\" ;;\n",
+    prompt: r#"codex_usage_segment() { @SEGMENT@; }
+PS1='$(codex_usage_segment) '"$PS1"
+"#,
+    after_codex: r#"_codex_usage_after() {
+  local n cmd
+  read -r n cmd <<< "$(HISTTIMEFORMAT= history 1)"
+  [[ $n == "$_codex_usage_seen" ]] && return
+  _codex_usage_seen=$n
+  [[ $cmd == codex || $cmd == "codex "* ]] || return
+  echo "codex usage: $(@AFTER@)"
+}
+PROMPT_COMMAND="_codex_usage_after${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+"#,
+};
+
+const ZSH: Script = Script {
+    completion: r#"_codex_usage() {
+  if [[ $words[CURRENT-1] == --format ]]; then compadd -- @FORMATS@
+  elif [[ $PREFIX == -* ]]; then compadd -- @FLAGS@
+  elif (( CURRENT == 2 )); then compadd -- @TOP@
+  else
+    case $words[2] in
+@NESTED@    esac
+  fi
+}
+(( $+functions[compdef] )) && compdef _codex_usage codex-usage
+"#,
+    nested: "      @FIRST@) compadd -- @REST@ ;;\n",
+    prompt: r#"codex_usage_segment() { @SEGMENT@ }
+setopt prompt_subst
+RPROMPT='$(codex_usage_segment)'${RPROMPT:+" $RPROMPT"}
+"#,
+    after_codex: r#"_codex_usage_preexec() { _codex_usage_cmd=$1 }
+_codex_usage_precmd() {
+  local cmd=$_codex_usage_cmd
+  unset _codex_usage_cmd
+  [[ $cmd == codex || $cmd == "codex "* ]] || return
+  echo "codex usage: $(@AFTER@)"
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec _codex_usage_preexec
+add-zsh-hook precmd _codex_usage_precmd
+"#,
+};
+
+const FISH: Script = Script {
+    completion: r#"complete -c codex-usage -f
+complete -c codex-usage -n __fish_use_subcommand -a '@TOP@'
+@NESTED@for flag in @FLAGS@
+    complete -c codex-usage -l (string sub -s 3 -- $flag)
+end
+complete -c codex-usage -l format -x -a '@FORMATS@'
+"#,
+    nested: "complete -c codex-usage -n '__fish_seen_subcommand_from @FIRST@' -a '@REST@'\n",
+    prompt: r#"function codex_usage_segment
+    @SEGMENT@
+end
+if functions -q fish_right_prompt
+    functions -c fish_right_prompt _codex_usage_right_prompt
+else
+    function _codex_usage_right_prompt; end
+end
+function fish_right_prompt
+    codex_usage_segment
+    _codex_usage_right_prompt
+end
+"#,
+    after_codex: r#"function _codex_usage_after --on-event fish_postexec
+    set -l cmd $argv[1]
+    string match -qr '^codex( |$)' -- $cmd; or return
+    echo "codex usage: "(@AFTER@)
+end
+"#,
+};
+
+pub fn init(shell: Shell, parts: Parts) -> String {
+    let script = match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    };
+    let (top, nested) = subcommands();
+    let nested: String = nested
+        .iter()
+        .map(|(first, rest)| {
+            script
+                .nested
+                .replace("@FIRST@", first)
+                .replace("@REST@", &rest.join(" "))
+        })
+        .collect();
+
+    let mut out = format!("# codex-usage shell-init {}\n", shell.name());
+    out.push_str(
+        &script
+            .completion
+            .replace("@NESTED@", &nested)
+            .replace("@TOP@", &top.join(" "))
+            .replace("@FLAGS@", &flags().join(" "))
+            .replace("@FORMATS@", &Format::NAMES.join(" ")),
+    );
+    if parts.prompt {
+        out.push_str(&script.prompt.replace("@SEGMENT@", SEGMENT_RUN));
+    }
+    if parts.after_codex {
+        out.push_str(&script.after_codex.replace("@AFTER@", AFTER_RUN));
+    }
+    out
+}
+
+// ─── env ──────────────────────────────────────────────────────────────────────
+
+/// `export NAME=value` lines (`set -gx` for fish) for `snap` as seen at
+/// `now`. Percentages are rounded to whole numbers, which is what shell
+/// arithmetic handles; a value the API left out is unset, so a stale one
+/// from an earlier `eval` doesn't linger.
+pub fn env(shell: Option<Shell>, snap: &Snapshot, now: DateTime<Utc>) -> String {
+    let rl = snap.usage.rate_limit.as_ref();
+    let window = |w: Option<&crate::api::RateWindow>| {
+        let pct = w
+            .and_then(|w| w.used_percent)
+            .map(|p| p.round().to_string());
+        let resets = w
+            .and_then(|w| w.resets_in(snap.fetched_at, now))
+            .map(|s| s.to_string());
+        (pct, resets)
+    };
+    let (primary, primary_resets) = window(rl.and_then(|r| r.primary_window.as_ref()));
+    let (secondary, secondary_resets) = window(rl.and_then(|r| r.secondary_window.as_ref()));
+    let reached = rl.and_then(|r| r.limit_reached).unwrap_or(false);
+    let vars = [
+        ("CODEX_USAGE_PLAN", snap.usage.plan_type.clone()),
+        ("CODEX_USAGE_PRIMARY", primary),
+        ("CODEX_USAGE_PRIMARY_RESETS_IN", primary_resets),
+        ("CODEX_USAGE_SECONDARY", secondary),
+        ("CODEX_USAGE_SECONDARY_RESETS_IN", secondary_resets),
+        (
+            "CODEX_USAGE_LIMIT_REACHED",
+            Some(u8::from(reached).to_string()),
+        ),
+        (
+            "CODEX_USAGE_FETCHED_AT",
+            Some(snap.fetched_at.timestamp().to_string()),
+        ),
+    ];
+
+    let fish = shell == Some(Shell::Fish);
+    let mut out = String::new();
+    for (name, value) in vars {
+        let line = match (value, fish) {
+            (Some(v), false) => format!("export {name}={}", quote(&v)),
+            (Some(v), true) => format!("set -gx {name} {}", quote(&v)),
+            (None, false) => format!("unset {name}"),
+            (None, true) => format!("set -e {name}"),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Single-quoted unless plainly safe; the same rules hold in every shell
+/// here
+fn quote(v: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    match !v.is_empty() && v.chars().all(safe) {
+        true => v.to_string(),
+        false => format!("'{}'", v.replace('\'', r"'\''")),
+    }
+}
+
+/// First words, and the words that may follow those that take one
+fn subcommands() -> (Vec<&'static str>, Vec<(&'static str, Vec<&'static str>)>) {
+    let mut top: Vec<&str> = Vec::new();
+    let mut nested: Vec<(&str, Vec<&str>)> = Vec::new();
+    for command in Command::ALL {
+        let mut words = command.name().split(' ');
+        let Some(first) = words.next().filter(|w| *w != "report") else {
+            continue;
+        };
+        if !top.contains(&first) {
+            top.push(first);
+        }
+        if let Some(second) = words.next() {
+            match nested.iter_mut().find(|(f, _)| *f == first) {
+                Some((_, rest)) => rest.push(second),
+                None => nested.push((first, vec![second])),
+            }
+        }
+    }
+    (top, nested)
+}
+
+/// Every `--flag` the help text mentions, in order
+fn flags() -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    for word in USAGE.split(|c: char| c.is_whitespace() || matches!(c, '[' | ']' | ',' | '=')) {
+        let word = word.trim_end_matches(['.', ';', ')']);
+        let is_flag = word.len() > 2
+            && word.starts_with("--")
+            && word[2..]
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c == '-');
+        if is_flag && !flags.contains(&word) {
+            flags.push(word);
+        }
+    }
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_lines() {
+        let now = crate::clock::parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let usage = include_str!("../tests/fixtures/missing_windows.json");
+        let mut snap = Snapshot::new(serde_json::from_str(usage).unwrap(), now);
+        let posix = env(None, &snap, now);
+        assert!(posix.contains("export CODEX_USAGE_PRIMARY=30\n"), "{posix}");
+        assert!(posix.contains("unset CODEX_USAGE_SECONDARY\n"), "{posix}");
+        assert!(env(Some(Shell::Fish), &snap, now).contains("set -e CODEX_USAGE_SECONDARY\n"));
+
+        snap.usage.plan_type = Some("o'brien plan".into());
+        assert!(env(None, &snap, now).contains("export CODEX_USAGE_PLAN='o'\\''brien plan'\n"));
+    }
+
+    #[test]
+    fn completion_words_and_parts() {
+        let (top, nested) = subcommands();
+        assert!(top.contains(&"history") && top.contains(&"shell-init"));
+        assert!(!top.contains(&"report"));
+        let history = nested.iter().find(|(f, _)| *f == "history").unwrap();
+        assert_eq!(history.1, ["replay", "export", "plans"]);
+        let flags = flags();
+        assert!(flags.contains(&"--max-age") && flags.contains(&"--after-codex"));
+        assert!(flags.iter().all(|f| !f.ends_with('-')));
+
+        let all = Parts {
+            prompt: true,
+            after_codex: true,
+        };
+        let none = Parts {
+            prompt: false,
+            after_codex: false,
+        };
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let full = init(shell, all);
+            assert!(full.contains("--max-age") && full.contains("codex_usage_segment"));
+            assert!(full.contains("codex usage: "));
+            let bare = init(shell, none);
+            assert!(!bare.contains("codex_usage_segment") && !bare.contains("codex usage: "));
+        }
+    }
+}
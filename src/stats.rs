@@ -0,0 +1,250 @@
+//! Opt-in local statistics about how codex-usage itself is called: runs per
+//! day, by command and output format, and failures by error code. Meant for
+//! seeing what your own integrations do — a prompt segment that calls it
+//! 5,000 times a day, a cron job that has been failing with E004 all week.
+//!
+//! Off unless `[stats] enabled = true` is in the config file or
+//! CODEX_USAGE_STATS=1 is set (CODEX_USAGE_STATS=0 turns it off again). The
+//! counters live in one small JSON file next to the history and are never
+//! sent anywhere.
+
+use crate::cli::Args;
+use crate::config::Config;
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Days of counters kept; older days are dropped on the next write
+const DAYS_KEPT: i64 = 90;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub days: BTreeMap<NaiveDate, Day>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Day {
+    pub runs: u64,
+    pub commands: BTreeMap<String, u64>,
+    pub formats: BTreeMap<String, u64>,
+    /// Failed runs by error code
+    pub failures: BTreeMap<String, u64>,
+}
+
+impl Day {
+    fn add(&mut self, other: &Day) {
+        self.runs += other.runs;
+        for (into, from) in [
+            (&mut self.commands, &other.commands),
+            (&mut self.formats, &other.formats),
+            (&mut self.failures, &other.failures),
+        ] {
+            for (k, n) in from {
+                *into.entry(k.clone()).or_default() += n;
+            }
+        }
+    }
+
+    fn failed(&self) -> u64 {
+        self.failures.values().sum()
+    }
+}
+
+pub fn path() -> PathBuf {
+    crate::paths::data_dir().join("stats.json")
+}
+
+/// CODEX_USAGE_STATS if set, else `[stats] enabled` in the config file
+pub fn enabled() -> bool {
+    match std::env::var("CODEX_USAGE_STATS")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "1" | "true" | "on" | "yes" => true,
+        "0" | "false" | "off" | "no" => false,
+        _ => Config::load().is_ok_and(|c| c.stats.enabled),
+    }
+}
+
+/// Count this run if stats are on. Best-effort: a stats problem must never
+/// turn into a failed run, so errors are dropped.
+pub fn record(args: &Args, failure: Option<&anyhow::Error>) {
+    if enabled() {
+        let _ = count(args, failure);
+    }
+}
+
+fn count(args: &Args, failure: Option<&anyhow::Error>) -> Result<()> {
+    let path = path();
+    let mut stats = load()?;
+    let today = Utc::now().date_naive();
+    let day = stats.days.entry(today).or_default();
+    day.runs += 1;
+    *day.commands.entry(args.command.name().into()).or_default() += 1;
+    *day.formats.entry(args.format.name().into()).or_default() += 1;
+    if let Some(e) = failure {
+        let code = crate::errors::classify(e).code;
+        *day.failures.entry(code.into()).or_default() += 1;
+    }
+    stats
+        .days
+        .retain(|d, _| *d > today - Duration::days(DAYS_KEPT));
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Replace by rename so a reader never sees half a file. Two runs racing
+    // can lose one count, which is fine for numbers like these.
+    let tmp = path.with_extension(format!("json.{}", std::process::id()));
+    std::fs::write(&tmp, serde_json::to_vec(&stats)?)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// The counters so far; no file means nothing recorded yet
+pub fn load() -> Result<Stats> {
+    let path = path();
+    match std::fs::read(&path) {
+        Ok(raw) => serde_json::from_slice(&raw)
+            .with_context(|| format!("Could not parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Stats::default()),
+        Err(e) => Err(e).with_context(|| format!("Could not read {}", path.display())),
+    }
+}
+
+/// `meta stats`: today, the last 7 days and everything kept, with the
+/// 7-day breakdown by command, format and error code
+pub fn render(stats: &Stats, enabled: bool, today: NaiveDate) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "\n  {} codex-usage runs {}",
+        "◆".cyan().bold(),
+        "(local only, never sent)".dimmed()
+    );
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+    if !enabled {
+        let _ = writeln!(
+            out,
+            "  Recording is off. Turn it on with {} under {} in the\n  config file, or CODEX_USAGE_STATS=1.",
+            "enabled = true".bold(),
+            "[stats]".bold()
+        );
+    }
+    let Some(first) = stats.days.keys().next() else {
+        let _ = writeln!(out, "  Nothing recorded yet.");
+        let _ = writeln!(out, "  {}\n", "─".repeat(67).dimmed());
+        return out;
+    };
+
+    let total = |from: NaiveDate| {
+        let mut sum = Day::default();
+        for day in stats.days.range(from..=today).map(|(_, d)| d) {
+            sum.add(day);
+        }
+        sum
+    };
+    let (day, week, all) = (
+        total(today),
+        total(today - Duration::days(6)),
+        total(*first),
+    );
+
+    let _ = writeln!(
+        out,
+        "  {:<14}{:>10}{:>10}{:>12}",
+        "",
+        "today",
+        "7 days",
+        format!("since {}", first.format("%m-%d"))
+    );
+    let _ = writeln!(
+        out,
+        "  {:<14}{:>10}{:>10}{:>12}",
+        "Runs", day.runs, week.runs, all.runs
+    );
+    let _ = writeln!(
+        out,
+        "  {:<14}{:>10}{:>10}{:>12}",
+        "Failed",
+        day.failed(),
+        week.failed(),
+        all.failed()
+    );
+    let days = (today - *first).num_days().clamp(0, 6) + 1;
+    let _ = writeln!(
+        out,
+        "  {:<14}{:>20}",
+        "Per day",
+        format!("{:.0} avg", week.runs as f64 / days as f64)
+    );
+
+    let breakdown = |counts: &BTreeMap<String, u64>| {
+        let mut counts: Vec<_> = counts.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        counts
+            .iter()
+            .map(|(k, n)| format!("{k} {n}"))
+            .collect::<Vec<_>>()
+            .join(" · ")
+    };
+    out.push('\n');
+    for (label, counts) in [
+        ("Commands", &week.commands),
+        ("Formats", &week.formats),
+        ("Failures", &week.failures),
+    ] {
+        if !counts.is_empty() {
+            let _ = writeln!(out, "  {label:<14}{}", breakdown(counts));
+        }
+    }
+    let _ = writeln!(out, "  {}", "(breakdowns cover the last 7 days)".dimmed());
+    let _ = writeln!(out, "  {}\n", "─".repeat(67).dimmed());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_the_last_week() {
+        colored::control::set_override(false);
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let day = |runs, command: &str, failures: &[(&str, u64)]| Day {
+            runs,
+            commands: [(command.to_string(), runs)].into(),
+            formats: [("plain".to_string(), runs)].into(),
+            failures: failures.iter().map(|(c, n)| (c.to_string(), *n)).collect(),
+        };
+        let stats = Stats {
+            days: [
+                (today - Duration::days(20), day(100, "all", &[])),
+                (
+                    today - Duration::days(2),
+                    day(4000, "report", &[("E004", 7)]),
+                ),
+                (today, day(1000, "report", &[("E005", 1)])),
+            ]
+            .into(),
+        };
+        let out = render(&stats, true, today);
+        let row = |label: &str| {
+            let line = out.lines().find(|l| l.trim_start().starts_with(label));
+            line.unwrap().split_whitespace().skip(1).collect::<Vec<_>>()
+        };
+        assert_eq!(row("Runs"), ["1000", "5000", "5100"]);
+        assert_eq!(row("Failed"), ["1", "8", "8"]);
+        assert!(out.contains("Commands      report 5000\n"));
+        assert!(out.contains("Failures      E004 7 · E005 1"));
+        assert!(!out.contains("Recording is off"));
+    }
+}
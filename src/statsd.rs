@@ -0,0 +1,113 @@
+//! `--statsd host:port`: send each run's usage as gauges to a StatsD daemon,
+//! or with `--dogstatsd` to a Datadog agent, tagged with plan, profile and
+//! any labels. Plain StatsD has no tags, so there every profile writes the
+//! same names and labels are left out.
+//! UDP, one datagram per run, nothing waited for.
+
+use crate::api::{RateWindow, Snapshot};
+use crate::labels::{self, Labels};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+pub const PREFIX: &str = "codex_usage";
+
+/// Where the Datadog agent listens by default, for `--dogstatsd` alone
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8125";
+
+/// One gauge per line: each window's percentage and reset countdown as seen
+/// at `now`, and the limit flag. Windows the API left out send nothing.
+pub fn packet(
+    profile: &str,
+    labels: &Labels,
+    snap: &Snapshot,
+    now: DateTime<Utc>,
+    dogstatsd: bool,
+) -> String {
+    let rl = snap.usage.rate_limit.as_ref();
+    let mut gauges = Vec::new();
+    let mut window = |name: &str, w: Option<&RateWindow>| {
+        let Some(w) = w else { return };
+        if let Some(pct) = w.used_percent {
+            gauges.push((format!("{name}.used_percent"), pct));
+        }
+        if let Some(secs) = w.resets_in(snap.fetched_at, now) {
+            gauges.push((format!("{name}.resets_in_seconds"), secs as f64));
+        }
+    };
+    window("five_hour", rl.and_then(|r| r.primary_window.as_ref()));
+    window("seven_day", rl.and_then(|r| r.secondary_window.as_ref()));
+    let reached = rl.and_then(|r| r.limit_reached).unwrap_or(false);
+    gauges.push(("limit_reached".into(), f64::from(u8::from(reached))));
+
+    let tags = match dogstatsd {
+        true => {
+            let plan = snap.usage.plan_type.as_deref().unwrap_or("unknown");
+            let mut tags = format!("|#plan:{},profile:{}", tag(plan), tag(profile));
+            for (k, v) in labels::extra(labels, &["plan", "profile"]) {
+                let _ = write!(tags, ",{}:{}", tag(k), tag(v));
+            }
+            tags
+        }
+        false => String::new(),
+    };
+    gauges
+        .iter()
+        .map(|(name, value)| format!("{PREFIX}.{name}:{value}|g{tags}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Send `packet` to `addr` over UDP
+pub fn send(addr: &str, packet: &str) -> Result<()> {
+    let target = addr
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut a| a.next())
+        .with_context(|| format!("Invalid --statsd address '{addr}' (expected host:port)"))?;
+    let local = match target.is_ipv6() {
+        true => "[::]:0",
+        false => "0.0.0.0:0",
+    };
+    UdpSocket::bind(local)
+        .and_then(|socket| socket.send_to(packet.as_bytes(), target))
+        .with_context(|| format!("Failed to reach StatsD at {addr}"))?;
+    Ok(())
+}
+
+/// Tag values can't hold the separators DogStatsD uses
+fn tag(v: &str) -> String {
+    v.replace([',', '|', '#', ':'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_timestamp;
+
+    #[test]
+    fn gauges_with_and_without_tags() {
+        let now = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let usage = serde_json::from_str(include_str!("../tests/fixtures/high.json")).unwrap();
+        let snap = Snapshot::new(usage, now);
+
+        let labels = Labels::from([
+            ("pod".to_string(), "api-0".to_string()),
+            // The gauges' own tags aren't overridden
+            ("plan".to_string(), "free".to_string()),
+        ]);
+        let plain = packet("work", &labels, &snap, now, false);
+        assert!(
+            plain.starts_with("codex_usage.five_hour.used_percent:95|g\n"),
+            "{plain}"
+        );
+        assert!(plain.ends_with("codex_usage.limit_reached:0|g"), "{plain}");
+
+        let dog = packet("work:laptop", &labels, &snap, now, true);
+        assert!(dog
+            .lines()
+            .all(|l| l.ends_with("|g|#plan:pro,profile:work_laptop,pod:api-0")));
+        assert_eq!(dog.lines().count(), plain.lines().count());
+    }
+}
@@ -0,0 +1,541 @@
+//! `status`: everything that decides what codex-usage shows, in one place —
+//! the active profile and where its token comes from, the last fetch and
+//! how it went, whether the `serve` daemon is alive and how old its data is,
+//! and which recorders and integrations are switched on. The first stop for
+//! "why is it showing stale or odd data?".
+
+use crate::auth::Identity;
+use crate::config::{Config, Profile};
+use crate::server::heartbeat::Heartbeat;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Width of the label column
+const LABEL: usize = 14;
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub profile: String,
+    pub credentials: Credential,
+    pub last_fetch: Option<LastFetch>,
+    /// When the profile's hot-cache snapshot was fetched
+    pub cached_at: Option<DateTime<Utc>>,
+    pub daemon: Option<Daemon>,
+    pub history: bool,
+    pub stats: bool,
+    pub data_dir: String,
+    /// Why `data_dir` is a fallback, if it is
+    pub data_fallback: Option<String>,
+    /// e.g. `Discord Rich Presence (home)`
+    pub integrations: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "state")]
+pub enum Credential {
+    Missing,
+    Unreadable {
+        source: String,
+        error: String,
+    },
+    Found {
+        source: String,
+        oauth: bool,
+        expires_at: Option<DateTime<Utc>>,
+        #[serde(flatten)]
+        identity: Identity,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct Daemon {
+    pub alive: bool,
+    pub heartbeat: Heartbeat,
+}
+
+// ─── Last fetch ───────────────────────────────────────────────────────────────
+
+/// How the most recent one-shot fetch went, kept so `status` can show a
+/// failure that left nothing in the history
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LastFetch {
+    pub at: DateTime<Utc>,
+    pub profile: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_percent: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secondary_percent: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<FetchError>,
+}
+
+/// Error code and the first line of the message
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FetchError {
+    pub code: String,
+    pub message: String,
+}
+
+fn last_fetch_path() -> PathBuf {
+    crate::paths::data_dir().join("last_fetch.json")
+}
+
+/// Note a fetch's outcome. Best-effort, like history.
+pub fn record_fetch(profile: &str, result: &Result<crate::api::Snapshot>) {
+    let last = last_fetch(profile, result);
+    let path = last_fetch_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(json) = serde_json::to_vec(&last) {
+        let _ = std::fs::write(&path, json);
+    }
+}
+
+fn last_fetch(profile: &str, result: &Result<crate::api::Snapshot>) -> LastFetch {
+    let pct = |w: Option<&crate::api::RateWindow>| w.and_then(|w| w.used_percent);
+    let mut last = LastFetch {
+        at: Utc::now(),
+        profile: profile.to_string(),
+        primary_percent: None,
+        secondary_percent: None,
+        error: None,
+    };
+    match result {
+        Ok(snap) => {
+            let rl = snap.usage.rate_limit.as_ref();
+            last.at = snap.fetched_at;
+            last.primary_percent = pct(rl.and_then(|r| r.primary_window.as_ref()));
+            last.secondary_percent = pct(rl.and_then(|r| r.secondary_window.as_ref()));
+        }
+        Err(e) => {
+            let message = format!("{e:#}");
+            last.error = Some(FetchError {
+                code: crate::errors::classify(e).code.to_string(),
+                message: message.lines().next().unwrap_or_default().to_string(),
+            });
+        }
+    }
+    last
+}
+
+fn read_last_fetch() -> Option<LastFetch> {
+    serde_json::from_slice(&std::fs::read(last_fetch_path()).ok()?).ok()
+}
+
+// ─── Gathering ────────────────────────────────────────────────────────────────
+
+/// Look everything up for `profile` (the default profile when `None`)
+pub fn gather(profile: Option<&str>, now: DateTime<Utc>) -> Result<Status> {
+    let config = Config::load()?;
+    let name = profile.unwrap_or(crate::config::DEFAULT_PROFILE);
+    let sources = match profile {
+        Some(name) => config.profile(name)?.sources(),
+        None => Profile::default().sources(),
+    };
+
+    let mut credentials = Credential::Missing;
+    for source in sources {
+        match source.read() {
+            Ok(None) => continue,
+            Err(e) => {
+                credentials = Credential::Unreadable {
+                    source: crate::anonymize::home(&source.label()),
+                    error: format!("{e:#}").lines().next().unwrap_or("").into(),
+                };
+            }
+            Ok(Some(mut creds)) => {
+                crate::anonymize::identity(&mut creds.identity);
+                credentials = Credential::Found {
+                    source: crate::anonymize::home(&source.label()),
+                    oauth: creds.is_oauth,
+                    expires_at: crate::fleet::jwt_expiry(&creds.access_token),
+                    identity: creds.identity,
+                };
+                break;
+            }
+        }
+    }
+
+    let mut integrations = Vec::new();
+    if config.discord.client_id.is_some() {
+        let profile = config.discord.profile.as_deref().unwrap_or("first profile");
+        integrations.push(format!("Discord Rich Presence ({profile}, while serving)"));
+    }
+    if !config.server.clients.is_empty() {
+        let n = config.server.clients.len();
+        integrations.push(format!(
+            "server clients: {n} ({})",
+            config
+                .server
+                .clients
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(Status {
+        profile: name.to_string(),
+        credentials,
+        last_fetch: read_last_fetch(),
+        cached_at: crate::cache::read(name)
+            .ok()
+            .flatten()
+            .map(|s| s.fetched_at),
+        daemon: crate::server::heartbeat::read()?.map(|heartbeat| Daemon {
+            alive: heartbeat.alive(now),
+            heartbeat,
+        }),
+        history: crate::history::enabled(),
+        stats: crate::stats::enabled(),
+        data_dir: crate::anonymize::home(&crate::paths::data_dir().display().to_string()),
+        data_fallback: crate::paths::data_fallback().map(String::from),
+        integrations,
+    })
+}
+
+// ─── Rendering ────────────────────────────────────────────────────────────────
+
+pub fn render(status: &Status, now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "\n  {} Status", "◆".cyan().bold());
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+    let label = |s: &str| format!("{s:<LABEL$}").bold();
+
+    let _ = writeln!(out, "  {}{}", label("Profile"), status.profile);
+    let creds = match &status.credentials {
+        Credential::Missing => "✗ none found — codex login".red().to_string(),
+        Credential::Unreadable { source, error } => {
+            format!("✗ {source} unreadable: {error}").red().to_string()
+        }
+        Credential::Found {
+            source,
+            oauth: false,
+            ..
+        } => format!("{source} — API key, can't see usage limits")
+            .yellow()
+            .to_string(),
+        Credential::Found {
+            source,
+            expires_at: Some(exp),
+            ..
+        } if *exp <= now => format!(
+            "{source} — {}",
+            format!("expired {} ago", age(now - *exp)).red()
+        ),
+        Credential::Found {
+            source,
+            expires_at: Some(exp),
+            ..
+        } => format!(
+            "{source} — expires {} (in {})",
+            exp.format("%Y-%m-%d %H:%M UTC"),
+            age(*exp - now)
+        ),
+        Credential::Found { source, .. } => source.clone(),
+    };
+    let _ = writeln!(out, "  {}{creds}", label("Credentials"));
+    if let Credential::Found { identity, .. } = &status.credentials {
+        if !identity.is_empty() {
+            let _ = writeln!(out, "  {}{}", label("Account"), account(identity, now));
+        }
+    }
+
+    let fetch = match &status.last_fetch {
+        None => "none yet".dimmed().to_string(),
+        Some(f) => {
+            let when = format!("{} ago ({})", age(now - f.at), f.profile);
+            match &f.error {
+                Some(FetchError { code, message }) => {
+                    format!("{} {when}: {code} {message}", "✗".red())
+                }
+                None => {
+                    let pct = |p: Option<f64>| p.map_or("—".to_string(), |p| format!("{p:.1}%"));
+                    format!(
+                        "{} {when}: 5h {}, 7d {}",
+                        "✓".green(),
+                        pct(f.primary_percent),
+                        pct(f.secondary_percent)
+                    )
+                }
+            }
+        }
+    };
+    let _ = writeln!(out, "  {}{fetch}", label("Last fetch"));
+    let cache = match status.cached_at {
+        Some(at) => format!("{} old (served to --max-age)", age(now - at)),
+        None => "empty".dimmed().to_string(),
+    };
+    let _ = writeln!(out, "  {}{cache}", label("Cache"));
+
+    write_daemon(&mut out, status.daemon.as_ref(), now);
+
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let _ = writeln!(
+        out,
+        "  {}history {} · stats {}",
+        label("Recording"),
+        on_off(status.history),
+        on_off(status.stats)
+    );
+    let data = match &status.data_fallback {
+        Some(why) => format!("{} {why}", "⚠".yellow()),
+        None => status.data_dir.clone(),
+    };
+    let _ = writeln!(out, "  {}{data}", label("Data"));
+    let integrations = match status.integrations.is_empty() {
+        true => "none configured".dimmed().to_string(),
+        false => status.integrations.join(" · "),
+    };
+    let _ = writeln!(out, "  {}{integrations}", label("Integrations"));
+    let _ = writeln!(out, "  {}\n", "─".repeat(67).dimmed());
+    out
+}
+
+/// `ada@example.com · plus · refreshed 2d 4h ago`, from whatever is known
+fn account(identity: &Identity, now: DateTime<Utc>) -> String {
+    let refreshed = identity
+        .refreshed_at
+        .map(|at| format!("refreshed {} ago", age(now - at)));
+    let plan = identity.plan.as_deref().map(crate::plans::display);
+    [identity.email.clone(), plan, refreshed]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+fn write_daemon(out: &mut String, daemon: Option<&Daemon>, now: DateTime<Utc>) {
+    let label = format!("{:<LABEL$}", "Daemon").bold();
+    let Some(Daemon { alive, heartbeat }) = daemon else {
+        let _ = writeln!(
+            out,
+            "  {label}{}",
+            "not running (never started; see `codex-usage serve`)".dimmed()
+        );
+        return;
+    };
+
+    if !alive {
+        let _ = writeln!(
+            out,
+            "  {label}{} last heartbeat {} ago (pid {})",
+            "✗ not running:".red(),
+            age(now - heartbeat.beat_at),
+            heartbeat.pid
+        );
+        for line in [
+            "Anything reading it is showing data from before then.",
+            "Restart it with `codex-usage serve`; to have that happen on its own,",
+            "run it under systemd (Restart=always) or launchd (KeepAlive).",
+        ] {
+            let _ = writeln!(out, "  {:<LABEL$}{line}", "");
+        }
+        return;
+    }
+
+    let _ = writeln!(
+        out,
+        "  {label}{} pid {} · http://{} · up {}",
+        "✓ running ·".green(),
+        heartbeat.pid,
+        heartbeat.listen,
+        age(now - heartbeat.started_at)
+    );
+    let _ = writeln!(
+        out,
+        "  {:<LABEL$}{}",
+        "",
+        format!(
+            "heartbeat {} ago, polling every {}s{}",
+            age(now - heartbeat.beat_at),
+            heartbeat.poll_seconds,
+            if heartbeat.adaptive {
+                " (adaptive)"
+            } else {
+                ""
+            }
+        )
+        .dimmed()
+    );
+    for (name, profile) in &heartbeat.profiles {
+        let data = match profile.fetched_at {
+            Some(at) => format!("data {} old", age(now - at)),
+            None => "no data yet".to_string(),
+        };
+        let data = match heartbeat.stale(profile, now) {
+            true => data.yellow(),
+            false => data.normal(),
+        };
+        let error = match (&profile.error, profile.polled_at) {
+            (Some(e), Some(at)) => format!(
+                " · last poll failed {} ago: {}",
+                age(now - at),
+                e.lines().next().unwrap_or_default()
+            )
+            .red()
+            .to_string(),
+            _ => String::new(),
+        };
+        let _ = writeln!(out, "    {:<w$}{data}{error}", name, w = LABEL - 2);
+    }
+}
+
+/// `4s`, `12m`, `3h 5m`, `2d 4h`
+pub fn age(d: chrono::Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    let (mins, hours, days) = (secs / 60, secs / 3600, secs / 86400);
+    match () {
+        _ if days > 0 => format!("{days}d {}h", hours % 24),
+        _ if hours > 0 => format!("{hours}h {}m", mins % 60),
+        _ if mins > 0 => format!("{mins}m"),
+        _ => format!("{secs}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::heartbeat::ProfileBeat;
+    use chrono::Duration;
+
+    #[test]
+    fn one_screen_of_state() {
+        colored::control::set_override(false);
+        let now = crate::clock::parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let heartbeat = |beat_at| Heartbeat {
+            pid: 4242,
+            listen: "127.0.0.1:8787".into(),
+            started_at: now - Duration::hours(3),
+            beat_at,
+            poll_seconds: 60,
+            adaptive: false,
+            profiles: [
+                (
+                    "home".to_string(),
+                    ProfileBeat {
+                        fetched_at: Some(now - Duration::seconds(35)),
+                        polled_at: Some(now - Duration::seconds(35)),
+                        error: None,
+                    },
+                ),
+                (
+                    "work".to_string(),
+                    ProfileBeat {
+                        fetched_at: Some(now - Duration::minutes(130)),
+                        polled_at: Some(now - Duration::seconds(40)),
+                        error: Some("Token rejected by the API (HTTP 401)".into()),
+                    },
+                ),
+            ]
+            .into(),
+        };
+        let status = |beat_at| Status {
+            profile: "default".into(),
+            credentials: Credential::Found {
+                source: "file ~/.codex/auth.json".into(),
+                oauth: true,
+                expires_at: Some(now + Duration::hours(75)),
+                identity: Identity {
+                    email: Some("ada@example.com".into()),
+                    plan: Some("plus".into()),
+                    refreshed_at: Some(now - Duration::hours(52)),
+                },
+            },
+            last_fetch: Some(LastFetch {
+                at: now - Duration::minutes(4),
+                profile: "default".into(),
+                primary_percent: None,
+                secondary_percent: None,
+                error: Some(FetchError {
+                    code: "E004".into(),
+                    message: "Failed to reach ChatGPT API".into(),
+                }),
+            }),
+            cached_at: Some(now - Duration::minutes(9)),
+            daemon: Some(Daemon {
+                alive: beat_at > now - Duration::seconds(30),
+                heartbeat: heartbeat(beat_at),
+            }),
+            history: true,
+            stats: false,
+            data_dir: "/home/ada/.local/share/codex-usage".into(),
+            data_fallback: None,
+            integrations: Vec::new(),
+        };
+
+        let live = render(&status(now - Duration::seconds(4)), now);
+        assert!(live.contains("auth.json — expires 2024-05-04 13:00 UTC (in 3d 3h)"));
+        assert!(live.contains("Account       ada@example.com · Plus · refreshed 2d 4h ago\n"));
+        assert!(live.contains("✗ 4m ago (default): E004 Failed to reach ChatGPT API"));
+        assert!(live.contains("Cache         9m old"));
+        assert!(live.contains("✓ running · pid 4242 · http://127.0.0.1:8787 · up 3h 0m"));
+        assert!(live.contains("    home        data 35s old\n"));
+        assert!(live.contains("    work        data 2h 10m old · last poll failed 40s ago: Token"));
+        assert!(live.contains("history on · stats off"));
+        assert!(live.contains("Data          /home/ada/.local/share/codex-usage\n"));
+
+        let dead = render(&status(now - Duration::minutes(20)), now);
+        assert!(dead.contains("✗ not running: last heartbeat 20m ago (pid 4242)"));
+        assert!(dead.contains("Restart=always"));
+        assert!(!dead.contains("home "));
+    }
+
+    #[test]
+    fn every_credential_and_fetch_outcome() {
+        colored::control::set_override(false);
+        let now = crate::clock::parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let ok = Ok(crate::api::Snapshot::plus((42.0, 3600), (12.5, 86400), now));
+        let failed = Err(anyhow::anyhow!(
+            "Failed to reach ChatGPT API\ncaused by: dns"
+        ));
+        let status = |credentials, result| Status {
+            profile: "work".into(),
+            credentials,
+            last_fetch: Some(last_fetch("work", &result)),
+            cached_at: None,
+            daemon: None,
+            history: false,
+            stats: true,
+            data_dir: "/tmp/codex-usage-1000".into(),
+            data_fallback: Some("/tmp/codex-usage-1000 (~/.local/share isn't writable)".into()),
+            integrations: vec!["Discord Rich Presence (home, while serving)".into()],
+        };
+        let found = |oauth, expires_at| Credential::Found {
+            source: "env CODEX_ACCESS_TOKEN".into(),
+            oauth,
+            expires_at,
+            identity: Identity::default(),
+        };
+
+        let out = render(&status(Credential::Missing, ok), now);
+        assert!(out.contains("Credentials   ✗ none found — codex login\n"));
+        assert!(out.contains("Last fetch    ✓ 0s ago (work): 5h 42.0%, 7d 12.5%\n"));
+        assert!(out.contains("Cache         empty\n"));
+        assert!(out.contains("Daemon        not running (never started"));
+        assert!(out.contains("history off · stats on"));
+        assert!(out.contains("Data          ⚠ /tmp/codex-usage-1000 (~/.local/share"));
+        assert!(out.contains("Integrations  Discord Rich Presence (home, while serving)\n"));
+        assert!(!out.contains("Account"));
+
+        // Only the first line of an error is kept, with its code
+        let out = render(&status(found(false, None), failed), now);
+        assert!(out.contains("✗ 0s ago (work): E004 Failed to reach ChatGPT API\n"));
+        assert!(out.contains("CODEX_ACCESS_TOKEN — API key, can't see usage limits"));
+        let expired = found(true, Some(now - chrono::Duration::hours(2)));
+        let out = render(
+            &status(
+                expired,
+                Ok(crate::api::Snapshot::plus((1.0, 1), (1.0, 1), now)),
+            ),
+            now,
+        );
+        assert!(out.contains("CODEX_ACCESS_TOKEN — expired 2h 0m ago"));
+    }
+}
@@ -0,0 +1,298 @@
+//! `team report`: the weekly window framed as an error budget across seats.
+//!
+//! Seats come from a JSON-lines file, one seat per line:
+//! `{"name": "alice", "access_token": "…", "account_id": "…"}`
+
+use crate::api::{self, Snapshot, WhamUsage};
+use crate::auth::Credentials;
+use crate::batch::Batch;
+use crate::clock::Clock;
+use crate::locale::Locale;
+use crate::render::layout::fit_width;
+use crate::render::Sort;
+use crate::week::WorkWeek;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::path::Path;
+
+/// A seat more than this many standard deviations above the mean is an outlier
+const OUTLIER_SIGMA: f64 = 1.5;
+
+/// Columns reserved for seat names
+const SEAT_WIDTH: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct Seat {
+    pub name: String,
+    pub access_token: String,
+    pub account_id: Option<String>,
+}
+
+impl Seat {
+    pub fn credentials(&self) -> Credentials {
+        Credentials {
+            access_token: self.access_token.clone(),
+            account_id: self.account_id.clone(),
+            is_oauth: true,
+            identity: crate::auth::Identity::from_jwt(&self.access_token),
+        }
+    }
+}
+
+/// Read a seats file; blank lines and `#` comments are ignored
+pub fn load_seats(path: &Path) -> Result<Vec<Seat>> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}", path.display()))?;
+    let mut seats = Vec::new();
+    for (n, line) in raw.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let seat: Seat = serde_json::from_str(line)
+            .with_context(|| format!("{}:{}: invalid seat entry", path.display(), n + 1))?;
+        seats.push(seat);
+    }
+    if seats.is_empty() {
+        bail!("{} lists no seats", path.display());
+    }
+    Ok(seats)
+}
+
+/// Weekly budget position of one seat
+#[derive(Debug)]
+pub struct SeatBudget {
+    pub name: String,
+    /// 0–100 of the weekly window consumed
+    pub consumed: f64,
+    /// 0–100 of the week still to run
+    pub week_remaining: f64,
+    pub limit_reached: bool,
+}
+
+impl SeatBudget {
+    pub fn from_snapshot(name: &str, snap: &Snapshot, clock: &Clock) -> Option<Self> {
+        let weekly = weekly_window(&snap.usage)?;
+        let reset = weekly.resets_in(snap.fetched_at, clock.now())?;
+        let length = weekly.length("secondary")? as f64;
+        Some(SeatBudget {
+            name: name.to_string(),
+            consumed: weekly.used_percent.unwrap_or(0.0).clamp(0.0, 100.0),
+            week_remaining: (reset as f64 / length * 100.0).clamp(0.0, 100.0),
+            limit_reached: snap
+                .usage
+                .rate_limit
+                .as_ref()
+                .and_then(|r| r.limit_reached)
+                .unwrap_or(false),
+        })
+    }
+
+    /// Percentage points consumed ahead of an even burn through the week
+    pub fn over_pace(&self) -> f64 {
+        self.consumed - (100.0 - self.week_remaining)
+    }
+}
+
+fn weekly_window(usage: &WhamUsage) -> Option<&api::RateWindow> {
+    usage.rate_limit.as_ref()?.secondary_window.as_ref()
+}
+
+/// Fetch every seat through `batch` and print the budget report
+pub fn report(
+    seats: &[Seat],
+    clock: &Clock,
+    week: Option<&WorkWeek>,
+    batch: &Batch,
+    locale: &Locale,
+    sort: Sort,
+) -> Result<()> {
+    let items: Vec<(String, &Seat)> = seats.iter().map(|s| (s.name.clone(), s)).collect();
+    let outcome = batch.run(&items, |seat| {
+        api::fetch_usage(&seat.credentials()).map(|usage| Snapshot::new(usage, clock.now()))
+    })?;
+    if outcome.resumed > 0 {
+        eprintln!(
+            "Resumed {} of {} seats from the checkpoint",
+            outcome.resumed,
+            seats.len()
+        );
+    }
+    let mut budgets = Vec::new();
+    let mut failures = Vec::new();
+    for (name, result) in outcome.results {
+        match result {
+            Ok(snap) => match SeatBudget::from_snapshot(&name, &snap, clock) {
+                Some(b) => budgets.push(b),
+                None => failures.push((name, "no weekly window".to_string())),
+            },
+            Err(e) => failures.push((name, e)),
+        }
+    }
+    // The calendar work week is the same for every seat
+    let work_week = week.map(|w| {
+        let elapsed = w.view(None, clock.now(), clock.now()).elapsed;
+        (w.label(), 100.0 - elapsed)
+    });
+    print!("{}", render(&budgets, &failures, work_week, locale, sort));
+    Ok(())
+}
+
+/// Render the report; split out so it stays a pure function of its inputs.
+/// `work_week` is the configured week's label and the 0–100 of it still to run.
+pub fn render(
+    budgets: &[SeatBudget],
+    failures: &[(String, String)],
+    work_week: Option<(String, f64)>,
+    locale: &Locale,
+    sort: Sort,
+) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "\n  {} Codex weekly budget — team", "◆".cyan().bold());
+    let _ = writeln!(out, "  {}", "─".repeat(67).dimmed());
+
+    if budgets.is_empty() {
+        let _ = writeln!(out, "  {}", "No seat returned usage data.".red());
+    } else {
+        let n = budgets.len() as f64;
+        let consumed = budgets.iter().map(|b| b.consumed).sum::<f64>() / n;
+        let remaining = budgets.iter().map(|b| b.week_remaining).sum::<f64>() / n;
+        let sigma = (budgets
+            .iter()
+            .map(|b| (b.consumed - consumed).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+
+        let headline = format!(
+            "{consumed:.0}% of weekly budget consumed with {remaining:.0}% of the week \
+             remaining across {} seat{}",
+            budgets.len(),
+            if budgets.len() == 1 { "" } else { "s" }
+        );
+        let over = consumed - (100.0 - remaining);
+        let _ = writeln!(out, "  {}", headline.bold());
+        if let Some((label, left)) = &work_week {
+            let _ = writeln!(
+                out,
+                "  {}",
+                format!("{left:.0}% of the work week ({label}) still to run").dimmed()
+            );
+        }
+        let _ = writeln!(
+            out,
+            "  {}\n",
+            if over > 0.0 {
+                format!("Burning {over:.0} pts ahead of an even pace.").yellow()
+            } else {
+                format!("{:.0} pts of headroom against an even pace.", -over).green()
+            }
+        );
+
+        let mut sorted: Vec<&SeatBudget> = budgets.iter().collect();
+        sort.apply(&mut sorted, |b| {
+            (&b.name, Some((b.limit_reached, b.consumed)))
+        });
+        for b in sorted {
+            let outlier =
+                b.limit_reached || (sigma > 0.0 && (b.consumed - consumed) / sigma > OUTLIER_SIGMA);
+            let pace = b.over_pace();
+            let pace = if pace > 0.0 {
+                format!("+{pace:.0} pts over pace").yellow()
+            } else {
+                format!("{pace:.0} pts").dimmed()
+            };
+            let row = format!(
+                "{} {:>5}% used  {:3.0}% of week left  {}",
+                fit_width(&b.name, SEAT_WIDTH),
+                locale.num(b.consumed, 1),
+                b.week_remaining,
+                pace
+            );
+            if outlier {
+                let why = if b.limit_reached {
+                    "limit reached"
+                } else {
+                    "outlier"
+                };
+                let _ = writeln!(out, "  {} {}  {}", "▲".red().bold(), row, why.red().bold());
+            } else {
+                let _ = writeln!(out, "    {row}");
+            }
+        }
+    }
+
+    for (name, err) in failures {
+        let first_line = err.lines().next().unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "  {} {} {}",
+            "✗".red(),
+            fit_width(name, SEAT_WIDTH),
+            first_line.dimmed()
+        );
+    }
+    let _ = writeln!(out, "  {}\n", "─".repeat(67).dimmed());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seat(name: &str, consumed: f64) -> SeatBudget {
+        SeatBudget {
+            name: name.into(),
+            consumed,
+            week_remaining: 52.0,
+            limit_reached: false,
+        }
+    }
+
+    #[test]
+    fn headline_and_outliers() {
+        colored::control::set_override(false);
+        let budgets: Vec<_> = [30.0, 32.0, 28.0, 31.0, 29.0, 78.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| seat(&format!("seat{i}"), c))
+            .collect();
+        let out = render(
+            &budgets,
+            &[],
+            Some(("Mon–Fri, UTC".into(), 40.0)),
+            &crate::locale::DEFAULT,
+            Sort::Severity,
+        );
+        assert!(out.contains(
+            "38% of weekly budget consumed with 52% of the week remaining across 6 seats"
+        ));
+        assert!(out.contains("40% of the work week (Mon–Fri, UTC) still to run"));
+        let flagged: Vec<_> = out.lines().filter(|l| l.contains("outlier")).collect();
+        assert_eq!(flagged.len(), 1);
+        assert!(flagged[0].contains("seat5"));
+    }
+
+    #[test]
+    fn seats_sort_by_severity_or_name() {
+        colored::control::set_override(false);
+        let mut capped = seat("bob", 40.0);
+        capped.limit_reached = true;
+        let budgets = [seat("carol", 90.0), capped, seat("alice", 60.0)];
+        let order = |sort| {
+            let out = render(&budgets, &[], None, &crate::locale::DEFAULT, sort);
+            out.lines()
+                .filter_map(|l| {
+                    l.split_whitespace()
+                        .find(|w| ["alice", "bob", "carol"].contains(w))
+                })
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        // A seat at its limit comes before fuller ones that aren't
+        assert_eq!(order(Sort::Severity), ["bob", "carol", "alice"]);
+        assert_eq!(order(Sort::Name), ["alice", "bob", "carol"]);
+    }
+}
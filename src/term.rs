@@ -0,0 +1,192 @@
+//! What the terminal can show. Colour depth comes from NO_COLOR / FORCE_COLOR,
+//! COLORTERM, TERM and, failing those, terminfo via `tput colors`. Output
+//! degrades rather than emitting sequences a basic terminal turns into mush:
+//! truecolor palette → the 8 standard colours → no colour, where bar patterns
+//! carry severity instead.
+//!
+//! Whether stdout is a terminal at all is detected too, and can be forced
+//! either way with `--tty` / `--no-tty` for wrappers that allocate a pty but
+//! can't cope with cursor tricks.
+
+use std::io::IsTerminal;
+use std::process::Command;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ColorLevel {
+    None,
+    /// The 8 standard colours plus bold/dim
+    #[default]
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Capability {
+    /// Treat stdout as an interactive terminal: colour and progress output
+    pub tty: bool,
+    pub color: ColorLevel,
+    /// Set when colour was turned off because of the terminal rather than by
+    /// request, to explain the plainer output
+    pub notice: Option<String>,
+}
+
+static CAP: OnceLock<Capability> = OnceLock::new();
+
+/// Detect once, with `tty` (from `--tty` / `--no-tty`) overriding the isatty
+/// check. Later calls return the first answer. `colored` is set to match, so
+/// everything printed agrees with the detection.
+pub fn init(tty: Option<bool>) -> &'static Capability {
+    CAP.get_or_init(|| {
+        let is_tty = tty.unwrap_or_else(|| std::io::stdout().is_terminal());
+        let cap = detect(|k| std::env::var(k).ok(), tput_colors, is_tty);
+        colored::control::set_override(cap.color != ColorLevel::None);
+        cap
+    })
+}
+
+/// The run's capability; detects without overrides if `init` wasn't called
+pub fn capability() -> &'static Capability {
+    init(None)
+}
+
+/// Pure detection over the environment, terminfo and whether stdout is a tty
+pub fn detect(
+    env: impl Fn(&str) -> Option<String>,
+    tput: impl Fn() -> Option<u32>,
+    is_tty: bool,
+) -> Capability {
+    Capability {
+        tty: is_tty,
+        ..depth(env, tput, is_tty)
+    }
+}
+
+fn depth(
+    env: impl Fn(&str) -> Option<String>,
+    tput: impl Fn() -> Option<u32>,
+    is_tty: bool,
+) -> Capability {
+    let set = |k: &str| env(k).is_some_and(|v| !v.is_empty());
+    let level = |color| Capability {
+        color,
+        ..Default::default()
+    };
+
+    // https://no-color.org
+    if set("NO_COLOR") || env("CLICOLOR").as_deref() == Some("0") {
+        return level(ColorLevel::None);
+    }
+    let forced = set("FORCE_COLOR") || env("CLICOLOR_FORCE").is_some_and(|v| v != "0");
+    if !is_tty && !forced {
+        return level(ColorLevel::None);
+    }
+
+    let colorterm = env("COLORTERM").unwrap_or_default().to_ascii_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return level(ColorLevel::TrueColor);
+    }
+    let term = env("TERM").unwrap_or_default();
+    if term.contains("256color") {
+        return level(ColorLevel::Ansi256);
+    }
+    if cfg!(windows) && term.is_empty() {
+        // Windows Terminal and recent conhost handle the standard colours
+        return level(ColorLevel::Basic);
+    }
+    if term == "dumb" && !forced {
+        return Capability {
+            color: ColorLevel::None,
+            notice: Some("TERM=dumb".to_string()),
+            ..Default::default()
+        };
+    }
+    match tput() {
+        Some(n) if n >= 256 => level(ColorLevel::Ansi256),
+        Some(n) if n >= 8 => level(ColorLevel::Basic),
+        Some(n) if !forced => Capability {
+            color: ColorLevel::None,
+            notice: Some(format!("terminal reports {n} colours")),
+            ..Default::default()
+        },
+        _ => level(ColorLevel::Basic),
+    }
+}
+
+/// `tput colors` for the current TERM; `None` if terminfo has no answer
+fn tput_colors() -> Option<u32> {
+    let out = Command::new("tput").arg("colors").output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&out.stdout)
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .map(|n| n.max(0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with(vars: &[(&str, &str)], tput: Option<u32>, tty: bool) -> Capability {
+        let vars: Vec<(String, String)> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        detect(
+            |k| vars.iter().find(|(n, _)| n == k).map(|(_, v)| v.clone()),
+            || tput,
+            tty,
+        )
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(
+            with(&[("COLORTERM", "truecolor")], None, true).color,
+            ColorLevel::TrueColor
+        );
+        assert_eq!(
+            with(&[("TERM", "xterm-256color")], None, true).color,
+            ColorLevel::Ansi256
+        );
+        assert_eq!(
+            with(&[("TERM", "xterm")], Some(8), true).color,
+            ColorLevel::Basic
+        );
+        assert_eq!(
+            with(&[("TERM", "xterm")], Some(8), false).color,
+            ColorLevel::None
+        );
+        assert_eq!(
+            with(&[("NO_COLOR", "1"), ("COLORTERM", "truecolor")], None, true).color,
+            ColorLevel::None
+        );
+
+        // Only capability-driven downgrades carry a notice
+        let vt52 = with(&[("TERM", "vt52")], Some(0), true);
+        assert_eq!(vt52.color, ColorLevel::None);
+        assert!(vt52.notice.is_some());
+        assert!(with(&[("NO_COLOR", "1")], None, true).notice.is_none());
+    }
+
+    #[test]
+    fn tty_decides_colour_unless_forced() {
+        // `--tty` under a wrapper: coloured as any terminal would be
+        let forced = with(&[("TERM", "xterm")], Some(8), true);
+        assert!(forced.tty);
+        assert_eq!(forced.color, ColorLevel::Basic);
+        // `--no-tty`: neither colour nor the progress line
+        let piped = with(&[("TERM", "xterm-256color")], Some(256), false);
+        assert!(!piped.tty);
+        assert_eq!(piped.color, ColorLevel::None);
+        // FORCE_COLOR keeps colour in a pipe, CLICOLOR=0 drops it anywhere
+        let coloured = with(&[("FORCE_COLOR", "1"), ("TERM", "xterm")], Some(8), false);
+        assert_eq!((coloured.tty, coloured.color), (false, ColorLevel::Basic));
+        let plain = with(&[("CLICOLOR", "0"), ("TERM", "xterm")], Some(8), true);
+        assert_eq!((plain.tty, plain.color), (true, ColorLevel::None));
+    }
+}
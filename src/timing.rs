@@ -0,0 +1,110 @@
+//! `--timing`: where one run's time went, printed to stderr at the end so
+//! "it's slow in my prompt" reports can say which part is slow.
+//!
+//! Like `har`, recording is process-wide: `start` switches it on, the code
+//! around each phase calls `record`, and `report` sums them up. The HTTP
+//! client doesn't report DNS, connect and the TLS handshake separately, so
+//! those and the server's time to answer are one phase, `request`; `--har`
+//! records the same wait per request.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Timing {
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+static TIMING: Mutex<Option<Timing>> = Mutex::new(None);
+
+/// Start timing the run
+pub fn start() {
+    *TIMING.lock().unwrap() = Some(Timing {
+        started: Instant::now(),
+        phases: Vec::new(),
+    });
+}
+
+pub fn record(phase: &'static str, took: Duration) {
+    if let Some(t) = TIMING.lock().unwrap().as_mut() {
+        t.phases.push((phase, took));
+    }
+}
+
+/// Run `f`, recording how long it took as `phase`
+pub fn measure<T>(phase: &'static str, f: impl FnOnce() -> T) -> T {
+    let clock = Instant::now();
+    let out = f();
+    record(phase, clock.elapsed());
+    out
+}
+
+/// The breakdown so far, if timing is on
+pub fn report() -> Option<String> {
+    let timing = TIMING.lock().unwrap();
+    let t = timing.as_ref()?;
+    Some(render(&t.phases, t.started.elapsed()))
+}
+
+/// One line per phase in first-seen order, repeats summed (`×3`), then
+/// whatever wasn't covered and the total
+fn render(phases: &[(&'static str, Duration)], total: Duration) -> String {
+    let mut summed: Vec<(&str, Duration, u32)> = Vec::new();
+    for &(phase, took) in phases {
+        match summed.iter_mut().find(|(p, ..)| *p == phase) {
+            Some((_, sum, n)) => {
+                *sum += took;
+                *n += 1;
+            }
+            None => summed.push((phase, took, 1)),
+        }
+    }
+    let ms = |d: Duration| format!("{:>9.1} ms", d.as_secs_f64() * 1000.0);
+    let covered: Duration = summed.iter().map(|(_, d, _)| *d).sum();
+
+    let mut out = String::from("\n  timing\n");
+    for (phase, took, n) in &summed {
+        let times = match n {
+            1 => String::new(),
+            n => format!("  ×{n}"),
+        };
+        out.push_str(&format!("    {phase:<16}{}{times}\n", ms(*took)));
+    }
+    out.push_str(&format!(
+        "    {:<16}{}\n",
+        "other",
+        ms(total.saturating_sub(covered))
+    ));
+    out.push_str(&format!("    {:<16}{}\n", "total", ms(total)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_repeated_phases() {
+        let ms = Duration::from_millis;
+        let out = render(
+            &[
+                ("credentials", ms(3)),
+                ("request", ms(200)),
+                ("request", ms(100)),
+                ("render", ms(1)),
+            ],
+            ms(310),
+        );
+        let lines: Vec<_> = out.lines().map(str::trim).collect();
+        assert_eq!(
+            lines[2..],
+            [
+                "credentials           3.0 ms",
+                "request             300.0 ms  ×2",
+                "render                1.0 ms",
+                "other                 6.0 ms",
+                "total               310.0 ms",
+            ]
+        );
+    }
+}
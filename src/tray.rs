@@ -0,0 +1,327 @@
+//! `tray`: usage in the system tray, or the menu bar on macOS. The icon is a
+//! pie of the fullest window in its severity colour, the tooltip lists every
+//! window with its reset, and the menu refreshes, opens the `tui` dashboard
+//! in a terminal, or quits. Fetches follow `--interval` and `--adaptive` as
+//! `--watch` does.
+
+use crate::api::Snapshot;
+use crate::pacing::Pacing;
+use crate::render::{label, window_rows, Level};
+use crate::status::age;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::f64::consts::TAU;
+
+/// Icon edge in pixels; every platform scales it to fit
+const ICON_SIZE: u32 = 32;
+
+/// The unfilled part of the pie, and the whole of it with no figures
+const EMPTY: [u8; 4] = [128, 128, 128, 110];
+
+#[cfg(feature = "tray")]
+pub use gui::run;
+
+#[cfg(not(feature = "tray"))]
+pub fn run(
+    _profile: Option<&str>,
+    _pacing: Pacing,
+    _now: impl Fn() -> DateTime<Utc> + Clone + Send + 'static,
+    _fetch: impl FnMut() -> Result<Snapshot> + Send,
+) -> Result<()> {
+    anyhow::bail!("This build of codex-usage was compiled without the `tray` feature")
+}
+
+/// e.g. "codex-usage · work (Plus)", then "5-hour session 42% · resets in
+/// 2h 13m" for each window, then the last failure if the latest fetch
+/// failed. The menu's first line is taken from it, so masking here under
+/// `--anonymize` covers both.
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+fn tooltip(
+    profile: &str,
+    snap: Option<&Snapshot>,
+    failure: Option<&str>,
+    now: DateTime<Utc>,
+) -> String {
+    let mut lines = Vec::new();
+    match snap.map(crate::anonymize::snapshot) {
+        Some(snap) => {
+            let plan = crate::plans::display(snap.usage.plan_type.as_deref().unwrap_or("unknown"));
+            lines.push(format!("codex-usage · {profile} ({plan})"));
+            for row in window_rows(&snap, now) {
+                let Some(pct) = row.window.as_ref().and_then(|w| w.used_percent) else {
+                    continue;
+                };
+                let reset = match row.resets_in {
+                    Some(secs) => format!(
+                        " · resets in {}",
+                        age(chrono::Duration::seconds(secs as i64))
+                    ),
+                    None => String::new(),
+                };
+                lines.push(format!("{} {pct:.0}%{reset}", label(&row)));
+            }
+        }
+        None => lines.push(format!("codex-usage · {profile}")),
+    }
+    if let Some(why) = failure {
+        lines.push(format!("Refresh failed: {why}"));
+    }
+    lines.join("\n")
+}
+
+/// The percentage the icon shows: the fullest window's
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+fn fullest(snap: &Snapshot, now: DateTime<Utc>) -> Option<f64> {
+    window_rows(snap, now)
+        .iter()
+        .filter_map(|r| r.window.as_ref()?.used_percent)
+        .reduce(f64::max)
+}
+
+/// RGBA pixels for the icon: a pie filled clockwise from twelve o'clock to
+/// `pct` in that level's colour, over a faint disc
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+fn icon_pixels(pct: Option<f64>) -> Vec<u8> {
+    let size = ICON_SIZE as f64;
+    let centre = size / 2.0;
+    let filled = pct.map(|p| p.clamp(0.0, 100.0));
+    let colour = filled.map(|p| {
+        let (r, g, b) = Level::of(p).rgb();
+        [r, g, b, 255]
+    });
+    let mut pixels = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let (dx, dy) = (x as f64 + 0.5 - centre, y as f64 + 0.5 - centre);
+            let pixel = if dx.hypot(dy) > centre - 1.0 {
+                [0; 4]
+            } else {
+                let angle = dx.atan2(-dy).rem_euclid(TAU);
+                match (filled, colour) {
+                    (Some(p), Some(colour)) if angle < p / 100.0 * TAU => colour,
+                    _ => EMPTY,
+                }
+            };
+            pixels.extend_from_slice(&pixel);
+        }
+    }
+    pixels
+}
+
+#[cfg(feature = "tray")]
+mod gui {
+    use super::*;
+    use anyhow::Context;
+    use std::process::Command;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use tao::event::{Event, StartCause};
+    use tao::event_loop::{ControlFlow, EventLoopBuilder};
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+    /// How often the tooltip's countdowns are redrawn between fetches
+    const TICK: Duration = Duration::from_secs(30);
+
+    enum UserEvent {
+        Fetched(std::result::Result<Box<Snapshot>, String>),
+        Menu(MenuEvent),
+    }
+
+    /// Show the icon and keep it current until Quit. Fetches run on their
+    /// own thread, so a slow request never stalls the menu.
+    pub fn run(
+        profile: Option<&str>,
+        pacing: Pacing,
+        now: impl Fn() -> DateTime<Utc> + Clone + Send + 'static,
+        mut fetch: impl FnMut() -> Result<Snapshot> + Send,
+    ) -> Result<()> {
+        let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build();
+        let proxy = event_loop.create_proxy();
+        let menu_proxy = Mutex::new(proxy.clone());
+        MenuEvent::set_event_handler(Some(move |e| {
+            let _ = menu_proxy.lock().unwrap().send_event(UserEvent::Menu(e));
+        }));
+
+        let status = MenuItem::new("Fetching…", false, None);
+        let refresh = MenuItem::new("Refresh now", true, None);
+        let dashboard = MenuItem::new("Open dashboard", true, None);
+        let quit = MenuItem::new("Quit", true, None);
+        let menu = Menu::new();
+        menu.append_items(&[
+            &status,
+            &PredefinedMenuItem::separator(),
+            &refresh,
+            &dashboard,
+            &PredefinedMenuItem::separator(),
+            &quit,
+        ])?;
+
+        let (refresh_tx, refresh_rx) = mpsc::channel::<()>();
+        let fetch_now = now.clone();
+        std::thread::scope(|scope| {
+            scope.spawn(move || loop {
+                let result = fetch().map_err(|e| format!("{e:#}"));
+                let wait = pacing.after(result.as_ref().ok(), fetch_now());
+                if proxy
+                    .send_event(UserEvent::Fetched(result.map(Box::new)))
+                    .is_err()
+                {
+                    return;
+                }
+                match refresh_rx.recv_timeout(wait) {
+                    Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            });
+
+            let name = profile
+                .unwrap_or(crate::config::DEFAULT_PROFILE)
+                .to_string();
+            let profile = profile.map(str::to_string);
+            let mut tray: Option<TrayIcon> = None;
+            let mut latest: Option<Snapshot> = None;
+            let mut failure: Option<String> = None;
+            event_loop.run(move |event, _, control_flow| {
+                *control_flow = ControlFlow::WaitUntil(Instant::now() + TICK);
+                match event {
+                    // macOS wants the icon made once the loop is running
+                    Event::NewEvents(StartCause::Init) => {
+                        let built = TrayIconBuilder::new()
+                            .with_menu(Box::new(menu.clone()))
+                            .with_tooltip(tooltip(&name, None, None, now()))
+                            .with_icon(icon(None))
+                            .build();
+                        match built {
+                            Ok(icon) => tray = Some(icon),
+                            Err(e) => {
+                                eprintln!("Could not show a tray icon: {e}");
+                                *control_flow = ControlFlow::ExitWithCode(1);
+                                return;
+                            }
+                        }
+                    }
+                    Event::UserEvent(UserEvent::Fetched(Ok(snap))) => {
+                        latest = Some(*snap);
+                        failure = None;
+                    }
+                    Event::UserEvent(UserEvent::Fetched(Err(why))) => {
+                        failure = why.lines().next().map(str::to_string);
+                    }
+                    Event::UserEvent(UserEvent::Menu(e)) if e.id == *refresh.id() => {
+                        status.set_text("Refreshing…");
+                        let _ = refresh_tx.send(());
+                        return;
+                    }
+                    Event::UserEvent(UserEvent::Menu(e)) if e.id == *dashboard.id() => {
+                        if let Err(e) = open_dashboard(profile.as_deref()) {
+                            eprintln!("{e:#}");
+                        }
+                        return;
+                    }
+                    Event::UserEvent(UserEvent::Menu(e)) if e.id == *quit.id() => {
+                        tray = None;
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {}
+                    _ => return,
+                }
+
+                let Some(tray) = &tray else { return };
+                let now = now();
+                let text = tooltip(&name, latest.as_ref(), failure.as_deref(), now);
+                status.set_text(text.lines().nth(1).unwrap_or("No figures yet"));
+                let _ = tray.set_tooltip(Some(text));
+                let _ = tray.set_icon(Some(icon(latest.as_ref().and_then(|s| fullest(s, now)))));
+            })
+        })
+    }
+
+    fn icon(pct: Option<f64>) -> Icon {
+        Icon::from_rgba(icon_pixels(pct), ICON_SIZE, ICON_SIZE)
+            .expect("icon pixels match the icon size")
+    }
+
+    /// `codex-usage tui` in a new terminal window
+    fn open_dashboard(profile: Option<&str>) -> Result<()> {
+        let exe = std::env::current_exe().context("Could not find the codex-usage executable")?;
+        let mut args = vec![exe.display().to_string(), "tui".to_string()];
+        if let Some(profile) = profile {
+            args.extend(["--profile".to_string(), profile.to_string()]);
+        }
+        let mut command = if cfg!(target_os = "macos") {
+            let line = args
+                .iter()
+                .map(|a| format!("'{}'", a.replace('\'', r"'\''")))
+                .collect::<Vec<_>>()
+                .join(" ");
+            // A JSON string is a valid AppleScript one for a command line
+            let script = format!(
+                "tell application \"Terminal\" to do script {}",
+                serde_json::to_string(&line)?
+            );
+            let mut c = Command::new("osascript");
+            c.args([
+                "-e",
+                &script,
+                "-e",
+                "tell application \"Terminal\" to activate",
+            ]);
+            c
+        } else if cfg!(windows) {
+            let mut c = Command::new("cmd");
+            c.args(["/C", "start", "codex-usage"]).args(&args);
+            c
+        } else {
+            let terminal = std::env::var("TERMINAL")
+                .ok()
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or_else(|| "x-terminal-emulator".to_string());
+            let mut c = Command::new(terminal);
+            c.arg("-e").args(&args);
+            c
+        };
+        let program = command.get_program().to_string_lossy().into_owned();
+        command
+            .spawn()
+            .with_context(|| format!("Could not open a terminal with {program}; set $TERMINAL"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tooltip_and_icon() {
+        let now = Utc::now();
+        let snap = Snapshot::plus((42.0, 2 * 3600 + 780), (75.0, 4 * 86400 + 7200), now);
+        assert_eq!(
+            tooltip("work", Some(&snap), Some("HTTP 502"), now),
+            "codex-usage · work (Plus)\n\
+             5-hour session 42% · resets in 2h 13m\n\
+             7-day rolling 75% · resets in 4d 2h\n\
+             Refresh failed: HTTP 502"
+        );
+        assert_eq!(fullest(&snap, now), Some(75.0));
+        crate::anonymize::enable_on_this_thread();
+        let masked = tooltip("work", Some(&snap), None, now);
+        assert!(masked.starts_with("codex-usage · work (•••)\n5-hour session 42%"));
+
+        let pixels = icon_pixels(Some(75.0));
+        assert_eq!(pixels.len(), (ICON_SIZE * ICON_SIZE * 4) as usize);
+        let at = |x: u32, y: u32| {
+            let i = ((y * ICON_SIZE + x) * 4) as usize;
+            [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+        };
+        let (r, g, b) = Level::Warning.rgb();
+        // Three quarters round from twelve o'clock is filled; the last isn't
+        assert_eq!(at(24, 16), [r, g, b, 255]);
+        assert_eq!(at(16, 24), [r, g, b, 255]);
+        assert_eq!(at(8, 10), EMPTY);
+        assert_eq!(at(0, 0), [0; 4]);
+    }
+}
@@ -0,0 +1,1061 @@
+//! `tui`: a full-screen dashboard. A gauge and countdown for every window,
+//! the plan, and a chart of the last few hours of samples, refetched every
+//! `--interval` seconds or as `--adaptive` paces it. `r` refreshes at once;
+//! `q`, Esc or Ctrl-C quits. With `--compare` a second profile stands in a
+//! column beside the first, and every refresh fetches both, so the two are
+//! always from the same moment.
+//!
+//! The chart starts from local history and grows with each fetch, so with
+//! history off it fills in as the dashboard runs. It shows the last few
+//! hours at first; the arrows pan back through the week, `+` and `-` zoom,
+//! and `v` starts a selection whose lowest, highest and change are shown
+//! under the chart. The mouse does the same: the wheel zooms (sideways, or
+//! with Shift, it pans), dragging selects, and hovering a gauge or the
+//! chart shows its exact figures on the bottom line.
+
+use crate::api::Snapshot;
+use crate::pacing::Pacing;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+/// How far back it can be panned, and so how much history it loads: the
+/// whole weekly window
+pub const REACH_HOURS: i64 = 7 * 24;
+
+#[cfg(feature = "tui")]
+pub use dashboard::run;
+
+#[cfg(not(feature = "tui"))]
+pub fn run(
+    _profiles: &[&str],
+    _pacing: Pacing,
+    _now: impl Fn() -> DateTime<Utc>,
+    _history: Vec<Snapshot>,
+    _fetch: impl FnMut(&str) -> Result<Snapshot>,
+) -> Result<()> {
+    anyhow::bail!("This build of codex-usage was compiled without the `tui` feature")
+}
+
+#[cfg(feature = "tui")]
+mod dashboard {
+    use super::*;
+    use crate::plans;
+    use crate::render::{label, window_rows, Level, WindowRow};
+    use crate::status::age;
+    use ratatui::crossterm::event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    };
+    use ratatui::crossterm::execute;
+    use ratatui::layout::{Constraint, Layout, Position, Rect};
+    use ratatui::style::{Color, Modifier, Style, Stylize};
+    use ratatui::symbols::Marker;
+    use ratatui::text::{Line, Span};
+    use ratatui::widgets::{Axis, Block, Chart, Dataset, Gauge, GraphType, Paragraph};
+    use ratatui::Frame;
+    use std::cell::RefCell;
+    use std::fmt::Write;
+    use std::time::{Duration, Instant};
+
+    /// How often the countdowns redraw, and how long a key waits at most
+    const TICK: Duration = Duration::from_millis(250);
+
+    /// Chart line colours, main window first; not severity colours, which
+    /// the gauges use
+    const SERIES: [Color; 2] = [Color::Cyan, Color::Magenta];
+
+    /// One profile's column
+    struct Pane<'a> {
+        profile: &'a str,
+        latest: Snapshot,
+        /// Samples within the chart's reach, oldest first
+        samples: Vec<Snapshot>,
+        failure: Option<String>,
+    }
+
+    struct Dashboard<'a> {
+        panes: Vec<Pane<'a>>,
+        /// Shared, so compared profiles line up
+        view: ChartView,
+        next_fetch: Instant,
+        refreshing: bool,
+        /// Where each pane was last drawn, for the mouse
+        drawn: RefCell<Vec<Drawn>>,
+        hover: Option<(usize, Target)>,
+    }
+
+    /// Where a pane's parts landed on screen
+    #[derive(Debug, Default)]
+    struct Drawn {
+        gauges: Vec<Rect>,
+        /// The chart's plotting area, inside its axes
+        plot: Rect,
+    }
+
+    /// What is under the mouse in a pane
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Target {
+        /// The gauge for the pane's row at this index
+        Gauge(usize),
+        /// The chart, at this time
+        Chart(DateTime<Utc>),
+    }
+
+    /// The stretch of time the charts show, and the selection on it
+    #[derive(Debug, Clone, PartialEq)]
+    struct ChartView {
+        /// Seconds across the chart
+        span: i64,
+        /// Seconds between its right edge and now
+        end: i64,
+        brush: Option<Brush>,
+    }
+
+    /// A selected stretch of time: where it started, and the cursor that
+    /// moves its other end while `selecting`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Brush {
+        anchor: DateTime<Utc>,
+        cursor: DateTime<Utc>,
+        selecting: bool,
+    }
+
+    /// How far back the chart reaches at first
+    const CHART_HOURS: i64 = 6;
+
+    /// How far a selection's cursor moves per key, as a share of the view
+    const CURSOR_STEPS: i64 = 40;
+
+    /// The closest zoom, in seconds across the chart
+    const MIN_SPAN: i64 = 15 * 60;
+
+    const REACH: i64 = REACH_HOURS * 3600;
+
+    impl Default for ChartView {
+        fn default() -> Self {
+            ChartView {
+                span: CHART_HOURS * 3600,
+                end: 0,
+                brush: None,
+            }
+        }
+    }
+
+    impl ChartView {
+        /// The times at the chart's left and right edges
+        fn range(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+            let right = now - chrono::Duration::seconds(self.end);
+            (right - chrono::Duration::seconds(self.span), right)
+        }
+
+        fn selecting(&self) -> bool {
+            self.brush.is_some_and(|b| b.selecting)
+        }
+
+        /// The selected stretch, earliest first
+        fn selected(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+            let b = self.brush?;
+            Some((b.anchor.min(b.cursor), b.anchor.max(b.cursor)))
+        }
+
+        /// Move by `quarters` of the chart's width; positive is later
+        fn pan(&mut self, quarters: i64) {
+            self.end = (self.end - quarters * self.span / 4).clamp(0, REACH - self.span);
+        }
+
+        /// Halve or double the time shown, about its middle
+        fn zoom(&mut self, closer: bool) {
+            let middle = self.end + self.span / 2;
+            self.span = match closer {
+                true => self.span / 2,
+                false => self.span * 2,
+            }
+            .clamp(MIN_SPAN, REACH);
+            self.end = (middle - self.span / 2).clamp(0, REACH - self.span);
+        }
+
+        /// Start selecting from the middle of the chart, or finish
+        fn select(&mut self, now: DateTime<Utc>) {
+            match &mut self.brush {
+                Some(b) if b.selecting => b.selecting = false,
+                _ => {
+                    let (left, right) = self.range(now);
+                    let middle = left + (right - left) / 2;
+                    self.brush = Some(Brush {
+                        anchor: middle,
+                        cursor: middle,
+                        selecting: true,
+                    });
+                }
+            }
+        }
+
+        /// Move the selection's cursor by `steps`, within the chart
+        fn extend(&mut self, steps: i64, now: DateTime<Utc>) {
+            let (left, right) = self.range(now);
+            let step = chrono::Duration::seconds(steps * self.span / CURSOR_STEPS);
+            if let Some(b) = self.brush.as_mut().filter(|b| b.selecting) {
+                b.cursor = (b.cursor + step).clamp(left, right);
+            }
+        }
+
+        /// The time under column `x` of `plot`, the chart's plotting area
+        fn time_at(&self, plot: Rect, x: u16, now: DateTime<Utc>) -> DateTime<Utc> {
+            let (left, _) = self.range(now);
+            let share = (f64::from(x.saturating_sub(plot.x)) + 0.5) / f64::from(plot.width.max(1));
+            left + chrono::Duration::seconds((share.min(1.0) * self.span as f64) as i64)
+        }
+
+        /// Act on a chart key; false for any other
+        fn key(&mut self, code: KeyCode, now: DateTime<Utc>) -> bool {
+            let selecting = self.selecting();
+            match code {
+                KeyCode::Left | KeyCode::Char('h') if selecting => self.extend(-1, now),
+                KeyCode::Right | KeyCode::Char('l') if selecting => self.extend(1, now),
+                KeyCode::Enter if selecting => self.select(now),
+                KeyCode::Left | KeyCode::Char('h') => self.pan(-1),
+                KeyCode::Right | KeyCode::Char('l') => self.pan(1),
+                KeyCode::Char('+' | '=') => self.zoom(true),
+                KeyCode::Char('-') => self.zoom(false),
+                KeyCode::Char('0') => {
+                    let brush = self.brush.take();
+                    *self = ChartView {
+                        brush,
+                        ..ChartView::default()
+                    };
+                }
+                KeyCode::Char('v') => self.select(now),
+                KeyCode::Esc if self.brush.is_some() => self.brush = None,
+                _ => return false,
+            }
+            true
+        }
+    }
+
+    /// One window across a selection: its lowest and highest reading, and
+    /// how far it moved from the first sample to the last
+    #[derive(Debug, PartialEq)]
+    struct RangeStats {
+        min: f64,
+        max: f64,
+        delta: f64,
+    }
+
+    fn range_stats(
+        samples: &[Snapshot],
+        window: &str,
+        (from, to): (DateTime<Utc>, DateTime<Utc>),
+    ) -> Option<RangeStats> {
+        let values: Vec<f64> = samples
+            .iter()
+            .filter(|s| (from..=to).contains(&s.fetched_at))
+            .filter_map(|s| used(s, window))
+            .collect();
+        let (first, last) = (values.first()?, values.last()?);
+        Some(RangeStats {
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            delta: last - first,
+        })
+    }
+
+    /// `window`'s percentage in `snap`, by the API's name for it
+    fn used(snap: &Snapshot, window: &str) -> Option<f64> {
+        let rl = snap.usage.rate_limit.as_ref()?;
+        let (_, w) = rl.windows().into_iter().find(|(n, _)| *n == window)?;
+        w.used_percent
+    }
+
+    /// `now`, or how long before it: `-6h`, `-1d 12h`, `-45m`
+    fn before_now(secs: i64) -> String {
+        match secs {
+            0 => "now".to_string(),
+            secs => {
+                let age = age(chrono::Duration::seconds(secs));
+                format!("-{}", age.trim_end_matches(" 0m").trim_end_matches(" 0h"))
+            }
+        }
+    }
+
+    /// Fetch and draw `profiles`, side by side, until the user quits;
+    /// `history` backfills the first one's chart. A failed first fetch is
+    /// returned before the screen is taken over; later ones keep the last
+    /// data up with the failure in the footer.
+    pub fn run(
+        profiles: &[&str],
+        pacing: Pacing,
+        now: impl Fn() -> DateTime<Utc>,
+        history: Vec<Snapshot>,
+        mut fetch: impl FnMut(&str) -> Result<Snapshot>,
+    ) -> Result<()> {
+        let mut history = Some(history);
+        let mut panes = Vec::new();
+        for &profile in profiles {
+            let latest = fetch(profile)?;
+            let mut pane = Pane {
+                profile,
+                samples: history
+                    .take()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(shown)
+                    .collect(),
+                latest: latest.clone(),
+                failure: None,
+            };
+            pane.add(latest, now());
+            panes.push(pane);
+        }
+        let mut dash = Dashboard {
+            panes,
+            view: ChartView::default(),
+            next_fetch: Instant::now(),
+            refreshing: false,
+            drawn: RefCell::default(),
+            hover: None,
+        };
+        dash.next_fetch = Instant::now() + dash.pace(&pacing, now());
+
+        let mut terminal = ratatui::init();
+        let result = (|| -> Result<()> {
+            execute!(std::io::stdout(), EnableMouseCapture)?;
+            loop {
+                terminal.draw(|f| dash.draw(f, now()))?;
+                if event::poll(TICK)? {
+                    match event::read()? {
+                        Event::Key(key) => {
+                            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                            match key.code {
+                                _ if key.kind != KeyEventKind::Press => {}
+                                code if dash.view.key(code, now()) => {}
+                                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                                KeyCode::Char('c') if ctrl => return Ok(()),
+                                KeyCode::Char('r') => dash.next_fetch = Instant::now(),
+                                _ => {}
+                            }
+                        }
+                        Event::Mouse(mouse) => dash.mouse(mouse, now()),
+                        _ => {}
+                    }
+                }
+                if Instant::now() >= dash.next_fetch {
+                    // Say so first; the request blocks the redraw
+                    dash.refreshing = true;
+                    terminal.draw(|f| dash.draw(f, now()))?;
+                    for pane in &mut dash.panes {
+                        match fetch(pane.profile) {
+                            Ok(snap) => {
+                                pane.add(snap, now());
+                                pane.failure = None;
+                            }
+                            Err(e) => {
+                                let why = format!("{e:#}");
+                                pane.failure = why.lines().next().map(str::to_string);
+                            }
+                        }
+                    }
+                    dash.refreshing = false;
+                    dash.next_fetch = Instant::now() + dash.pace(&pacing, now());
+                }
+            }
+        })();
+        let _ = execute!(std::io::stdout(), DisableMouseCapture);
+        ratatui::restore();
+        result
+    }
+
+    impl Dashboard<'_> {
+        /// Until the next refresh, which fetches every pane: as soon as the
+        /// busiest of them needs it
+        fn pace(&self, pacing: &Pacing, now: DateTime<Utc>) -> Duration {
+            self.panes
+                .iter()
+                .map(|p| pacing.after(p.failure.is_none().then_some(&p.latest), now))
+                .min()
+                .unwrap_or(pacing.base)
+        }
+
+        fn draw(&self, frame: &mut Frame, now: DateTime<Utc>) {
+            let [body, footer] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+            let columns = self.panes.len() as u32;
+            let areas = Layout::horizontal(vec![Constraint::Ratio(1, columns); self.panes.len()])
+                .split(body);
+            let drawn = self
+                .panes
+                .iter()
+                .zip(areas.iter())
+                .map(|(pane, area)| pane.draw(frame, *area, &self.view, now))
+                .collect();
+            self.drawn.replace(drawn);
+            frame.render_widget(self.footer(now), footer);
+        }
+
+        /// What is under `(x, y)`, and in which pane
+        fn target(&self, x: u16, y: u16, now: DateTime<Utc>) -> Option<(usize, Target)> {
+            let at = Position::new(x, y);
+            self.drawn
+                .borrow()
+                .iter()
+                .enumerate()
+                .find_map(|(i, drawn)| {
+                    if drawn.plot.contains(at) {
+                        return Some((i, Target::Chart(self.view.time_at(drawn.plot, x, now))));
+                    }
+                    let row = drawn.gauges.iter().position(|g| g.contains(at))?;
+                    Some((i, Target::Gauge(row)))
+                })
+        }
+
+        /// The wheel zooms the chart, or pans it sideways or with Shift; a
+        /// drag across it selects; moving shows what is underneath
+        fn mouse(&mut self, mouse: MouseEvent, now: DateTime<Utc>) {
+            let target = self.target(mouse.column, mouse.row, now);
+            let on_chart = matches!(target, Some((_, Target::Chart(_))));
+            let shift = mouse.modifiers.contains(KeyModifiers::SHIFT);
+            let view = &mut self.view;
+            match (mouse.kind, target) {
+                (MouseEventKind::Moved, target) => self.hover = target,
+                (MouseEventKind::ScrollUp, _) if on_chart && shift => view.pan(1),
+                (MouseEventKind::ScrollDown, _) if on_chart && shift => view.pan(-1),
+                (MouseEventKind::ScrollUp, _) if on_chart => view.zoom(true),
+                (MouseEventKind::ScrollDown, _) if on_chart => view.zoom(false),
+                (MouseEventKind::ScrollRight, _) if on_chart => view.pan(1),
+                (MouseEventKind::ScrollLeft, _) if on_chart => view.pan(-1),
+                (MouseEventKind::Down(MouseButton::Left), Some((_, Target::Chart(t)))) => {
+                    view.brush = Some(Brush {
+                        anchor: t,
+                        cursor: t,
+                        selecting: true,
+                    });
+                }
+                (MouseEventKind::Drag(MouseButton::Left), Some((_, Target::Chart(t)))) => {
+                    if let Some(b) = view.brush.as_mut().filter(|b| b.selecting) {
+                        b.cursor = t;
+                    }
+                    self.hover = target;
+                }
+                (MouseEventKind::Up(MouseButton::Left), _) => {
+                    // A click without a drag clears the selection
+                    view.brush = view.brush.filter(|b| b.anchor != b.cursor).map(|b| Brush {
+                        selecting: false,
+                        ..b
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        /// The exact figures under the mouse, for the bottom line
+        fn hovered(&self, now: DateTime<Utc>) -> Option<String> {
+            let (i, target) = self.hover?;
+            let pane = self.panes.get(i)?;
+            let text = match target {
+                Target::Gauge(row) => {
+                    let row = window_rows(&pane.latest, now).into_iter().nth(row)?;
+                    let mut text = label(&row);
+                    match row.window.as_ref().and_then(|w| w.used_percent) {
+                        Some(pct) => write!(text, ": {pct}% used").ok()?,
+                        None => text.push_str(": not available"),
+                    }
+                    if let Some(secs) = row.resets_in {
+                        let at = now + chrono::Duration::seconds(secs as i64);
+                        write!(text, ", resets {} UTC", at.format("%a %H:%M:%S")).ok()?;
+                    }
+                    text
+                }
+                Target::Chart(t) => {
+                    let nearest = pane
+                        .samples
+                        .iter()
+                        .min_by_key(|s| (s.fetched_at - t).num_seconds().abs())?;
+                    let mut text = format!(
+                        "{} {}",
+                        nearest.fetched_at.format("%a %H:%M UTC"),
+                        before_now((now - nearest.fetched_at).num_seconds())
+                    );
+                    for row in window_rows(nearest, nearest.fetched_at)
+                        .iter()
+                        .filter(|r| r.feature.is_none())
+                    {
+                        if let Some(pct) = used(nearest, &row.name) {
+                            write!(text, " · {} {pct}%", row.short_span()).ok()?;
+                        }
+                    }
+                    text
+                }
+            };
+            Some(match self.panes.len() {
+                1 => text,
+                _ => format!("{}: {text}", pane.profile),
+            })
+        }
+
+        fn footer(&self, now: DateTime<Utc>) -> Line<'_> {
+            let mut spans = Vec::new();
+            let oldest = self.panes.iter().min_by_key(|p| p.latest.fetched_at);
+            if let Some(updated) = oldest.and_then(|p| crate::render::updated(&p.latest, now)) {
+                spans.push(Span::raw(format!("updated {updated} · ")).dim());
+            }
+            let next = match self.refreshing {
+                true => "refreshing…".to_string(),
+                false => {
+                    let left = self.next_fetch.saturating_duration_since(Instant::now());
+                    format!("next refresh in {}s", left.as_secs())
+                }
+            };
+            match self.hovered(now) {
+                Some(hovered) => {
+                    spans.push(Span::raw(format!("{next} · ")).dim());
+                    spans.push(Span::raw(hovered));
+                }
+                None => {
+                    let keys = match self.view.selecting() {
+                        true => "←→ extend · Enter done · Esc clear",
+                        false => "r refresh · ←→ pan · +- zoom · v select · q quit",
+                    };
+                    spans.push(Span::raw(format!("{next} · {keys}")).dim());
+                }
+            }
+            for pane in &self.panes {
+                let Some(why) = &pane.failure else {
+                    continue;
+                };
+                let why = match self.panes.len() {
+                    1 => format!("  ✗ {why}"),
+                    _ => format!("  ✗ {}: {why}", pane.profile),
+                };
+                spans.push(Span::raw(why).fg(colour(Level::Critical)));
+            }
+            Line::from(spans)
+        }
+    }
+
+    impl Pane<'_> {
+        /// Show `snap` and chart it, dropping samples that fell off the chart
+        fn add(&mut self, snap: Snapshot, now: DateTime<Utc>) {
+            let snap = shown(&snap);
+            let since = now - chrono::Duration::hours(REACH_HOURS);
+            self.samples.retain(|s| s.fetched_at >= since);
+            self.samples.push(snap.clone());
+            self.latest = snap;
+        }
+
+        /// Draw into `area`, and say where the gauges and chart went
+        fn draw(
+            &self,
+            frame: &mut Frame,
+            area: Rect,
+            view: &ChartView,
+            now: DateTime<Utc>,
+        ) -> Drawn {
+            let rows: Vec<WindowRow> = window_rows(&self.latest, now);
+            let selected = view.selected();
+            let [header, gauges, chart, stats] = Layout::vertical([
+                Constraint::Length(3),
+                Constraint::Length(3 * rows.len() as u16),
+                Constraint::Min(8),
+                Constraint::Length(selected.map_or(0, |_| 1)),
+            ])
+            .areas(area);
+
+            frame.render_widget(self.header(), header);
+            let areas = Layout::vertical(vec![Constraint::Length(3); rows.len()]).split(gauges);
+            for (row, area) in rows.iter().zip(areas.iter()) {
+                frame.render_widget(gauge(row), *area);
+            }
+            let plot = self.draw_chart(frame, chart, &rows, view, now);
+            if let Some(range) = selected {
+                frame.render_widget(self.stats(&rows, range, now), stats);
+            }
+            Drawn {
+                gauges: areas.to_vec(),
+                plot,
+            }
+        }
+
+        /// `selected 2h 30m, to 1h ago · 5h 12–64% Δ +38 pts · …`
+        fn stats(
+            &self,
+            rows: &[WindowRow],
+            (from, to): (DateTime<Utc>, DateTime<Utc>),
+            now: DateTime<Utc>,
+        ) -> Line<'_> {
+            let mut text = format!("selected {}, to {} ago", age(to - from), age(now - to));
+            for row in rows.iter().filter(|r| r.feature.is_none()) {
+                let _ = match range_stats(&self.samples, &row.name, (from, to)) {
+                    Some(r) => write!(
+                        text,
+                        " · {} {:.0}–{:.0}% Δ {:+.0} pts",
+                        row.short_span(),
+                        r.min,
+                        r.max,
+                        r.delta
+                    ),
+                    None => write!(text, " · {} no samples", row.short_span()),
+                };
+            }
+            Line::from(Span::raw(text).yellow())
+        }
+
+        fn header(&self) -> Paragraph<'_> {
+            let usage = &self.latest.usage;
+            let plan = plans::display(usage.plan_type.as_deref().unwrap_or("unknown"));
+            let mut line = vec![
+                Span::raw("OpenAI "),
+                Span::raw(format!("{plan} Plan")).yellow().bold(),
+            ];
+            if let Some(w) = self.latest.workspace.as_ref().and_then(|w| w.describe()) {
+                line.push(Span::raw(format!(" · {w}")).dim());
+            }
+            let rl = usage.rate_limit.as_ref();
+            if rl.is_some_and(|r| r.limit_reached == Some(true)) {
+                line.push(
+                    Span::raw("  LIMIT REACHED")
+                        .fg(colour(Level::Critical))
+                        .bold(),
+                );
+            }
+            let title = format!(" codex-usage · {} ", self.profile);
+            Paragraph::new(Line::from(line)).block(Block::bordered().title(title.cyan().bold()))
+        }
+
+        /// Percent used over time for the main windows; feature limits
+        /// would crowd it. Returns where the lines are plotted.
+        fn draw_chart(
+            &self,
+            frame: &mut Frame,
+            area: Rect,
+            rows: &[WindowRow],
+            view: &ChartView,
+            now: DateTime<Utc>,
+        ) -> Rect {
+            let hours = |t: DateTime<Utc>| (t - now).num_seconds() as f64 / 3600.0;
+            let series: Vec<Vec<(f64, f64)>> = ["primary", "secondary"]
+                .iter()
+                .map(|name| {
+                    self.samples
+                        .iter()
+                        .filter_map(|s| {
+                            Some((hours(s.fetched_at), used(s, name)?.clamp(0.0, 100.0)))
+                        })
+                        .collect()
+                })
+                .collect();
+            // The selection's edges, as upright lines
+            let edges: Vec<[(f64, f64); 2]> = view
+                .brush
+                .iter()
+                .flat_map(|b| [b.anchor, b.cursor])
+                .map(|t| [(hours(t), 0.0), (hours(t), 100.0)])
+                .collect();
+            let mut datasets: Vec<Dataset> = series
+                .iter()
+                .zip(rows.iter().filter(|r| r.feature.is_none()))
+                .zip(SERIES)
+                .map(|((points, row), colour)| {
+                    Dataset::default()
+                        .name(row.short_span())
+                        .marker(Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().fg(colour))
+                        .data(points)
+                })
+                .collect();
+            for edge in &edges {
+                datasets.push(
+                    Dataset::default()
+                        .marker(Marker::Braille)
+                        .graph_type(GraphType::Line)
+                        .style(Style::default().fg(Color::Yellow))
+                        .data(edge),
+                );
+            }
+            let (left, right) = (view.end + view.span, view.end);
+            let title = match right {
+                0 => format!(" Last {} ", before_now(view.span).trim_start_matches('-')),
+                _ => format!(" {} to {} ", before_now(left), before_now(right)),
+            };
+            let labels = [
+                before_now(left),
+                before_now((left + right) / 2),
+                before_now(right),
+            ];
+            let plot = plot_area(Block::bordered().inner(area), &labels[0]);
+            let chart = Chart::new(datasets)
+                .block(Block::bordered().title(title))
+                .x_axis(
+                    Axis::default()
+                        .bounds([-left as f64 / 3600.0, -right as f64 / 3600.0])
+                        .labels(labels)
+                        .style(Style::default().dim()),
+                )
+                .y_axis(
+                    Axis::default()
+                        .bounds([0.0, 100.0])
+                        .labels(["0%", "50%", "100%"])
+                        .style(Style::default().dim()),
+                );
+            frame.render_widget(chart, area);
+            plot
+        }
+    }
+
+    /// Where `Chart` plots within `inner` when its first x label is
+    /// `first`: right of the y labels (or of the part of `first` that sticks
+    /// out left, if wider) and the y axis, above the x axis and its labels
+    fn plot_area(inner: Rect, first: &str) -> Rect {
+        let y_labels = "100%".len() as u16;
+        let left = y_labels
+            .max((first.chars().count() as u16).saturating_sub(1))
+            .min(inner.width / 3)
+            + 1;
+        Rect {
+            x: inner.x + left,
+            y: inner.y,
+            width: inner.width.saturating_sub(left),
+            height: inner.height.saturating_sub(2),
+        }
+    }
+
+    /// `snap` as `--anonymize` lets it be drawn
+    fn shown(snap: &Snapshot) -> Snapshot {
+        crate::anonymize::snapshot(snap).into_owned()
+    }
+
+    /// One window: its label as the title, filled to its percentage in the
+    /// fancy view's colour for it, with the reset countdown
+    fn gauge(row: &WindowRow) -> Gauge<'static> {
+        let block = Block::bordered().title(format!(" {} ", label(row)));
+        let Some(pct) = row.window.as_ref().and_then(|w| w.used_percent) else {
+            return Gauge::default()
+                .block(block)
+                .ratio(0.0)
+                .label("not available");
+        };
+        let pct = pct.clamp(0.0, 100.0);
+        let reset = match row.resets_in {
+            Some(secs) => format!(
+                " · resets in {}",
+                age(chrono::Duration::seconds(secs as i64))
+            ),
+            None => String::new(),
+        };
+        Gauge::default()
+            .block(block)
+            .gauge_style(
+                Style::default()
+                    .fg(colour(Level::of(pct)))
+                    .add_modifier(Modifier::BOLD),
+            )
+            .ratio(pct / 100.0)
+            .label(format!("{pct:.1}%{reset}"))
+            .use_unicode(true)
+    }
+
+    fn colour(level: Level) -> Color {
+        let (r, g, b) = level.rgb();
+        Color::Rgb(r, g, b)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ratatui::backend::TestBackend;
+        use ratatui::Terminal;
+
+        fn pane(profile: &str, latest: Snapshot) -> Pane<'_> {
+            Pane {
+                profile,
+                samples: vec![latest.clone()],
+                latest,
+                failure: None,
+            }
+        }
+
+        fn screen(dash: &Dashboard, now: DateTime<Utc>, width: u16) -> String {
+            let mut terminal = Terminal::new(TestBackend::new(width, 24)).unwrap();
+            terminal.draw(|f| dash.draw(f, now)).unwrap();
+            terminal
+                .backend()
+                .buffer()
+                .content()
+                .iter()
+                .map(|c| c.symbol())
+                .collect()
+        }
+
+        #[test]
+        fn draws_gauges_and_chart() {
+            let now = Utc::now();
+            let snap = |pct, at| Snapshot::plus((pct, 3600), (18.0, 3600), at);
+            let old = snap(10.0, now - chrono::Duration::hours(REACH_HOURS + 1));
+            let recent = snap(30.0, now - chrono::Duration::hours(2));
+            let mut work = pane("work", recent.clone());
+            work.samples = vec![old, recent];
+            work.failure = Some("HTTP 502".into());
+            work.add(snap(42.0, now), now);
+            // The sample from before the chart can reach is dropped
+            assert_eq!(work.samples.len(), 2);
+            let mut dash = Dashboard {
+                panes: vec![work],
+                view: ChartView::default(),
+                next_fetch: Instant::now() + Duration::from_secs(30),
+                refreshing: false,
+                drawn: RefCell::default(),
+                hover: None,
+            };
+
+            let out = screen(&dash, now, 90);
+            for text in [
+                "codex-usage · work",
+                "OpenAI Plus Plan",
+                "5-hour session",
+                "42.0% · resets in 1h 0m",
+                "Last 6h",
+                "✗ HTTP 502",
+            ] {
+                assert!(out.contains(text), "{text:?} missing");
+            }
+
+            // With --anonymize the plan is masked from the next sample on
+            crate::anonymize::enable_on_this_thread();
+            dash.panes[0].add(snap(43.0, now), now);
+            let out = screen(&dash, now, 90);
+            assert!(out.contains("OpenAI ••• Plan") && !out.contains("Plus"));
+        }
+
+        #[test]
+        fn compared_profiles_stand_side_by_side() {
+            let now = Utc::now();
+            let mut home = pane("home", Snapshot::plus((12.0, 3600), (30.0, 86400), now));
+            home.failure = Some("HTTP 502".into());
+            let work = pane("work", Snapshot::plus((64.0, 600), (81.0, 86400), now));
+            let dash = Dashboard {
+                panes: vec![home, work],
+                view: ChartView::default(),
+                next_fetch: Instant::now() + Duration::from_secs(30),
+                refreshing: false,
+                drawn: RefCell::default(),
+                hover: None,
+            };
+
+            // Each profile's column: left, then right, on the same rows
+            let out = screen(&dash, now, 120);
+            let lines: Vec<String> = out
+                .chars()
+                .collect::<Vec<_>>()
+                .chunks(120)
+                .map(|l| l.iter().collect())
+                .collect();
+            let row = lines
+                .iter()
+                .find(|l| l.contains("codex-usage · home"))
+                .unwrap();
+            assert!(row.find("home").unwrap() < 60 && row.find("work").unwrap() >= 60);
+            let row = lines.iter().find(|l| l.contains("12.0%")).unwrap();
+            assert!(row.find("12.0%").unwrap() < 60 && row.find("64.0%").unwrap() >= 60);
+            // One countdown for both; a failure names its profile
+            assert_eq!(out.matches("next refresh in").count(), 1);
+            assert!(out.contains("✗ home: HTTP 502"));
+
+            // The next refresh comes when the busier profile needs it
+            let pacing = Pacing {
+                base: Duration::from_secs(300),
+                adaptive: true,
+            };
+            let alone = Dashboard {
+                panes: vec![pane("home", dash.panes[0].latest.clone())],
+                ..dash
+            };
+            let both = Dashboard {
+                panes: vec![
+                    pane("home", alone.panes[0].latest.clone()),
+                    pane("work", Snapshot::plus((64.0, 600), (81.0, 86400), now)),
+                ],
+                view: ChartView::default(),
+                next_fetch: Instant::now(),
+                refreshing: false,
+                drawn: RefCell::default(),
+                hover: None,
+            };
+            assert!(both.pace(&pacing, now) < alone.pace(&pacing, now));
+        }
+
+        #[test]
+        fn chart_pans_zooms_and_stays_in_reach() {
+            let now = Utc::now();
+            let mut view = ChartView::default();
+            let hours = |secs: i64| secs as f64 / 3600.0;
+            // Nothing later than now
+            assert!(view.key(KeyCode::Right, now));
+            assert_eq!(view.end, 0);
+            view.key(KeyCode::Left, now);
+            assert_eq!((hours(view.span), hours(view.end)), (6.0, 1.5));
+            // Zooming keeps the middle where it was
+            view.key(KeyCode::Char('+'), now);
+            assert_eq!((hours(view.span), hours(view.end)), (3.0, 3.0));
+            for _ in 0..10 {
+                view.key(KeyCode::Char('+'), now);
+            }
+            assert_eq!(view.span, MIN_SPAN);
+            for _ in 0..10 {
+                view.key(KeyCode::Char('-'), now);
+            }
+            assert_eq!((view.span, view.end), (REACH, 0));
+            view.key(KeyCode::Char('0'), now);
+            assert_eq!(view, ChartView::default());
+            for _ in 0..200 {
+                view.key(KeyCode::Char('h'), now);
+            }
+            assert_eq!(view.end + view.span, REACH);
+            assert!(!view.key(KeyCode::Char('q'), now) && !view.key(KeyCode::Esc, now));
+
+            assert_eq!(before_now(0), "now");
+            assert_eq!(before_now(6 * 3600), "-6h");
+            assert_eq!(before_now(36 * 3600), "-1d 12h");
+            assert_eq!(before_now(45 * 60), "-45m");
+        }
+
+        #[test]
+        fn a_selection_shows_its_range_stats() {
+            let now = crate::clock::parse_timestamp("2024-05-01T12:00:00Z").unwrap();
+            let at = |mins_ago| now - chrono::Duration::minutes(mins_ago);
+            let mut work = pane("work", Snapshot::plus((50.0, 3600), (30.0, 86400), now));
+            work.samples = [
+                (240, 10.0),
+                (180, 35.0),
+                (150, 64.0),
+                (120, 22.0),
+                (30, 40.0),
+            ]
+            .into_iter()
+            .map(|(ago, pct)| Snapshot::plus((pct, 3600), (20.0 + pct / 10.0, 86400), at(ago)))
+            .collect();
+
+            // From the middle, three hours ago, back an hour and a half
+            let mut view = ChartView::default();
+            assert!(view.key(KeyCode::Char('v'), now));
+            assert!(view.selecting());
+            for _ in 0..10 {
+                view.key(KeyCode::Left, now);
+            }
+            // Keys move the cursor rather than the view while selecting
+            assert_eq!(view.end, 0);
+            assert!(view.key(KeyCode::Enter, now));
+            assert!(!view.selecting());
+            assert_eq!(view.selected(), Some((at(270), at(180))));
+            view.key(KeyCode::Left, now);
+            assert_eq!(view.end, 90 * 60);
+
+            assert_eq!(
+                range_stats(&work.samples, "primary", (at(240), at(120))),
+                Some(RangeStats {
+                    min: 10.0,
+                    max: 64.0,
+                    delta: 12.0
+                })
+            );
+            assert_eq!(range_stats(&work.samples, "primary", (at(20), now)), None);
+
+            let mut dash = Dashboard {
+                panes: vec![work],
+                view,
+                next_fetch: Instant::now() + Duration::from_secs(30),
+                refreshing: false,
+                drawn: RefCell::default(),
+                hover: None,
+            };
+            let out = screen(&dash, now, 120);
+            assert!(out.contains("-7h 30m to -1h 30m"), "{out}");
+            assert!(
+                out.contains(
+                    "selected 1h 30m, to 3h 0m ago · 5h 10–35% Δ +25 pts · 7d 21–24% Δ +2 pts"
+                ),
+                "{out}"
+            );
+            // Esc drops the selection before it would quit
+            assert!(dash.view.key(KeyCode::Esc, now));
+            assert_eq!(dash.view.selected(), None);
+            dash.view.key(KeyCode::Char('0'), now);
+            assert!(!screen(&dash, now, 120).contains("selected"));
+        }
+
+        #[test]
+        fn the_mouse_hovers_zooms_and_selects() {
+            let now = crate::clock::parse_timestamp("2024-05-01T12:00:00Z").unwrap();
+            let mut work = pane("work", Snapshot::plus((42.37, 3600), (18.0, 86400), now));
+            let earlier = now - chrono::Duration::hours(2);
+            work.samples
+                .insert(0, Snapshot::plus((30.0, 3600), (17.0, 86400), earlier));
+            let mut dash = Dashboard {
+                panes: vec![work],
+                view: ChartView::default(),
+                next_fetch: Instant::now() + Duration::from_secs(30),
+                refreshing: false,
+                drawn: RefCell::default(),
+                hover: None,
+            };
+            let mut terminal = Terminal::new(TestBackend::new(120, 24)).unwrap();
+            terminal.draw(|f| dash.draw(f, now)).unwrap();
+            let (gauge, plot) = {
+                let drawn = dash.drawn.borrow();
+                (drawn[0].gauges[0], drawn[0].plot)
+            };
+            // The plot sits just right of the y axis and above the x axis
+            let buffer = terminal.backend().buffer();
+            assert_eq!(buffer[(plot.x - 1, plot.y)].symbol(), "│");
+            assert_eq!(buffer[(plot.x, plot.bottom())].symbol(), "─");
+
+            let mouse = |kind, column, row| MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            };
+            dash.mouse(mouse(MouseEventKind::Moved, gauge.x + 2, gauge.y), now);
+            let out = screen(&dash, now, 120);
+            assert!(
+                out.contains("5-hour session: 42.37% used, resets Wed 13:00:00 UTC"),
+                "{out}"
+            );
+            // Two hours back on a six-hour chart is two thirds across
+            let two_hours_ago = plot.x + plot.width * 2 / 3;
+            dash.mouse(mouse(MouseEventKind::Moved, two_hours_ago, plot.y + 1), now);
+            let out = screen(&dash, now, 120);
+            assert!(out.contains("Wed 10:00 UTC -2h · 5h 30% · 7d 17%"), "{out}");
+
+            dash.mouse(mouse(MouseEventKind::ScrollUp, plot.x, plot.y), now);
+            assert!(dash.view.span < CHART_HOURS * 3600);
+            dash.mouse(mouse(MouseEventKind::ScrollDown, plot.x, plot.y), now);
+            assert_eq!(dash.view.span, CHART_HOURS * 3600);
+            // The wheel does nothing off the chart
+            dash.mouse(mouse(MouseEventKind::ScrollUp, gauge.x, gauge.y), now);
+            assert_eq!(dash.view.span, CHART_HOURS * 3600);
+
+            let left = MouseButton::Left;
+            dash.mouse(mouse(MouseEventKind::Down(left), plot.x, plot.y), now);
+            assert!(dash.view.selecting());
+            let half = plot.x + plot.width / 2;
+            dash.mouse(mouse(MouseEventKind::Drag(left), half, plot.y), now);
+            dash.mouse(mouse(MouseEventKind::Up(left), half, plot.y), now);
+            let (from, to) = dash.view.selected().unwrap();
+            assert!(
+                (from - (now - chrono::Duration::hours(6)))
+                    .num_minutes()
+                    .abs()
+                    < 5
+            );
+            assert!(
+                (to - (now - chrono::Duration::hours(3)))
+                    .num_minutes()
+                    .abs()
+                    < 5
+            );
+            // A click without a drag clears it
+            dash.mouse(mouse(MouseEventKind::Down(left), half, plot.y), now);
+            dash.mouse(mouse(MouseEventKind::Up(left), half, plot.y), now);
+            assert_eq!(dash.view.selected(), None);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! `--watch`: the report redrawn in place until `q` or Ctrl-C. It fetches
+//! every `--interval` seconds (or as `--adaptive` paces it) and redraws once
+//! a second in between, so countdowns and the age line keep moving. On a
+//! terminal it takes keys too: `r` refreshes now, `p` moves on to the next
+//! profile, and `a` switches resets between countdowns and times of day.
+//! Piped, it prints each fetch's report in turn instead, which makes a
+//! poller's log.
+
+use crate::anonymize;
+use crate::api::Snapshot;
+use crate::pacing::Pacing;
+use crate::render::{Format, Options, BUFFER_CAPACITY};
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use colored::Colorize;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{IsTerminal, Write as _};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Seconds between fetches when `--interval` isn't given
+pub const DEFAULT_INTERVAL: u64 = 60;
+
+/// Shortest `--interval`; the usage API isn't meant to be polled harder
+pub const MIN_INTERVAL: u64 = 10;
+
+/// How often the screen is redrawn between fetches
+const TICK: Duration = Duration::from_secs(1);
+
+/// How often a wait checks for Ctrl-C or a key
+const POLL: Duration = Duration::from_millis(100);
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// What a key asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Refresh,
+    NextProfile,
+    ResetClock,
+    Quit,
+}
+
+impl Key {
+    fn of(event: &Event) -> Option<Key> {
+        let Event::Key(key) = event else {
+            return None;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            _ if key.kind != KeyEventKind::Press => None,
+            // Raw mode turns Ctrl-C into a key rather than a signal
+            KeyCode::Char('c') if ctrl => Some(Key::Quit),
+            KeyCode::Char('r') => Some(Key::Refresh),
+            KeyCode::Char('p') => Some(Key::NextProfile),
+            KeyCode::Char('a') => Some(Key::ResetClock),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Key::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// The terminal in raw mode, so keys arrive as they are pressed, until
+/// dropped, however the loop ends
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Option<RawMode> {
+        crossterm::terminal::enable_raw_mode()
+            .ok()
+            .map(|()| RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+/// What is on screen: whose report, in what form, and the last good
+/// snapshot of each profile shown so far
+struct Watch<'a> {
+    format: Format,
+    opts: Options,
+    profiles: &'a [String],
+    current: usize,
+    latest: BTreeMap<String, Snapshot>,
+    failure: Option<String>,
+    next_fetch: Instant,
+}
+
+impl Watch<'_> {
+    fn profile(&self) -> &str {
+        &self.profiles[self.current]
+    }
+
+    /// Act on `key`; false once it asks to quit. `here` is the offset reset
+    /// times are shown in when `a` turns them on.
+    fn press(&mut self, key: Key, here: FixedOffset) -> bool {
+        match key {
+            Key::Refresh => self.next_fetch = Instant::now(),
+            Key::NextProfile if self.profiles.len() > 1 => {
+                self.current = (self.current + 1) % self.profiles.len();
+                self.opts.profile = Some(self.profile().to_string());
+                self.failure = None;
+                // Up-to-date figures for the profile now on screen
+                self.next_fetch = Instant::now();
+            }
+            Key::NextProfile => {}
+            Key::ResetClock => {
+                self.opts.reset_clock = match self.opts.reset_clock {
+                    Some(_) => None,
+                    None => Some(here),
+                }
+            }
+            Key::Quit => return false,
+        }
+        true
+    }
+
+    /// One screen: the report, then when the next fetch is due and the
+    /// keys. On a terminal it overwrites the last screen line by line
+    /// rather than clearing first, so the redraw doesn't flicker; lines end
+    /// in `\r\n`, as raw mode no longer returns the cursor at a newline.
+    fn frame(&self, out: &mut String, now: DateTime<Utc>, tty: bool, keys: bool) {
+        let mut report = String::with_capacity(BUFFER_CAPACITY);
+        match self.latest.get(self.profile()) {
+            Some(snap) => self.format.renderer(self.opts.clone()).render_into(
+                &mut report,
+                &anonymize::snapshot(snap),
+                now,
+            ),
+            None => report = format!("\n  Fetching {}…\n", self.profile()),
+        }
+        if !tty {
+            out.push_str(&report);
+            return;
+        }
+        out.push_str("\x1b[H");
+        for line in report.lines() {
+            let _ = write!(out, "{line}\x1b[K\r\n");
+        }
+        if let Some(why) = &self.failure {
+            let _ = write!(
+                out,
+                "  {} {}\x1b[K\r\n",
+                "✗ Refresh failed:".red(),
+                why.dimmed()
+            );
+        }
+        let left = self.next_fetch.saturating_duration_since(Instant::now());
+        let mut footer = String::new();
+        if self.profiles.len() > 1 {
+            let _ = write!(footer, "{} · ", self.profile());
+        }
+        let _ = write!(footer, "Next refresh in {}s · ", left.as_secs());
+        footer.push_str(match keys {
+            true => "r refresh · p profile · a reset times · q quit",
+            false => "Ctrl-C to quit",
+        });
+        let _ = write!(out, "  {}\x1b[K\r\n\x1b[J", footer.dimmed());
+    }
+}
+
+/// Fetch and draw until `q` or Ctrl-C, starting from `opts.profile`; `p`
+/// steps through `profiles`. A failed first fetch is returned as the error;
+/// later ones leave the last report up with the failure under it.
+pub fn run(
+    format: Format,
+    opts: Options,
+    profiles: &[String],
+    pacing: Pacing,
+    tty: bool,
+    now: impl Fn() -> DateTime<Utc>,
+    mut fetch: impl FnMut(&str) -> Result<Snapshot>,
+) -> Result<()> {
+    on_ctrl_c();
+    let current = opts
+        .profile
+        .as_deref()
+        .and_then(|p| profiles.iter().position(|name| name == p))
+        .unwrap_or_default();
+    let mut watch = Watch {
+        format,
+        opts,
+        profiles,
+        current,
+        latest: BTreeMap::new(),
+        failure: None,
+        next_fetch: Instant::now(),
+    };
+    let first = fetch(watch.profile())?;
+    watch.next_fetch = Instant::now() + pacing.after(Some(&first), now());
+    watch.latest.insert(watch.profile().to_string(), first);
+
+    let keys = match tty && std::io::stdin().is_terminal() {
+        true => RawMode::enable(),
+        false => None,
+    };
+    let mut out = String::with_capacity(BUFFER_CAPACITY);
+    if tty {
+        print!("\x1b[2J");
+    }
+    let mut fetched = true;
+    while !STOP.load(Ordering::Relaxed) {
+        if tty || fetched {
+            out.clear();
+            watch.frame(&mut out, now(), tty, keys.is_some());
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(out.as_bytes())?;
+            stdout.flush()?;
+        }
+        if !tty {
+            if let Some(why) = watch.failure.take() {
+                eprintln!("Refresh failed: {why}");
+            }
+        }
+
+        let until = match tty {
+            true => watch.next_fetch.min(Instant::now() + TICK),
+            false => watch.next_fetch,
+        };
+        if let Some(key) = wait_until(until, keys.is_some())? {
+            let here = *chrono::Local::now().offset();
+            if !watch.press(key, here) {
+                break;
+            }
+        }
+        fetched = Instant::now() >= watch.next_fetch && !STOP.load(Ordering::Relaxed);
+        if fetched {
+            let profile = watch.profile().to_string();
+            match fetch(&profile) {
+                Ok(snap) => {
+                    watch.latest.insert(profile.clone(), snap);
+                    watch.failure = None;
+                }
+                Err(e) => {
+                    watch.failure = Some(format!("{e:#}").lines().next().unwrap_or_default().into())
+                }
+            }
+            let current = watch
+                .failure
+                .is_none()
+                .then(|| watch.latest.get(&profile))
+                .flatten();
+            watch.next_fetch = Instant::now() + pacing.after(current, now());
+        }
+    }
+    Ok(())
+}
+
+/// Wait until `deadline`, Ctrl-C, or with `keys` one of the keys
+fn wait_until(deadline: Instant, keys: bool) -> Result<Option<Key>> {
+    while !STOP.load(Ordering::Relaxed) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(None);
+        }
+        if !keys {
+            std::thread::sleep(left.min(POLL));
+        } else if event::poll(left.min(POLL))? {
+            if let Some(key) = Key::of(&event::read()?) {
+                return Ok(Some(key));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Ctrl-C ends the loop at its next check instead of killing the process
+/// partway through writing history or a frame. A request already under way
+/// finishes first; a second Ctrl-C leaves at once.
+#[cfg(unix)]
+fn on_ctrl_c() {
+    extern "C" fn handle(_: libc::c_int) {
+        if STOP.swap(true, Ordering::Relaxed) {
+            // SAFETY: _exit is async-signal-safe
+            unsafe { libc::_exit(130) }
+        }
+    }
+    // SAFETY: the handler only touches an atomic and calls _exit, both
+    // async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, handle as *const () as libc::sighandler_t);
+    }
+}
+
+/// Elsewhere Ctrl-C ends the process as usual when piped; on a terminal raw
+/// mode already makes it a key
+#[cfg(not(unix))]
+fn on_ctrl_c() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyEvent;
+
+    fn watch(profiles: &[String], format: Format, now: DateTime<Utc>) -> Watch<'_> {
+        Watch {
+            format,
+            opts: Options {
+                profile: Some(profiles[0].clone()),
+                ..Options::default()
+            },
+            profiles,
+            current: 0,
+            latest: BTreeMap::from([(
+                profiles[0].clone(),
+                Snapshot::plus((12.0, 3600), (4.5, 86400), now),
+            )]),
+            failure: None,
+            // Half a second over, so it still reads 42s when drawn
+            next_fetch: Instant::now() + Duration::from_millis(42_500),
+        }
+    }
+
+    #[test]
+    fn frames_overwrite_in_place() {
+        colored::control::set_override(false);
+        let profiles = ["work".to_string()];
+        let mut watch = watch(&profiles, Format::Plain, Utc::now());
+        watch.failure = Some("HTTP 502".into());
+
+        let mut out = String::new();
+        watch.frame(&mut out, Utc::now(), true, false);
+        assert!(out.starts_with("\x1b[H"));
+        assert!(out.ends_with("\x1b[J"));
+        assert!(out
+            .split("\r\n")
+            .all(|l| l.ends_with("\x1b[K") || l == "\x1b[J"));
+        assert!(!out.replace("\r\n", "").contains('\n'));
+        assert!(out.contains("✗ Refresh failed: HTTP 502"));
+        assert!(out.contains("  Next refresh in 42s · Ctrl-C to quit"));
+
+        // Piped, each fetch is just the report
+        let mut piped = String::new();
+        watch.frame(&mut piped, Utc::now(), false, false);
+        assert!(!piped.contains('\x1b') && !piped.contains("Next refresh"));
+    }
+
+    #[test]
+    fn keys_refresh_switch_profile_and_show_reset_times() {
+        colored::control::set_override(false);
+        let press = |code| Key::of(&Event::Key(KeyEvent::from(code)));
+        assert_eq!(press(KeyCode::Char('r')), Some(Key::Refresh));
+        assert_eq!(press(KeyCode::Char('p')), Some(Key::NextProfile));
+        assert_eq!(press(KeyCode::Char('a')), Some(Key::ResetClock));
+        assert_eq!(press(KeyCode::Char('q')), Some(Key::Quit));
+        assert_eq!(press(KeyCode::Char('x')), None);
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(Key::of(&Event::Key(ctrl_c)), Some(Key::Quit));
+
+        let profiles = ["home".to_string(), "work".to_string()];
+        let now = crate::clock::parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let mut watch = watch(&profiles, Format::Fancy, now);
+        let here = FixedOffset::east_opt(2 * 3600).unwrap();
+        let screen = |watch: &Watch| {
+            let mut out = String::new();
+            watch.frame(&mut out, now, true, true);
+            out
+        };
+        let out = screen(&watch);
+        assert!(out.contains("resets in 1h 0m"), "{out}");
+        assert!(out.contains("home · Next refresh in 42s · r refresh · p profile"));
+
+        // Resets as times of day where the terminal is, the weekday for
+        // one after today there, and back
+        assert!(watch.press(Key::ResetClock, here));
+        let out = screen(&watch);
+        assert!(out.contains("resets at 13:00"), "{out}");
+        assert!(out.contains("resets at Thu 12:00"), "{out}");
+        assert!(watch.press(Key::ResetClock, here));
+        assert!(screen(&watch).contains("resets in 1h 0m"));
+
+        // The next profile, fetched at once, and round again
+        assert!(watch.press(Key::NextProfile, here));
+        assert_eq!(watch.opts.profile.as_deref(), Some("work"));
+        assert!(watch.next_fetch <= Instant::now());
+        let out = screen(&watch);
+        assert!(out.contains("Fetching work…") && out.contains("work · Next refresh"));
+        assert!(watch.press(Key::NextProfile, here));
+        assert_eq!(watch.profile(), "home");
+
+        assert!(!watch.press(Key::Quit, here));
+    }
+}
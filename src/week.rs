@@ -0,0 +1,228 @@
+//! Work-week alignment. The API's weekly window is a rolling 7 days; people
+//! work Mon–Fri in a particular time zone. With `[week]` configured, reports
+//! show both: the rolling window as the API sees it, and how much of the
+//! calendar work week is gone and what the remaining budget allows per work day.
+//!
+//! ```toml
+//! [week]
+//! timezone = "Australia/Brisbane"
+//! days = "mon-fri"
+//! ```
+
+use crate::api::RateWindow;
+use crate::config::WeekConfig;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+const SECS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, Clone)]
+pub struct WorkWeek {
+    pub tz: Tz,
+    /// Work days, Monday first
+    days: Vec<Weekday>,
+}
+
+/// The work-week view of one snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeekView {
+    /// 0–100 of this calendar work week's working time already gone
+    pub elapsed: f64,
+    /// Working days (fractional) before the weekly window resets
+    pub days_left: Option<f64>,
+    /// Weekly budget left, spread evenly over those days
+    pub per_day: Option<f64>,
+}
+
+impl WorkWeek {
+    /// The configured work week; `None` when `[week]` is absent
+    pub fn from_config(cfg: &WeekConfig) -> Result<Option<Self>> {
+        if cfg.timezone.is_none() && cfg.days.is_none() {
+            return Ok(None);
+        }
+        let tz = match &cfg.timezone {
+            Some(name) => name
+                .parse::<Tz>()
+                .map_err(|_| anyhow::anyhow!("Unknown time zone '{name}' in [week]"))?,
+            None => Tz::UTC,
+        };
+        let days = parse_days(cfg.days.as_deref().unwrap_or("mon-fri"), "[week] days")?;
+        Ok(Some(WorkWeek { tz, days }))
+    }
+
+    /// e.g. "Mon–Fri, Australia/Brisbane"
+    pub fn label(&self) -> String {
+        // A run of days, possibly wrapping past Sunday, reads as a range
+        let first = self
+            .days
+            .iter()
+            .copied()
+            .find(|d| !self.days.contains(&d.pred()));
+        let days = match first {
+            Some(first) if self.days.len() > 2 => {
+                let mut last = first;
+                while self.days.contains(&last.succ()) && last.succ() != first {
+                    last = last.succ();
+                }
+                let run = last.num_days_from_monday() + 7 - first.num_days_from_monday();
+                if (run % 7) as usize + 1 == self.days.len() {
+                    format!("{first}–{last}")
+                } else {
+                    self.list()
+                }
+            }
+            _ => self.list(),
+        };
+        format!("{days}, {}", self.tz)
+    }
+
+    fn list(&self) -> String {
+        let names: Vec<String> = self.days.iter().map(|d| d.to_string()).collect();
+        names.join(", ")
+    }
+
+    pub fn view(
+        &self,
+        weekly: Option<&RateWindow>,
+        fetched_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> WeekView {
+        let start = self.week_start(now);
+        let total = self.days.len() as f64;
+        let elapsed = if total > 0.0 {
+            (self.work_days(start, now) / total * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        };
+        let reset = weekly.and_then(|w| Some((w, w.resets_in(fetched_at, now)?)));
+        let days_left = reset
+            .map(|(_, secs)| self.work_days(now, now + chrono::Duration::seconds(secs as i64)));
+        let per_day = reset.zip(days_left).and_then(|((w, _), days)| {
+            let left = 100.0 - w.used_percent.unwrap_or(0.0).clamp(0.0, 100.0);
+            (days > 0.0).then(|| left / days.max(1.0))
+        });
+        WeekView {
+            elapsed,
+            days_left,
+            per_day,
+        }
+    }
+
+    /// Working time between two instants, in days
+    pub fn work_days(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        if to <= from {
+            return 0.0;
+        }
+        let mut date = from.with_timezone(&self.tz).date_naive();
+        let last = to.with_timezone(&self.tz).date_naive();
+        let mut secs = 0.0;
+        while date <= last {
+            if self.days.contains(&date.weekday()) {
+                let day_start = self.midnight(date).max(from);
+                let day_end = self.midnight(date + Days::new(1)).min(to);
+                if day_end > day_start {
+                    secs += (day_end - day_start).num_seconds() as f64;
+                }
+            }
+            date = date + Days::new(1);
+        }
+        secs / SECS_PER_DAY
+    }
+
+    /// Local Monday 00:00 of the week containing `now`
+    fn week_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.with_timezone(&self.tz).date_naive();
+        let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+        self.midnight(monday)
+    }
+
+    /// Start of a local calendar day. Where DST skips midnight, the day
+    /// starts at the first instant that exists.
+    fn midnight(&self, date: NaiveDate) -> DateTime<Utc> {
+        let mut t = date.and_hms_opt(0, 0, 0).expect("midnight is valid");
+        loop {
+            if let Some(dt) = self.tz.from_local_datetime(&t).earliest() {
+                return dt.with_timezone(&Utc);
+            }
+            t += chrono::Duration::minutes(30);
+        }
+    }
+}
+
+/// "mon-fri", "mon,wed,fri" or "sun-thu" into Monday-first weekdays; `what`
+/// names the setting in errors
+pub(crate) fn parse_days(s: &str, what: &str) -> Result<Vec<Weekday>> {
+    let day = |d: &str| {
+        d.trim()
+            .parse::<Weekday>()
+            .map_err(|_| anyhow::anyhow!("Unknown day '{}' in {what}", d.trim()))
+    };
+    let mut days = Vec::new();
+    for part in s.split(',').filter(|p| !p.trim().is_empty()) {
+        match part.split_once('-') {
+            Some((a, b)) => {
+                let (mut d, end) = (day(a)?, day(b)?);
+                days.push(d);
+                while d != end {
+                    d = d.succ();
+                    days.push(d);
+                }
+            }
+            None => days.push(day(part)?),
+        }
+    }
+    if days.is_empty() {
+        bail!("{what} is empty");
+    }
+    days.sort_by_key(|d| d.num_days_from_monday());
+    days.dedup();
+    Ok(days)
+}
+
+impl std::str::FromStr for WorkWeek {
+    type Err = anyhow::Error;
+
+    /// "mon-fri@Australia/Brisbane", as accepted on the command line
+    fn from_str(s: &str) -> Result<Self> {
+        let (days, tz) = s.split_once('@').unwrap_or((s, "UTC"));
+        WorkWeek::from_config(&WeekConfig {
+            timezone: Some(tz.to_string()),
+            days: Some(days.to_string()),
+        })?
+        .context("empty work week")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Snapshot;
+    use crate::clock::parse_timestamp;
+
+    #[test]
+    fn brisbane_work_week() {
+        let week: WorkWeek = "mon-fri@Australia/Brisbane".parse().unwrap();
+        assert_eq!(week.label(), "Mon–Fri, Australia/Brisbane");
+
+        // Wednesday 12:00 in Brisbane (UTC+10): 2.5 of 5 work days gone
+        let now = parse_timestamp("2024-05-01T02:00:00Z").unwrap();
+        // Weekly window resets Monday 12:00 local: Wed pm, Thu, Fri, Mon am
+        let snap = Snapshot::plus((0.0, 0), (60.0, 5 * 86_400), now);
+        let weekly = snap.usage.rate_limit.unwrap().secondary_window.unwrap();
+        let view = week.view(Some(&weekly), now, now);
+        assert_eq!(view.elapsed, 50.0);
+        assert_eq!(view.days_left, Some(3.0));
+        let per_day = view.per_day.unwrap();
+        assert!((per_day - 40.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn day_lists() {
+        let week: WorkWeek = "sun-thu".parse().unwrap();
+        assert_eq!(week.label(), "Sun–Thu, UTC");
+        let week: WorkWeek = "mon,wed,fri".parse().unwrap();
+        assert_eq!(week.label(), "Mon, Wed, Fri, UTC");
+        assert!("mon-funday".parse::<WorkWeek>().is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Which workspace a team or business token acts in. The usage response only
+//! names the plan, so someone with a personal and a work login can't tell
+//! from it which one they're looking at; ChatGPT's account check names the
+//! workspace and the seat's role in it. It is fetched only for workspace
+//! plans, and only as a nicety: any failure just leaves it out. Like the
+//! usage request, its body is capped by CODEX_USAGE_MAX_BODY and it is
+//! captured by `--har`.
+
+use crate::api;
+use crate::auth::Credentials;
+use crate::har;
+use anyhow::{bail, Result};
+use chrono::Utc;
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Longest the report waits on the account check
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the account check lives, relative to the usage endpoint's base
+const ACCOUNTS_PATH: &str = "accounts/check/v4-2023-04-27";
+
+/// Plans whose tokens belong to a workspace rather than a person
+const WORKSPACE_PLANS: &[&str] = &["team", "business", "enterprise", "edu", "education"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `owner`, `admin` or `member`, or the API's own word for newer roles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+impl Workspace {
+    /// `Acme Corp (admin)`, or whichever half is known
+    pub fn describe(&self) -> Option<String> {
+        match (&self.name, &self.role) {
+            (Some(name), Some(role)) => Some(format!("{name} ({role})")),
+            (Some(name), None) => Some(name.clone()),
+            (None, Some(role)) => Some(format!("workspace {role}")),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Whether tokens on `plan` have a workspace to look up
+pub fn applies(plan: Option<&str>) -> bool {
+    plan.is_some_and(|p| WORKSPACE_PLANS.contains(&p.to_ascii_lowercase().as_str()))
+}
+
+/// The workspace `creds` act in, if the account check names one
+pub fn fetch(creds: &Credentials) -> Result<Option<Workspace>> {
+    let url = endpoint();
+    let headers = api::headers(creds);
+    let mut req = Client::builder().timeout(TIMEOUT).build()?.get(&url);
+    for (name, value) in &headers {
+        req = req.header(*name, value);
+    }
+
+    let entry = har::capturing().then(|| har::Entry::new(Utc::now(), &url, &headers));
+    let clock = Instant::now();
+    let resp = match req.send() {
+        Ok(resp) => resp,
+        Err(e) => {
+            if let Some(entry) = entry {
+                har::record(entry.failed(&e, clock.elapsed()));
+            }
+            return Err(e.into());
+        }
+    };
+    let status = resp.status();
+    let version = resp.version();
+    let response_headers = resp.headers().clone();
+    let wait = clock.elapsed();
+
+    let mut body = har::Tee::new(resp, entry.is_some());
+    let result = read_body(status, &mut body);
+    if let Some(entry) = entry {
+        har::record(entry.response(
+            status,
+            version,
+            &response_headers,
+            body.into_captured(),
+            wait,
+            clock.elapsed(),
+        ));
+    }
+    Ok(parse(&result?, creds.account_id.as_deref()))
+}
+
+/// The account check's JSON, read no further than the usage body's cap
+fn read_body(status: StatusCode, body: impl Read) -> Result<Value> {
+    if !status.is_success() {
+        bail!(
+            "account check returned HTTP {status}: {}",
+            api::read_snippet(body)
+        );
+    }
+    let capped = api::CappedReader::new(body, api::max_body_bytes());
+    Ok(serde_json::from_reader(capped)?)
+}
+
+/// The account check beside the usage endpoint, so CODEX_USAGE_ENDPOINT
+/// moves both
+pub fn endpoint() -> String {
+    let usage = api::endpoint();
+    match usage.strip_suffix("wham/usage") {
+        Some(base) => format!("{base}{ACCOUNTS_PATH}"),
+        None => format!("{}/{ACCOUNTS_PATH}", usage.trim_end_matches('/')),
+    }
+}
+
+/// The entry for `account_id`, else the only workspace listed. Personal
+/// accounts have no name and are skipped.
+fn parse(body: &Value, account_id: Option<&str>) -> Option<Workspace> {
+    let accounts = body.get("accounts")?.as_object()?;
+    let account = |entry: &Value| entry.get("account").cloned();
+    let structure = |a: &Value| a.get("structure").and_then(Value::as_str).map(String::from);
+
+    let chosen = match account_id.and_then(|id| accounts.get(id)) {
+        Some(entry) => account(entry).filter(|a| structure(a).as_deref() != Some("personal"))?,
+        None => {
+            let mut workspaces = accounts
+                .values()
+                .filter_map(account)
+                .filter(|a| structure(a).as_deref() == Some("workspace"));
+            let only = workspaces.next()?;
+            if workspaces.next().is_some() {
+                return None;
+            }
+            only
+        }
+    };
+    let text = |key: &str| {
+        chosen
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+    };
+    let workspace = Workspace {
+        name: text("name"),
+        role: text("account_user_role").map(|r| role(&r)),
+    };
+    workspace.describe().map(|_| workspace)
+}
+
+/// `account-owner` → `owner`, `standard-user` → `member`
+fn role(raw: &str) -> String {
+    match raw {
+        "standard-user" => "member".to_string(),
+        r => r.strip_prefix("account-").unwrap_or(r).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn picks_the_token_account() {
+        let body = json!({"accounts": {
+            "default": {"account": {"structure": "personal", "name": null,
+                                    "account_user_role": "account-owner"}},
+            "a1": {"account": {"structure": "workspace", "name": "Acme Corp",
+                               "account_user_role": "standard-user"}},
+            "b2": {"account": {"structure": "workspace", "name": "Side Gig",
+                               "account_user_role": "account-admin"}},
+        }});
+        let acme = parse(&body, Some("a1")).unwrap();
+        assert_eq!(acme.describe().as_deref(), Some("Acme Corp (member)"));
+        assert_eq!(
+            parse(&body, Some("b2"))
+                .and_then(|w| w.describe())
+                .as_deref(),
+            Some("Side Gig (admin)")
+        );
+        // Two workspaces and no account id: no way to tell which
+        assert_eq!(parse(&body, None), None);
+        assert_eq!(parse(&body, Some("default")), None);
+        assert!(applies(Some("Business")) && !applies(Some("plus")) && !applies(None));
+    }
+
+    #[test]
+    fn bodies_are_capped() {
+        let body = br#"{"accounts": {}}"#;
+        assert_eq!(
+            read_body(StatusCode::OK, &body[..]).unwrap(),
+            json!({"accounts": {}})
+        );
+        let huge = std::io::repeat(b' ').take(api::max_body_bytes() + 1);
+        assert!(read_body(StatusCode::OK, huge).is_err());
+        let err = read_body(StatusCode::BAD_GATEWAY, &b"upstream\n  down"[..]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "account check returned HTTP 502 Bad Gateway: upstream down"
+        );
+    }
+}